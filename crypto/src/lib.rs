@@ -2,6 +2,8 @@ extern crate crypto as rcrypto;
 extern crate primitives;
 extern crate siphasher;
 
+mod muhash;
+
 pub use rcrypto::digest::Digest;
 use std::hash::Hasher;
 use rcrypto::sha1::Sha1;
@@ -10,6 +12,8 @@ use rcrypto::ripemd160::Ripemd160;
 use siphasher::sip::SipHasher24;
 use primitives::hash::{H32, H160, H256};
 
+pub use muhash::{MuHash3072, MUHASH_STATE_SIZE};
+
 pub struct DHash160 {
 	sha256: Sha256,
 	ripemd: Ripemd160,
@@ -0,0 +1,269 @@
+//! MuHash3072 rolling set hash.
+//! https://github.com/bitcoin/bitcoin/blob/master/src/crypto/muhash.h
+
+use rcrypto::chacha20::ChaCha20;
+use rcrypto::symmetriccipher::SynchronousStreamCipher;
+use primitives::hash::H256;
+use sha256;
+
+/// Number of 64-bit limbs in 3072-bit number.
+const LIMBS: usize = 48;
+/// Size of serialized 3072-bit number.
+pub const NUM3072_SIZE: usize = LIMBS * 8;
+/// The prime modulus is 2^3072 - MAX_PRIME_DIFF.
+const MAX_PRIME_DIFF: u64 = 1103717;
+
+/// 3072-bit number, modulo 2^3072 - 1103717.
+#[derive(Clone, Copy)]
+struct Num3072 {
+	limbs: [u64; LIMBS],
+}
+
+impl Num3072 {
+	fn one() -> Self {
+		let mut limbs = [0u64; LIMBS];
+		limbs[0] = 1;
+		Num3072 {
+			limbs: limbs,
+		}
+	}
+
+	fn from_bytes(bytes: &[u8]) -> Self {
+		assert_eq!(bytes.len(), NUM3072_SIZE);
+		let mut limbs = [0u64; LIMBS];
+		for (index, limb) in limbs.iter_mut().enumerate() {
+			let mut value = 0u64;
+			for (shift, byte) in bytes[index * 8..index * 8 + 8].iter().enumerate() {
+				value |= (*byte as u64) << (shift * 8);
+			}
+			*limb = value;
+		}
+
+		let mut result = Num3072 {
+			limbs: limbs,
+		};
+		result.full_reduce();
+		result
+	}
+
+	fn to_bytes(&self, out: &mut [u8]) {
+		assert_eq!(out.len(), NUM3072_SIZE);
+		for (index, limb) in self.limbs.iter().enumerate() {
+			for shift in 0..8 {
+				out[index * 8 + shift] = (limb >> (shift * 8)) as u8;
+			}
+		}
+	}
+
+	/// Returns true if number is greater or equal to the modulus.
+	fn is_overflow(&self) -> bool {
+		self.limbs[0] > u64::max_value() - MAX_PRIME_DIFF &&
+			self.limbs[1..].iter().all(|limb| *limb == u64::max_value())
+	}
+
+	/// Subtracts modulus from the number if it is not less than the modulus.
+	fn full_reduce(&mut self) {
+		if self.is_overflow() {
+			// x - (2^3072 - d) = x + d - 2^3072
+			let mut carry = MAX_PRIME_DIFF as u128;
+			for limb in self.limbs.iter_mut() {
+				let sum = *limb as u128 + carry;
+				*limb = sum as u64;
+				carry = sum >> 64;
+			}
+		}
+	}
+
+	fn multiply(&mut self, other: &Num3072) {
+		let mut product = [0u64; LIMBS * 2];
+		for i in 0..LIMBS {
+			let mut carry = 0u128;
+			for j in 0..LIMBS {
+				let value = product[i + j] as u128 + self.limbs[i] as u128 * other.limbs[j] as u128 + carry;
+				product[i + j] = value as u64;
+				carry = value >> 64;
+			}
+			product[i + LIMBS] = carry as u64;
+		}
+
+		// 2^3072 = d (mod p), so high part of the product is folded into the lower part
+		let mut carry = 0u128;
+		for i in 0..LIMBS {
+			let value = product[i] as u128 + product[i + LIMBS] as u128 * MAX_PRIME_DIFF as u128 + carry;
+			self.limbs[i] = value as u64;
+			carry = value >> 64;
+		}
+
+		while carry != 0 {
+			let mut fold = carry * MAX_PRIME_DIFF as u128;
+			carry = 0;
+			for limb in self.limbs.iter_mut() {
+				let value = *limb as u128 + fold;
+				*limb = value as u64;
+				fold = value >> 64;
+				if fold == 0 {
+					break;
+				}
+			}
+			carry += fold;
+		}
+
+		self.full_reduce();
+	}
+
+	fn square(&mut self) {
+		let copy = *self;
+		self.multiply(&copy);
+	}
+
+	/// Computes multiplicative inverse using Fermat's little theorem: a^(p - 2).
+	fn inverse(&self) -> Num3072 {
+		// p - 2 = 2^3072 - (d + 2), bits of the exponent are processed from the most significant
+		let low = u64::max_value() - (MAX_PRIME_DIFF + 2) + 1;
+		let mut result = Num3072::one();
+		for limb_index in (0..LIMBS).rev() {
+			let limb = if limb_index == 0 { low } else { u64::max_value() };
+			for bit in (0..64).rev() {
+				result.square();
+				if (limb >> bit) & 1 == 1 {
+					result.multiply(self);
+				}
+			}
+		}
+		result
+	}
+
+	fn divide(&mut self, other: &Num3072) {
+		let inverse = other.inverse();
+		self.multiply(&inverse);
+	}
+}
+
+/// Rolling hash of the set of byte strings.
+/// Elements could be added and removed in any order, the resulting hash depends only on the set contents.
+#[derive(Clone, Copy)]
+pub struct MuHash3072 {
+	numerator: Num3072,
+	denominator: Num3072,
+}
+
+/// Serialized size of the MuHash3072 state.
+pub const MUHASH_STATE_SIZE: usize = NUM3072_SIZE * 2;
+
+impl Default for MuHash3072 {
+	fn default() -> Self {
+		MuHash3072 {
+			numerator: Num3072::one(),
+			denominator: Num3072::one(),
+		}
+	}
+}
+
+impl MuHash3072 {
+	pub fn new() -> Self {
+		MuHash3072::default()
+	}
+
+	/// Restores hash state from bytes, previously created by `to_bytes`.
+	pub fn from_bytes(bytes: &[u8]) -> Self {
+		assert_eq!(bytes.len(), MUHASH_STATE_SIZE);
+		MuHash3072 {
+			numerator: Num3072::from_bytes(&bytes[..NUM3072_SIZE]),
+			denominator: Num3072::from_bytes(&bytes[NUM3072_SIZE..]),
+		}
+	}
+
+	/// Serializes hash state.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut result = vec![0u8; MUHASH_STATE_SIZE];
+		self.numerator.to_bytes(&mut result[..NUM3072_SIZE]);
+		self.denominator.to_bytes(&mut result[NUM3072_SIZE..]);
+		result
+	}
+
+	/// Adds element to the set.
+	pub fn insert(&mut self, data: &[u8]) {
+		self.numerator.multiply(&Self::element(data));
+	}
+
+	/// Removes element from the set.
+	pub fn remove(&mut self, data: &[u8]) {
+		self.denominator.multiply(&Self::element(data));
+	}
+
+	/// Adds all elements of other set to this set.
+	pub fn combine(&mut self, other: &MuHash3072) {
+		self.numerator.multiply(&other.numerator);
+		self.denominator.multiply(&other.denominator);
+	}
+
+	/// Computes resulting 256-bit hash of the set.
+	pub fn finalize(&self) -> H256 {
+		let mut value = self.numerator;
+		value.divide(&self.denominator);
+		let mut bytes = [0u8; NUM3072_SIZE];
+		value.to_bytes(&mut bytes);
+		sha256(&bytes)
+	}
+
+	fn element(data: &[u8]) -> Num3072 {
+		let key = sha256(data);
+		let mut cipher = ChaCha20::new(&*key, &[0u8; 8]);
+		let zeros = [0u8; NUM3072_SIZE];
+		let mut keystream = [0u8; NUM3072_SIZE];
+		cipher.process(&zeros, &mut keystream);
+		Num3072::from_bytes(&keystream)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::hash::H256;
+	use super::MuHash3072;
+
+	fn from_int(i: u8) -> MuHash3072 {
+		let mut data = [0u8; 32];
+		data[0] = i;
+		let mut result = MuHash3072::new();
+		result.insert(&data);
+		result
+	}
+
+	#[test]
+	fn test_muhash3072() {
+		// https://github.com/bitcoin/bitcoin/blob/master/src/test/crypto_tests.cpp
+		let expected: H256 = "10d312b100cbd32ada024a6646e40d3482fcff103668d2625f10002a607d5863".into();
+
+		let mut acc = from_int(0);
+		acc.combine(&from_int(1));
+		let mut data = [0u8; 32];
+		data[0] = 2;
+		acc.remove(&data);
+		assert_eq!(acc.finalize(), expected.reversed());
+
+		let mut acc2 = from_int(0);
+		data[0] = 1;
+		acc2.insert(&data);
+		data[0] = 2;
+		acc2.remove(&data);
+		assert_eq!(acc2.finalize(), expected.reversed());
+	}
+
+	#[test]
+	fn test_muhash3072_order_independence() {
+		let mut first = MuHash3072::new();
+		first.insert(b"a");
+		first.insert(b"b");
+		first.insert(b"c");
+		first.remove(b"b");
+
+		let mut second = MuHash3072::new();
+		second.insert(b"c");
+		second.insert(b"a");
+
+		assert_eq!(first.finalize(), second.finalize());
+
+		let restored = MuHash3072::from_bytes(&first.to_bytes());
+		assert_eq!(restored.finalize(), second.finalize());
+	}
+}
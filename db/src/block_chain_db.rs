@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use parking_lot::RwLock;
 use hash::H256;
+use primitives::bigint::U256;
 use bytes::Bytes;
 use chain::{
 	IndexedBlock, IndexedBlockHeader, IndexedTransaction,
@@ -15,26 +17,102 @@ use kv::{
 	KeyValueDatabase, OverlayDatabase, Transaction as DBTransaction, Value, DiskDatabase,
 	DatabaseConfig, MemoryDatabase, AutoFlushingOverlayDatabase, KeyValue, Key, KeyState, CacheDatabase
 };
+use block_files::{BlockFiles, BlockFilesHeights};
 use kv::{
 	COL_COUNT, COL_BLOCK_HASHES, COL_BLOCK_HEADERS, COL_BLOCK_TRANSACTIONS, COL_TRANSACTIONS,
-	COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS
+	COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS, COL_UTXOS
 };
 use storage::{
 	BlockRef, Error, BlockHeaderProvider, BlockProvider, BlockOrigin, TransactionMeta,
 	TransactionMetaProvider, TransactionProvider, TransactionOutputProvider, BlockChain, Store,
-	SideChainOrigin, ForkChain, Forkable, CanonStore, ConfigStore, BestBlock
+	SideChainOrigin, ForkChain, Forkable, CanonStore, ConfigStore, BestBlock, UtxoEntry, BlockUndo,
+	UtxoSetInfo, ScriptHistoryProvider, ScriptHistoryEntry, ScriptHistoryKind, ScriptHistoryKey, script_hash,
+	SpendingInputProvider, SpendingInput, BlockFilterProvider, BlockFilter, UtxoSnapshotStore, HeadersStore,
+	UtxoSnapshotValidation,
 };
 
 const KEY_BEST_BLOCK_NUMBER: &'static str = "best_block_number";
 const KEY_BEST_BLOCK_HASH: &'static str = "best_block_hash";
+const KEY_UTXO_SET_INFO: &'static str = "utxo_set_info";
+const KEY_BLOCKS_SIZE: &'static str = "blocks_size";
+const KEY_PRUNED_HEIGHT: &'static str = "pruned_height";
+const KEY_DB_VERSION: &'static str = "db_version";
+const KEY_MIGRATION_PROGRESS: &'static str = "migration_progress";
+const KEY_CHAIN_TIPS: &'static str = "chain_tips";
+const KEY_ADDRESS_INDEX_HEIGHT: &'static str = "address_index_height";
+const KEY_SPENT_INDEX_HEIGHT: &'static str = "spent_index_height";
+const KEY_BLOCK_FILTER_INDEX_HEIGHT: &'static str = "block_filter_index_height";
+const KEY_UTXO_SNAPSHOT_VALIDATION: &'static str = "utxo_snapshot_validation";
+const KEY_BLOCK_FILES_HEIGHTS: &'static str = "block_files_heights";
+const KEY_HEADERS_ONLY: &'static str = "headers_only";
+
+/// Version of the database schema, written by this version of the code.
+/// Databases without version record are either empty or have been created before unspent outputs column (version 1).
+pub const DB_VERSION: u32 = 5;
+/// Number of blocks, processed by migration between progress records.
+const MIGRATION_BATCH_SIZE: u32 = 10_000;
 
 const MAX_FORK_ROUTE_PRESET: usize = 2048;
+/// Number of transactions, which unspent outputs are written at once when snapshot is loaded.
+const UTXO_SNAPSHOT_BATCH_SIZE: usize = 100_000;
+/// Number of the latest blocks, which bodies are never pruned.
+const MIN_BLOCKS_TO_KEEP: u32 = 288;
+/// Maximal number of remembered chain tips. When exceeded, the oldest side chain tips are forgotten.
+pub const MAX_CHAIN_TIPS: usize = 256;
 
 pub struct BlockChainDatabase<T> where T: KeyValueDatabase {
 	best_block: RwLock<BestBlock>,
+	/// Target size of stored canon chain block bodies. None if pruning is disabled.
+	prune_target: Option<u64>,
+	/// Is history of every script recorded
+	address_index: bool,
+	/// Is input, spending every output, recorded
+	spent_index: bool,
+	/// Are compact filters of canon blocks built
+	block_filter_index: bool,
+	/// Flat files, storing block bodies. None if bodies are stored in the database.
+	block_files: Option<Arc<BlockFiles>>,
+	/// Is this an uncommitted fork of another database. Block files are only removed once the fork is committed.
+	is_fork: bool,
 	db: T,
 }
 
+/// Upgrades database from the previous version of the schema.
+/// Migration must be resumable: it could be interrupted and restarted at any moment.
+struct Migration<T> where T: KeyValueDatabase {
+	/// Version of the schema after migration
+	version: u32,
+	/// Human-readable description of the migration
+	description: &'static str,
+	migrate: fn(&BlockChainDatabase<T>) -> Result<(), Error>,
+}
+
+/// All registered migrations, ordered by version
+fn migrations<T>() -> Vec<Migration<T>> where T: KeyValueDatabase {
+	vec![
+		Migration {
+			version: 2,
+			description: "build unspent outputs set, block undo data and blocks size",
+			migrate: BlockChainDatabase::replay_canon_chain,
+		},
+		Migration {
+			version: 3,
+			description: "compute chainwork of canon blocks",
+			migrate: BlockChainDatabase::compute_canon_chainwork,
+		},
+		Migration {
+			version: 4,
+			description: "record chain tips",
+			migrate: BlockChainDatabase::record_chain_tips,
+		},
+		Migration {
+			version: 5,
+			description: "record size of stored block bodies",
+			migrate: BlockChainDatabase::record_blocks_size,
+		},
+	]
+}
+
 pub struct ForkChainDatabase<'a, T> where T: 'a + KeyValueDatabase {
 	blockchain: BlockChainDatabase<OverlayDatabase<'a, T>>,
 }
@@ -54,20 +132,22 @@ impl BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<DiskDatabase>>
 		fs::create_dir_all(path.as_ref()).map_err(|err| Error::DatabaseError(err.to_string()))?;
 		let mut cfg = DatabaseConfig::with_columns(Some(COL_COUNT));
 
-		cfg.set_cache(Some(COL_TRANSACTIONS), total_cache / 4);
+		cfg.set_cache(Some(COL_UTXOS), total_cache / 4);
 		cfg.set_cache(Some(COL_TRANSACTIONS_META), total_cache / 4);
-		cfg.set_cache(Some(COL_BLOCK_HEADERS), total_cache / 4);
+		cfg.set_cache(Some(COL_TRANSACTIONS), total_cache / 8);
+		cfg.set_cache(Some(COL_BLOCK_HEADERS), total_cache / 8);
 
 		cfg.set_cache(Some(COL_BLOCK_HASHES), total_cache / 12);
 		cfg.set_cache(Some(COL_BLOCK_TRANSACTIONS), total_cache / 12);
 		cfg.set_cache(Some(COL_BLOCK_NUMBERS), total_cache / 12);
 
 		cfg.bloom_filters.insert(Some(COL_TRANSACTIONS_META), 32);
+		cfg.bloom_filters.insert(Some(COL_UTXOS), 10);
 
-		match DiskDatabase::open(cfg, path) {
-			Ok(db) => Ok(Self::open_with_cache(db)),
-			Err(err) => Err(Error::DatabaseError(err))
-		}
+		let db = DiskDatabase::open(cfg, path).map_err(Error::DatabaseError)?;
+		let db = Self::open_with_cache(db);
+		db.migrate()?;
+		Ok(db)
 	}
 }
 
@@ -90,6 +170,12 @@ impl<T> BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<T>>> where
 		let best_block = Self::read_best_block(&db).unwrap_or_default();
 		BlockChainDatabase {
 			best_block: RwLock::new(best_block),
+			prune_target: None,
+			address_index: false,
+			spent_index: false,
+			block_filter_index: false,
+			block_files: None,
+			is_fork: false,
 			db: db,
 		}
 	}
@@ -114,16 +200,325 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		let best_block = Self::read_best_block(&db).unwrap_or_default();
 		BlockChainDatabase {
 			best_block: RwLock::new(best_block),
+			prune_target: None,
+			address_index: false,
+			spent_index: false,
+			block_filter_index: false,
+			block_files: None,
+			is_fork: false,
 			db: db,
 		}
 	}
 
+	/// Enables recording of transactions, funding and spending outputs of every script
+	pub fn with_address_index(mut self) -> Self {
+		self.address_index = true;
+		self
+	}
+
+	/// Enables recording of inputs, spending every output
+	pub fn with_spent_index(mut self) -> Self {
+		self.spent_index = true;
+		self
+	}
+
+	/// Enables building of compact filters of every canonized block
+	pub fn with_block_filter_index(mut self) -> Self {
+		self.block_filter_index = true;
+		self
+	}
+
+	/// Enables storing of block bodies in flat files.
+	/// Blocks, which have been stored in the database before, are still read from it.
+	pub fn with_block_files(mut self, block_files: BlockFiles) -> Self {
+		self.block_files = Some(Arc::new(block_files));
+		self
+	}
+
+	/// Enables pruning of the oldest block bodies once their total size exceeds given number of bytes
+	pub fn with_prune_target(mut self, prune_target: u64) -> Self {
+		self.prune_target = Some(prune_target);
+		self
+	}
+
 	pub fn best_block(&self) -> BestBlock {
 		self.best_block.read().clone()
 	}
 
+	/// Returns number of the first canon chain block, which body has not been pruned
+	pub fn pruned_height(&self) -> u32 {
+		self.get(Key::Meta(KEY_PRUNED_HEIGHT))
+			.and_then(Value::as_meta)
+			.map(|height| deserialize(&**height).expect("Inconsistent DB. Invalid pruned height."))
+			.unwrap_or_default()
+	}
+
+	/// Returns number of the first canon chain block, since which the optional index has been built without gaps
+	fn index_height(&self, key: &'static str) -> Option<u32> {
+		self.get(Key::Meta(key))
+			.and_then(Value::as_meta)
+			.map(|height| deserialize(&**height).expect("Inconsistent DB. Invalid index height."))
+	}
+
+	/// Records the height, since which the optional index is built, once it is enabled.
+	/// The height is forgotten once a block is canonized with the index disabled, because it leaves a gap in the index.
+	fn update_index_height(&self, update: &mut DBTransaction, key: &'static str, enabled: bool, height: u32) {
+		match (enabled, self.index_height(key)) {
+			(true, None) => update.insert(KeyValue::Meta(key, serialize(&height))),
+			(false, Some(_)) => update.delete(Key::Meta(key)),
+			_ => (),
+		}
+	}
+
+	/// Returns version of the database schema
+	pub fn db_version(&self) -> Option<u32> {
+		self.get(Key::Meta(KEY_DB_VERSION))
+			.and_then(Value::as_meta)
+			.map(|version| deserialize(&**version).expect("Inconsistent DB. Invalid db version."))
+	}
+
+	/// Upgrades the database to the current schema version.
+	/// Fails if the database has been written by newer version of the code.
+	pub fn migrate(&self) -> Result<(), Error> {
+		let version = match self.db_version() {
+			Some(version) => version,
+			None if self.block_hash(0).is_none() => {
+				let mut update = DBTransaction::new();
+				update.insert(KeyValue::Meta(KEY_DB_VERSION, serialize(&DB_VERSION)));
+				return self.db.write(update).map_err(Error::DatabaseError);
+			},
+			None => 1,
+		};
+
+		if version > DB_VERSION {
+			return Err(Error::NewerDatabaseVersion(version, DB_VERSION));
+		}
+
+		for migration in migrations().into_iter().skip_while(|migration| migration.version <= version) {
+			info!(target: "db", "Migrating database to version {}: {}", migration.version, migration.description);
+			(migration.migrate)(self)?;
+
+			let mut update = DBTransaction::new();
+			update.insert(KeyValue::Meta(KEY_DB_VERSION, serialize(&migration.version)));
+			update.delete(Key::Meta(KEY_MIGRATION_PROGRESS));
+			self.db.write(update).map_err(Error::DatabaseError)?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns progress, recorded by interrupted migration
+	fn migration_progress(&self) -> Option<u32> {
+		self.get(Key::Meta(KEY_MIGRATION_PROGRESS))
+			.and_then(Value::as_meta)
+			.map(|progress| deserialize(&**progress).expect("Inconsistent DB. Invalid migration progress."))
+	}
+
+	/// Canonizes all canon blocks once again, starting from genesis, to fill columns, added in version 2.
+	/// Progress record holds the number of the best block before migration, while best block
+	/// records are updated by every canonization.
+	fn replay_canon_chain(&self) -> Result<(), Error> {
+		let last_block = match self.migration_progress() {
+			Some(last_block) => last_block,
+			None => {
+				let last_block = self.best_block().number;
+				let mut update = DBTransaction::new();
+				update.insert(KeyValue::Meta(KEY_MIGRATION_PROGRESS, serialize(&last_block)));
+				update.delete(Key::Meta(KEY_BEST_BLOCK_HASH));
+				update.delete(Key::Meta(KEY_BEST_BLOCK_NUMBER));
+				update.delete(Key::Meta(KEY_UTXO_SET_INFO));
+				update.delete(Key::Meta(KEY_BLOCKS_SIZE));
+				self.db.write(update).map_err(Error::DatabaseError)?;
+				*self.best_block.write() = BestBlock::default();
+				last_block
+			},
+		};
+
+		loop {
+			let best_block = self.best_block();
+			let number = match best_block.hash.is_zero() {
+				true => 0,
+				false if best_block.number < last_block => best_block.number + 1,
+				false => return Ok(()),
+			};
+
+			let hash = self.block_hash(number).ok_or(Error::CannotCanonize)?;
+			self.canonize(&hash)?;
+			if number % MIGRATION_BATCH_SIZE == 0 {
+				info!(target: "db", "Replayed {} of {} blocks", number, last_block);
+			}
+		}
+	}
+
+	/// Rebuilds unspent outputs set, transactions meta, undo data and indexes from stored canon blocks.
+	/// Canon blocks are replayed in the same way as during migration, so interrupted reindex is resumed by the next call.
+	pub fn reindex_chainstate(&self) -> Result<(), Error> {
+		if self.migration_progress().is_none() {
+			if self.pruned_height() != 0 {
+				return Err(Error::CannotReindex("bodies of pruned blocks are missing".into()));
+			}
+			self.clear_chainstate()?;
+		}
+
+		self.replay_canon_chain()?;
+
+		let mut update = DBTransaction::new();
+		update.delete(Key::Meta(KEY_MIGRATION_PROGRESS));
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Removes everything, derived from canon blocks, except for the canon chain itself
+	fn clear_chainstate(&self) -> Result<(), Error> {
+		let best_block = self.best_block();
+		let mut update = DBTransaction::new();
+		for number in 0..best_block.number + 1 {
+			let hash = self.block_hash(number)
+				.ok_or_else(|| Error::CannotReindex(format!("canon block {} is missing", number)))?;
+			let block = self.block(hash.clone().into())
+				.ok_or_else(|| Error::CannotReindex(format!("body of canon block {} is missing", number)))?;
+
+			update.delete(Key::BlockUndo(hash.clone()));
+			update.delete(Key::BlockFilter(hash));
+			// spending entries are in the history of scripts, funded by canon blocks
+			let script_hashes: HashSet<H256> = block.transactions.iter()
+				.flat_map(|tx| tx.raw.outputs.iter())
+				.map(|output| script_hash(&output.script_pubkey))
+				.collect();
+			for script_hash in script_hashes {
+				for (key, _) in self.db.script_history(&script_hash) {
+					update.delete(Key::ScriptHistory(key));
+				}
+			}
+			for tx in &block.transactions {
+				update.delete(Key::TransactionMeta(tx.hash.clone()));
+				if !tx.raw.is_coinbase() {
+					for input in &tx.raw.inputs {
+						update.delete(Key::SpentBy(input.previous_output.clone()));
+					}
+				}
+			}
+
+			if number % MIGRATION_BATCH_SIZE == 0 {
+				self.db.write(update).map_err(Error::DatabaseError)?;
+				update = DBTransaction::new();
+				info!(target: "db", "Cleared state of {} of {} blocks", number, best_block.number);
+			}
+		}
+		update.delete(Key::Meta(KEY_ADDRESS_INDEX_HEIGHT));
+		update.delete(Key::Meta(KEY_SPENT_INDEX_HEIGHT));
+		update.delete(Key::Meta(KEY_BLOCK_FILTER_INDEX_HEIGHT));
+		self.db.write(update).map_err(Error::DatabaseError)?;
+
+		// all unspent outputs are removed, even those, which have not been created by canon blocks
+		let mut update = DBTransaction::new();
+		for (outpoint, _) in self.db.utxos() {
+			update.delete(Key::Utxo(outpoint));
+			if update.operations.len() == UTXO_SNAPSHOT_BATCH_SIZE {
+				self.db.write(update).map_err(Error::DatabaseError)?;
+				update = DBTransaction::new();
+			}
+		}
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Computes chainwork of all canon blocks. Side chain blocks, inserted before the migration,
+	/// are left without chainwork and their children are treated as blocks with unknown parent.
+	fn compute_canon_chainwork(&self) -> Result<(), Error> {
+		let best_block = self.best_block();
+		let mut number = self.migration_progress().unwrap_or_default();
+		let mut chainwork = match number {
+			0 => U256::zero(),
+			number => self.block_chainwork(BlockRef::Number(number - 1)).ok_or(Error::CannotCanonize)?,
+		};
+
+		while number <= best_block.number {
+			let mut update = DBTransaction::new();
+			let last_number = ::std::cmp::min(number + MIGRATION_BATCH_SIZE, best_block.number + 1);
+			for number in number..last_number {
+				let header = self.block_header(BlockRef::Number(number)).ok_or(Error::CannotCanonize)?;
+				chainwork = chainwork + header.raw.bits.to_work();
+				update.insert(KeyValue::BlockChainwork(header.hash, chainwork));
+			}
+			update.insert(KeyValue::Meta(KEY_MIGRATION_PROGRESS, serialize(&last_number)));
+			self.db.write(update).map_err(Error::DatabaseError)?;
+
+			number = last_number;
+			info!(target: "db", "Computed chainwork of {} of {} blocks", number, best_block.number + 1);
+		}
+
+		Ok(())
+	}
+
+	/// Records the best block as the only chain tip. Side chain blocks, inserted before the migration, are not tracked.
+	fn record_chain_tips(&self) -> Result<(), Error> {
+		let mut update = DBTransaction::new();
+		update.insert(KeyValue::Meta(KEY_CHAIN_TIPS, serialize(&List::from(vec![self.best_block().hash]))));
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Sums sizes of canon block bodies, which are not pruned, if databases of previous versions have not recorded it.
+	/// Progress record holds the number of the next block, while blocks size record holds the size of preceding blocks.
+	fn record_blocks_size(&self) -> Result<(), Error> {
+		let best_block = self.best_block();
+		let (mut number, mut blocks_size) = match self.migration_progress() {
+			Some(number) => (number, self.blocks_size()),
+			None if self.get(Key::Meta(KEY_BLOCKS_SIZE)).is_some() || best_block.hash.is_zero() => return Ok(()),
+			None => (self.pruned_height(), 0),
+		};
+
+		while number <= best_block.number {
+			let last_number = ::std::cmp::min(number + MIGRATION_BATCH_SIZE, best_block.number + 1);
+			for number in number..last_number {
+				let block = self.block(BlockRef::Number(number)).ok_or(Error::CannotCanonize)?;
+				blocks_size += block.size() as u64;
+			}
+
+			let mut update = DBTransaction::new();
+			update.insert(KeyValue::Meta(KEY_BLOCKS_SIZE, serialize(&blocks_size)));
+			update.insert(KeyValue::Meta(KEY_MIGRATION_PROGRESS, serialize(&last_number)));
+			self.db.write(update).map_err(Error::DatabaseError)?;
+
+			number = last_number;
+			info!(target: "db", "Recorded size of {} of {} blocks", number, best_block.number + 1);
+		}
+
+		Ok(())
+	}
+
+	/// Returns hashes of stored blocks, which have no stored children
+	pub fn chain_tips(&self) -> Vec<H256> {
+		self.get(Key::Meta(KEY_CHAIN_TIPS))
+			.and_then(Value::as_meta)
+			.map(|tips| deserialize::<_, List<H256>>(&**tips).expect("Inconsistent DB. Invalid chain tips.").into())
+			.unwrap_or_default()
+	}
+
+	/// Replaces `removed` chain tip with `added` block. If there are too many tips, the oldest side chain
+	/// tips are forgotten (the best block and the added block are always kept).
+	fn update_chain_tips(&self, update: &mut DBTransaction, removed: &H256, added: H256, best_hash: &H256) {
+		let mut tips = self.chain_tips();
+		tips.retain(|tip| tip != removed && *tip != added);
+		tips.push(added);
+
+		let mut stale_tips = tips.len().saturating_sub(MAX_CHAIN_TIPS);
+		tips.retain(|tip| match stale_tips != 0 && tip != best_hash {
+			true => {
+				stale_tips -= 1;
+				false
+			},
+			false => true,
+		});
+		update.insert(KeyValue::Meta(KEY_CHAIN_TIPS, serialize(&List::from(tips))));
+	}
+
 	pub fn fork(&self, side_chain: SideChainOrigin) -> Result<ForkChainDatabase<T>, Error> {
-		let overlay = BlockChainDatabase::open(OverlayDatabase::new(&self.db));
+		let mut overlay = BlockChainDatabase::open(OverlayDatabase::new(&self.db));
+		overlay.prune_target = self.prune_target;
+		overlay.address_index = self.address_index;
+		overlay.spent_index = self.spent_index;
+		overlay.block_filter_index = self.block_filter_index;
+		overlay.block_files = self.block_files.clone();
+		overlay.is_fork = true;
 
 		for hash in side_chain.decanonized_route.into_iter().rev() {
 			let decanonized_hash = overlay.decanonize()?;
@@ -144,7 +539,8 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 	pub fn switch_to_fork(&self, fork: ForkChainDatabase<T>) -> Result<(), Error> {
 		let mut best_block = self.best_block.write();
 		*best_block = fork.blockchain.best_block.read().clone();
-		fork.blockchain.db.flush().map_err(Error::DatabaseError)
+		fork.blockchain.db.flush().map_err(Error::DatabaseError)?;
+		self.remove_pruned_block_files()
 	}
 
 	pub fn block_origin(&self, header: &IndexedBlockHeader) -> Result<BlockOrigin, Error> {
@@ -161,15 +557,21 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			})
 		}
 
-		if !self.contains_block(header.raw.previous_header_hash.clone().into()) {
-			return Err(Error::UnknownParent)
-		}
+		let parent_chainwork = match self.block_chainwork(header.raw.previous_header_hash.clone().into()) {
+			Some(parent_chainwork) => parent_chainwork,
+			None => return Err(Error::UnknownParent),
+		};
+		let chainwork = parent_chainwork + header.raw.bits.to_work();
+		let best_chainwork = self.block_chainwork(best_block.hash.clone().into())
+			.expect("chainwork is stored for every inserted block; best block is inserted; qed");
 
 		let mut sidechain_route = Vec::new();
 		let mut next_hash = header.raw.previous_header_hash.clone();
 
 		for fork_len in 0..MAX_FORK_ROUTE_PRESET {
 			match self.block_number(&next_hash) {
+				// bodies of decanonized blocks are required to revert them
+				Some(number) if number + 1 < self.pruned_height() => return Err(Error::AncientFork),
 				Some(number) => {
 					let block_number = number + fork_len as u32 + 1;
 					let origin = SideChainOrigin {
@@ -179,8 +581,10 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 							.filter_map(|decanonized_bn| self.block_hash(decanonized_bn))
 							.collect(),
 						block_number: block_number,
+						chainwork: chainwork,
 					};
-					if block_number > best_block.number {
+					// side chain only becomes canon when it has more work than the canon chain
+					if origin.chainwork > best_chainwork {
 						return Ok(BlockOrigin::SideChainBecomingCanonChain(origin))
 					} else {
 						return Ok(BlockOrigin::SideChain(origin))
@@ -208,18 +612,58 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			return Err(Error::UnknownParent);
 		}
 
+		let parent_chainwork = match parent_hash.is_zero() {
+			true => U256::default(),
+			false => self.ancestors_chainwork(&parent_hash)?,
+		};
+		let chainwork = parent_chainwork + block.header.raw.bits.to_work();
+
 		let mut update = DBTransaction::new();
-		update.insert(KeyValue::BlockHeader(block.hash().clone(), block.header.raw));
+		self.update_chain_tips(&mut update, &parent_hash, block.hash().clone(), &self.best_block().hash);
+		update.insert(KeyValue::BlockChainwork(block.hash().clone(), chainwork));
+		update.insert(KeyValue::BlockHeader(block.hash().clone(), block.header.raw.clone()));
 		let tx_hashes = block.transactions.iter().map(|tx| tx.hash.clone()).collect::<Vec<_>>();
 		update.insert(KeyValue::BlockTransactions(block.header.hash.clone(), List::from(tx_hashes)));
 
-		for tx in block.transactions.into_iter() {
-			update.insert(KeyValue::Transaction(tx.hash, tx.raw));
+		match self.block_files {
+			Some(ref block_files) => {
+				// block is written to the file before the index, so the index never references missing data
+				let location = block_files.append(&block).map_err(Error::DatabaseError)?;
+				let mut heights = self.block_files_heights();
+				heights.insert(location.file, self.child_block_height(&parent_hash));
+				update.insert(KeyValue::Meta(KEY_BLOCK_FILES_HEIGHTS, serialize(&heights)));
+				update.insert(KeyValue::BlockLocation(block.header.hash.clone(), location));
+			},
+			None => for tx in block.transactions.into_iter() {
+				update.insert(KeyValue::Transaction(tx.hash, tx.raw));
+			},
 		}
 
 		self.db.write(update).map_err(Error::DatabaseError)
 	}
 
+	/// Returns chainwork of the stored block. If it has no stored chainwork, work of its headers is summed up
+	/// until an ancestor with stored chainwork (or genesis) is met.
+	fn ancestors_chainwork(&self, hash: &H256) -> Result<U256, Error> {
+		let mut work = U256::default();
+		let mut hash = hash.clone();
+		loop {
+			if let Some(chainwork) = self.block_chainwork(hash.clone().into()) {
+				return Ok(chainwork + work);
+			}
+
+			let header = match self.block_header(hash.clone().into()) {
+				Some(header) => header,
+				None => return Err(Error::DatabaseError(format!("Cannot compute chainwork: header {} is not found", hash.reversed()))),
+			};
+			work = work + header.raw.bits.to_work();
+			if header.raw.previous_header_hash.is_zero() {
+				return Ok(work);
+			}
+			hash = header.raw.previous_header_hash;
+		}
+	}
+
 	/// Rollbacks single best block
 	fn rollback_best(&self) -> Result<H256, Error> {
 		let best_block_hash = self.best_block.read().hash.clone();
@@ -232,7 +676,12 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		// {CanonChain, SideChain, SideChainBecomingCanonChain}
 		let mut update = DBTransaction::new();
 		update.delete(Key::BlockHeader(decanonized_hash.clone()));
+		update.delete(Key::BlockChainwork(decanonized_hash.clone()));
 		update.delete(Key::BlockTransactions(decanonized_hash.clone()));
+		update.delete(Key::BlockLocation(decanonized_hash.clone()));
+		// parent is the new best block, so it becomes a tip
+		let best_hash = self.best_block().hash;
+		self.update_chain_tips(&mut update, &decanonized_hash, best_hash.clone(), &best_hash);
 		for tx_hash in tx_to_decanonize {
 			update.delete(Key::Transaction(tx_hash));
 		}
@@ -319,12 +768,129 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			}
 		}
 
-		for (hash, meta) in modified_meta.into_iter() {
-			update.insert(KeyValue::TransactionMeta(hash, meta));
+		for (hash, meta) in modified_meta.iter() {
+			update.insert(KeyValue::TransactionMeta(hash.clone(), meta.clone()));
+		}
+
+		// outputs are added before inputs are spent, because with canonical transactions
+		// ordering transaction could spend outputs of transactions that follow it in the block
+		let mut created_utxos: HashMap<OutPoint, UtxoEntry> = HashMap::new();
+		for (tx_index, tx) in block.transactions.iter().enumerate() {
+			for (output_index, output) in tx.raw.outputs.iter().enumerate() {
+				if UtxoEntry::is_unspendable(output) {
+					continue;
+				}
+
+				let outpoint = OutPoint {
+					hash: tx.hash.clone(),
+					index: output_index as u32,
+				};
+				created_utxos.insert(outpoint, UtxoEntry::new(output.clone(), new_best_block.number, tx_index == 0));
+			}
+		}
+
+		let mut undo = BlockUndo::default();
+		for tx in block.transactions.iter().skip(1) {
+			for input in &tx.raw.inputs {
+				// outputs, created and spent in the same block are never written to the db
+				if created_utxos.remove(&input.previous_output).is_some() {
+					continue;
+				}
+
+				let entry = self.utxo(&input.previous_output)
+					.ok_or_else(|| {
+						error!(
+							target: "db",
+							"Cannot find unspent output during canonization of tx {}: {}/{}",
+							tx.hash.reversed(),
+							input.previous_output.hash.reversed(),
+							input.previous_output.index,
+						);
+						Error::CannotCanonize
+					})?;
+				update.delete(Key::Utxo(input.previous_output.clone()));
+				undo.spent.push((input.previous_output.clone(), entry));
+			}
+		}
+
+		// outputs of duplicate transactions (BIP30 exceptions) overwrite unspent outputs of the original transactions
+		for outpoint in created_utxos.keys() {
+			if let Some(entry) = self.utxo(outpoint) {
+				undo.overwritten.push((outpoint.clone(), entry));
+			}
+		}
+
+		// genesis outputs are not spendable in Bitcoin Core, so they are excluded from the statistics
+		let mut utxo_set_info = self.read_utxo_set_info();
+		let mut overwritten_transactions = HashSet::new();
+		for &(ref outpoint, ref entry) in undo.overwritten.iter().filter(|&&(_, ref entry)| entry.height() != 0) {
+			utxo_set_info.remove_output(outpoint, entry);
+			overwritten_transactions.insert(&outpoint.hash);
+		}
+
+		let mut created_transactions = HashSet::new();
+		for (outpoint, entry) in created_utxos.iter().filter(|&(_, entry)| entry.height() != 0) {
+			utxo_set_info.insert_output(outpoint, entry);
+			// transaction with overwritten outputs is already counted
+			if !overwritten_transactions.contains(&outpoint.hash) {
+				created_transactions.insert(&outpoint.hash);
+			}
+		}
+		utxo_set_info.transactions += created_transactions.len() as u64;
+
+		let mut spent_transactions = HashSet::new();
+		for &(ref outpoint, ref entry) in undo.spent.iter().filter(|&&(_, ref entry)| entry.height() != 0) {
+			utxo_set_info.remove_output(outpoint, entry);
+			spent_transactions.insert(&outpoint.hash);
+		}
+		for hash in spent_transactions {
+			let meta = modified_meta.get(hash).expect("meta of every spent transaction is modified above; qed");
+			if !self.has_unspent_outputs(hash, meta) {
+				utxo_set_info.transactions -= 1;
+			}
+		}
+		update.insert(KeyValue::Meta(KEY_UTXO_SET_INFO, serialize(&utxo_set_info)));
+
+		let mut blocks_size = self.blocks_size() + block.size() as u64;
+		if let Some(prune_target) = self.prune_target {
+			self.prune(&mut update, new_best_block.number, &mut blocks_size, prune_target)?;
+		}
+		update.insert(KeyValue::Meta(KEY_BLOCKS_SIZE, serialize(&blocks_size)));
+
+		self.update_index_height(&mut update, KEY_ADDRESS_INDEX_HEIGHT, self.address_index, new_best_block.number);
+		self.update_index_height(&mut update, KEY_SPENT_INDEX_HEIGHT, self.spent_index, new_best_block.number);
+		self.update_index_height(&mut update, KEY_BLOCK_FILTER_INDEX_HEIGHT, self.block_filter_index, new_best_block.number);
+		if self.address_index {
+			self.index_scripts(&mut update, &block, new_best_block.number, &undo);
+		}
+
+		if self.spent_index {
+			for tx in block.transactions.iter().skip(1) {
+				for (input_index, input) in tx.raw.inputs.iter().enumerate() {
+					update.insert(KeyValue::SpentBy(input.previous_output.clone(), SpendingInput {
+						tx_hash: tx.hash.clone(),
+						input_index: input_index as u32,
+						height: new_best_block.number,
+					}));
+				}
+			}
+		}
+
+		if self.block_filter_index {
+			self.index_block_filter(&mut update, &block, &undo);
 		}
 
+		for (outpoint, entry) in created_utxos {
+			update.insert(KeyValue::Utxo(outpoint, entry));
+		}
+		update.insert(KeyValue::BlockUndo(new_best_block.hash.clone(), undo));
+
 		self.db.write(update).map_err(Error::DatabaseError)?;
 		*best_block = new_best_block;
+
+		if !self.is_fork {
+			self.remove_pruned_block_files()?;
+		}
 		Ok(())
 	}
 
@@ -391,6 +957,80 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			update.insert(KeyValue::TransactionMeta(hash, meta));
 		}
 
+		let undo = self.get(Key::BlockUndo(block_hash.clone()))
+			.and_then(Value::as_block_undo)
+			.ok_or_else(|| {
+				error!(target: "db", "Cannot find undo data during decanonization of block {}", block_hash.reversed());
+				Error::CannotDecanonize
+			})?;
+
+		let mut utxo_set_info = self.read_utxo_set_info();
+		for tx in &block.transactions {
+			let mut has_unspent_outputs = false;
+			for output_index in 0..tx.raw.outputs.len() {
+				let outpoint = OutPoint {
+					hash: tx.hash.clone(),
+					index: output_index as u32,
+				};
+				if let Some(entry) = self.utxo(&outpoint) {
+					if entry.height() != 0 {
+						utxo_set_info.remove_output(&outpoint, &entry);
+						has_unspent_outputs = true;
+					}
+				}
+				update.delete(Key::Utxo(outpoint));
+			}
+
+			if has_unspent_outputs {
+				utxo_set_info.transactions -= 1;
+			}
+		}
+
+		let mut spent_transactions = HashSet::new();
+		for &(ref outpoint, ref entry) in undo.spent.iter().filter(|&&(_, ref entry)| entry.height() != 0) {
+			utxo_set_info.insert_output(outpoint, entry);
+			spent_transactions.insert(&outpoint.hash);
+		}
+		for hash in spent_transactions {
+			let meta = self.transaction_meta(hash).ok_or(Error::CannotDecanonize)?;
+			if !self.has_unspent_outputs(hash, &meta) {
+				utxo_set_info.transactions += 1;
+			}
+		}
+
+		// outputs, overwritten by duplicate transactions, are unspent again
+		let mut overwritten_transactions = HashSet::new();
+		for &(ref outpoint, ref entry) in undo.overwritten.iter().filter(|&&(_, ref entry)| entry.height() != 0) {
+			utxo_set_info.insert_output(outpoint, entry);
+			overwritten_transactions.insert(&outpoint.hash);
+		}
+		utxo_set_info.transactions += overwritten_transactions.len() as u64;
+		update.insert(KeyValue::Meta(KEY_UTXO_SET_INFO, serialize(&utxo_set_info)));
+
+		if self.address_index {
+			self.unindex_scripts(&mut update, &block, block_number, &undo);
+		}
+
+		if self.spent_index {
+			for tx in block.transactions.iter().skip(1) {
+				for input in &tx.raw.inputs {
+					update.delete(Key::SpentBy(input.previous_output.clone()));
+				}
+			}
+		}
+
+		if self.block_filter_index {
+			update.delete(Key::BlockFilter(block_hash.clone()));
+		}
+
+		for (outpoint, entry) in undo.spent.into_iter().chain(undo.overwritten) {
+			update.insert(KeyValue::Utxo(outpoint, entry));
+		}
+		update.delete(Key::BlockUndo(block_hash.clone()));
+
+		let blocks_size = self.blocks_size().saturating_sub(block.size() as u64);
+		update.insert(KeyValue::Meta(KEY_BLOCKS_SIZE, serialize(&blocks_size)));
+
 		for tx in block.transactions {
 			update.delete(Key::TransactionMeta(tx.hash));
 		}
@@ -400,6 +1040,438 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		Ok(block_hash)
 	}
 
+	/// Replaces the chain, which only contains genesis block, with given headers chain and the unspent
+	/// outputs set at its best block. Bodies of all these blocks are treated as pruned.
+	pub fn load_utxo_snapshot(
+		&self,
+		headers: Vec<IndexedBlockHeader>,
+		utxos: &mut dyn Iterator<Item = Result<(OutPoint, UtxoEntry), Error>>,
+	) -> Result<UtxoSetInfo, Error> {
+		let mut best_block = self.best_block.write();
+		if best_block.number != 0 {
+			return Err(Error::CannotLoadUtxoSnapshot("database already contains blocks after genesis".into()));
+		}
+		if headers.first().map(|genesis| &genesis.hash) != Some(&best_block.hash) {
+			return Err(Error::CannotLoadUtxoSnapshot("snapshot chain does not start with genesis block".into()));
+		}
+
+		let mut update = DBTransaction::new();
+		let mut chainwork = self.block_chainwork(best_block.hash.clone().into()).unwrap_or_default();
+		for (number, pair) in headers.windows(2).enumerate() {
+			let (parent, header) = (&pair[0], &pair[1]);
+			if header.raw.previous_header_hash != parent.hash {
+				return Err(Error::CannotLoadUtxoSnapshot(format!("header {} is not a child of header {}", header.hash.reversed(), parent.hash.reversed())));
+			}
+
+			let number = number as u32 + 1;
+			chainwork = chainwork + header.raw.bits.to_work();
+			update.insert(KeyValue::BlockHeader(header.hash.clone(), header.raw.clone()));
+			update.insert(KeyValue::BlockChainwork(header.hash.clone(), chainwork));
+			update.insert(KeyValue::BlockHash(number, header.hash.clone()));
+			update.insert(KeyValue::BlockNumber(header.hash.clone(), number));
+		}
+		self.db.write(update).map_err(Error::DatabaseError)?;
+
+		// outputs are written in batches, so that the whole set never has to fit into memory
+		let mut utxo_set_info = UtxoSetInfo::default();
+		let mut modified_meta: HashMap<H256, TransactionMeta> = HashMap::new();
+		let mut update = DBTransaction::new();
+		for utxo in utxos {
+			use std::collections::hash_map::Entry;
+
+			let (outpoint, entry) = utxo?;
+			let meta = match modified_meta.entry(outpoint.hash.clone()) {
+				Entry::Occupied(occupied) => occupied.into_mut(),
+				Entry::Vacant(vacant) => {
+					let meta = match self.transaction_meta(&outpoint.hash) {
+						Some(meta) => meta,
+						None if entry.is_coinbase() => TransactionMeta::new_coinbase(entry.height(), 0),
+						None => TransactionMeta::new(entry.height(), 0),
+					};
+					// meta, written by one of previous batches, already has unspent outputs
+					if meta.is_fully_spent() && entry.height() != 0 {
+						utxo_set_info.transactions += 1;
+					}
+					vacant.insert(meta)
+				},
+			};
+
+			// outputs, which are missing from the snapshot, are treated as spent
+			meta.grow_outputs(outpoint.index as usize + 1);
+			meta.denote_unused(outpoint.index as usize);
+
+			// genesis outputs are not spendable in Bitcoin Core, so they are excluded from the statistics
+			if entry.height() != 0 {
+				utxo_set_info.insert_output(&outpoint, &entry);
+			}
+			update.insert(KeyValue::Utxo(outpoint, entry));
+
+			if modified_meta.len() == UTXO_SNAPSHOT_BATCH_SIZE {
+				for (hash, meta) in modified_meta.drain() {
+					update.insert(KeyValue::TransactionMeta(hash, meta));
+				}
+				self.db.write(update).map_err(Error::DatabaseError)?;
+				update = DBTransaction::new();
+			}
+		}
+
+		for (hash, meta) in modified_meta {
+			update.insert(KeyValue::TransactionMeta(hash, meta));
+		}
+
+		let snapshot_block = BestBlock {
+			number: headers.len() as u32 - 1,
+			hash: headers.last().expect("first header is checked above; qed").hash.clone(),
+		};
+		update.insert(KeyValue::Meta(KEY_BEST_BLOCK_HASH, serialize(&snapshot_block.hash)));
+		update.insert(KeyValue::Meta(KEY_BEST_BLOCK_NUMBER, serialize(&snapshot_block.number)));
+		update.insert(KeyValue::Meta(KEY_UTXO_SET_INFO, serialize(&utxo_set_info)));
+		update.insert(KeyValue::Meta(KEY_PRUNED_HEIGHT, serialize(&(snapshot_block.number + 1))));
+		update.insert(KeyValue::Meta(KEY_CHAIN_TIPS, serialize(&List::from(vec![snapshot_block.hash.clone()]))));
+		// history below the snapshot is validated in the background
+		let validation = UtxoSnapshotValidation {
+			block_hash: snapshot_block.hash.clone(),
+			block_height: snapshot_block.number,
+			utxo_set_hash: utxo_set_info.muhash.finalize(),
+			is_failed: false,
+		};
+		update.insert(KeyValue::Meta(KEY_UTXO_SNAPSHOT_VALIDATION, serialize(&validation)));
+		self.db.write(update).map_err(Error::DatabaseError)?;
+
+		*best_block = snapshot_block;
+		Ok(utxo_set_info)
+	}
+
+	/// Returns the loaded unspent outputs set snapshot, which history is not yet validated
+	pub fn utxo_snapshot_validation(&self) -> Option<UtxoSnapshotValidation> {
+		self.get(Key::Meta(KEY_UTXO_SNAPSHOT_VALIDATION))
+			.and_then(Value::as_meta)
+			.map(|validation| deserialize(&**validation).expect("Inconsistent DB. Invalid utxo snapshot validation."))
+	}
+
+	/// Forgets the loaded snapshot once its history is valid. Otherwise the snapshot is remembered as failed.
+	pub fn finish_utxo_snapshot_validation(&self, is_valid: bool) -> Result<(), Error> {
+		let mut validation = match self.utxo_snapshot_validation() {
+			Some(validation) => validation,
+			None => return Ok(()),
+		};
+
+		let mut update = DBTransaction::new();
+		match is_valid {
+			true => update.delete(Key::Meta(KEY_UTXO_SNAPSHOT_VALIDATION)),
+			false => {
+				validation.is_failed = true;
+				update.insert(KeyValue::Meta(KEY_UTXO_SNAPSHOT_VALIDATION, serialize(&validation)));
+			},
+		}
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Marks the database as the one, which only stores headers (of the light client).
+	/// Bodies of all blocks, including the future ones, are treated as pruned after that.
+	pub fn set_headers_only(&self) -> Result<(), Error> {
+		let mut update = DBTransaction::new();
+		update.insert(KeyValue::Meta(KEY_HEADERS_ONLY, serialize(&true)));
+		update.insert(KeyValue::Meta(KEY_PRUNED_HEIGHT, serialize(&u32::max_value())));
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Returns true if the database only stores headers
+	pub fn is_headers_only(&self) -> bool {
+		self.get(Key::Meta(KEY_HEADERS_ONLY))
+			.and_then(Value::as_meta)
+			.map(|headers_only| deserialize(&**headers_only).expect("Inconsistent DB. Invalid headers only flag."))
+			.unwrap_or(false)
+	}
+
+	/// Inserts header of the block, which body is never stored, and makes it the best block if its chain
+	/// has more work than the canon chain.
+	pub fn insert_header(&self, header: &IndexedBlockHeader) -> Result<bool, Error> {
+		let mut best_block = self.best_block.write();
+		if self.contains_block(header.hash.clone().into()) {
+			return Ok(false);
+		}
+
+		let parent_hash = header.raw.previous_header_hash.clone();
+		let chainwork = match self.block_chainwork(parent_hash.clone().into()) {
+			Some(parent_chainwork) => parent_chainwork + header.raw.bits.to_work(),
+			None => return Err(Error::UnknownParent),
+		};
+		let best_chainwork = self.block_chainwork(best_block.hash.clone().into())
+			.expect("chainwork is stored for every inserted block; best block is inserted; qed");
+
+		let mut update = DBTransaction::new();
+		self.update_chain_tips(&mut update, &parent_hash, header.hash.clone(), &best_block.hash);
+		update.insert(KeyValue::BlockChainwork(header.hash.clone(), chainwork));
+		update.insert(KeyValue::BlockHeader(header.hash.clone(), header.raw.clone()));
+
+		// side chain only becomes canon when it has more work than the canon chain
+		if chainwork <= best_chainwork {
+			self.db.write(update).map_err(Error::DatabaseError)?;
+			return Ok(false);
+		}
+
+		let mut canonized_route = vec![header.hash.clone()];
+		let mut next_hash = parent_hash;
+		let ancestor = loop {
+			match self.block_number(&next_hash) {
+				Some(number) => break number,
+				None => {
+					canonized_route.push(next_hash.clone());
+					next_hash = self.block_header(next_hash.into())
+						.expect("chainwork is only stored for blocks with known ancestors; qed")
+						.raw.previous_header_hash;
+				},
+			}
+		};
+
+		for number in ancestor + 1..best_block.number + 1 {
+			let hash = self.block_hash(number).expect("canon block number is less than best block number; qed");
+			// there's no undo data for headers => we can't revert blocks with bodies
+			if self.get(Key::BlockTransactions(hash.clone())).is_some() {
+				return Err(Error::CannotDecanonize);
+			}
+			update.delete(Key::BlockHash(number));
+			update.delete(Key::BlockNumber(hash));
+		}
+
+		let new_best_block = BestBlock {
+			number: ancestor + canonized_route.len() as u32,
+			hash: header.hash.clone(),
+		};
+		for (index, hash) in canonized_route.into_iter().rev().enumerate() {
+			let number = ancestor + index as u32 + 1;
+			update.insert(KeyValue::BlockHash(number, hash.clone()));
+			update.insert(KeyValue::BlockNumber(hash, number));
+		}
+
+		update.insert(KeyValue::Meta(KEY_BEST_BLOCK_HASH, serialize(&new_best_block.hash)));
+		update.insert(KeyValue::Meta(KEY_BEST_BLOCK_NUMBER, serialize(&new_best_block.number)));
+		self.db.write(update).map_err(Error::DatabaseError)?;
+
+		*best_block = new_best_block;
+		Ok(true)
+	}
+
+	/// Appends block transactions to the history of scripts, which outputs they fund and spend
+	fn index_scripts(&self, update: &mut DBTransaction, block: &IndexedBlock, height: u32, undo: &BlockUndo) {
+		for (script_hash, entries) in Self::script_history_entries(block, height, undo) {
+			for (index, entry) in entries.into_iter().enumerate() {
+				update.insert(KeyValue::ScriptHistory(ScriptHistoryKey {
+					script_hash: script_hash.clone(),
+					height: height,
+					index: index as u32,
+				}, entry));
+			}
+		}
+	}
+
+	/// Removes transactions of decanonized block from the history of scripts
+	fn unindex_scripts(&self, update: &mut DBTransaction, block: &IndexedBlock, height: u32, undo: &BlockUndo) {
+		for (script_hash, entries) in Self::script_history_entries(block, height, undo) {
+			for index in 0..entries.len() {
+				update.delete(Key::ScriptHistory(ScriptHistoryKey {
+					script_hash: script_hash.clone(),
+					height: height,
+					index: index as u32,
+				}));
+			}
+		}
+	}
+
+	/// Returns history entries of the block transactions, grouped by script and ordered within the block
+	fn script_history_entries(block: &IndexedBlock, height: u32, undo: &BlockUndo) -> HashMap<H256, Vec<ScriptHistoryEntry>> {
+		let spent_outputs: HashMap<&OutPoint, &TransactionOutput> = undo.spent.iter()
+			.map(|&(ref outpoint, ref entry)| (outpoint, entry.output()))
+			.collect();
+		let block_transactions: HashMap<&H256, &IndexedTransaction> = block.transactions.iter()
+			.map(|tx| (&tx.hash, tx))
+			.collect();
+
+		let mut history: HashMap<H256, Vec<ScriptHistoryEntry>> = HashMap::new();
+		for tx in &block.transactions {
+			for (output_index, output) in tx.raw.outputs.iter().enumerate() {
+				history.entry(script_hash(&output.script_pubkey)).or_insert_with(Vec::new).push(ScriptHistoryEntry {
+					tx_hash: tx.hash.clone(),
+					height: height,
+					kind: ScriptHistoryKind::Funding,
+					outpoint: OutPoint {
+						hash: tx.hash.clone(),
+						index: output_index as u32,
+					},
+					value: output.value,
+				});
+			}
+
+			if tx.raw.is_coinbase() {
+				continue;
+			}
+
+			for input in &tx.raw.inputs {
+				// spent output is either from the utxo set, or from the same block
+				let output = spent_outputs.get(&input.previous_output).cloned()
+					.or_else(|| block_transactions.get(&input.previous_output.hash)
+						.and_then(|prev_tx| prev_tx.raw.outputs.get(input.previous_output.index as usize)));
+				if let Some(output) = output {
+					history.entry(script_hash(&output.script_pubkey)).or_insert_with(Vec::new).push(ScriptHistoryEntry {
+						tx_hash: tx.hash.clone(),
+						height: height,
+						kind: ScriptHistoryKind::Spending,
+						outpoint: input.previous_output.clone(),
+						value: output.value,
+					});
+				}
+			}
+		}
+
+		history
+	}
+
+	/// Builds compact filter of the block, chaining its header to the filter of the parent block
+	fn index_block_filter(&self, update: &mut DBTransaction, block: &IndexedBlock, undo: &BlockUndo) {
+		let previous_header = if block.header.raw.previous_header_hash.is_zero() {
+			H256::default()
+		} else {
+			match self.block_filter(&block.header.raw.previous_header_hash) {
+				Some(previous_filter) => previous_filter.header,
+				None => {
+					// filters chain can only be built starting from the genesis block
+					trace!(target: "db", "Filter of the parent block is not found: {}", block.hash().reversed());
+					return;
+				},
+			}
+		};
+
+		// outputs, created and spent in the same block, are already among block outputs
+		let spent_scripts = undo.spent.iter().map(|&(_, ref entry)| -> &[u8] { &entry.output().script_pubkey });
+		let filter = BlockFilter::build_basic(block, spent_scripts, &previous_header);
+		update.insert(KeyValue::BlockFilter(block.hash().clone(), filter));
+	}
+
+	/// Prunes bodies of the oldest canon chain blocks until their total size fits into the target.
+	/// Headers, numbers and transactions meta are kept, so pruned blocks are still known to the chain.
+	fn prune(&self, update: &mut DBTransaction, best_block_number: u32, blocks_size: &mut u64, prune_target: u64) -> Result<(), Error> {
+		let mut pruned_height = self.pruned_height();
+		while *blocks_size > prune_target && pruned_height + MIN_BLOCKS_TO_KEEP <= best_block_number {
+			let block = match self.block_hash(pruned_height).and_then(|hash| self.block(hash.into())) {
+				Some(block) => block,
+				None => {
+					error!(target: "db", "Block is not found during pruning: {}", pruned_height);
+					return Err(Error::CannotCanonize);
+				},
+			};
+
+			trace!(target: "db", "prune block {} {}", pruned_height, block.hash().reversed());
+
+			update.delete(Key::BlockTransactions(block.hash().clone()));
+			update.delete(Key::BlockLocation(block.hash().clone()));
+			update.delete(Key::BlockUndo(block.hash().clone()));
+			for tx in &block.transactions {
+				update.delete(Key::Transaction(tx.hash.clone()));
+			}
+
+			*blocks_size = blocks_size.saturating_sub(block.size() as u64);
+			pruned_height += 1;
+		}
+
+		update.insert(KeyValue::Meta(KEY_PRUNED_HEIGHT, serialize(&pruned_height)));
+		Ok(())
+	}
+
+	/// Returns highest heights of blocks in block files, which have not been removed yet
+	fn block_files_heights(&self) -> BlockFilesHeights {
+		self.get(Key::Meta(KEY_BLOCK_FILES_HEIGHTS))
+			.and_then(Value::as_meta)
+			.map(|heights| deserialize(&**heights).expect("Inconsistent DB. Invalid block files heights."))
+			.unwrap_or_default()
+	}
+
+	/// Returns height of the block with given parent. Side chain blocks are followed back to the canon chain.
+	fn child_block_height(&self, parent_hash: &H256) -> u32 {
+		let mut height = 0;
+		let mut hash = parent_hash.clone();
+		while !hash.is_zero() {
+			if let Some(number) = self.block_number(&hash) {
+				return number + height + 1;
+			}
+
+			hash = match self.block_header(hash.into()) {
+				Some(header) => header.raw.previous_header_hash,
+				// file is kept at least until the current best block is pruned
+				None => return self.best_block().number + height + 1,
+			};
+			height += 1;
+		}
+		height
+	}
+
+	/// Removes block files, which only contain blocks below the pruned height.
+	/// Must be called after the pruned height is written, so that the index never references removed files.
+	fn remove_pruned_block_files(&self) -> Result<(), Error> {
+		let block_files = match (self.prune_target, self.block_files.as_ref()) {
+			(Some(_), Some(block_files)) => block_files,
+			_ => return Ok(()),
+		};
+
+		let mut heights = self.block_files_heights();
+		let removed = heights.remove_below(self.pruned_height(), block_files.current_file());
+		if removed.is_empty() {
+			return Ok(());
+		}
+
+		// removal of block locations must reach the disk before the files are gone
+		self.db.flush().map_err(Error::DatabaseError)?;
+		for number in removed {
+			trace!(target: "db", "remove pruned block file {}", number);
+			block_files.remove(number).map_err(Error::DatabaseError)?;
+		}
+
+		// files, which are already missing, are skipped if this write is lost
+		let mut update = DBTransaction::new();
+		update.insert(KeyValue::Meta(KEY_BLOCK_FILES_HEIGHTS, serialize(&heights)));
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Returns total size of canon chain block bodies, which are not pruned
+	fn blocks_size(&self) -> u64 {
+		self.get(Key::Meta(KEY_BLOCKS_SIZE))
+			.and_then(Value::as_meta)
+			.map(|size| deserialize(&**size).expect("Inconsistent DB. Invalid blocks size."))
+			.unwrap_or_default()
+	}
+
+	fn utxo(&self, outpoint: &OutPoint) -> Option<UtxoEntry> {
+		self.get(Key::Utxo(outpoint.clone()))
+			.and_then(Value::as_utxo)
+	}
+
+	/// Returns true if any output of the transaction, which is not spent according to the meta, is in the utxo set
+	fn has_unspent_outputs(&self, hash: &H256, meta: &TransactionMeta) -> bool {
+		(0..meta.outputs_len())
+			.filter(|index| meta.is_spent(*index) == Some(false))
+			.any(|index| self.utxo(&OutPoint { hash: hash.clone(), index: index as u32 }).is_some())
+	}
+
+	fn read_utxo_set_info(&self) -> UtxoSetInfo {
+		self.get(Key::Meta(KEY_UTXO_SET_INFO))
+			.and_then(Value::as_meta)
+			.map(|info| deserialize(&**info).expect("Inconsistent DB. Invalid utxo set info."))
+			.unwrap_or_default()
+	}
+
+	/// Reads block body from flat files, if it has been stored there
+	fn block_from_files(&self, hash: &H256) -> Option<IndexedBlock> {
+		let block_files = self.block_files.as_ref()?;
+		let location = self.get(Key::BlockLocation(hash.clone())).and_then(Value::as_block_location)?;
+		match block_files.read(&location) {
+			Ok(block) => Some(block),
+			Err(err) => {
+				error!(target: "db", "Cannot read block {} from {:?}: {}", hash.reversed(), location, err);
+				None
+			},
+		}
+	}
+
 	fn get(&self, key: Key) -> Option<Value> {
 		self.db.get(&key).expect("db value to be fine").into_option()
 	}
@@ -425,6 +1497,12 @@ impl<T> BlockHeaderProvider for BlockChainDatabase<T> where T: KeyValueDatabase
 					.map(|header| IndexedBlockHeader::new(block_hash, header))
 			})
 	}
+
+	fn block_chainwork(&self, block_ref: BlockRef) -> Option<U256> {
+		self.resolve_hash(block_ref)
+			.and_then(|block_hash| self.get(Key::BlockChainwork(block_hash)))
+			.and_then(Value::as_block_chainwork)
+	}
 }
 
 impl<T> BlockProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
@@ -442,9 +1520,19 @@ impl<T> BlockProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 		self.resolve_hash(block_ref)
 			.and_then(|block_hash| {
 				self.block_header(block_hash.clone().into())
-					.map(|header| {
-						let transactions = self.block_transactions(block_hash.into());
-						IndexedBlock::new(header, transactions)
+					.and_then(|header| {
+						if let Some(block) = self.block_from_files(&block_hash) {
+							return Some(block);
+						}
+
+						// body of pruned block is not available
+						self.get(Key::BlockTransactions(block_hash))
+							.and_then(Value::as_block_transactions)
+							.map(List::into)
+							.and_then(|hashes: Vec<H256>| hashes.into_iter()
+								.map(|hash| self.transaction(&hash))
+								.collect::<Option<Vec<_>>>())
+							.map(|transactions| IndexedBlock::new(header, transactions))
 					})
 			})
 	}
@@ -455,6 +1543,12 @@ impl<T> BlockProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 			.is_some()
 	}
 
+	fn is_block_pruned(&self, block_ref: BlockRef) -> bool {
+		self.resolve_hash(block_ref)
+			.map(|hash| self.get(Key::BlockHeader(hash.clone())).is_some() && self.get(Key::BlockTransactions(hash)).is_none())
+			.unwrap_or(false)
+	}
+
 	fn block_transaction_hashes(&self, block_ref: BlockRef) -> Vec<H256> {
 		self.resolve_hash(block_ref)
 			.and_then(|hash| self.get(Key::BlockTransactions(hash)))
@@ -464,6 +1558,10 @@ impl<T> BlockProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	}
 
 	fn block_transactions(&self, block_ref: BlockRef) -> Vec<IndexedTransaction> {
+		if let Some(block) = self.resolve_hash(block_ref.clone()).and_then(|hash| self.block_from_files(&hash)) {
+			return block.transactions;
+		}
+
 		self.block_transaction_hashes(block_ref)
 			.into_iter()
 			.filter_map(|hash| self.get(Key::Transaction(hash))
@@ -473,6 +1571,44 @@ impl<T> BlockProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	}
 }
 
+impl<T> ScriptHistoryProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn script_history(&self, script_hash: &H256) -> Vec<ScriptHistoryEntry> {
+		self.db.script_history(script_hash)
+			.map(|(_, entry)| entry)
+			.collect()
+	}
+
+	fn address_index_height(&self) -> Option<u32> {
+		self.index_height(KEY_ADDRESS_INDEX_HEIGHT)
+	}
+}
+
+impl<T> SpendingInputProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn spending_input(&self, outpoint: &OutPoint) -> Option<SpendingInput> {
+		self.get(Key::SpentBy(outpoint.clone()))
+			.and_then(Value::as_spent_by)
+	}
+
+	fn spent_index_height(&self) -> Option<u32> {
+		match self.spent_index {
+			// index is started by the next canonized block
+			true => Some(self.index_height(KEY_SPENT_INDEX_HEIGHT).unwrap_or_else(|| self.best_block().number + 1)),
+			false => None,
+		}
+	}
+}
+
+impl<T> BlockFilterProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn block_filter(&self, hash: &H256) -> Option<BlockFilter> {
+		self.get(Key::BlockFilter(hash.clone()))
+			.and_then(Value::as_block_filter)
+	}
+
+	fn block_filter_index_height(&self) -> Option<u32> {
+		self.index_height(KEY_BLOCK_FILTER_INDEX_HEIGHT)
+	}
+}
+
 impl<T> TransactionMetaProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn transaction_meta(&self, hash: &H256) -> Option<TransactionMeta> {
 		self.get(Key::TransactionMeta(hash.clone()))
@@ -486,17 +1622,29 @@ impl<T> TransactionProvider for BlockChainDatabase<T> where T: KeyValueDatabase
 	}
 
 	fn transaction(&self, hash: &H256) -> Option<IndexedTransaction> {
-		self.get(Key::Transaction(hash.clone()))
-			.and_then(Value::as_transaction)
-			.map(|tx| IndexedTransaction::new(*hash, tx))
+		if let Some(tx) = self.get(Key::Transaction(hash.clone())).and_then(Value::as_transaction) {
+			return Some(IndexedTransaction::new(*hash, tx));
+		}
+
+		// transactions of blocks in flat files are only found for the canon chain
+		self.transaction_meta(hash)
+			.and_then(|meta| self.block_hash(meta.height()))
+			.and_then(|block_hash| self.block_from_files(&block_hash))
+			.and_then(|block| block.transactions.into_iter().find(|tx| tx.hash == *hash))
 	}
 }
 
 impl<T> TransactionOutputProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn transaction_output(&self, prevout: &OutPoint, _transaction_index: usize) -> Option<TransactionOutput> {
-		// return previous transaction outputs only for canon chain transactions
+		// utxo set contains only canon chain transactions outputs
+		if let Some(entry) = self.utxo(prevout) {
+			return Some(entry.into_output());
+		}
+
+		// spent outputs are still returned, so that callers could detect double spends
 		self.transaction_meta(&prevout.hash)
-			.and_then(|_| self.transaction(&prevout.hash))
+			.and_then(|meta| meta.is_spent(prevout.index as usize))
+			.and_then(|is_spent| if is_spent { self.transaction(&prevout.hash) } else { None })
 			.and_then(|tx| tx.raw.outputs.into_iter().nth(prevout.index as usize))
 	}
 
@@ -541,7 +1689,44 @@ impl<T> Forkable for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn switch_to_fork<'a>(&self, fork: Box<dyn ForkChain + 'a>) -> Result<(), Error> {
 		let mut best_block = self.best_block.write();
 		*best_block = fork.store().best_block();
-		fork.flush()
+		fork.flush()?;
+		self.remove_pruned_block_files()
+	}
+}
+
+impl<T> UtxoSnapshotStore for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		self.db.utxos()
+	}
+
+	fn load_utxo_snapshot(
+		&self,
+		headers: Vec<IndexedBlockHeader>,
+		utxos: &mut dyn Iterator<Item = Result<(OutPoint, UtxoEntry), Error>>,
+	) -> Result<UtxoSetInfo, Error> {
+		BlockChainDatabase::load_utxo_snapshot(self, headers, utxos)
+	}
+
+	fn utxo_snapshot_validation(&self) -> Option<UtxoSnapshotValidation> {
+		BlockChainDatabase::utxo_snapshot_validation(self)
+	}
+
+	fn finish_utxo_snapshot_validation(&self, is_valid: bool) -> Result<(), Error> {
+		BlockChainDatabase::finish_utxo_snapshot_validation(self, is_valid)
+	}
+}
+
+impl<T> HeadersStore for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn insert_header(&self, header: &IndexedBlockHeader) -> Result<bool, Error> {
+		BlockChainDatabase::insert_header(self, header)
+	}
+
+	fn set_headers_only(&self) -> Result<(), Error> {
+		BlockChainDatabase::set_headers_only(self)
+	}
+
+	fn is_headers_only(&self) -> bool {
+		BlockChainDatabase::is_headers_only(self)
 	}
 }
 
@@ -549,6 +1734,10 @@ impl<T> CanonStore for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn as_store(&self) -> &dyn Store {
 		&*self
 	}
+
+	fn reindex_chainstate(&self) -> Result<(), Error> {
+		BlockChainDatabase::reindex_chainstate(self)
+	}
 }
 
 impl<T> Store for BlockChainDatabase<T> where T: KeyValueDatabase {
@@ -565,6 +1754,24 @@ impl<T> Store for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn difficulty(&self) -> f64 {
 		self.best_header().raw.bits.to_f64()
 	}
+
+	/// get statistics of the unspent outputs set along with the best block they are computed at
+	fn utxo_set_info(&self) -> (BestBlock, UtxoSetInfo) {
+		let best_block = self.best_block.read();
+		(best_block.clone(), self.read_utxo_set_info())
+	}
+
+	fn chain_tips(&self) -> Vec<H256> {
+		BlockChainDatabase::chain_tips(self)
+	}
+
+	fn pruned_height(&self) -> u32 {
+		BlockChainDatabase::pruned_height(self)
+	}
+
+	fn unspent_output(&self, outpoint: &OutPoint) -> Option<UtxoEntry> {
+		self.utxo(outpoint)
+	}
 }
 
 impl<T> ConfigStore for BlockChainDatabase<T> where T: KeyValueDatabase {
@@ -0,0 +1,298 @@
+//! Block bodies, appended to rotating flat files
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+use chain::{Block, IndexedBlock};
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader, serialize, deserialize};
+
+/// New file is started once the current one would grow beyond this size
+const MAX_BLOCK_FILE_SIZE: u64 = 128 * 1024 * 1024;
+/// Every block in the file is prefixed by its length
+const BLOCK_PREFIX_SIZE: u64 = 4;
+
+/// Location of the serialized block in block files
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockLocation {
+	/// Number of the file
+	pub file: u32,
+	/// Offset of the serialized block in the file
+	pub offset: u32,
+	/// Length of the serialized block
+	pub length: u32,
+}
+
+impl Serializable for BlockLocation {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.file)
+			.append(&self.offset)
+			.append(&self.length);
+	}
+}
+
+impl Deserializable for BlockLocation {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(BlockLocation {
+			file: reader.read()?,
+			offset: reader.read()?,
+			length: reader.read()?,
+		})
+	}
+}
+
+/// Highest heights of blocks in block files, which have not been removed yet
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlockFilesHeights {
+	/// Number of the first file, which has not been removed
+	pub first_file: u32,
+	/// Highest height of block in every file, starting with the first one
+	pub heights: Vec<u32>,
+}
+
+impl BlockFilesHeights {
+	/// Records that the block of given height has been appended to given file
+	pub fn insert(&mut self, file: u32, height: u32) {
+		if file < self.first_file {
+			return;
+		}
+
+		let index = (file - self.first_file) as usize;
+		// heights of files, written before heights have been tracked, are unknown, so these files are never removed
+		while self.heights.len() < index {
+			self.heights.push(u32::max_value());
+		}
+		match self.heights.get_mut(index) {
+			Some(max_height) => *max_height = ::std::cmp::max(*max_height, height),
+			None => self.heights.push(height),
+		}
+	}
+
+	/// Forgets the oldest files, which only contain blocks below given height, and returns their numbers.
+	/// File, which blocks are currently appended to, is never forgotten.
+	pub fn remove_below(&mut self, height: u32, current_file: u32) -> Vec<u32> {
+		let mut removed = Vec::new();
+		while self.first_file < current_file && self.heights.first().map_or(false, |max_height| *max_height < height) {
+			self.heights.remove(0);
+			removed.push(self.first_file);
+			self.first_file += 1;
+		}
+		removed
+	}
+}
+
+impl Serializable for BlockFilesHeights {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.first_file)
+			.append_list(&self.heights);
+	}
+}
+
+impl Deserializable for BlockFilesHeights {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(BlockFilesHeights {
+			first_file: reader.read()?,
+			heights: reader.read_list()?,
+		})
+	}
+}
+
+/// File, which blocks are currently appended to
+struct CurrentFile {
+	number: u32,
+	file: File,
+	size: u64,
+}
+
+/// Stores serialized blocks in `blkNNNNN.dat` files, so that the whole block is read at once.
+/// Files are append-only: blocks are never removed from them, but the whole file is removed once all its blocks are pruned.
+pub struct BlockFiles {
+	path: PathBuf,
+	current: Mutex<CurrentFile>,
+}
+
+impl BlockFiles {
+	/// Opens block files in given directory. Blocks are appended to the last existing file.
+	pub fn open<P>(path: P) -> Result<Self, String> where P: AsRef<Path> {
+		let path = path.as_ref().to_path_buf();
+		fs::create_dir_all(&path).map_err(|err| err.to_string())?;
+
+		let mut number = 0;
+		for entry in fs::read_dir(&path).map_err(|err| err.to_string())? {
+			let file_name = entry.map_err(|err| err.to_string())?.file_name();
+			if let Some(file_number) = file_name.to_str().and_then(parse_file_name) {
+				number = ::std::cmp::max(number, file_number);
+			}
+		}
+
+		let current = open_file(&path, number)?;
+		Ok(BlockFiles {
+			path: path,
+			current: Mutex::new(current),
+		})
+	}
+
+	/// Appends block to the current file and returns its location
+	pub fn append(&self, block: &IndexedBlock) -> Result<BlockLocation, String> {
+		let block = serialize(&Block::new(block.header.raw.clone(), block.transactions.iter().map(|tx| tx.raw.clone()).collect()));
+		let length = block.len() as u64;
+
+		let mut current = self.current.lock();
+		if current.size != 0 && current.size + BLOCK_PREFIX_SIZE + length > MAX_BLOCK_FILE_SIZE {
+			let number = current.number + 1;
+			*current = open_file(&self.path, number)?;
+		}
+
+		let offset = current.size + BLOCK_PREFIX_SIZE;
+		if offset > u32::max_value() as u64 || length > u32::max_value() as u64 {
+			return Err(format!("Block of {} bytes does not fit into block file {} of {} bytes", length, current.number, current.size));
+		}
+
+		let location = BlockLocation {
+			file: current.number,
+			offset: offset as u32,
+			length: length as u32,
+		};
+
+		current.file.write_all(&serialize(&location.length)).map_err(|err| err.to_string())?;
+		current.file.write_all(&block).map_err(|err| err.to_string())?;
+		// block must be on disk before the index, which references it, is written
+		current.file.flush().map_err(|err| err.to_string())?;
+		current.file.sync_data().map_err(|err| err.to_string())?;
+		current.size += BLOCK_PREFIX_SIZE + length;
+		Ok(location)
+	}
+
+	/// Returns number of the file, which blocks are currently appended to
+	pub fn current_file(&self) -> u32 {
+		self.current.lock().number
+	}
+
+	/// Removes file with given number. Missing file is not an error.
+	pub fn remove(&self, number: u32) -> Result<(), String> {
+		match fs::remove_file(file_path(&self.path, number)) {
+			Err(ref err) if err.kind() != io::ErrorKind::NotFound => Err(err.to_string()),
+			_ => Ok(()),
+		}
+	}
+
+	/// Reads block from given location
+	pub fn read(&self, location: &BlockLocation) -> Result<IndexedBlock, String> {
+		let mut file = File::open(file_path(&self.path, location.file)).map_err(|err| err.to_string())?;
+		file.seek(SeekFrom::Start(location.offset as u64)).map_err(|err| err.to_string())?;
+		let mut block = vec![0u8; location.length as usize];
+		file.read_exact(&mut block).map_err(|err| err.to_string())?;
+		deserialize::<_, Block>(block.as_slice())
+			.map(IndexedBlock::from_raw)
+			.map_err(|err| format!("{:?}", err))
+	}
+}
+
+fn file_path(path: &Path, number: u32) -> PathBuf {
+	path.join(format!("blk{:05}.dat", number))
+}
+
+fn parse_file_name(file_name: &str) -> Option<u32> {
+	if file_name.starts_with("blk") && file_name.ends_with(".dat") {
+		file_name[3..file_name.len() - 4].parse().ok()
+	} else {
+		None
+	}
+}
+
+fn open_file(path: &Path, number: u32) -> Result<CurrentFile, String> {
+	let file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(file_path(path, number))
+		.map_err(|err| err.to_string())?;
+	let size = file.metadata().map_err(|err| err.to_string())?.len();
+	Ok(CurrentFile {
+		number: number,
+		file: file,
+		size: size,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	extern crate test_data;
+
+	use chain::IndexedBlock;
+	use self::tempdir::TempDir;
+	use ser::{serialize, deserialize};
+	use super::{BlockFiles, BlockFilesHeights, BlockLocation, MAX_BLOCK_FILE_SIZE, file_path};
+
+	#[test]
+	fn test_block_files_append_read() {
+		let dir = TempDir::new("block_files").unwrap();
+		let b0: IndexedBlock = test_data::block_h0().into();
+		let b1: IndexedBlock = test_data::block_h1().into();
+
+		let files = BlockFiles::open(dir.path()).unwrap();
+		let l0 = files.append(&b0).unwrap();
+		let l1 = files.append(&b1).unwrap();
+		assert_eq!(l0, BlockLocation { file: 0, offset: 4, length: b0.size() as u32 });
+		assert_eq!(l1, BlockLocation { file: 0, offset: 8 + b0.size() as u32, length: b1.size() as u32 });
+		assert_eq!(files.read(&l1).unwrap(), b1);
+		drop(files);
+
+		// blocks are appended to the end of the last file after reopening
+		let files = BlockFiles::open(dir.path()).unwrap();
+		files.current.lock().size = MAX_BLOCK_FILE_SIZE;
+		let l2 = files.append(&b0).unwrap();
+		assert_eq!(l2, BlockLocation { file: 1, offset: 4, length: b0.size() as u32 });
+		assert_eq!(files.read(&l0).unwrap(), b0);
+		assert_eq!(files.read(&l2).unwrap(), b0);
+
+		let files = BlockFiles::open(dir.path()).unwrap();
+		assert_eq!(files.current.lock().number, 1);
+	}
+
+	#[test]
+	fn test_block_files_heights() {
+		let mut heights = BlockFilesHeights::default();
+		heights.insert(0, 0);
+		heights.insert(0, 5);
+		// side chain block, which is lower than the best block of the file
+		heights.insert(0, 3);
+		heights.insert(2, 20);
+		heights.insert(2, 10);
+		assert_eq!(heights, BlockFilesHeights { first_file: 0, heights: vec![5, u32::max_value(), 20] });
+		assert_eq!(deserialize::<_, BlockFilesHeights>(serialize(&heights).as_ref()).unwrap(), heights);
+
+		// file with unknown heights is never removed
+		assert_eq!(heights.remove_below(100, 3), vec![0]);
+		assert_eq!(heights, BlockFilesHeights { first_file: 1, heights: vec![u32::max_value(), 20] });
+
+		// the current file is never removed
+		let mut heights = BlockFilesHeights::default();
+		heights.insert(0, 5);
+		heights.insert(1, 10);
+		assert_eq!(heights.remove_below(5, 1), vec![]);
+		assert_eq!(heights.remove_below(11, 1), vec![0]);
+		assert_eq!(heights.remove_below(11, 1), vec![]);
+		assert_eq!(heights, BlockFilesHeights { first_file: 1, heights: vec![10] });
+	}
+
+	#[test]
+	fn test_block_files_remove() {
+		let dir = TempDir::new("block_files").unwrap();
+		let b0: IndexedBlock = test_data::block_h0().into();
+
+		let files = BlockFiles::open(dir.path()).unwrap();
+		files.append(&b0).unwrap();
+		files.current.lock().size = MAX_BLOCK_FILE_SIZE;
+		files.append(&b0).unwrap();
+		assert_eq!(files.current_file(), 1);
+
+		files.remove(0).unwrap();
+		assert!(!file_path(dir.path(), 0).exists());
+		assert!(file_path(dir.path(), 1).exists());
+		// missing file is not an error
+		files.remove(0).unwrap();
+	}
+}
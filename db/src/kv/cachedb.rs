@@ -1,7 +1,8 @@
 use lru_cache::LruCache;
 use parking_lot::Mutex;
 use hash::H256;
-use chain::BlockHeader;
+use chain::{BlockHeader, OutPoint};
+use storage::{UtxoEntry, ScriptHistoryEntry, ScriptHistoryKey};
 use kv::{KeyValueDatabase, KeyState, Operation, KeyValue, Key, Value, Transaction};
 
 pub struct CacheDatabase<T> where T: KeyValueDatabase {
@@ -44,4 +45,16 @@ impl<T> KeyValueDatabase for CacheDatabase<T> where T: KeyValueDatabase {
 		}
 		self.db.get(key)
 	}
+
+	fn flush(&self) -> Result<(), String> {
+		self.db.flush()
+	}
+
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		self.db.utxos()
+	}
+
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> {
+		self.db.script_history(script_hash)
+	}
 }
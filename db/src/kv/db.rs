@@ -1,7 +1,21 @@
+use hash::H256;
+use chain::OutPoint;
+use storage::{UtxoEntry, ScriptHistoryEntry, ScriptHistoryKey};
 use kv::{Transaction, KeyState, Key, Value};
 
 pub trait KeyValueDatabase: Send + Sync {
 	fn write(&self, tx: Transaction) -> Result<(), String>;
 
 	fn get(&self, key: &Key) -> Result<KeyState<Value>, String>;
+
+	/// Writes changes, buffered in memory, to the underlying database
+	fn flush(&self) -> Result<(), String> {
+		Ok(())
+	}
+
+	/// Iterates over all entries of the unspent outputs set in unspecified order
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a>;
+
+	/// Iterates over history entries of the script, ordered by height
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a>;
 }
@@ -3,12 +3,16 @@
 use std::collections::HashMap;
 use std::path::Path;
 use rocksdb::{
-	DB, Writable, WriteBatch, WriteOptions, IteratorMode, DBIterator,
+	DB, Writable, WriteBatch, WriteOptions, IteratorMode, DBIterator, Direction,
 	Options, DBCompactionStyle, BlockBasedOptions, Cache, Column, ReadOptions
 };
 use bytes::Bytes;
+use hash::H256;
+use chain::OutPoint;
+use ser::{serialize, deserialize};
+use storage::{UtxoEntry, ScriptHistoryEntry, ScriptHistoryKey};
 use kv::{Transaction, RawTransaction, RawOperation, Location, Value, KeyValueDatabase, Key, KeyState, RawKeyValue,
-RawKey};
+RawKey, COL_UTXOS, COL_SCRIPT_HISTORY};
 
 const DB_BACKGROUND_FLUSHES: i32 = 2;
 const DB_BACKGROUND_COMPACTIONS: i32 = 2;
@@ -132,6 +136,22 @@ impl KeyValueDatabase for Database {
 			None => Ok(KeyState::Unknown)
 		}
 	}
+
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		Box::new(self.iter(Location::Column(COL_UTXOS))
+			.map(|(key, value)| (
+				deserialize(&*key).expect("Inconsistent DB. Invalid utxo key."),
+				deserialize(&*value).expect("Inconsistent DB. Invalid utxo entry."),
+			)))
+	}
+
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> {
+		Box::new(self.iter_from_prefix(Location::Column(COL_SCRIPT_HISTORY), serialize(script_hash).take())
+			.map(|(key, value)| (
+				deserialize(&*key).expect("Inconsistent DB. Invalid script history key."),
+				deserialize(&*value).expect("Inconsistent DB. Invalid script history entry."),
+			)))
+	}
 }
 
 impl Database {
@@ -292,6 +312,17 @@ impl Database {
 			}
 		}
 	}
+
+	/// Iterates over entries, which keys start with given prefix, in the order of keys
+	pub fn iter_from_prefix(&self, location: Location, prefix: Vec<u8>) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> {
+		let DBAndColumns { ref db, ref cfs } = self.db;
+		let iter = match location {
+			Location::DB => db.iterator_opt(IteratorMode::From(&prefix, Direction::Forward), &self.read_opts),
+			Location::Column(column) => db.iterator_cf_opt(cfs[column as usize], IteratorMode::From(&prefix, Direction::Forward), &self.read_opts)
+				.expect("iterator params are valid; qed"),
+		};
+		iter.take_while(move |&(ref key, _)| key.starts_with(&prefix))
+	}
 }
 
 #[cfg(test)]
@@ -339,6 +370,15 @@ mod tests {
 		transaction.delete_raw(Location::DB, key1);
 		db.write(transaction).unwrap();
 		assert_eq!(&*db.get(&RawKey::new(Location::DB, key3 as &[u8])).unwrap().unwrap(), b"elephant");
+
+		let mut batch = RawTransaction::default();
+		batch.insert_raw(Location::DB, b"kez1", b"fox");
+		db.write(batch).unwrap();
+
+		let contents: Vec<_> = db.iter_from_prefix(Location::DB, b"key".to_vec()).collect();
+		assert_eq!(contents.len(), 2);
+		assert_eq!(&*contents[0].0, &*key2);
+		assert_eq!(&*contents[1].0, &*key3);
 	}
 
 	#[test]
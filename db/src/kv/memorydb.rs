@@ -1,13 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::sync::Arc;
 use std::mem::replace;
 use parking_lot::RwLock;
 use hash::H256;
+use primitives::bigint::U256;
 use bytes::Bytes;
 use ser::List;
-use chain::{Transaction as ChainTransaction, BlockHeader};
+use chain::{Transaction as ChainTransaction, BlockHeader, OutPoint};
 use kv::{Transaction, Key, KeyState, Operation, Value, KeyValueDatabase, KeyValue};
-use storage::{TransactionMeta};
+use storage::{TransactionMeta, UtxoEntry, BlockUndo, ScriptHistoryEntry, ScriptHistoryKey, SpendingInput, BlockFilter};
+use block_files::BlockLocation;
 
 #[derive(Default, Debug)]
 struct InnerDatabase {
@@ -19,6 +21,13 @@ struct InnerDatabase {
 	transaction_meta: HashMap<H256, KeyState<TransactionMeta>>,
 	block_number: HashMap<H256, KeyState<u32>>,
 	configuration: HashMap<&'static str, KeyState<Bytes>>,
+	utxo: HashMap<OutPoint, KeyState<UtxoEntry>>,
+	block_undo: HashMap<H256, KeyState<BlockUndo>>,
+	script_history: BTreeMap<ScriptHistoryKey, KeyState<ScriptHistoryEntry>>,
+	spent_by: HashMap<OutPoint, KeyState<SpendingInput>>,
+	block_filter: HashMap<H256, KeyState<BlockFilter>>,
+	block_chainwork: HashMap<H256, KeyState<U256>>,
+	block_location: HashMap<H256, KeyState<BlockLocation>>,
 }
 
 #[derive(Default, Debug)]
@@ -53,6 +62,27 @@ impl MemoryDatabase {
 		let configuration = replace(&mut db.configuration, HashMap::default()).into_iter()
 			.flat_map(|(key, state)| state.into_operation(key, KeyValue::Configuration, Key::Configuration));
 
+		let utxo = replace(&mut db.utxo, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::Utxo, Key::Utxo));
+
+		let block_undo = replace(&mut db.block_undo, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::BlockUndo, Key::BlockUndo));
+
+		let script_history = replace(&mut db.script_history, BTreeMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::ScriptHistory, Key::ScriptHistory));
+
+		let spent_by = replace(&mut db.spent_by, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::SpentBy, Key::SpentBy));
+
+		let block_filter = replace(&mut db.block_filter, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::BlockFilter, Key::BlockFilter));
+
+		let block_chainwork = replace(&mut db.block_chainwork, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::BlockChainwork, Key::BlockChainwork));
+
+		let block_location = replace(&mut db.block_location, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::BlockLocation, Key::BlockLocation));
+
 		Transaction {
 			operations: meta
 				.chain(block_hash)
@@ -62,6 +92,13 @@ impl MemoryDatabase {
 				.chain(transaction_meta)
 				.chain(block_number)
 				.chain(configuration)
+				.chain(utxo)
+				.chain(block_undo)
+				.chain(script_history)
+				.chain(spent_by)
+				.chain(block_filter)
+				.chain(block_chainwork)
+				.chain(block_location)
 				.collect()
 		}
 	}
@@ -81,6 +118,13 @@ impl KeyValueDatabase for MemoryDatabase {
 					KeyValue::TransactionMeta(key, value) => { db.transaction_meta.insert(key, KeyState::Insert(value)); },
 					KeyValue::BlockNumber(key, value) => { db.block_number.insert(key, KeyState::Insert(value)); },
 					KeyValue::Configuration(key, value) => { db.configuration.insert(key, KeyState::Insert(value)); },
+					KeyValue::Utxo(key, value) => { db.utxo.insert(key, KeyState::Insert(value)); },
+					KeyValue::BlockUndo(key, value) => { db.block_undo.insert(key, KeyState::Insert(value)); },
+					KeyValue::ScriptHistory(key, value) => { db.script_history.insert(key, KeyState::Insert(value)); },
+					KeyValue::SpentBy(key, value) => { db.spent_by.insert(key, KeyState::Insert(value)); },
+					KeyValue::BlockFilter(key, value) => { db.block_filter.insert(key, KeyState::Insert(value)); },
+					KeyValue::BlockChainwork(key, value) => { db.block_chainwork.insert(key, KeyState::Insert(value)); },
+					KeyValue::BlockLocation(key, value) => { db.block_location.insert(key, KeyState::Insert(value)); },
 				},
 				Operation::Delete(delete) => match delete {
 					Key::Meta(key) => { db.meta.insert(key, KeyState::Delete); }
@@ -91,6 +135,13 @@ impl KeyValueDatabase for MemoryDatabase {
 					Key::TransactionMeta(key) => { db.transaction_meta.insert(key, KeyState::Delete); }
 					Key::BlockNumber(key) => { db.block_number.insert(key, KeyState::Delete); }
 					Key::Configuration(key) => { db.configuration.insert(key, KeyState::Delete); }
+					Key::Utxo(key) => { db.utxo.insert(key, KeyState::Delete); }
+					Key::BlockUndo(key) => { db.block_undo.insert(key, KeyState::Delete); }
+					Key::ScriptHistory(key) => { db.script_history.insert(key, KeyState::Delete); }
+					Key::SpentBy(key) => { db.spent_by.insert(key, KeyState::Delete); }
+					Key::BlockFilter(key) => { db.block_filter.insert(key, KeyState::Delete); }
+					Key::BlockChainwork(key) => { db.block_chainwork.insert(key, KeyState::Delete); }
+					Key::BlockLocation(key) => { db.block_location.insert(key, KeyState::Delete); }
 				}
 			}
 		}
@@ -108,10 +159,46 @@ impl KeyValueDatabase for MemoryDatabase {
 			Key::TransactionMeta(ref key) => db.transaction_meta.get(key).cloned().unwrap_or_default().map(Value::TransactionMeta),
 			Key::BlockNumber(ref key) => db.block_number.get(key).cloned().unwrap_or_default().map(Value::BlockNumber),
 			Key::Configuration(ref key) => db.configuration.get(key).cloned().unwrap_or_default().map(Value::Configuration),
+			Key::Utxo(ref key) => db.utxo.get(key).cloned().unwrap_or_default().map(Value::Utxo),
+			Key::BlockUndo(ref key) => db.block_undo.get(key).cloned().unwrap_or_default().map(Value::BlockUndo),
+			Key::ScriptHistory(ref key) => db.script_history.get(key).cloned().unwrap_or_default().map(Value::ScriptHistory),
+			Key::SpentBy(ref key) => db.spent_by.get(key).cloned().unwrap_or_default().map(Value::SpentBy),
+			Key::BlockFilter(ref key) => db.block_filter.get(key).cloned().unwrap_or_default().map(Value::BlockFilter),
+			Key::BlockChainwork(ref key) => db.block_chainwork.get(key).cloned().unwrap_or_default().map(Value::BlockChainwork),
+			Key::BlockLocation(ref key) => db.block_location.get(key).cloned().unwrap_or_default().map(Value::BlockLocation),
 		};
 
 		Ok(result)
 	}
+
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		let utxos: Vec<_> = self.db.read().utxo.iter()
+			.filter_map(|(outpoint, state)| state.clone().into_option().map(|entry| (outpoint.clone(), entry)))
+			.collect();
+		Box::new(utxos.into_iter())
+	}
+
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> {
+		let history = self.script_history_states(script_hash).into_iter()
+			.filter_map(|(key, state)| state.into_option().map(|entry| (key, entry)));
+		Box::new(history)
+	}
+}
+
+impl MemoryDatabase {
+	/// Returns all changes of the unspent outputs set
+	pub fn utxo_states(&self) -> HashMap<OutPoint, KeyState<UtxoEntry>> {
+		self.db.read().utxo.clone()
+	}
+
+	/// Returns all changes of the script history, ordered by height and index of the entry
+	pub fn script_history_states(&self, script_hash: &H256) -> BTreeMap<ScriptHistoryKey, KeyState<ScriptHistoryEntry>> {
+		let first = ScriptHistoryKey { script_hash: script_hash.clone(), height: 0, index: 0 };
+		let last = ScriptHistoryKey { script_hash: script_hash.clone(), height: u32::max_value(), index: u32::max_value() };
+		self.db.read().script_history.range(first..=last)
+			.map(|(key, state)| (key.clone(), state.clone()))
+			.collect()
+	}
 }
 
 #[derive(Debug)]
@@ -143,4 +230,12 @@ impl KeyValueDatabase for SharedMemoryDatabase {
 	fn get(&self, key: &Key) -> Result<KeyState<Value>, String> {
 		self.db.get(key)
 	}
+
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		self.db.utxos()
+	}
+
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> {
+		self.db.script_history(script_hash)
+	}
 }
@@ -14,5 +14,7 @@ pub use self::transaction::{
 	RawTransaction, Transaction, RawOperation, Operation, Location, KeyState,
 	Key, Value, KeyValue, RawKeyValue, RawKey,
 	COL_COUNT, COL_META, COL_BLOCK_HASHES, COL_BLOCK_HEADERS, COL_BLOCK_TRANSACTIONS,
-	COL_TRANSACTIONS, COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS
+	COL_TRANSACTIONS, COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS, COL_UTXOS, COL_BLOCK_UNDOS,
+	COL_SCRIPT_HISTORY, COL_SPENT_BY, COL_BLOCK_FILTERS, COL_BLOCK_CHAINWORK,
+	COL_BLOCK_LOCATIONS,
 };
@@ -1,4 +1,7 @@
 use parking_lot::Mutex;
+use hash::H256;
+use chain::OutPoint;
+use storage::{UtxoEntry, ScriptHistoryEntry, ScriptHistoryKey};
 use kv::{Transaction, Value, KeyValueDatabase, MemoryDatabase, KeyState, Key};
 
 pub struct OverlayDatabase<'a, T> where T: 'a + KeyValueDatabase {
@@ -30,6 +33,14 @@ impl<'a, T> KeyValueDatabase for OverlayDatabase<'a, T> where T: 'a + KeyValueDa
 			exists => Ok(exists)
 		}
 	}
+
+	fn utxos<'b>(&'b self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'b> {
+		overlay_utxos(&self.overlay, self.db)
+	}
+
+	fn script_history<'b>(&'b self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'b> {
+		overlay_script_history(&self.overlay, self.db, script_hash)
+	}
 }
 
 pub struct AutoFlushingOverlayDatabase<T> where T: KeyValueDatabase {
@@ -48,10 +59,6 @@ impl<T> AutoFlushingOverlayDatabase<T> where T: KeyValueDatabase {
 			max_operations: max_operations,
 		}
 	}
-
-	fn flush(&self) -> Result<(), String> {
-		self.db.write(self.overlay.drain_transaction())
-	}
 }
 
 impl<T> KeyValueDatabase for AutoFlushingOverlayDatabase<T> where T: KeyValueDatabase {
@@ -72,6 +79,19 @@ impl<T> KeyValueDatabase for AutoFlushingOverlayDatabase<T> where T: KeyValueDat
 			exists => Ok(exists)
 		}
 	}
+
+	fn flush(&self) -> Result<(), String> {
+		self.db.write(self.overlay.drain_transaction())?;
+		self.db.flush()
+	}
+
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> {
+		overlay_utxos(&self.overlay, &self.db)
+	}
+
+	fn script_history<'a>(&'a self, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> {
+		overlay_script_history(&self.overlay, &self.db, script_hash)
+	}
 }
 
 impl<T> Drop for AutoFlushingOverlayDatabase<T> where T: KeyValueDatabase {
@@ -79,3 +99,25 @@ impl<T> Drop for AutoFlushingOverlayDatabase<T> where T: KeyValueDatabase {
 		self.flush().expect("Failed to save database");
 	}
 }
+
+/// Iterates over unspent outputs of the database with overlay changes applied
+fn overlay_utxos<'a, T>(overlay: &MemoryDatabase, db: &'a T) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a> where T: KeyValueDatabase {
+	let changes = overlay.utxo_states();
+	let inserted: Vec<_> = changes.iter()
+		.filter_map(|(outpoint, state)| state.clone().into_option().map(|entry| (outpoint.clone(), entry)))
+		.collect();
+	let unchanged = db.utxos()
+		.filter(move |&(ref outpoint, _)| !changes.contains_key(outpoint));
+	Box::new(unchanged.chain(inserted))
+}
+
+/// Iterates over history entries of the script in the database with overlay changes applied
+fn overlay_script_history<'a, T>(overlay: &MemoryDatabase, db: &'a T, script_hash: &H256) -> Box<dyn Iterator<Item = (ScriptHistoryKey, ScriptHistoryEntry)> + 'a> where T: KeyValueDatabase {
+	let changes = overlay.script_history_states(script_hash);
+	let mut history: Vec<_> = db.script_history(script_hash)
+		.filter(|&(ref key, _)| !changes.contains_key(key))
+		.chain(changes.iter().filter_map(|(key, state)| state.clone().into_option().map(|entry| (key.clone(), entry))))
+		.collect();
+	history.sort_by_key(|&(ref key, _)| (key.height, key.index));
+	Box::new(history.into_iter())
+}
@@ -1,10 +1,12 @@
 use bytes::Bytes;
 use hash::H256;
+use primitives::bigint::U256;
 use ser::{serialize, List, deserialize};
-use chain::{Transaction as ChainTransaction, BlockHeader};
-use storage::{TransactionMeta};
+use chain::{Transaction as ChainTransaction, BlockHeader, OutPoint};
+use storage::{TransactionMeta, UtxoEntry, BlockUndo, ScriptHistoryEntry, ScriptHistoryKey, SpendingInput, BlockFilter};
+use block_files::BlockLocation;
 
-pub const COL_COUNT: u32 = 10;
+pub const COL_COUNT: u32 = 15;
 pub const COL_META: u32 = 0;
 pub const COL_BLOCK_HASHES: u32 = 1;
 pub const COL_BLOCK_HEADERS: u32 = 2;
@@ -13,6 +15,13 @@ pub const COL_TRANSACTIONS: u32 = 4;
 pub const COL_TRANSACTIONS_META: u32 = 5;
 pub const COL_BLOCK_NUMBERS: u32 = 6;
 pub const COL_CONFIGURATION: u32 = 7;
+pub const COL_UTXOS: u32 = 8;
+pub const COL_BLOCK_UNDOS: u32 = 9;
+pub const COL_SCRIPT_HISTORY: u32 = 10;
+pub const COL_SPENT_BY: u32 = 11;
+pub const COL_BLOCK_FILTERS: u32 = 12;
+pub const COL_BLOCK_CHAINWORK: u32 = 13;
+pub const COL_BLOCK_LOCATIONS: u32 = 14;
 
 #[derive(Debug)]
 pub enum Operation {
@@ -30,6 +39,13 @@ pub enum KeyValue {
 	TransactionMeta(H256, TransactionMeta),
 	BlockNumber(H256, u32),
 	Configuration(&'static str, Bytes),
+	Utxo(OutPoint, UtxoEntry),
+	BlockUndo(H256, BlockUndo),
+	ScriptHistory(ScriptHistoryKey, ScriptHistoryEntry),
+	SpentBy(OutPoint, SpendingInput),
+	BlockFilter(H256, BlockFilter),
+	BlockChainwork(H256, U256),
+	BlockLocation(H256, BlockLocation),
 }
 
 #[derive(Debug)]
@@ -42,6 +58,13 @@ pub enum Key {
 	TransactionMeta(H256),
 	BlockNumber(H256),
 	Configuration(&'static str),
+	Utxo(OutPoint),
+	BlockUndo(H256),
+	ScriptHistory(ScriptHistoryKey),
+	SpentBy(OutPoint),
+	BlockFilter(H256),
+	BlockChainwork(H256),
+	BlockLocation(H256),
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +77,13 @@ pub enum Value {
 	TransactionMeta(TransactionMeta),
 	BlockNumber(u32),
 	Configuration(Bytes),
+	Utxo(UtxoEntry),
+	BlockUndo(BlockUndo),
+	ScriptHistory(ScriptHistoryEntry),
+	SpentBy(SpendingInput),
+	BlockFilter(BlockFilter),
+	BlockChainwork(U256),
+	BlockLocation(BlockLocation),
 }
 
 impl Value {
@@ -67,6 +97,13 @@ impl Value {
 			Key::TransactionMeta(_) => deserialize(bytes).map(Value::TransactionMeta),
 			Key::BlockNumber(_) => deserialize(bytes).map(Value::BlockNumber),
 			Key::Configuration(_) => deserialize(bytes).map(Value::Configuration),
+			Key::Utxo(_) => deserialize(bytes).map(Value::Utxo),
+			Key::BlockUndo(_) => deserialize(bytes).map(Value::BlockUndo),
+			Key::ScriptHistory(_) => deserialize(bytes).map(Value::ScriptHistory),
+			Key::SpentBy(_) => deserialize(bytes).map(Value::SpentBy),
+			Key::BlockFilter(_) => deserialize(bytes).map(Value::BlockFilter),
+			Key::BlockChainwork(_) => deserialize(bytes).map(Value::BlockChainwork),
+			Key::BlockLocation(_) => deserialize(bytes).map(Value::BlockLocation),
 		}.map_err(|e| format!("{:?}", e))
 	}
 
@@ -125,6 +162,55 @@ impl Value {
 			_ => None,
 		}
 	}
+
+	pub fn as_utxo(self) -> Option<UtxoEntry> {
+		match self {
+			Value::Utxo(entry) => Some(entry),
+			_ => None,
+		}
+	}
+
+	pub fn as_block_undo(self) -> Option<BlockUndo> {
+		match self {
+			Value::BlockUndo(undo) => Some(undo),
+			_ => None,
+		}
+	}
+
+	pub fn as_script_history(self) -> Option<ScriptHistoryEntry> {
+		match self {
+			Value::ScriptHistory(entry) => Some(entry),
+			_ => None,
+		}
+	}
+
+	pub fn as_spent_by(self) -> Option<SpendingInput> {
+		match self {
+			Value::SpentBy(input) => Some(input),
+			_ => None,
+		}
+	}
+
+	pub fn as_block_filter(self) -> Option<BlockFilter> {
+		match self {
+			Value::BlockFilter(filter) => Some(filter),
+			_ => None,
+		}
+	}
+
+	pub fn as_block_chainwork(self) -> Option<U256> {
+		match self {
+			Value::BlockChainwork(chainwork) => Some(chainwork),
+			_ => None,
+		}
+	}
+
+	pub fn as_block_location(self) -> Option<BlockLocation> {
+		match self {
+			Value::BlockLocation(location) => Some(location),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -228,6 +314,13 @@ impl<'a> From<&'a KeyValue> for RawKeyValue {
 			KeyValue::TransactionMeta(ref key, ref value) => (COL_TRANSACTIONS_META, serialize(key), serialize(value)),
 			KeyValue::BlockNumber(ref key, ref value) => (COL_BLOCK_NUMBERS, serialize(key), serialize(value)),
 			KeyValue::Configuration(ref key, ref value) => (COL_CONFIGURATION, serialize(key), serialize(value)),
+			KeyValue::Utxo(ref key, ref value) => (COL_UTXOS, serialize(key), serialize(value)),
+			KeyValue::BlockUndo(ref key, ref value) => (COL_BLOCK_UNDOS, serialize(key), serialize(value)),
+			KeyValue::ScriptHistory(ref key, ref value) => (COL_SCRIPT_HISTORY, serialize(key), serialize(value)),
+			KeyValue::SpentBy(ref key, ref value) => (COL_SPENT_BY, serialize(key), serialize(value)),
+			KeyValue::BlockFilter(ref key, ref value) => (COL_BLOCK_FILTERS, serialize(key), serialize(value)),
+			KeyValue::BlockChainwork(ref key, ref value) => (COL_BLOCK_CHAINWORK, serialize(key), serialize(value)),
+			KeyValue::BlockLocation(ref key, ref value) => (COL_BLOCK_LOCATIONS, serialize(key), serialize(value)),
 		};
 
 		RawKeyValue {
@@ -263,6 +356,13 @@ impl<'a> From<&'a Key> for RawKey {
 			Key::TransactionMeta(ref key) => (COL_TRANSACTIONS_META, serialize(key)),
 			Key::BlockNumber(ref key) => (COL_BLOCK_NUMBERS, serialize(key)),
 			Key::Configuration(ref key) => (COL_CONFIGURATION, serialize(key)),
+			Key::Utxo(ref key) => (COL_UTXOS, serialize(key)),
+			Key::BlockUndo(ref key) => (COL_BLOCK_UNDOS, serialize(key)),
+			Key::ScriptHistory(ref key) => (COL_SCRIPT_HISTORY, serialize(key)),
+			Key::SpentBy(ref key) => (COL_SPENT_BY, serialize(key)),
+			Key::BlockFilter(ref key) => (COL_BLOCK_FILTERS, serialize(key)),
+			Key::BlockChainwork(ref key) => (COL_BLOCK_CHAINWORK, serialize(key)),
+			Key::BlockLocation(ref key) => (COL_BLOCK_LOCATIONS, serialize(key)),
 		};
 
 		RawKey {
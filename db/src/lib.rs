@@ -13,6 +13,8 @@ extern crate storage;
 
 pub mod kv;
 mod block_chain_db;
+mod block_files;
 
-pub use block_chain_db::{BlockChainDatabase, ForkChainDatabase};
+pub use block_chain_db::{BlockChainDatabase, ForkChainDatabase, DB_VERSION, MAX_CHAIN_TIPS};
+pub use block_files::{BlockFiles, BlockLocation};
 pub use primitives::{hash, bytes};
\ No newline at end of file
@@ -2,11 +2,19 @@ extern crate chain;
 extern crate storage;
 extern crate db;
 extern crate test_data;
+extern crate tempdir;
 
-use chain::IndexedBlock;
-use storage::{ForkChain, BlockProvider, SideChainOrigin};
-use db::BlockChainDatabase;
-use db::kv::{MemoryDatabase, SharedMemoryDatabase};
+use chain::{IndexedBlock, OutPoint};
+use storage::{
+	ForkChain, BlockProvider, BlockHeaderProvider, SideChainOrigin, TransactionOutputProvider, TransactionProvider, Store, Error,
+	ScriptHistoryProvider, ScriptHistoryEntry, ScriptHistoryKind, script_hash,
+	SpendingInputProvider, SpendingInput, BlockFilterProvider, filter_header, BlockOrigin, UtxoSnapshotStore, HeadersStore,
+	TransactionMetaProvider, CheckLevel, Inconsistency, check_consistency, BlockChain,
+};
+use db::{BlockChainDatabase, BlockFiles, DB_VERSION, MAX_CHAIN_TIPS};
+use db::hash::H256;
+use tempdir::TempDir;
+use db::kv::{MemoryDatabase, SharedMemoryDatabase, KeyValueDatabase, Key, Value, KeyValue, Transaction as DBTransaction};
 
 #[test]
 fn insert_block() {
@@ -97,6 +105,7 @@ fn switch_to_simple_fork() {
 		canonized_route: Vec::new(),
 		decanonized_route: Vec::new(),
 		block_number: 2,
+		chainwork: Default::default(),
 	};
 
 	let fork = store.fork(side_chain_origin).unwrap();
@@ -114,6 +123,7 @@ fn switch_to_simple_fork() {
 		canonized_route: Vec::new(),
 		decanonized_route: vec![b2.hash().clone()],
 		block_number: 2,
+		chainwork: Default::default(),
 	};
 
 	let fork = store.fork(side_chain_origin).unwrap();
@@ -128,3 +138,994 @@ fn switch_to_simple_fork() {
 	assert_eq!(store.best_block().hash, store.block_hash(2).unwrap());
 
 }
+
+#[test]
+fn utxo_set_is_updated_on_canonize_and_decanonize() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(10).build()
+			.build()
+		.transaction()
+			.output().value(20).build()
+			.output().value(30).build()
+			.output().value(0).script_pubkey("6a00").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b0_tx1 = b0.transactions[1].hash.clone();
+
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(40).build()
+			.build()
+		.transaction()
+			.input().hash(b0_tx1.clone()).index(0).build()
+			.output().value(15).build()
+			.build()
+		.derived_transaction(1, 0)
+			.output().value(10).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+	let b1_tx2 = b1.transactions[2].hash.clone();
+
+	let utxo = |hash: &H256, index: u32| shared_database.get(&Key::Utxo(OutPoint { hash: hash.clone(), index: index }))
+		.unwrap()
+		.into_option()
+		.and_then(Value::as_utxo);
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+
+	let coinbase = utxo(&b0.transactions[0].hash, 0).unwrap();
+	assert!(coinbase.is_coinbase());
+	assert_eq!(coinbase.height(), 0);
+	assert_eq!(utxo(&b0_tx1, 0).unwrap().output().value, 20);
+	assert_eq!(utxo(&b0_tx1, 1).unwrap().output().value, 30);
+	assert!(!utxo(&b0_tx1, 1).unwrap().is_coinbase());
+	// unspendable outputs are never added to the utxo set
+	assert!(utxo(&b0_tx1, 2).is_none());
+
+	store.canonize(b1.hash()).unwrap();
+
+	assert!(utxo(&b0_tx1, 0).is_none());
+	assert!(utxo(&b0_tx1, 1).is_some());
+	// output, created and spent in the same block
+	assert!(utxo(&b1_tx1, 0).is_none());
+	assert_eq!(utxo(&b1_tx2, 0).unwrap().height(), 1);
+	// spent output is still available to detect double spends
+	let spent_outpoint = OutPoint { hash: b0_tx1.clone(), index: 0 };
+	assert!(store.is_spent(&spent_outpoint));
+	assert_eq!(store.transaction_output(&spent_outpoint, 0).unwrap().value, 20);
+
+	store.decanonize().unwrap();
+
+	assert_eq!(utxo(&b0_tx1, 0).unwrap().output().value, 20);
+	assert!(utxo(&b0_tx1, 1).is_some());
+	assert!(utxo(&b1_tx1, 0).is_none());
+	assert!(utxo(&b1_tx2, 0).is_none());
+	assert!(utxo(&b1.transactions[0].hash, 0).is_none());
+	assert!(!store.is_spent(&spent_outpoint));
+	assert!(shared_database.get(&Key::BlockUndo(b1.hash().clone())).unwrap().into_option().is_none());
+}
+
+#[test]
+fn utxo_set_info_is_updated_on_canonize_and_decanonize() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(10).build()
+			.build()
+		.transaction()
+			.output().value(20).build()
+			.output().value(30).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+
+	let b2: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(40).build()
+			.build()
+		.transaction()
+			.input().hash(b1_tx1.clone()).index(0).build()
+			.input().hash(b1_tx1.clone()).index(1).build()
+			.output().value(45).build()
+			.build()
+		.merkled_header().parent(b1.hash().clone()).build()
+		.build()
+		.into();
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.insert(b2.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+
+	// genesis outputs are excluded
+	let (best_block, info) = store.utxo_set_info();
+	assert_eq!(best_block.number, 0);
+	assert_eq!(info.transactions, 0);
+	assert_eq!(info.transaction_outputs, 0);
+	let empty_muhash = info.muhash.finalize();
+
+	store.canonize(b1.hash()).unwrap();
+	let (_, b1_info) = store.utxo_set_info();
+	assert_eq!(b1_info.transactions, 2);
+	assert_eq!(b1_info.transaction_outputs, 3);
+	assert_eq!(b1_info.total_amount, 60);
+	assert!(b1_info.muhash.finalize() != empty_muhash);
+
+	store.canonize(b2.hash()).unwrap();
+	let (best_block, info) = store.utxo_set_info();
+	assert_eq!(best_block.hash, *b2.hash());
+	assert_eq!(info.transactions, 3);
+	assert_eq!(info.transaction_outputs, 3);
+	assert_eq!(info.total_amount, 95);
+
+	store.decanonize().unwrap();
+	let (_, info) = store.utxo_set_info();
+	assert_eq!(info.transactions, b1_info.transactions);
+	assert_eq!(info.transaction_outputs, b1_info.transaction_outputs);
+	assert_eq!(info.bogo_size, b1_info.bogo_size);
+	assert_eq!(info.total_amount, b1_info.total_amount);
+	assert_eq!(info.muhash.finalize(), b1_info.muhash.finalize());
+
+	store.decanonize().unwrap();
+	let (_, info) = store.utxo_set_info();
+	assert_eq!(info.transactions, 0);
+	assert_eq!(info.muhash.finalize(), empty_muhash);
+}
+
+#[test]
+fn utxo_set_info_is_updated_when_duplicate_transaction_overwrites_outputs() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(50).build().build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(10).build().build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	// coinbase of b2 is the duplicate of b1 coinbase (like BIP30 exceptions)
+	let b2: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(10).build().build()
+		.merkled_header().parent(b1.hash().clone()).build()
+		.build()
+		.into();
+	assert_eq!(b1.transactions[0].hash, b2.transactions[0].hash);
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.insert(b2.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	store.canonize(b1.hash()).unwrap();
+	let (_, b1_info) = store.utxo_set_info();
+
+	store.canonize(b2.hash()).unwrap();
+	let (_, info) = store.utxo_set_info();
+	assert_eq!(info.transactions, 1);
+	assert_eq!(info.transaction_outputs, 1);
+	assert_eq!(info.total_amount, 10);
+	assert_eq!(info.bogo_size, b1_info.bogo_size);
+	assert!(info.muhash.finalize() != b1_info.muhash.finalize());
+
+	store.decanonize().unwrap();
+	let (_, info) = store.utxo_set_info();
+	assert_eq!(info.transactions, b1_info.transactions);
+	assert_eq!(info.transaction_outputs, b1_info.transaction_outputs);
+	assert_eq!(info.total_amount, b1_info.total_amount);
+	assert_eq!(info.muhash.finalize(), b1_info.muhash.finalize());
+}
+
+#[test]
+fn prune_old_block_bodies() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).with_prune_target(0);
+
+	let mut blocks: Vec<IndexedBlock> = Vec::new();
+	for i in 0..300 {
+		let block: IndexedBlock = test_data::block_builder()
+			.transaction()
+				.coinbase()
+				.output().value(i).build()
+				.build()
+			.merkled_header().parent(blocks.last().map(|b| b.hash().clone()).unwrap_or_default()).build()
+			.build()
+			.into();
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+		blocks.push(block);
+	}
+
+	// 288 latest blocks are always kept
+	assert_eq!(store.pruned_height(), 12);
+	for block in &blocks[..12] {
+		assert!(store.contains_block(block.hash().clone().into()));
+		assert!(store.is_block_pruned(block.hash().clone().into()));
+		assert!(store.block(block.hash().clone().into()).is_none());
+		assert!(store.block_header(block.hash().clone().into()).is_some());
+		assert!(store.transaction(&block.transactions[0].hash).is_none());
+	}
+	for block in &blocks[12..] {
+		assert!(!store.is_block_pruned(block.hash().clone().into()));
+		assert_eq!(store.block(block.hash().clone().into()).as_ref(), Some(block));
+	}
+
+	// fork, which requires pruned block to be decanonized, could not be handled
+	let fork: IndexedBlock = test_data::block_builder()
+		.header().parent(blocks[10].hash().clone()).build()
+		.build()
+		.into();
+	assert_eq!(store.block_origin(&fork.header).unwrap_err(), Error::AncientFork);
+
+	let fork: IndexedBlock = test_data::block_builder()
+		.header().parent(blocks[11].hash().clone()).build()
+		.build()
+		.into();
+	assert!(store.block_origin(&fork.header).is_ok());
+}
+
+#[test]
+fn prune_blocks_stored_before_pruning_is_enabled() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+
+	let mut blocks: Vec<IndexedBlock> = Vec::new();
+	for i in 0..301 {
+		let block: IndexedBlock = test_data::block_builder()
+			.transaction()
+				.coinbase()
+				.output().value(i).build()
+				.build()
+			.merkled_header().parent(blocks.last().map(|b| b.hash().clone()).unwrap_or_default()).build()
+			.build()
+			.into();
+		blocks.push(block);
+	}
+	for block in &blocks[..300] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	assert_eq!(Store::pruned_height(&store), 0);
+
+	// database, written before blocks size has been recorded
+	let mut update = DBTransaction::new();
+	update.delete(Key::Meta("blocks_size"));
+	update.insert(KeyValue::Meta("db_version", vec![4, 0, 0, 0].into()));
+	shared_database.write(update).unwrap();
+
+	let store = BlockChainDatabase::open(shared_database.clone()).with_prune_target(0);
+	store.migrate().unwrap();
+	assert_eq!(store.db_version(), Some(DB_VERSION));
+	store.insert(blocks[300].clone()).unwrap();
+	store.canonize(blocks[300].hash()).unwrap();
+	assert_eq!(Store::pruned_height(&store), 13);
+	assert!(store.is_block_pruned(blocks[12].hash().clone().into()));
+	assert!(!store.is_block_pruned(blocks[13].hash().clone().into()));
+}
+
+#[test]
+fn script_history_is_updated_on_canonize_and_decanonize() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).with_address_index();
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("51").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b0_coinbase = b0.transactions[0].hash.clone();
+
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("52").build()
+			.build()
+		.transaction()
+			.input().hash(b0_coinbase.clone()).index(0).build()
+			.output().value(20).script_pubkey("51").build()
+			.output().value(30).script_pubkey("53").build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+
+	let script1 = script_hash(&[0x51]);
+	let script3 = script_hash(&[0x53]);
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+
+	assert_eq!(store.script_history(&script1), vec![ScriptHistoryEntry {
+		tx_hash: b0_coinbase.clone(),
+		height: 0,
+		kind: ScriptHistoryKind::Funding,
+		outpoint: OutPoint { hash: b0_coinbase.clone(), index: 0 },
+		value: 50,
+	}]);
+	assert_eq!(store.script_balance(&script1), 50);
+
+	store.canonize(b1.hash()).unwrap();
+
+	let history = store.script_history(&script1);
+	assert_eq!(history.len(), 3);
+	assert_eq!(history[1].tx_hash, b1_tx1);
+	assert_eq!(history[2].tx_hash, b1_tx1);
+	assert_eq!(history.iter().filter(|entry| entry.kind == ScriptHistoryKind::Spending).count(), 1);
+	assert_eq!(store.script_balance(&script1), 20);
+	assert_eq!(store.script_balance(&script3), 30);
+
+	store.decanonize().unwrap();
+
+	assert_eq!(store.script_history(&script1).len(), 1);
+	assert_eq!(store.script_balance(&script1), 50);
+	assert!(store.script_history(&script3).is_empty());
+}
+
+#[test]
+fn address_index_height_is_forgotten_once_index_is_disabled() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+	let b0: IndexedBlock = test_data::block_h0().into();
+	let b1: IndexedBlock = test_data::block_h1().into();
+	let b2: IndexedBlock = test_data::block_h2().into();
+
+	store.insert(b0.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	assert_eq!(store.address_index_height(), None);
+
+	let store = BlockChainDatabase::open(shared_database.clone()).with_address_index();
+	store.insert(b1.clone()).unwrap();
+	store.canonize(b1.hash()).unwrap();
+	assert_eq!(store.address_index_height(), Some(1));
+
+	let store = BlockChainDatabase::open(shared_database.clone());
+	store.insert(b2.clone()).unwrap();
+	store.canonize(b2.hash()).unwrap();
+	assert_eq!(store.address_index_height(), None);
+
+	let store = BlockChainDatabase::open(shared_database).with_address_index();
+	store.reindex_chainstate().unwrap();
+	assert_eq!(store.address_index_height(), Some(0));
+}
+
+#[test]
+fn spent_index_is_updated_on_canonize_and_decanonize() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).with_spent_index();
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(50).build().build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b0_coinbase = b0.transactions[0].hash.clone();
+
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(50).build().build()
+		.transaction()
+			.input().hash(b0_coinbase.clone()).index(0).build()
+			.output().value(50).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+	let outpoint = OutPoint { hash: b0_coinbase, index: 0 };
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	assert_eq!(store.spending_input(&outpoint), None);
+
+	store.canonize(b1.hash()).unwrap();
+	assert_eq!(store.spending_input(&outpoint), Some(SpendingInput {
+		tx_hash: b1_tx1,
+		input_index: 0,
+		height: 1,
+	}));
+
+	store.decanonize().unwrap();
+	assert_eq!(store.spending_input(&outpoint), None);
+}
+
+#[test]
+fn block_filters_are_chained_and_removed_on_decanonize() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default()).with_block_filter_index();
+	let b0: IndexedBlock = test_data::block_h0().into();
+	let b1: IndexedBlock = test_data::block_h1().into();
+
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	store.canonize(b1.hash()).unwrap();
+
+	let f0 = store.block_filter(b0.hash()).unwrap();
+	let f1 = store.block_filter(b1.hash()).unwrap();
+	assert_eq!(f0.header, filter_header(&f0.filter_hash(), &H256::default()));
+	assert_eq!(f1.header, filter_header(&f1.filter_hash(), &f0.header));
+	assert_eq!(store.block_filter_index_height(), Some(0));
+
+	store.decanonize().unwrap();
+	assert_eq!(store.block_filter(b0.hash()), Some(f0));
+	assert_eq!(store.block_filter(b1.hash()), None);
+}
+
+#[test]
+fn side_chain_with_more_work_becomes_canon() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let b0: IndexedBlock = test_data::block_builder().header().build().build().into();
+	let b1: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+	let b2: IndexedBlock = test_data::block_builder().header().parent(b1.hash().clone()).build().build().into();
+
+	for block in vec![&b0, &b1, &b2] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+
+	let b0_chainwork = b0.header.raw.bits.to_work();
+	let b2_chainwork = store.block_chainwork(b2.hash().clone().into()).unwrap();
+	assert_eq!(store.block_chainwork(b0.hash().clone().into()), Some(b0_chainwork));
+	assert_eq!(b2_chainwork, b0_chainwork + b1.header.raw.bits.to_work() + b2.header.raw.bits.to_work());
+	assert_eq!(store.block_chainwork(2.into()), Some(b2_chainwork));
+
+	// shorter side chain with more work
+	let fork: IndexedBlock = test_data::block_builder()
+		.header().parent(b0.hash().clone()).bits(0x1d00ffff.into()).build()
+		.build()
+		.into();
+	match store.block_origin(&fork.header).unwrap() {
+		BlockOrigin::SideChainBecomingCanonChain(origin) => {
+			assert_eq!(origin.block_number, 1);
+			assert_eq!(origin.chainwork, b0_chainwork + fork.header.raw.bits.to_work());
+			assert!(origin.chainwork > b2_chainwork);
+		},
+		origin => panic!("unexpected block origin: {:?}", origin),
+	}
+
+	// side chain with the same work
+	let fork: IndexedBlock = test_data::block_builder()
+		.header().parent(b1.hash().clone()).build()
+		.build()
+		.into();
+	match store.block_origin(&fork.header).unwrap() {
+		BlockOrigin::SideChain(origin) => assert_eq!(origin.chainwork, b2_chainwork),
+		origin => panic!("unexpected block origin: {:?}", origin),
+	}
+}
+
+#[test]
+fn chainwork_is_computed_from_ancestors_without_stored_chainwork() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+	let b0: IndexedBlock = test_data::block_builder().header().build().build().into();
+	let b1: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+	let b2: IndexedBlock = test_data::block_builder().header().parent(b1.hash().clone()).build().build().into();
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+
+	let mut update = DBTransaction::new();
+	update.delete(Key::BlockChainwork(b1.hash().clone()));
+	shared_database.write(update).unwrap();
+
+	let store = BlockChainDatabase::open(shared_database);
+	store.insert(b2.clone()).unwrap();
+	let chainwork = b0.header.raw.bits.to_work() + b1.header.raw.bits.to_work() + b2.header.raw.bits.to_work();
+	assert_eq!(store.block_chainwork(b2.hash().clone().into()), Some(chainwork));
+}
+
+#[test]
+fn chain_tips_are_updated_on_insert_and_rollback() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let b0: IndexedBlock = test_data::block_builder().header().build().build().into();
+	let b1: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+	let b2: IndexedBlock = test_data::block_builder().header().parent(b1.hash().clone()).build().build().into();
+	let fork1: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).nonce(1).build().build().into();
+	let fork2: IndexedBlock = test_data::block_builder().header().parent(fork1.hash().clone()).nonce(1).build().build().into();
+
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	assert_eq!(store.chain_tips(), vec![b1.hash().clone()]);
+
+	store.insert(fork1.clone()).unwrap();
+	store.insert(fork2.clone()).unwrap();
+	assert_eq!(store.chain_tips(), vec![b1.hash().clone(), fork2.hash().clone()]);
+
+	store.insert(b2.clone()).unwrap();
+	store.canonize(b2.hash()).unwrap();
+	assert_eq!(store.chain_tips(), vec![fork2.hash().clone(), b2.hash().clone()]);
+
+	store.rollback_best().unwrap();
+	assert_eq!(store.chain_tips(), vec![fork2.hash().clone(), b1.hash().clone()]);
+}
+
+#[test]
+fn oldest_side_chain_tips_are_forgotten() {
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let b0: IndexedBlock = test_data::block_builder().header().build().build().into();
+	let b1: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+
+	let forks: Vec<IndexedBlock> = (0..MAX_CHAIN_TIPS as u32).map(|nonce| test_data::block_builder()
+		.header().parent(b0.hash().clone()).nonce(nonce + 1).build()
+		.build()
+		.into())
+		.collect();
+	for fork in &forks {
+		store.insert(fork.clone()).unwrap();
+	}
+
+	// the best block is never forgotten
+	let tips = store.chain_tips();
+	assert_eq!(tips.len(), MAX_CHAIN_TIPS);
+	assert_eq!(tips[0], b1.hash().clone());
+	assert!(!tips.contains(forks[0].hash()));
+	assert_eq!(tips[MAX_CHAIN_TIPS - 1], forks[MAX_CHAIN_TIPS - 1].hash().clone());
+
+	// tip is replaced by its child
+	let child: IndexedBlock = test_data::block_builder().header().parent(forks[1].hash().clone()).build().build().into();
+	store.insert(child.clone()).unwrap();
+	let tips = store.chain_tips();
+	assert_eq!(tips.len(), MAX_CHAIN_TIPS);
+	assert!(!tips.contains(forks[1].hash()));
+	assert_eq!(tips[MAX_CHAIN_TIPS - 1], child.hash().clone());
+}
+
+#[test]
+fn utxo_snapshot_is_loaded_into_empty_database() {
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("51").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("52").build()
+			.build()
+		.transaction()
+			.input().hash(b0.transactions[0].hash.clone()).index(0).build()
+			.output().value(20).script_pubkey("51").build()
+			.output().value(30).script_pubkey("53").build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+	let b2: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("54").build()
+			.build()
+		.transaction()
+			.input().hash(b1_tx1.clone()).index(0).build()
+			.output().value(20).script_pubkey("55").build()
+			.build()
+		.merkled_header().parent(b1.hash().clone()).build()
+		.build()
+		.into();
+
+	let source = BlockChainDatabase::open(MemoryDatabase::default());
+	for block in vec![&b0, &b1, &b2] {
+		source.insert(block.clone()).unwrap();
+		source.canonize(block.hash()).unwrap();
+	}
+	let headers = (0..3).map(|number| source.block_header(number.into()).unwrap()).collect();
+	let mut utxos = source.utxos()
+		.filter(|&(_, ref entry)| entry.height() != 0)
+		.map(Ok)
+		.collect::<Vec<_>>()
+		.into_iter();
+
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	store.insert(b0.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	let info = store.load_utxo_snapshot(headers, &mut utxos).unwrap();
+
+	let (source_best, source_info) = source.utxo_set_info();
+	let (best, stored_info) = store.utxo_set_info();
+	assert_eq!(best, source_best);
+	assert_eq!(info.transactions, source_info.transactions);
+	assert_eq!(stored_info.transaction_outputs, source_info.transaction_outputs);
+	assert_eq!(stored_info.total_amount, source_info.total_amount);
+	assert_eq!(stored_info.muhash.finalize(), source_info.muhash.finalize());
+	assert_eq!(store.block_chainwork(b2.hash().clone().into()), source.block_chainwork(b2.hash().clone().into()));
+	assert!(store.is_block_pruned(b2.hash().clone().into()));
+	assert!(store.block(b1.hash().clone().into()).is_none());
+	assert!(store.transaction_output(&OutPoint { hash: b1_tx1.clone(), index: 0 }, 0).is_none());
+	assert!(store.transaction_output(&OutPoint { hash: b1_tx1.clone(), index: 1 }, 0).is_some());
+
+	// history below the snapshot is not yet validated
+	let validation = store.utxo_snapshot_validation().unwrap();
+	assert_eq!(validation.block_hash, *b2.hash());
+	assert_eq!(validation.block_height, 2);
+	assert_eq!(validation.utxo_set_hash, source_info.muhash.finalize());
+	assert!(!validation.is_failed);
+
+	// the chain is continued on top of the snapshot
+	let b3: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("56").build()
+			.build()
+		.transaction()
+			.input().hash(b1_tx1.clone()).index(1).build()
+			.output().value(30).script_pubkey("57").build()
+			.build()
+		.merkled_header().parent(b2.hash().clone()).build()
+		.build()
+		.into();
+	store.insert(b3.clone()).unwrap();
+	store.canonize(b3.hash()).unwrap();
+	assert_eq!(store.best_block().number, 3);
+	assert!(store.transaction_output(&OutPoint { hash: b1_tx1.clone(), index: 1 }, 0).is_none());
+
+	// snapshot can only be loaded into the database without blocks after genesis
+	let headers = (0..3).map(|number| source.block_header(number.into()).unwrap()).collect();
+	assert!(store.load_utxo_snapshot(headers, &mut Vec::new().into_iter()).is_err());
+
+	// failed validation is remembered, valid snapshot is forgotten
+	store.finish_utxo_snapshot_validation(false).unwrap();
+	assert!(store.utxo_snapshot_validation().unwrap().is_failed);
+	store.finish_utxo_snapshot_validation(true).unwrap();
+	assert_eq!(store.utxo_snapshot_validation(), None);
+}
+
+#[test]
+fn consistency_check_reports_corrupted_entries() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b0_coinbase = b0.transactions[0].hash.clone();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).build()
+			.build()
+		.transaction()
+			.input().hash(b0_coinbase.clone()).index(0).build()
+			.output().value(20).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].hash.clone();
+
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	assert!(check_consistency(&store, 0, CheckLevel::TransactionMeta).is_empty());
+
+	let mut b0_coinbase_meta = store.transaction_meta(&b0_coinbase).unwrap();
+	b0_coinbase_meta.denote_unused(0);
+	let mut update = DBTransaction::new();
+	update.insert(KeyValue::TransactionMeta(b0_coinbase.clone(), b0_coinbase_meta));
+	update.insert(KeyValue::BlockNumber(b1.hash().clone(), 5));
+	update.delete(Key::Transaction(b1_tx1.clone()));
+	shared_database.write(update).unwrap();
+
+	assert_eq!(check_consistency(&store, 0, CheckLevel::MerkleRoots), vec![
+		Inconsistency::BlockNumber { number: 1, hash: b1.hash().clone(), stored_number: Some(5) },
+	]);
+	assert_eq!(check_consistency(&store, 1, CheckLevel::TransactionMeta), vec![
+		Inconsistency::BlockNumber { number: 1, hash: b1.hash().clone(), stored_number: Some(5) },
+		Inconsistency::MissingTransactions { number: 1, hash: b1.hash().clone() },
+	]);
+
+	let mut update = DBTransaction::new();
+	update.insert(KeyValue::Transaction(b1_tx1.clone(), b1.transactions[1].raw.clone()));
+	shared_database.write(update).unwrap();
+	assert_eq!(check_consistency(&store, 1, CheckLevel::TransactionMeta), vec![
+		Inconsistency::BlockNumber { number: 1, hash: b1.hash().clone(), stored_number: Some(5) },
+		Inconsistency::UnmarkedSpentOutput { outpoint: OutPoint { hash: b0_coinbase.clone(), index: 0 } },
+	]);
+}
+
+#[test]
+fn database_is_migrated_from_unversioned_schema() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone());
+	store.migrate().unwrap();
+	assert_eq!(store.db_version(), Some(DB_VERSION));
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("51").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("52").build()
+			.build()
+		.transaction()
+			.input().hash(b0.transactions[0].hash.clone()).index(0).build()
+			.output().value(20).build()
+			.output().value(30).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b2: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("53").build()
+			.build()
+		.transaction()
+			.input().hash(b1.transactions[1].hash.clone()).index(1).build()
+			.output().value(30).build()
+			.build()
+		.merkled_header().parent(b1.hash().clone()).build()
+		.build()
+		.into();
+	for block in vec![&b0, &b1, &b2] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	let (best_block, utxo_set_info) = store.utxo_set_info();
+	let mut utxos: Vec<_> = store.utxos().collect();
+
+	// strip everything, which has been added to the schema after version 1
+	let mut update = DBTransaction::new();
+	for &(ref outpoint, _) in &utxos {
+		update.delete(Key::Utxo(outpoint.clone()));
+	}
+	for block in vec![&b0, &b1, &b2] {
+		update.delete(Key::BlockUndo(block.hash().clone()));
+		update.delete(Key::BlockChainwork(block.hash().clone()));
+	}
+	update.delete(Key::Meta("utxo_set_info"));
+	update.delete(Key::Meta("blocks_size"));
+	update.delete(Key::Meta("db_version"));
+	update.delete(Key::Meta("chain_tips"));
+	shared_database.write(update).unwrap();
+
+	let store = BlockChainDatabase::open(shared_database.clone());
+	assert_eq!(store.db_version(), None);
+	store.migrate().unwrap();
+	assert_eq!(store.db_version(), Some(DB_VERSION));
+
+	let (migrated_best_block, migrated_utxo_set_info) = store.utxo_set_info();
+	let mut migrated_utxos: Vec<_> = store.utxos().collect();
+	utxos.sort_by_key(|&(ref outpoint, _)| (outpoint.hash.reversed().to_vec(), outpoint.index));
+	migrated_utxos.sort_by_key(|&(ref outpoint, _)| (outpoint.hash.reversed().to_vec(), outpoint.index));
+	assert_eq!(migrated_best_block, best_block);
+	assert_eq!(migrated_utxo_set_info.transactions, utxo_set_info.transactions);
+	assert_eq!(migrated_utxo_set_info.muhash.finalize(), utxo_set_info.muhash.finalize());
+	assert_eq!(migrated_utxos, utxos);
+	assert_eq!(store.chain_tips(), vec![b2.hash().clone()]);
+	assert!(shared_database.get(&Key::BlockUndo(b2.hash().clone())).unwrap().into_option().is_some());
+	assert_eq!(store.block_chainwork(b2.hash().clone().into()), Some(
+		b0.header.raw.bits.to_work() + b1.header.raw.bits.to_work() + b2.header.raw.bits.to_work()
+	));
+	assert!(check_consistency(&store, 0, CheckLevel::TransactionMeta).is_empty());
+
+	// database, written by the newer version, is never opened
+	let mut update = DBTransaction::new();
+	update.insert(KeyValue::Meta("db_version", vec![0xff, 0, 0, 0].into()));
+	shared_database.write(update).unwrap();
+	assert_eq!(store.migrate(), Err(Error::NewerDatabaseVersion(255, DB_VERSION)));
+}
+
+#[test]
+fn chainstate_is_reindexed_from_stored_blocks() {
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone()).with_address_index().with_spent_index();
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("51").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("52").build()
+			.build()
+		.transaction()
+			.input().hash(b0.transactions[0].hash.clone()).index(0).build()
+			.output().value(20).script_pubkey("51").build()
+			.output().value(30).script_pubkey("53").build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	let (best_block, utxo_set_info) = store.utxo_set_info();
+	let script1 = script_hash(&[0x51]);
+	let script1_history = store.script_history(&script1);
+	let b0_coinbase_output = OutPoint { hash: b0.transactions[0].hash.clone(), index: 0 };
+	let spending_input = store.spending_input(&b0_coinbase_output);
+
+	// corrupt the chainstate
+	let bogus_outpoint = OutPoint { hash: H256::from(1), index: 0 };
+	let mut update = DBTransaction::new();
+	update.delete(Key::TransactionMeta(b1.transactions[1].hash.clone()));
+	update.delete(Key::SpentBy(b0_coinbase_output.clone()));
+	update.insert(KeyValue::Utxo(bogus_outpoint.clone(), store.utxos().next().unwrap().1));
+	shared_database.write(update).unwrap();
+	assert!(!check_consistency(&store, 0, CheckLevel::TransactionMeta).is_empty());
+
+	store.reindex_chainstate().unwrap();
+
+	let (reindexed_best_block, reindexed_utxo_set_info) = store.utxo_set_info();
+	assert_eq!(reindexed_best_block, best_block);
+	assert_eq!(reindexed_utxo_set_info.muhash.finalize(), utxo_set_info.muhash.finalize());
+	assert!(store.utxos().all(|(outpoint, _)| outpoint != bogus_outpoint));
+	assert_eq!(store.script_history(&script1), script1_history);
+	assert_eq!(store.spending_input(&b0_coinbase_output), spending_input);
+	assert!(check_consistency(&store, 0, CheckLevel::TransactionMeta).is_empty());
+}
+
+#[test]
+fn block_bodies_are_stored_in_block_files() {
+	let dir = TempDir::new("block_files").unwrap();
+	let shared_database = SharedMemoryDatabase::default();
+	let store = BlockChainDatabase::open(shared_database.clone())
+		.with_block_files(BlockFiles::open(dir.path()).unwrap());
+
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("51").build()
+			.build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1: IndexedBlock = test_data::block_builder()
+		.transaction()
+			.coinbase()
+			.output().value(50).script_pubkey("52").build()
+			.build()
+		.transaction()
+			.input().hash(b0.transactions[0].hash.clone()).index(0).build()
+			.output().value(20).build()
+			.build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	let b1_tx1 = b1.transactions[1].clone();
+
+	for block in vec![&b0, &b1] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+
+	assert!(shared_database.get(&Key::Transaction(b1_tx1.hash.clone())).unwrap().into_option().is_none());
+	assert!(shared_database.get(&Key::BlockLocation(b1.hash().clone())).unwrap().into_option().is_some());
+	assert_eq!(store.block(b1.hash().clone().into()), Some(b1.clone()));
+	assert_eq!(store.block_transactions(1.into()), b1.transactions);
+	assert_eq!(store.transaction(&b1_tx1.hash), Some(b1_tx1.clone()));
+	assert!(check_consistency(&store, 0, CheckLevel::TransactionMeta).is_empty());
+
+	// bodies are still read after rollback of the best block
+	store.decanonize().unwrap();
+	assert_eq!(store.block(b1.hash().clone().into()), Some(b1.clone()));
+	assert_eq!(store.transaction(&b1_tx1.hash), None);
+
+	// blocks, stored in the database, are read from it
+	let b2: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+	let store = BlockChainDatabase::open(shared_database.clone());
+	store.insert(b2.clone()).unwrap();
+	let store = store.with_block_files(BlockFiles::open(dir.path()).unwrap());
+	assert_eq!(store.block(b2.hash().clone().into()), Some(b2));
+	assert_eq!(store.block(b0.hash().clone().into()), Some(b0));
+}
+
+#[test]
+fn headers_chain_is_stored_without_bodies() {
+	let b0: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(50).build().build()
+		.merkled_header().build()
+		.build()
+		.into();
+	let b1 = test_data::block_builder().header().parent(b0.hash().clone()).build().build();
+	let b2 = test_data::block_builder().header().parent(b1.hash()).build().build();
+	let b2_side = test_data::block_builder().header().parent(b1.hash()).nonce(1).build().build();
+	let b3_side = test_data::block_builder().header().parent(b2_side.hash()).build().build();
+
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	store.insert(b0.clone()).unwrap();
+	store.canonize(b0.hash()).unwrap();
+	assert!(!store.is_headers_only());
+	store.set_headers_only().unwrap();
+	assert!(store.is_headers_only());
+	assert_eq!(store.pruned_height(), u32::max_value());
+
+	assert_eq!(store.insert_header(&b2.block_header.clone().into()), Err(Error::UnknownParent));
+	assert_eq!(store.insert_header(&b1.block_header.clone().into()), Ok(true));
+	assert_eq!(store.insert_header(&b2.block_header.clone().into()), Ok(true));
+	assert_eq!(store.insert_header(&b2.block_header.clone().into()), Ok(false));
+	assert_eq!(store.best_block().number, 2);
+	assert_eq!(store.best_block().hash, b2.hash());
+	assert!(store.is_block_pruned(1.into()));
+	assert!(store.block(2.into()).is_none());
+
+	// side chain with the same work is stored, but not canonized
+	assert_eq!(store.insert_header(&b2_side.block_header.clone().into()), Ok(false));
+	assert_eq!(store.block_hash(2), Some(b2.hash()));
+	assert_eq!(store.block_number(&b2_side.hash()), None);
+
+	// side chain with more work replaces the canon headers
+	assert_eq!(store.insert_header(&b3_side.block_header.clone().into()), Ok(true));
+	assert_eq!(store.best_block().number, 3);
+	assert_eq!(store.block_hash(2), Some(b2_side.hash()));
+	assert_eq!(store.block_hash(3), Some(b3_side.hash()));
+	assert_eq!(store.block_number(&b2.hash()), None);
+	assert_eq!(store.chain_tips().len(), 2);
+	assert_eq!(store.pruned_height(), u32::max_value());
+
+	// blocks with bodies are never reverted in favor of headers
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	let b1_full: IndexedBlock = test_data::block_builder()
+		.transaction().coinbase().output().value(50).build().build()
+		.merkled_header().parent(b0.hash().clone()).build()
+		.build()
+		.into();
+	for block in vec![&b0, &b1_full] {
+		store.insert(block.clone()).unwrap();
+		store.canonize(block.hash()).unwrap();
+	}
+	let b1_header = test_data::block_builder().header().parent(b0.hash().clone()).nonce(2).build().build();
+	let b2_header = test_data::block_builder().header().parent(b1_header.hash()).build().build();
+	assert_eq!(store.insert_header(&b1_header.block_header.clone().into()), Ok(false));
+	assert_eq!(store.insert_header(&b2_header.block_header.clone().into()), Err(Error::CannotDecanonize));
+	assert_eq!(store.best_block().hash, b1_full.hash().clone());
+}
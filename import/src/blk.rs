@@ -1,41 +1,134 @@
 use std::{io, fs, path};
+use std::io::{Read, BufRead, Write};
 use std::collections::BTreeSet;
-use ser::{ReadIterator, deserialize_iterator, Error as ReaderError};
+use hash::H32;
+use ser::{Reader, Error as ReaderError, Stream, SERIALIZE_TRANSACTION_WITNESS, serialize};
+use chain::{IndexedBlock, Transaction};
 use block::Block;
 use fs::read_blk_dir;
 
-pub fn open_blk_file<P>(path: P) -> Result<BlkFile, io::Error> where P: AsRef<path::Path> {
+/// Every block in the blk file is prefixed by magic and block size
+const BLOCK_PREFIX_SIZE: u64 = 8;
+/// Name of the file with the key, which bitcoind uses to obfuscate blk files
+const XOR_KEY_FILE_NAME: &'static str = "xor.dat";
+/// Size of the bitcoind obfuscation key
+const XOR_KEY_SIZE: usize = 8;
+
+/// Blk files reading error
+#[derive(Debug, PartialEq)]
+pub enum Error {
+	/// Block cannot be read
+	Read(ReaderError),
+	/// Block is prefixed by magic of another network
+	InvalidMagic(H32),
+}
+
+pub fn open_blk_file<P>(path: P, xor_key: Vec<u8>) -> Result<BlkFile, io::Error> where P: AsRef<path::Path> {
 	trace!("Opening blk file: {:?}", path.as_ref());
 	let file = fs::File::open(path)?;
 	let blk_file = BlkFile {
-		reader: deserialize_iterator(file),
+		reader: XorReader {
+			inner: io::BufReader::new(file),
+			key: xor_key,
+			position: 0,
+		},
 	};
 	Ok(blk_file)
 }
 
 pub struct BlkFile {
-	reader: ReadIterator<fs::File, Block>,
+	reader: XorReader<io::BufReader<fs::File>>,
 }
 
 impl Iterator for BlkFile {
 	type Item = Result<Block, ReaderError>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		self.reader.next()
+		// bitcoind preallocates blk files, so they may end with zero bytes, which are not obfuscated
+		match self.reader.skip_raw_zeros() {
+			Ok(true) => None,
+			Ok(false) => Some(Reader::from_read(&mut self.reader).read()),
+			Err(err) => Some(Err(err.into())),
+		}
+	}
+}
+
+/// Reverts obfuscation of blk file, where every byte is xored with the byte of the key at the same position
+struct XorReader<R> {
+	inner: R,
+	key: Vec<u8>,
+	position: usize,
+}
+
+impl<R> XorReader<R> where R: BufRead {
+	/// Skips zero bytes of the underlying (not de-obfuscated) data. Returns true if the end is reached.
+	fn skip_raw_zeros(&mut self) -> Result<bool, io::Error> {
+		loop {
+			let (skipped, is_whole_buffer) = {
+				let buf = self.inner.fill_buf()?;
+				if buf.is_empty() {
+					return Ok(true);
+				}
+
+				let skipped = buf.iter().take_while(|byte| **byte == 0).count();
+				(skipped, skipped == buf.len())
+			};
+
+			self.inner.consume(skipped);
+			self.position += skipped;
+			if !is_whole_buffer {
+				return Ok(false);
+			}
+		}
 	}
 }
 
-/// Creates iterator over bitcoind database blocks
-pub fn open_blk_dir<P>(path: P) -> Result<BlkDir, io::Error> where P: AsRef<path::Path> {
+impl<R> Read for XorReader<R> where R: Read {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+		let read = self.inner.read(buf)?;
+		if !self.key.is_empty() {
+			for (index, byte) in buf[..read].iter_mut().enumerate() {
+				*byte ^= self.key[(self.position + index) % self.key.len()];
+			}
+		}
+		self.position += read;
+		Ok(read)
+	}
+}
+
+/// Reads bitcoind obfuscation key. Empty key is returned if blk files are not obfuscated.
+fn read_xor_key(path: &path::Path) -> Result<Vec<u8>, io::Error> {
+	let mut key = Vec::new();
+	match fs::File::open(path.join(XOR_KEY_FILE_NAME)) {
+		Ok(mut file) => { file.read_to_end(&mut key)?; },
+		Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(key),
+		Err(err) => return Err(err),
+	}
+
+	if key.len() != XOR_KEY_SIZE {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid obfuscation key"));
+	}
+
+	if key.iter().all(|byte| *byte == 0) {
+		key.clear();
+	}
+	Ok(key)
+}
+
+/// Creates iterator over bitcoind database blocks, prefixed by given network magic.
+/// Blocks are returned in the order they are stored in blk files, which is not necessarily the chain order.
+pub fn open_blk_dir<P>(path: P, magic: u32) -> Result<BlkDir, io::Error> where P: AsRef<path::Path> {
+	let xor_key = read_xor_key(path.as_ref())?;
 	let files = read_blk_dir(path)?.collect::<Result<BTreeSet<_>, _>>()?;
 
 	let iter = files.into_iter()
 		// flatten results...
-		.flat_map(|file| open_blk_file(file.path))
+		.flat_map(move |file| open_blk_file(file.path, xor_key.clone()))
 		// flat iterators over each block in each file
 		.flat_map(|file| file);
 
 	let blk_dir = BlkDir {
+		magic: H32::from(&serialize(&magic) as &[u8]),
 		iter: Box::new(iter),
 	};
 
@@ -44,14 +137,168 @@ pub fn open_blk_dir<P>(path: P) -> Result<BlkDir, io::Error> where P: AsRef<path
 
 /// Bitcoind database blocks iterator
 pub struct BlkDir {
+	magic: H32,
 	iter: Box<dyn Iterator<Item = Result<Block, ReaderError>>>,
 }
 
 impl Iterator for BlkDir {
-	type Item = Result<Block, ReaderError>;
+	type Item = Result<Block, Error>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		self.iter.next()
+		let magic = &self.magic;
+		self.iter.next().map(|block| match block {
+			Ok(ref block) if block.magic != *magic => Err(Error::InvalidMagic(block.magic.clone())),
+			Ok(block) => Ok(block),
+			Err(err) => Err(Error::Read(err)),
+		})
+	}
+}
+
+
+/// Creates writer of bitcoind database blocks. New blk file is started once the current one would grow beyond `max_file_size`.
+pub fn create_blk_dir_writer<P>(path: P, magic: u32, max_file_size: u64) -> Result<BlkDirWriter, io::Error> where P: AsRef<path::Path> {
+	fs::create_dir_all(path.as_ref())?;
+	let mut writer = BlkDirWriter {
+		path: path.as_ref().to_path_buf(),
+		magic: magic,
+		max_file_size: max_file_size,
+		number: 0,
+		file: None,
+		file_size: 0,
+	};
+	writer.open_file()?;
+	Ok(writer)
+}
+
+/// Bitcoind database blocks writer
+pub struct BlkDirWriter {
+	path: path::PathBuf,
+	magic: u32,
+	max_file_size: u64,
+	number: u32,
+	file: Option<io::BufWriter<fs::File>>,
+	file_size: u64,
+}
+
+impl BlkDirWriter {
+	/// Appends block to the current blk file
+	pub fn append(&mut self, block: &IndexedBlock) -> Result<(), io::Error> {
+		let mut stream = Stream::with_flags(SERIALIZE_TRANSACTION_WITNESS);
+		stream.append(&block.header.raw);
+		stream.append_list::<Transaction, &Transaction>(&block.transactions.iter().map(|tx| &tx.raw).collect::<Vec<_>>());
+		let block = stream.out();
+
+		let record_size = BLOCK_PREFIX_SIZE + block.len() as u64;
+		if self.file_size != 0 && self.file_size + record_size > self.max_file_size {
+			self.number += 1;
+			self.open_file()?;
+		}
+
+		let mut prefix = Stream::new();
+		prefix.append(&self.magic).append(&(block.len() as u32));
+		let file = self.file.as_mut().expect("file is opened in constructor; qed");
+		file.write_all(&prefix.out())?;
+		file.write_all(&block)?;
+		self.file_size += record_size;
+		Ok(())
+	}
+
+	/// Flushes all written blocks to the disk
+	pub fn flush(&mut self) -> Result<(), io::Error> {
+		match self.file {
+			Some(ref mut file) => file.flush(),
+			None => Ok(()),
+		}
+	}
+
+	/// Opens new blk file. Existing files are never overwritten.
+	fn open_file(&mut self) -> Result<(), io::Error> {
+		self.flush()?;
+		let file_path = self.path.join(format!("blk{:05}.dat", self.number));
+		trace!("Creating blk file: {:?}", file_path);
+		let file = fs::OpenOptions::new().write(true).create_new(true).open(file_path)?;
+		self.file = Some(io::BufWriter::new(file));
+		self.file_size = 0;
+		Ok(())
 	}
 }
 
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	extern crate test_data;
+
+	use std::fs;
+	use chain::IndexedBlock;
+	use self::tempdir::TempDir;
+	use super::{open_blk_dir, create_blk_dir_writer, Error};
+
+	const MAGIC: u32 = 0xd9b4bef9;
+
+	fn write_blocks(path: &::std::path::Path, blocks: &[IndexedBlock]) {
+		let mut writer = create_blk_dir_writer(path, MAGIC, 1024 * 1024).unwrap();
+		for block in blocks {
+			writer.append(block).unwrap();
+		}
+		writer.flush().unwrap();
+	}
+
+	#[test]
+	fn test_blk_dir_write_read() {
+		let dir = TempDir::new("blk_dir").unwrap();
+		let blocks: Vec<IndexedBlock> = vec![test_data::block_h0().into(), test_data::block_h1().into(), test_data::block_h2().into()];
+
+		// every file could only fit single block
+		let mut writer = create_blk_dir_writer(dir.path(), MAGIC, 300).unwrap();
+		for block in &blocks {
+			writer.append(block).unwrap();
+		}
+		writer.flush().unwrap();
+		assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+		let read: Vec<_> = open_blk_dir(dir.path(), MAGIC).unwrap().map(|blk| blk.unwrap()).collect();
+		assert_eq!(read.iter().map(|blk| blk.block.clone()).collect::<Vec<_>>(), blocks);
+		assert!(read.iter().all(|blk| blk.magic == "f9beb4d9".into()));
+		assert_eq!(read[1].block_size as usize, blocks[1].size());
+
+		// existing files are not overwritten
+		assert!(create_blk_dir_writer(dir.path(), MAGIC, 300).is_err());
+	}
+
+	#[test]
+	fn test_blk_dir_read_obfuscated_preallocated() {
+		let dir = TempDir::new("blk_dir").unwrap();
+		let blocks: Vec<IndexedBlock> = vec![test_data::block_h0().into(), test_data::block_h1().into()];
+		write_blocks(dir.path(), &blocks);
+
+		let key = [0x01u8, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+		let file_path = dir.path().join("blk00000.dat");
+		let mut data = fs::read(&file_path).unwrap();
+		for (index, byte) in data.iter_mut().enumerate() {
+			*byte ^= key[index % key.len()];
+		}
+		// preallocated space is filled with raw zeros, which are not obfuscated
+		data.extend(vec![0u8; 100]);
+		fs::write(&file_path, data).unwrap();
+		fs::write(dir.path().join("xor.dat"), key).unwrap();
+
+		let read: Vec<_> = open_blk_dir(dir.path(), MAGIC).unwrap().map(|blk| blk.unwrap().block).collect();
+		assert_eq!(read, blocks);
+
+		fs::write(dir.path().join("xor.dat"), &key[..4]).unwrap();
+		assert!(open_blk_dir(dir.path(), MAGIC).is_err());
+	}
+
+	#[test]
+	fn test_blk_dir_invalid_magic() {
+		let dir = TempDir::new("blk_dir").unwrap();
+		write_blocks(dir.path(), &[test_data::block_h0().into()]);
+
+		let read: Vec<_> = open_blk_dir(dir.path(), 0x0709110b).unwrap().collect();
+		assert_eq!(read.len(), 1);
+		match read[0] {
+			Err(Error::InvalidMagic(ref magic)) => assert_eq!(*magic, "f9beb4d9".into()),
+			_ => panic!("expected invalid magic error"),
+		}
+	}
+}
@@ -12,4 +12,4 @@ mod fs;
 
 pub use primitives::{hash, bytes};
 
-pub use blk::{open_blk_dir, BlkDir};
+pub use blk::{open_blk_dir, BlkDir, Error, create_blk_dir_writer, BlkDirWriter};
@@ -1,10 +1,123 @@
+use std::io;
+use ser::{
+	Serializable, Stream, CompactInteger,
+	Deserializable, Reader, Error as ReaderError,
+};
 use chain::{BlockHeader, ShortTransactionID};
 use common::PrefilledTransaction;
 
-#[derive(Debug, PartialEq, Serializable, Deserializable)]
+#[derive(Debug, PartialEq)]
 pub struct BlockHeaderAndIDs {
 	pub header: BlockHeader,
 	pub nonce: u64,
 	pub short_ids: Vec<ShortTransactionID>,
+	/// Prefilled transactions, ordered by their absolute index in the block
 	pub prefilled_transactions: Vec<PrefilledTransaction>,
 }
+
+impl Serializable for BlockHeaderAndIDs {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.header)
+			.append(&self.nonce)
+			.append_list(&self.short_ids)
+			.append(&CompactInteger::from(self.prefilled_transactions.len()));
+
+		// indexes are differentially encoded: every index is the difference from the previous index, minus one
+		let mut next_index = 0;
+		for prefilled_transaction in &self.prefilled_transactions {
+			stream
+				.append(&CompactInteger::from(prefilled_transaction.index - next_index))
+				.append(&prefilled_transaction.transaction);
+			next_index = prefilled_transaction.index + 1;
+		}
+	}
+}
+
+impl Deserializable for BlockHeaderAndIDs {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let header = reader.read()?;
+		let nonce = reader.read()?;
+		let short_ids = reader.read_list()?;
+		let mut prefilled_transactions: Vec<PrefilledTransaction> = reader.read_list()?;
+
+		let mut next_index = 0;
+		for prefilled_transaction in &mut prefilled_transactions {
+			prefilled_transaction.index = absolute_index(next_index, prefilled_transaction.index)?;
+			next_index = prefilled_transaction.index + 1;
+		}
+
+		Ok(BlockHeaderAndIDs {
+			header: header,
+			nonce: nonce,
+			short_ids: short_ids,
+			prefilled_transactions: prefilled_transactions,
+		})
+	}
+}
+
+/// Converts differentially encoded index to the absolute index. Indexes are limited to 16 bits.
+pub fn absolute_index(next_index: usize, differential_index: usize) -> Result<usize, ReaderError> {
+	match next_index.checked_add(differential_index) {
+		Some(index) if index <= u16::max_value() as usize => Ok(index),
+		_ => Err(ReaderError::MalformedData),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ser::{serialize, deserialize, Stream, CompactInteger, Error as ReaderError};
+	use chain::{BlockHeader, Transaction, TransactionInput};
+	use common::PrefilledTransaction;
+	use super::BlockHeaderAndIDs;
+
+	fn transaction() -> Transaction {
+		Transaction {
+			inputs: vec![TransactionInput::coinbase(vec![1u8].into())],
+			..Default::default()
+		}
+	}
+
+	fn block_header_and_ids(indexes: &[usize]) -> BlockHeaderAndIDs {
+		BlockHeaderAndIDs {
+			header: BlockHeader {
+				version: 1,
+				previous_header_hash: 2.into(),
+				merkle_root_hash: 3.into(),
+				time: 4,
+				bits: 5.into(),
+				nonce: 6,
+			},
+			nonce: 42,
+			short_ids: vec![[1u8; 6].into()],
+			prefilled_transactions: indexes.iter().map(|index| PrefilledTransaction {
+				index: *index,
+				transaction: transaction(),
+			}).collect(),
+		}
+	}
+
+	#[test]
+	fn block_header_and_ids_differential_indexes() {
+		let message = block_header_and_ids(&[0, 1, 5]);
+		let serialized = serialize(&message);
+
+		let mut expected = Stream::new();
+		expected
+			.append(&message.header)
+			.append(&message.nonce)
+			.append_list(&message.short_ids)
+			.append(&CompactInteger::from(3usize))
+			.append(&CompactInteger::from(0usize)).append(&transaction())
+			.append(&CompactInteger::from(0usize)).append(&transaction())
+			.append(&CompactInteger::from(3usize)).append(&transaction());
+		assert_eq!(serialized, expected.out());
+		assert_eq!(deserialize::<_, BlockHeaderAndIDs>(serialized.as_ref()).unwrap(), message);
+	}
+
+	#[test]
+	fn block_header_and_ids_index_overflow() {
+		let serialized = serialize(&block_header_and_ids(&[0, 65536]));
+		assert_eq!(deserialize::<_, BlockHeaderAndIDs>(serialized.as_ref()).unwrap_err(), ReaderError::MalformedData);
+	}
+}
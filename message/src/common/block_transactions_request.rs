@@ -4,18 +4,25 @@ use ser::{
 	Serializable, Stream, CompactInteger,
 	Deserializable, Reader, Error as ReaderError,
 };
+use common::block_header_and_ids::absolute_index;
 
 #[derive(Debug, PartialEq)]
 pub struct BlockTransactionsRequest {
 	pub blockhash: H256,
+	/// Ascending absolute indexes of requested transactions
 	pub indexes: Vec<usize>,
 }
 
 impl Serializable for BlockTransactionsRequest {
 	fn serialize(&self, stream: &mut Stream) {
+		// indexes are differentially encoded: every index is the difference from the previous index, minus one
 		let indexes: Vec<CompactInteger> = self.indexes
 			.iter()
-			.map(|x| (*x).into())
+			.scan(0, |next_index, index| {
+				let differential_index = *index - *next_index;
+				*next_index = *index + 1;
+				Some(differential_index.into())
+			})
 			.collect();
 
 		stream
@@ -27,13 +34,43 @@ impl Serializable for BlockTransactionsRequest {
 impl Deserializable for BlockTransactionsRequest {
 	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
 		let blockhash = reader.read()?;
-		let indexes: Vec<CompactInteger> = reader.read_list()?;
+		let differential_indexes: Vec<CompactInteger> = reader.read_list()?;
+
+		let mut indexes = Vec::with_capacity(differential_indexes.len());
+		let mut next_index = 0;
+		for differential_index in differential_indexes {
+			let index = absolute_index(next_index, differential_index.into())?;
+			indexes.push(index);
+			next_index = index + 1;
+		}
 
 		let request = BlockTransactionsRequest {
 			blockhash: blockhash,
-			indexes: indexes.into_iter().map(Into::into).collect(),
+			indexes: indexes,
 		};
 
 		Ok(request)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use ser::{serialize, deserialize, Stream, CompactInteger};
+	use super::BlockTransactionsRequest;
+
+	#[test]
+	fn block_transactions_request_differential_indexes() {
+		let request = BlockTransactionsRequest {
+			blockhash: 5.into(),
+			indexes: vec![1, 2, 10],
+		};
+		let serialized = serialize(&request);
+
+		let mut expected = Stream::new();
+		expected
+			.append(&request.blockhash)
+			.append_list::<CompactInteger, CompactInteger>(&[1usize.into(), 0usize.into(), 7usize.into()]);
+		assert_eq!(serialized, expected.out());
+		assert_eq!(deserialize::<_, BlockTransactionsRequest>(serialized.as_ref()).unwrap(), request);
+	}
+}
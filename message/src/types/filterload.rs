@@ -19,7 +19,7 @@ pub enum FilterFlags {
 	PubKeyOnly = 2,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FilterLoad {
 	// TODO: check how this should be serialized
 	pub filter: Bytes,
@@ -0,0 +1,39 @@
+use std::io;
+use hash::H256;
+use ser::{Stream, Reader};
+use {Payload, MessageResult};
+
+/// Distance between blocks, which filter headers are included in the 'cfcheckpt' message
+pub const CFCHECKPT_INTERVAL: u32 = 1_000;
+
+#[derive(Debug, PartialEq)]
+pub struct GetCFCheckpt {
+	pub filter_type: u8,
+	pub stop_hash: H256,
+}
+
+impl Payload for GetCFCheckpt {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"getcfcheckpt"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let get_cfcheckpt = GetCFCheckpt {
+			filter_type: reader.read()?,
+			stop_hash: reader.read()?,
+		};
+
+		Ok(get_cfcheckpt)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream
+			.append(&self.filter_type)
+			.append(&self.stop_hash);
+		Ok(())
+	}
+}
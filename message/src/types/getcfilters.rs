@@ -0,0 +1,42 @@
+use std::io;
+use hash::H256;
+use ser::{Stream, Reader};
+use {Payload, MessageResult};
+
+/// Maximal number of filters, which could be requested by single 'getcfilters' message
+pub const GETCFILTERS_MAX_FILTERS: u32 = 1_000;
+
+#[derive(Debug, PartialEq)]
+pub struct GetCFilters {
+	pub filter_type: u8,
+	pub start_height: u32,
+	pub stop_hash: H256,
+}
+
+impl Payload for GetCFilters {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"getcfilters"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let get_cfilters = GetCFilters {
+			filter_type: reader.read()?,
+			start_height: reader.read()?,
+			stop_hash: reader.read()?,
+		};
+
+		Ok(get_cfilters)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream
+			.append(&self.filter_type)
+			.append(&self.start_height)
+			.append(&self.stop_hash);
+		Ok(())
+	}
+}
@@ -1,6 +1,9 @@
 pub mod addr;
 mod block;
 mod blocktxn;
+mod cfcheckpt;
+mod cfheaders;
+mod cfilter;
 mod compactblock;
 mod feefilter;
 mod filteradd;
@@ -9,6 +12,9 @@ mod filterload;
 mod getaddr;
 mod getblocks;
 mod getblocktxn;
+mod getcfcheckpt;
+mod getcfheaders;
+mod getcfilters;
 mod getdata;
 mod getheaders;
 mod headers;
@@ -28,6 +34,9 @@ pub mod version;
 pub use self::addr::Addr;
 pub use self::block::Block;
 pub use self::blocktxn::BlockTxn;
+pub use self::cfcheckpt::CFCheckpt;
+pub use self::cfheaders::CFHeaders;
+pub use self::cfilter::CFilter;
 pub use self::compactblock::CompactBlock;
 pub use self::feefilter::FeeFilter;
 pub use self::filterload::{FilterLoad, FILTERLOAD_MAX_FILTER_LEN, FILTERLOAD_MAX_HASH_FUNCS};
@@ -37,6 +46,9 @@ pub use self::filteradd::{FilterAdd, FILTERADD_MAX_DATA_LEN};
 pub use self::getaddr::GetAddr;
 pub use self::getblocks::{GetBlocks, GETBLOCKS_MAX_RESPONSE_HASHES};
 pub use self::getblocktxn::GetBlockTxn;
+pub use self::getcfcheckpt::{GetCFCheckpt, CFCHECKPT_INTERVAL};
+pub use self::getcfheaders::{GetCFHeaders, GETCFHEADERS_MAX_HEADERS};
+pub use self::getcfilters::{GetCFilters, GETCFILTERS_MAX_FILTERS};
 pub use self::getdata::{GetData, GETDATA_MAX_INVENTORY_LEN};
 pub use self::getheaders::{GetHeaders, GETHEADERS_MAX_RESPONSE_HEADERS};
 pub use self::headers::{Headers, HEADERS_MAX_HEADERS_LEN};
@@ -1,5 +1,6 @@
 extern crate byteorder;
 extern crate heapsize;
+extern crate time;
 
 extern crate bitcrypto as crypto;
 extern crate chain;
@@ -16,11 +17,13 @@ mod block_assembler;
 mod cpu_miner;
 mod fee;
 mod memory_pool;
+mod memory_pool_dump;
 
 pub use block_assembler::{BlockAssembler, BlockTemplate};
 pub use cpu_miner::find_solution;
 pub use memory_pool::{MemoryPool, HashedOutPoint, Information as MemoryPoolInformation,
 	OrderingStrategy as MemoryPoolOrderingStrategy, DoubleSpendCheckResult, NonFinalDoubleSpendSet};
+pub use memory_pool_dump::{MemoryPoolDump, MemoryPoolDumpEntry};
 pub use fee::{FeeCalculator, transaction_fee, transaction_fee_rate};
 
 #[cfg(feature = "test-helpers")]
@@ -17,7 +17,9 @@ use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
 use ser::{Serializable, serialize};
 use heapsize::HeapSizeOf;
+use time;
 use fee::MemoryPoolFeeCalculator;
+use memory_pool_dump::{MemoryPoolDump, MemoryPoolDumpEntry};
 
 /// Transactions ordering strategy
 #[cfg_attr(feature="cargo-clippy", allow(enum_variant_names))]
@@ -62,6 +64,8 @@ pub struct Entry {
 	pub size: usize,
 	/// Throughout index of this transaction in memory pool (non persistent)
 	pub storage_index: u64,
+	/// Time, when transaction has entered the memory pool
+	pub time: u32,
 	/// Transaction fee (stored for efficiency)
 	pub miner_fee: u64,
 	/// Virtual transaction fee (a way to prioritize/penalize transaction)
@@ -677,6 +681,11 @@ impl MemoryPool {
 		MemoryPoolIterator::new(self, strategy)
 	}
 
+	/// Iterator over memory pool transactions in unspecified order, which is cheaper than any ordering strategy
+	pub fn entries<'a>(&'a self) -> impl Iterator<Item = &'a Entry> + 'a {
+		self.storage.by_hash.values()
+	}
+
 	/// Removes single transaction by its hash.
 	/// All descendants remain in the pool.
 	pub fn remove_by_hash(&mut self, h: &H256) -> Option<IndexedTransaction> {
@@ -759,6 +768,26 @@ impl MemoryPool {
 		self.storage.is_output_spent(prevout)
 	}
 
+	/// Set time, when transaction has entered the memory pool
+	pub fn set_entry_time(&mut self, h: &H256, time: u32) {
+		if let Some(entry) = self.storage.by_hash.get_mut(h) {
+			entry.time = time;
+		}
+	}
+
+	/// Dumps all transactions in the order they have entered the `MemoryPool`
+	pub fn dump(&self) -> MemoryPoolDump {
+		MemoryPoolDump {
+			entries: self.iter(OrderingStrategy::ByTimestamp)
+				.map(|entry| MemoryPoolDumpEntry {
+					transaction: entry.transaction.clone(),
+					time: entry.time,
+					virtual_fee: entry.miner_virtual_fee,
+				})
+				.collect(),
+		}
+	}
+
 	fn make_entry<FC: MemoryPoolFeeCalculator>(&mut self, t: IndexedTransaction, fc: &FC) -> Option<Entry> {
 		let ancestors = self.get_ancestors(&t.raw);
 		let size = self.get_transaction_size(&t.raw);
@@ -775,6 +804,7 @@ impl MemoryPool {
 			hash: t.hash,
 			ancestors: ancestors,
 			storage_index: storage_index,
+			time: time::get_time().sec as u32,
 			size: size,
 			miner_fee: miner_fee,
 			miner_virtual_fee: 0,
@@ -916,6 +946,24 @@ pub mod tests {
 		assert!(size3 > size2);
 	}
 
+	#[test]
+	fn test_memory_pool_dump() {
+		let chain = &mut ChainBuilder::new();
+		TransactionBuilder::with_output(100).store(chain)
+			.into_input(0).add_output(100).store(chain)
+			.into_input(0).add_output(100).store(chain);
+
+		let mut pool = to_memory_pool(chain);
+		pool.set_entry_time(&chain.at(1).hash(), 500);
+		pool.set_virtual_fee(&chain.at(2).hash(), 1000);
+
+		let dump = pool.dump();
+		assert_eq!(dump.entries.iter().map(|entry| entry.transaction.clone()).collect::<Vec<_>>(), vec![chain.at(0), chain.at(1), chain.at(2)]);
+		assert_eq!(dump.entries[1].time, 500);
+		assert_eq!(dump.entries[1].virtual_fee, 0);
+		assert_eq!(dump.entries[2].virtual_fee, 1000);
+	}
+
 	#[test]
 	fn test_memory_pool_insert_same_transaction() {
 		let mut pool = MemoryPool::new();
@@ -1060,6 +1108,20 @@ pub mod tests {
 		}
 	}
 
+	#[test]
+	fn test_memory_pool_entries() {
+		let chain = &mut ChainBuilder::new();
+		TransactionBuilder::with_output(10).store(chain)
+			.set_output(20).store(chain);
+		let pool = to_memory_pool(chain);
+
+		let mut hashes: Vec<_> = pool.entries().map(|entry| entry.hash.clone()).collect();
+		hashes.sort();
+		let mut expected_hashes = vec![chain.hash(0), chain.hash(1)];
+		expected_hashes.sort();
+		assert_eq!(hashes, expected_hashes);
+	}
+
 	#[test]
 	fn test_memory_pool_timestamp_ordering_strategy() {
 		let chain = &mut ChainBuilder::new();
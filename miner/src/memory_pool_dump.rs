@@ -0,0 +1,90 @@
+//! Memory pool dump, used to keep pending transactions across restarts
+
+use std::io;
+use chain::Transaction;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader};
+
+/// Version of the dump format
+const MEMORY_POOL_DUMP_VERSION: u32 = 1;
+
+/// Transaction from the memory pool with its entry information
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryPoolDumpEntry {
+	/// Transaction
+	pub transaction: Transaction,
+	/// Time, when transaction has entered the memory pool
+	pub time: u32,
+	/// Virtual transaction fee
+	pub virtual_fee: i64,
+}
+
+/// All memory pool transactions in the order they have entered the pool, so that ancestors are always dumped before descendants
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryPoolDump {
+	pub entries: Vec<MemoryPoolDumpEntry>,
+}
+
+impl Serializable for MemoryPoolDumpEntry {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.transaction)
+			.append(&self.time)
+			.append(&self.virtual_fee);
+	}
+}
+
+impl Deserializable for MemoryPoolDumpEntry {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(MemoryPoolDumpEntry {
+			transaction: reader.read()?,
+			time: reader.read()?,
+			virtual_fee: reader.read()?,
+		})
+	}
+}
+
+impl Serializable for MemoryPoolDump {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&MEMORY_POOL_DUMP_VERSION)
+			.append_list(&self.entries);
+	}
+}
+
+impl Deserializable for MemoryPoolDump {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let version: u32 = reader.read()?;
+		if version != MEMORY_POOL_DUMP_VERSION {
+			return Err(ReaderError::MalformedData);
+		}
+
+		Ok(MemoryPoolDump {
+			entries: reader.read_list()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test_data;
+
+	use ser::{serialize, serialize_with_flags, deserialize, SERIALIZE_TRANSACTION_WITNESS, Error as ReaderError};
+	use super::{MemoryPoolDump, MemoryPoolDumpEntry};
+
+	#[test]
+	fn test_memory_pool_dump_serialization_roundtrip() {
+		let dump = MemoryPoolDump {
+			entries: vec![
+				MemoryPoolDumpEntry { transaction: test_data::block_h1().transactions[0].clone(), time: 100, virtual_fee: 0 },
+				MemoryPoolDumpEntry { transaction: test_data::block_h2().transactions[0].clone(), time: 200, virtual_fee: -50 },
+			],
+		};
+
+		let serialized = serialize_with_flags(&dump, SERIALIZE_TRANSACTION_WITNESS);
+		assert_eq!(dump, deserialize(serialized.as_ref()).unwrap());
+
+		let mut corrupted: Vec<u8> = serialize(&dump).into();
+		corrupted[0] = 0;
+		assert_eq!(deserialize::<_, MemoryPoolDump>(corrupted.as_slice()).unwrap_err(), ReaderError::MalformedData);
+	}
+}
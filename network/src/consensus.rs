@@ -1,4 +1,6 @@
+use std::collections::BTreeMap;
 use hash::H256;
+use primitives::bigint::U256;
 use {Network, Magic, Deployment};
 
 #[derive(Debug, Clone)]
@@ -28,6 +30,25 @@ pub struct ConsensusParams {
 	pub csv_deployment: Option<Deployment>,
 	/// BIP141, BIP143, BIP147 deployment
 	pub segwit_deployment: Option<Deployment>,
+	/// Trusted snapshot of the unspent outputs set, which could be loaded instead of verifying the history.
+	pub utxo_snapshot: Option<UtxoSnapshotParams>,
+	/// Block, which is assumed to have valid scripts (as well as all its ancestors).
+	pub assume_valid: Option<H256>,
+	/// The best headers chain should have at least this much work.
+	pub minimum_chain_work: U256,
+	/// Hashes of the main chain blocks at given heights. The chain can't fork below the last known checkpoint.
+	pub checkpoints: BTreeMap<u32, H256>,
+}
+
+#[derive(Debug, Clone)]
+/// Hash of the unspent outputs set, trusted at given block.
+pub struct UtxoSnapshotParams {
+	/// Height of the block, at which the snapshot is taken.
+	pub block_height: u32,
+	/// Hash of the block, at which the snapshot is taken.
+	pub block_hash: H256,
+	/// MuHash3072 of the unspent outputs set (the `muhash` field of `gettxoutsetinfo` response).
+	pub utxo_set_hash: H256,
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +111,15 @@ impl ConsensusParams {
 					}),
 					ConsensusFork::BitcoinCash(_) => None,
 				},
+				assume_valid: match fork {
+					ConsensusFork::BitcoinCore => Some(H256::from_reversed_str("0000000000000000005214481d2d96f898e3d5416e43359c145944a909d242e0")), // 506067
+					// last common block before BCH fork
+					ConsensusFork::BitcoinCash(_) => Some(H256::from_reversed_str("0000000000000000003b9ce759c2a087d52abc4266f8f4ebd6d768b89defa50a")), // 477890
+				},
+				minimum_chain_work: match fork {
+					ConsensusFork::BitcoinCore => "000000000000000000000000000000000000000000f91c579d57cad4bc5278cc",
+					ConsensusFork::BitcoinCash(_) => "000000000000000000000000000000000000000000723d3581fe1bd55373540a",
+				}.parse().expect("hardcoded value should parse without errors"),
 				fork: fork,
 				rule_change_activation_threshold: 1916, // 95%
 				miner_confirmation_window: 2016,
@@ -100,6 +130,24 @@ impl ConsensusParams {
 					timeout: 1493596800,
 					activation: Some(419328),
 				}),
+				// no snapshot has been published yet
+				utxo_snapshot: None,
+				// all checkpoints are below BCH fork
+				checkpoints: checkpoints(&[
+					(11111, "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"),
+					(33333, "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6"),
+					(74000, "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20"),
+					(105000, "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97"),
+					(134444, "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe"),
+					(168000, "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763"),
+					(193000, "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317"),
+					(210000, "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"),
+					(216116, "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e"),
+					(225430, "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932"),
+					(250000, "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214"),
+					(279000, "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40"),
+					(295000, "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983"),
+				]),
 			},
 			Network::Testnet => ConsensusParams {
 				network: network,
@@ -117,6 +165,15 @@ impl ConsensusParams {
 					}),
 					ConsensusFork::BitcoinCash(_) => None,
 				},
+				assume_valid: match fork {
+					ConsensusFork::BitcoinCore => Some(H256::from_reversed_str("0000000002e9e7b00e1f6dc5123a04aad68dd0f0968d8c7aa45f6640795c37b1")), // 1135275
+					// last common block before BCH fork
+					ConsensusFork::BitcoinCash(_) => Some(H256::from_reversed_str("00000000000128796ee387cf110ccb9d2f36cffaf7f73079c995377c65ac0dcc")), // 1079274
+				},
+				minimum_chain_work: match fork {
+					ConsensusFork::BitcoinCore => "00000000000000000000000000000000000000000000002830dab7f76dbb7d63",
+					ConsensusFork::BitcoinCash(_) => "00000000000000000000000000000000000000000000001f057509eba81aed91",
+				}.parse().expect("hardcoded value should parse without errors"),
 				fork: fork,
 				rule_change_activation_threshold: 1512, // 75%
 				miner_confirmation_window: 2016,
@@ -127,6 +184,11 @@ impl ConsensusParams {
 					timeout: 1493596800,
 					activation: Some(770112),
 				}),
+				// no snapshot has been published yet
+				utxo_snapshot: None,
+				checkpoints: checkpoints(&[
+					(546, "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70"),
+				]),
 			},
 			Network::Regtest | Network::Unitest => ConsensusParams {
 				network: network,
@@ -154,6 +216,10 @@ impl ConsensusParams {
 					timeout: 0,
 					activation: Some(0),
 				}),
+				utxo_snapshot: None,
+				assume_valid: None,
+				minimum_chain_work: U256::zero(),
+				checkpoints: BTreeMap::new(),
 			},
 		}
 	}
@@ -162,6 +228,11 @@ impl ConsensusParams {
 		self.network.magic(&self.fork)
 	}
 
+	/// Returns height of the last checkpoint.
+	pub fn last_checkpoint_height(&self) -> Option<u32> {
+		self.checkpoints.keys().next_back().cloned()
+	}
+
 	pub fn is_bip30_exception(&self, hash: &H256, height: u32) -> bool {
 		(height == 91842 && hash == &H256::from_reversed_str("00000000000a4d0a398161ffc163c503763b1f4360639393e0e4c8e300e0caec")) ||
 		(height == 91880 && hash == &H256::from_reversed_str("00000000000743f190a18c5577a3c2d2a1f610ae9601ac046a38084ccb7cd721"))
@@ -177,6 +248,12 @@ impl ConsensusParams {
 	}
 }
 
+fn checkpoints(checkpoints: &[(u32, &'static str)]) -> BTreeMap<u32, H256> {
+	checkpoints.iter()
+		.map(|&(height, hash)| (height, H256::from_reversed_str(hash)))
+		.collect()
+}
+
 impl ConsensusFork {
 	/// Absolute (across all forks) maximum block size. Currently is 8MB for post-HF BitcoinCash
 	pub fn absolute_maximum_block_size() -> usize {
@@ -330,6 +407,21 @@ mod tests {
 		assert_eq!(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore).miner_confirmation_window, 144);
 	}
 
+	#[test]
+	fn test_consensus_assume_valid() {
+		assert!(ConsensusParams::new(Network::Mainnet, ConsensusFork::BitcoinCore).assume_valid.is_some());
+		assert!(ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore).assume_valid.is_some());
+		assert_eq!(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore).assume_valid, None);
+		assert!(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore).minimum_chain_work.is_zero());
+	}
+
+	#[test]
+	fn test_consensus_checkpoints() {
+		assert_eq!(ConsensusParams::new(Network::Mainnet, ConsensusFork::BitcoinCore).last_checkpoint_height(), Some(295000));
+		assert_eq!(ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore).last_checkpoint_height(), Some(546));
+		assert_eq!(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore).last_checkpoint_height(), None);
+	}
+
 	#[test]
 	fn test_consensus_fork_min_block_size() {
 		assert_eq!(ConsensusFork::BitcoinCore.min_block_size(0), 0);
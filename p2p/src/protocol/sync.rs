@@ -41,6 +41,9 @@ pub trait InboundSyncConnection : Send + Sync {
 	fn on_get_block_txn(&self, message: types::GetBlockTxn);
 	fn on_block_txn(&self, message: types::BlockTxn);
 	fn on_notfound(&self, message: types::NotFound);
+	fn on_get_cfilters(&self, message: types::GetCFilters);
+	fn on_get_cfheaders(&self, message: types::GetCFHeaders);
+	fn on_get_cfcheckpt(&self, message: types::GetCFCheckpt);
 }
 
 pub trait OutboundSyncConnection : Send + Sync {
@@ -63,9 +66,14 @@ pub trait OutboundSyncConnection : Send + Sync {
 	fn send_feefilter(&self, message: &types::FeeFilter);
 	fn send_send_compact(&self, message: &types::SendCompact);
 	fn send_compact_block(&self, message: &types::CompactBlock);
+	fn send_witness_compact_block(&self, message: &types::CompactBlock);
 	fn send_get_block_txn(&self, message: &types::GetBlockTxn);
 	fn send_block_txn(&self, message: &types::BlockTxn);
+	fn send_witness_block_txn(&self, message: &types::BlockTxn);
 	fn send_notfound(&self, message: &types::NotFound);
+	fn send_cfilter(&self, message: &types::CFilter);
+	fn send_cfheaders(&self, message: &types::CFHeaders);
+	fn send_cfcheckpt(&self, message: &types::CFCheckpt);
 	fn ignored(&self, id: u32);
 	fn close(&self);
 }
@@ -159,6 +167,10 @@ impl OutboundSyncConnection for OutboundSync {
 		self.context.send_request(message);
 	}
 
+	fn send_witness_compact_block(&self, message: &types::CompactBlock) {
+		self.context.send_request_with_flags(message, SERIALIZE_TRANSACTION_WITNESS);
+	}
+
 	fn send_get_block_txn(&self, message: &types::GetBlockTxn) {
 		self.context.send_request(message);
 	}
@@ -167,10 +179,26 @@ impl OutboundSyncConnection for OutboundSync {
 		self.context.send_request(message);
 	}
 
+	fn send_witness_block_txn(&self, message: &types::BlockTxn) {
+		self.context.send_request_with_flags(message, SERIALIZE_TRANSACTION_WITNESS);
+	}
+
 	fn send_notfound(&self, message: &types::NotFound) {
 		self.context.send_request(message);
 	}
 
+	fn send_cfilter(&self, message: &types::CFilter) {
+		self.context.send_request(message);
+	}
+
+	fn send_cfheaders(&self, message: &types::CFHeaders) {
+		self.context.send_request(message);
+	}
+
+	fn send_cfcheckpt(&self, message: &types::CFCheckpt) {
+		self.context.send_request(message);
+	}
+
 	fn ignored(&self, id: u32) {
 		self.context.ignore_response(id);
 	}
@@ -324,6 +352,18 @@ impl Protocol for SyncProtocol {
 			let message: types::NotFound = deserialize_payload(payload, version)?;
 			self.inbound_connection.on_notfound(message);
 		}
+		else if command == &types::GetCFilters::command() {
+			let message: types::GetCFilters = deserialize_payload(payload, version)?;
+			self.inbound_connection.on_get_cfilters(message);
+		}
+		else if command == &types::GetCFHeaders::command() {
+			let message: types::GetCFHeaders = deserialize_payload(payload, version)?;
+			self.inbound_connection.on_get_cfheaders(message);
+		}
+		else if command == &types::GetCFCheckpt::command() {
+			let message: types::GetCFCheckpt = deserialize_payload(payload, version)?;
+			self.inbound_connection.on_get_cfcheckpt(message);
+		}
 		Ok(())
 	}
 
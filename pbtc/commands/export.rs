@@ -0,0 +1,53 @@
+use clap::ArgMatches;
+use storage::BlockRef;
+use config::Config;
+use util::init_db;
+
+/// Maximal size of the blk file, used by Bitcoin Core
+const DEFAULT_MAX_FILE_SIZE: u64 = 128;
+
+pub fn export(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
+	init_db(&cfg)?;
+
+	let best_block_number = cfg.db.best_block().number;
+	let from = match matches.value_of("from") {
+		Some(from) => from.parse().map_err(|_| "Invalid from - should be block number".to_owned())?,
+		None => 0,
+	};
+	let to = match matches.value_of("to") {
+		Some(to) => to.parse().map_err(|_| "Invalid to - should be block number".to_owned())?,
+		None => best_block_number,
+	};
+	if from > to || to > best_block_number {
+		return Err(format!("Invalid blocks range {}..{} - best block is {}", from, to, best_block_number));
+	}
+	let max_file_size: u64 = match matches.value_of("max-file-size") {
+		Some(size) => size.parse().map_err(|_| "Invalid max-file-size - should be size in MB".to_owned())?,
+		None => DEFAULT_MAX_FILE_SIZE,
+	};
+	let max_file_size = max_file_size.checked_mul(1024 * 1024)
+		.ok_or_else(|| "Invalid max-file-size - too large".to_owned())?;
+
+	let blk_path = matches.value_of("PATH").expect("PATH is required in cli.yml; qed");
+	let mut writer = ::import::create_blk_dir_writer(blk_path, cfg.consensus.magic(), max_file_size)
+		.map_err(|err| format!("Failed to create export directory: {}", err))?;
+
+	for number in from..to + 1 {
+		if cfg.db.is_block_pruned(BlockRef::Number(number)) {
+			return Err(format!("Block {} is pruned", number));
+		}
+		let block = cfg.db.block(BlockRef::Number(number))
+			.ok_or_else(|| format!("Block {} is missing from the database", number))?;
+		writer.append(&block).map_err(|err| format!("Cannot write block {}: {}", number, err))?;
+
+		let counter = number - from + 1;
+		if counter % 1000 == 0 {
+			info!(target: "sync", "Exported {} blocks", counter);
+		}
+	}
+	writer.flush().map_err(|err| format!("Cannot write blocks: {}", err))?;
+
+	info!("Finished export of {} blocks", to - from + 1);
+
+	Ok(())
+}
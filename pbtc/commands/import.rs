@@ -3,18 +3,26 @@ use sync::{create_sync_blocks_writer, Error};
 use config::Config;
 use util::init_db;
 
+/// Default maximal size of blocks (in MB), which are kept in memory until their parent is imported
+const DEFAULT_ORPHANS_MEMORY: usize = 256;
+
 pub fn import(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
 	init_db(&cfg)?;
 
+	let orphans_memory: usize = match matches.value_of("orphans-memory") {
+		Some(size) => size.parse().map_err(|_| "Invalid orphans-memory - should be size in MB".to_owned())?,
+		None => DEFAULT_ORPHANS_MEMORY,
+	};
+
 	let blk_path = matches.value_of("PATH").expect("PATH is required in cli.yml; qed");
-	let blk_dir = ::import::open_blk_dir(blk_path)
+	let blk_dir = ::import::open_blk_dir(blk_path, cfg.consensus.magic())
 		.map_err(|err| format!("Failed to open import directory: {}", err))?;
 
-	let mut writer = create_sync_blocks_writer(cfg.db, cfg.consensus, cfg.verification_params);
+	let mut writer = create_sync_blocks_writer(cfg.db, cfg.consensus, cfg.verification_params)
+		.with_max_orphaned_blocks_size(orphans_memory * 1024 * 1024);
 	let mut counter = 0;
 	let mut previous_hash = None;
 	for blk in blk_dir {
-		// TODO: verify magic!
 		let blk = blk.map_err(|err| format!("Cannot read block: {:?}. Previous block: {:?}", err, previous_hash))?;
 		let blk_hash = blk.block.hash().reversed();
 		match writer.append_block(blk.block) {
@@ -24,7 +32,7 @@ pub fn import(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
 					info!(target: "sync", "Imported {} blocks", counter);
 				}
 			}
-			Err(Error::TooManyOrphanBlocks) => return Err("Too many orphan (unordered) blocks".into()),
+			Err(Error::TooManyOrphanBlocks) => return Err("Too many orphan (unordered) blocks - try increasing --orphans-memory".into()),
 			Err(err) => return Err(format!("Cannot append block: {:?}. Block: {}", err, blk_hash)),
 		}
 
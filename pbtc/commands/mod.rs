@@ -1,7 +1,15 @@
 mod import;
+mod export;
 mod start;
 mod rollback;
+mod txoutset;
+mod verifydb;
+mod reindex;
 
 pub use self::import::import;
+pub use self::export::export;
 pub use self::start::start;
-pub use self::rollback::rollback;
\ No newline at end of file
+pub use self::rollback::rollback;
+pub use self::txoutset::{dumptxoutset, loadtxoutset};
+pub use self::verifydb::verifydb;
+pub use self::reindex::reindex;
\ No newline at end of file
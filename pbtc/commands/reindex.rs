@@ -0,0 +1,60 @@
+use clap::ArgMatches;
+use storage::BlockRef;
+use sync::{create_sync_blocks_writer, Error};
+use config::Config;
+use util::{init_db, init_genesis, open_reindex_db, replace_with_reindex_db};
+
+pub fn reindex(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
+	init_db(&cfg)?;
+
+	if matches.is_present("chainstate") {
+		cfg.db.reindex_chainstate().map_err(|err| format!("{}", err))?;
+		info!("Finished reindex of chainstate at block {}", cfg.db.best_block().number);
+		return Ok(());
+	}
+
+	if cfg.db.pruned_height() != 0 {
+		return Err("Cannot reindex pruned database".into());
+	}
+
+	// canon chain is found by headers, so that corrupted block numbers are not trusted
+	let mut canon_chain = Vec::new();
+	let mut hash = cfg.db.best_block().hash;
+	while let Some(header) = cfg.db.block_header(BlockRef::Hash(hash.clone())) {
+		canon_chain.push(hash);
+		hash = header.raw.previous_header_hash;
+	}
+	if canon_chain.last() != Some(cfg.network.genesis_block().hash()) {
+		return Err(format!("Header of block {} is missing from the database", hash.reversed()));
+	}
+
+	// blocks, already written by interrupted reindex, are skipped by the writer
+	let reindex_db = open_reindex_db(&cfg.data_dir, &cfg.db_options);
+	init_genesis(&reindex_db, cfg.network)?;
+
+	let mut writer = create_sync_blocks_writer(reindex_db, cfg.consensus, cfg.verification_params);
+	let mut counter = 0;
+	for hash in canon_chain.into_iter().rev().skip(1) {
+		let block = cfg.db.block(BlockRef::Hash(hash.clone()))
+			.ok_or_else(|| format!("Block {} is missing from the database", hash.reversed()))?;
+		match writer.append_block(block) {
+			Ok(_) => {
+				counter += 1;
+				if counter % 1000 == 0 {
+					info!(target: "sync", "Reindexed {} blocks", counter);
+				}
+			}
+			Err(Error::TooManyOrphanBlocks) => return Err("Too many orphan (unordered) blocks".into()),
+			Err(err) => return Err(format!("Cannot append block: {:?}. Block: {}", err, hash.reversed())),
+		}
+	}
+
+	// both databases must be closed before the reindexed one replaces the original
+	drop(writer);
+	drop(cfg.db);
+	replace_with_reindex_db(&cfg.data_dir)?;
+
+	info!("Finished reindex of {} blocks", counter);
+
+	Ok(())
+}
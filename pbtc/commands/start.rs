@@ -1,14 +1,22 @@
 use std::net::SocketAddr;
 use std::thread;
+use std::time::Duration;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::sync::atomic::{AtomicBool, Ordering};
-use sync::{create_sync_peers, create_local_sync_node, create_sync_connection_factory, SyncListener};
+use futures::sync::oneshot;
+use libc;
+use chain::IndexedTransaction;
+use sync::{create_sync_peers, create_local_sync_node, create_light_sync_node, create_sync_connection_factory, SyncListener,
+	LightClientListener};
 use primitives::hash::H256;
-use util::{init_db, node_table_path};
+use util::{init_db, init_genesis, node_table_path, memory_pool_path, open_utxo_snapshot_db, remove_utxo_snapshot_db};
 use {config, p2p, PROTOCOL_VERSION, PROTOCOL_MINIMUM};
 use super::super::rpc;
 
+/// Set by the signal handler, when the node is asked to stop
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
 enum BlockNotifierTask {
 	NewBlock(H256),
 	Stop,
@@ -81,12 +89,74 @@ impl Drop for BlockNotifier {
 	}
 }
 
+/// Logs transactions, matched by the light client
+struct MatchedTransactionsLogger;
+
+impl LightClientListener for MatchedTransactionsLogger {
+	fn transaction_matched(&self, transaction: &IndexedTransaction, block_hash: Option<&H256>) {
+		match block_hash {
+			Some(block_hash) => info!(target: "pbtc", "Transaction {} is included in block {}",
+				transaction.hash.to_reversed_str(), block_hash.to_reversed_str()),
+			None => info!(target: "pbtc", "Transaction {} is received from memory pool", transaction.hash.to_reversed_str()),
+		}
+	}
+}
+
+extern "C" fn on_shutdown_signal(_: libc::c_int) {
+	SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Returns future, which is resolved once SIGINT or SIGTERM is received
+fn shutdown_signal() -> oneshot::Receiver<()> {
+	unsafe {
+		libc::signal(libc::SIGINT, on_shutdown_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+		libc::signal(libc::SIGTERM, on_shutdown_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+	}
+
+	let (tx, rx) = oneshot::channel();
+	thread::Builder::new()
+		.name("Shutdown signal thread".to_owned())
+		.spawn(move || {
+			while !SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+				thread::sleep(Duration::from_millis(100));
+			}
+			let _ = tx.send(());
+		})
+		.expect("Error creating shutdown signal thread");
+	rx
+}
+
 pub fn start(cfg: config::Config) -> Result<(), String> {
 	let mut el = p2p::event_loop();
 
 	init_db(&cfg)?;
 
+	// history of scripts is only useful when it is complete
+	if cfg.db_options.address_index && cfg.db.address_index_height() != Some(0) {
+		return Err("Address index has not been built for all blocks. Run `reindex --chainstate` to build it".into());
+	}
+	// filters are not served, unless the whole filters chain is built
+	if cfg.db_options.block_filter_index && cfg.db.block_filter_index_height() != Some(0) {
+		return Err("Block filter index has not been built for all blocks. Run `reindex --chainstate` to build it".into());
+	}
+
+	// blocks below the loaded utxo snapshot are validated in the background, using the separate database
+	let utxo_snapshot_db = match cfg.db.utxo_snapshot_validation() {
+		Some(ref snapshot) if snapshot.is_failed => return Err(format!("Blocks below the loaded utxo snapshot block {} ({}) do not match the snapshot. Remove the database and synchronize from scratch", snapshot.block_height, snapshot.block_hash.to_reversed_str())),
+		Some(_) if cfg.light_client_params.is_none() => {
+			let utxo_snapshot_db = open_utxo_snapshot_db(&cfg.data_dir, &cfg.db_options);
+			init_genesis(&utxo_snapshot_db, cfg.network)?;
+			Some(utxo_snapshot_db)
+		},
+		Some(_) => None,
+		None => {
+			remove_utxo_snapshot_db(&cfg.data_dir)?;
+			None
+		},
+	};
+
 	let nodes_path = node_table_path(&cfg);
+	let memory_pool_path = memory_pool_path(&cfg.data_dir);
 
 	let p2p_cfg = p2p::Config {
 		threads: cfg.p2p_threads,
@@ -100,19 +170,35 @@ pub fn start(cfg: config::Config) -> Result<(), String> {
 			services: cfg.services,
 			user_agent: cfg.user_agent,
 			start_height: 0,
-			relay: true,
+			// light client only wants transactions, matching its filter
+			relay: cfg.light_client_params.is_none(),
 		},
 		peers: cfg.connect.map_or_else(|| vec![], |x| vec![x]),
 		seeds: cfg.seednodes,
 		node_table_path: nodes_path,
-		preferable_services: cfg.services,
+		preferable_services: match cfg.light_client_params {
+			Some(_) => cfg.services.with_network(true).with_bloom(true),
+			None => cfg.services,
+		},
 		internet_protocol: cfg.internet_protocol,
 	};
 
 	let sync_peers = create_sync_peers();
-	let local_sync_node = create_local_sync_node(cfg.consensus, cfg.db.clone(), sync_peers.clone(), cfg.verification_params);
+	let is_light_client = cfg.light_client_params.is_some();
+	let local_sync_node = match cfg.light_client_params {
+		Some(params) => create_light_sync_node(cfg.consensus, cfg.db.clone(), sync_peers.clone(), params, Box::new(MatchedTransactionsLogger)),
+		None => create_local_sync_node(cfg.consensus, cfg.db.clone(), sync_peers.clone(), cfg.verification_params, utxo_snapshot_db),
+	};
 	let sync_connection_factory = create_sync_connection_factory(sync_peers.clone(), local_sync_node.clone());
 
+	// transactions from the previous run are verified again before they're inserted to the memory pool
+	if !is_light_client && memory_pool_path.exists() {
+		match local_sync_node.load_memory_pool(&memory_pool_path) {
+			Ok(loaded) => info!(target: "pbtc", "Loaded {} memory pool transactions", loaded),
+			Err(err) => warn!(target: "pbtc", "{}", err),
+		}
+	}
+
 	if let Some(block_notify_command) = cfg.block_notify_command {
 		local_sync_node.install_sync_listener(Box::new(BlockNotifier::new(block_notify_command)));
 	}
@@ -121,13 +207,20 @@ pub fn start(cfg: config::Config) -> Result<(), String> {
 	let rpc_deps = rpc::Dependencies {
 		network: cfg.network,
 		storage: cfg.db,
-		local_sync_node: local_sync_node,
+		local_sync_node: local_sync_node.clone(),
 		p2p_context: p2p.context().clone(),
 		remote: el.remote(),
+		memory_pool_path: memory_pool_path.clone(),
 	};
 	let _rpc_server = rpc::new_http(cfg.rpc_config, rpc_deps)?;
 
 	p2p.run().map_err(|_| "Failed to start p2p module")?;
-	el.run(p2p::forever()).unwrap();
+	el.run(shutdown_signal()).map_err(|_| "Shutdown signal thread has stopped")?;
+
+	info!(target: "pbtc", "Shutting down");
+	if !is_light_client {
+		let saved = local_sync_node.save_memory_pool(&memory_pool_path)?;
+		info!(target: "pbtc", "Saved {} memory pool transactions", saved);
+	}
 	Ok(())
 }
@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use clap::ArgMatches;
+use chain::{IndexedBlockHeader, BlockHeader, OutPoint};
+use primitives::hash::H256;
+use network::{Network, UtxoSnapshotParams};
+use ser::{Reader, Serializable, Deserializable, serialize};
+use storage::{self, BlockRef, UtxoEntry, UtxoSetInfo, UtxoSnapshotHeader};
+use verification::is_valid_proof_of_work_hash;
+use config::Config;
+use util::init_db;
+
+pub fn dumptxoutset(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
+	init_db(&cfg)?;
+
+	let path = matches.value_of("FILE").expect("FILE is required in cli.yml; qed");
+	let file = File::create(path).map_err(|err| format!("Cannot create snapshot file: {}", err))?;
+	let mut writer = BufWriter::new(file);
+
+	let (best_block, utxo_set_info) = cfg.db.utxo_set_info();
+	let snapshot_header = UtxoSnapshotHeader {
+		block_hash: best_block.hash.clone(),
+		block_height: best_block.number,
+		outputs: utxo_set_info.transaction_outputs,
+	};
+	write(&mut writer, &snapshot_header)?;
+
+	for number in 0..best_block.number + 1 {
+		let header = cfg.db.block_header(BlockRef::Number(number))
+			.ok_or_else(|| format!("Block header {} is missing from the database", number))?;
+		write(&mut writer, &header.raw)?;
+	}
+
+	// genesis outputs are unspendable and are not the part of the utxo set statistics
+	let mut outputs = 0u64;
+	for (outpoint, entry) in cfg.db.utxos().filter(|&(_, ref entry)| entry.height() != 0) {
+		write(&mut writer, &outpoint)?;
+		write(&mut writer, &entry)?;
+		outputs += 1;
+	}
+
+	if outputs != snapshot_header.outputs {
+		return Err(format!("Database contains {} unspent outputs, while {} are expected", outputs, snapshot_header.outputs));
+	}
+
+	writer.flush().map_err(|err| format!("Cannot write snapshot file: {}", err))?;
+	info!("Dumped {} unspent outputs at block {} ({})", outputs, best_block.number, best_block.hash.reversed());
+
+	Ok(())
+}
+
+pub fn loadtxoutset(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
+	init_db(&cfg)?;
+
+	let path = matches.value_of("FILE").expect("FILE is required in cli.yml; qed");
+	let params = match matches.value_of("utxo-set-hash") {
+		Some(utxo_set_hash) if cfg.network == Network::Regtest => regtest_snapshot_params(path, utxo_set_hash)?,
+		Some(_) => return Err("Unspent outputs set hash could only be given on regtest".into()),
+		None => match cfg.consensus.utxo_snapshot.clone() {
+			Some(params) => params,
+			None => return Err(format!("Loading utxo snapshots is not supported on {:?}: its consensus parameters have no trusted snapshot height, block hash and set hash", cfg.network)),
+		},
+	};
+
+	// the whole snapshot is verified before anything is written to the database
+	let mut reader = open(path)?;
+	let headers = read_headers(&mut reader, &params)?;
+	let mut utxo_set_info = UtxoSetInfo::default();
+	for utxo in read_utxos(&mut reader, headers.len() as u64 - 1) {
+		let (outpoint, entry) = utxo.map_err(|err| format!("{}", err))?;
+		utxo_set_info.insert_output(&outpoint, &entry);
+	}
+	if utxo_set_info.muhash.finalize() != params.utxo_set_hash {
+		return Err(format!("Snapshot hash {} does not match the trusted hash {}", utxo_set_info.muhash.finalize().reversed(), params.utxo_set_hash.reversed()));
+	}
+
+	let mut reader = open(path)?;
+	let headers = read_headers(&mut reader, &params)?;
+	let mut utxos = read_utxos(&mut reader, headers.len() as u64 - 1);
+	let utxo_set_info = cfg.db.load_utxo_snapshot(headers, &mut utxos).map_err(|err| format!("{}", err))?;
+
+	info!("Loaded {} unspent outputs at block {} ({}). Blocks below the snapshot are validated in the background, once the node is started",
+		utxo_set_info.transaction_outputs, params.block_height, params.block_hash.reversed());
+
+	Ok(())
+}
+
+/// Regtest chains are local, so the snapshot is trusted at the block it is taken at, with the given set hash
+fn regtest_snapshot_params(path: &str, utxo_set_hash: &str) -> Result<UtxoSnapshotParams, String> {
+	let utxo_set_hash: H256 = utxo_set_hash.parse().map_err(|err| format!("Invalid unspent outputs set hash: {:?}", err))?;
+	let snapshot_header: UtxoSnapshotHeader = read(&mut open(path)?)?;
+	Ok(UtxoSnapshotParams {
+		block_height: snapshot_header.block_height,
+		block_hash: snapshot_header.block_hash,
+		utxo_set_hash: utxo_set_hash,
+	})
+}
+
+fn open(path: &str) -> Result<Reader<BufReader<File>>, String> {
+	let file = File::open(path).map_err(|err| format!("Cannot open snapshot file: {}", err))?;
+	Ok(Reader::from_read(BufReader::new(file)))
+}
+
+fn write<W: Write, T: Serializable>(writer: &mut W, value: &T) -> Result<(), String> {
+	writer.write_all(&serialize(value)).map_err(|err| format!("Cannot write snapshot file: {}", err))
+}
+
+fn read<R: Read, T: Deserializable>(reader: &mut Reader<R>) -> Result<T, String> {
+	reader.read().map_err(|err| format!("Cannot read snapshot file: {:?}", err))
+}
+
+/// Reads snapshot header and headers chain and checks them against trusted snapshot parameters
+fn read_headers<R: Read>(reader: &mut Reader<R>, params: &UtxoSnapshotParams) -> Result<Vec<IndexedBlockHeader>, String> {
+	let snapshot_header: UtxoSnapshotHeader = read(reader)?;
+	if snapshot_header.block_hash != params.block_hash || snapshot_header.block_height != params.block_height {
+		return Err(format!("Snapshot is taken at block {} ({}), while trusted snapshot is at block {} ({})",
+			snapshot_header.block_height, snapshot_header.block_hash.reversed(), params.block_height, params.block_hash.reversed()));
+	}
+
+	let mut headers: Vec<IndexedBlockHeader> = Vec::with_capacity(params.block_height as usize + 1);
+	for number in 0..params.block_height + 1 {
+		let header = IndexedBlockHeader::from_raw(read::<_, BlockHeader>(reader)?);
+		if !is_valid_proof_of_work_hash(header.raw.bits, &header.hash) {
+			return Err(format!("Header {} ({}) has invalid proof of work", number, header.hash.reversed()));
+		}
+		if let Some(parent) = headers.last() {
+			if header.raw.previous_header_hash != parent.hash {
+				return Err(format!("Header {} ({}) is not a child of the previous header", number, header.hash.reversed()));
+			}
+		}
+		headers.push(header);
+	}
+
+	if headers.last().map(|header| &header.hash) != Some(&params.block_hash) {
+		return Err("Snapshot headers chain does not end with the trusted block".into());
+	}
+
+	Ok(headers)
+}
+
+/// Reads all unspent outputs of the snapshot, checking that they're all created at or before snapshot block
+fn read_utxos<'a, R: Read>(reader: &'a mut Reader<R>, block_height: u64) -> impl Iterator<Item = Result<(OutPoint, UtxoEntry), storage::Error>> + 'a {
+	let mut finished = false;
+	::std::iter::from_fn(move || {
+		if finished || reader.is_finished() {
+			return None;
+		}
+
+		let utxo = read::<_, OutPoint>(reader)
+			.and_then(|outpoint| read::<_, UtxoEntry>(reader).map(|entry| (outpoint, entry)))
+			.and_then(|(outpoint, entry)| match entry.height() as u64 {
+				0 => Err("Snapshot contains unspendable genesis output".to_owned()),
+				height if height > block_height => Err(format!("Snapshot contains output, created at block {}", height)),
+				_ => Ok((outpoint, entry)),
+			});
+		finished = utxo.is_err();
+		Some(utxo.map_err(storage::Error::CannotLoadUtxoSnapshot))
+	})
+}
@@ -0,0 +1,36 @@
+use clap::ArgMatches;
+use storage::{CheckLevel, check_consistency};
+use config::Config;
+use util::init_db;
+
+/// Number of the latest blocks, checked by default
+const DEFAULT_CHECK_DEPTH: u32 = 288;
+
+pub fn verifydb(cfg: Config, matches: &ArgMatches) -> Result<(), String> {
+	init_db(&cfg)?;
+
+	let depth = match matches.value_of("depth") {
+		Some(depth) => depth.parse().map_err(|_| "Invalid depth - should be number of blocks".to_owned())?,
+		None => DEFAULT_CHECK_DEPTH,
+	};
+	let level = match matches.value_of("level") {
+		Some(level) => level.parse().ok().and_then(CheckLevel::from_u8).ok_or_else(|| "Invalid level - should be number from 0 to 3".to_owned())?,
+		None => CheckLevel::TransactionMeta,
+	};
+
+	let best_block = cfg.db.best_block();
+	info!("Verifying database at block {} ({}), depth: {}, level: {:?}", best_block.number, best_block.hash.reversed(), depth, level);
+
+	let inconsistencies = check_consistency(cfg.db.as_store(), depth, level);
+	for inconsistency in &inconsistencies {
+		error!("Database inconsistency: {}", inconsistency);
+	}
+
+	match inconsistencies.len() {
+		0 => {
+			info!("No inconsistencies found");
+			Ok(())
+		},
+		count => Err(format!("Found {} database inconsistencies", count)),
+	}
+}
@@ -10,8 +10,9 @@ use {USER_AGENT, REGTEST_USER_AGENT};
 use primitives::hash::H256;
 use rpc::HttpConfiguration as RpcHttpConfig;
 use verification::VerificationLevel;
-use sync::VerificationParameters;
-use util::open_db;
+use keys::Address;
+use sync::{LightClientParams, VerificationParameters};
+use util::{open_db, block_files_path, DbOptions};
 
 pub struct Config {
 	pub network: Network,
@@ -32,10 +33,14 @@ pub struct Config {
 	pub rpc_config: RpcHttpConfig,
 	pub block_notify_command: Option<String>,
 	pub verification_params: VerificationParameters,
+	pub light_client_params: Option<LightClientParams>,
+	pub db_options: DbOptions,
 	pub db: storage::SharedStore,
 }
 
 pub const DEFAULT_DB_CACHE: usize = 512;
+/// Minimal size of stored blocks bodies in pruning mode, in MB.
+pub const MIN_PRUNE_TARGET: u64 = 550;
 
 pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 	let db_cache = match matches.value_of("db-cache") {
@@ -48,7 +53,33 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 		None => None,
 	};
 
-	let db = open_db(&data_dir, db_cache);
+	let prune = match matches.value_of("prune") {
+		Some(s) => {
+			let prune: u64 = s.parse().map_err(|_| "Invalid prune size - should be number in MB".to_owned())?;
+			if prune < MIN_PRUNE_TARGET {
+				return Err(format!("Prune size should be at least {} MB", MIN_PRUNE_TARGET));
+			}
+			Some(prune)
+		},
+		None => None,
+	};
+
+	let address_index = matches.is_present("address-index");
+	let spent_index = matches.is_present("spent-index");
+	let block_filter_index = matches.is_present("block-filter-index");
+	let block_files = matches.is_present("block-files") || block_files_path(&data_dir).exists();
+	if block_files && prune.is_some() {
+		return Err("Pruning is not supported when block bodies are stored in block files".into());
+	}
+	let db_options = DbOptions {
+		cache: db_cache,
+		prune_target: prune.map(|prune| prune * 1024 * 1024),
+		address_index: address_index,
+		spent_index: spent_index,
+		block_filter_index: block_filter_index,
+		block_files: block_files,
+	};
+	let db = open_db(&data_dir, &db_options);
 
 	let quiet = matches.is_present("quiet");
 	let network = match (matches.is_present("testnet"), matches.is_present("regtest")) {
@@ -59,7 +90,10 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 	};
 
 	let consensus_fork = parse_consensus_fork(network, &db, &matches)?;
-	let consensus = ConsensusParams::new(network, consensus_fork);
+	let mut consensus = ConsensusParams::new(network, consensus_fork);
+	if let Some(s) = matches.value_of("minimumchainwork") {
+		consensus.minimum_chain_work = s.parse().map_err(|_| "Invalid minimum chain work".to_owned())?;
+	}
 
 	let (in_connections, out_connections) = match network {
 		Network::Testnet | Network::Mainnet | Network::Other(_) => (10, 10),
@@ -127,7 +161,18 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 		None => None,
 	};
 
-	let services = Services::default().with_network(true);
+	let light_client_params = match matches.is_present("light") {
+		true => Some(parse_light_client_params(matches)?),
+		false => None,
+	};
+
+	// pruned node (and node, started from the utxo snapshot) only serves the latest blocks
+	// light client only serves block headers
+	let has_pruned_blocks = prune.is_some() || db.pruned_height() != 0;
+	let services = Services::default()
+		.with_network(!has_pruned_blocks && light_client_params.is_none())
+		.with_network_limited(has_pruned_blocks)
+		.with_compact_filters(block_filter_index);
 	let services = match &consensus.fork {
 		&ConsensusFork::BitcoinCash(_) => services.with_bitcoin_cash(true),
 		&ConsensusFork::BitcoinCore => services.with_witness(true),
@@ -149,6 +194,15 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 		_ => network.default_verification_edge(),
 	};
 
+	let assume_valid = match matches.value_of("assumevalid") {
+		Some("0") => None,
+		Some(s) => {
+			let block: H256 = s.parse().map_err(|_| "Invalid assumevalid block".to_owned())?;
+			Some(block.reversed())
+		},
+		None => consensus.assume_valid.clone(),
+	};
+
 	let config = Config {
 		quiet: quiet,
 		network: network,
@@ -170,7 +224,10 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 		verification_params: VerificationParameters {
 			verification_level: verification_level,
 			verification_edge: verification_edge,
+			assume_valid: assume_valid,
 		},
+		light_client_params: light_client_params,
+		db_options: db_options,
 		db: db,
 	};
 
@@ -203,6 +260,27 @@ fn parse_consensus_fork(network: Network, db: &storage::SharedStore, matches: &c
 	};
 }
 
+fn parse_light_client_params(matches: &clap::ArgMatches) -> Result<LightClientParams, String> {
+	let watched_elements = match matches.values_of("watch-address") {
+		Some(addresses) => addresses
+			.map(|address| address.parse::<Address>()
+				.map(|address| address.hash.to_vec().into())
+				.map_err(|_| format!("Invalid watch address: {}", address)))
+			.collect::<Result<Vec<_>, _>>()?,
+		None => return Err("Light client requires at least one --watch-address".into()),
+	};
+
+	let earliest_block_time = match matches.value_of("watch-since") {
+		Some(s) => s.parse().map_err(|_| "Invalid watch-since time".to_owned())?,
+		None => 0,
+	};
+
+	Ok(LightClientParams {
+		watched_elements: watched_elements,
+		earliest_block_time: earliest_block_time,
+	})
+}
+
 fn parse_rpc_config(network: Network, matches: &clap::ArgMatches) -> Result<RpcHttpConfig, String> {
 	let mut config = RpcHttpConfig::with_port(network.rpc_port());
 	config.enabled = !matches.is_present("no-jsonrpc");
@@ -7,6 +7,7 @@ extern crate log;
 extern crate env_logger;
 extern crate app_dirs;
 extern crate libc;
+extern crate futures;
 
 extern crate storage;
 extern crate db;
@@ -21,6 +22,7 @@ extern crate sync;
 extern crate import;
 extern crate rpc as ethcore_rpc;
 extern crate primitives;
+extern crate serialization as ser;
 extern crate verification;
 
 mod commands;
@@ -65,7 +67,12 @@ fn run() -> Result<(), String> {
 
 	match matches.subcommand() {
 		("import", Some(import_matches)) => commands::import(cfg, import_matches),
+		("export", Some(export_matches)) => commands::export(cfg, export_matches),
 		("rollback", Some(rollback_matches)) => commands::rollback(cfg, rollback_matches),
+		("dumptxoutset", Some(dump_matches)) => commands::dumptxoutset(cfg, dump_matches),
+		("loadtxoutset", Some(load_matches)) => commands::loadtxoutset(cfg, load_matches),
+		("verifydb", Some(verifydb_matches)) => commands::verifydb(cfg, verifydb_matches),
+		("reindex", Some(reindex_matches)) => commands::reindex(cfg, reindex_matches),
 		_ => commands::start(cfg),
 	}
 }
@@ -1,4 +1,5 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use rpc_apis::{self, ApiSet};
 use ethcore_rpc::{Server, start_http, MetaIoHandler, Compatibility, Remote};
@@ -14,6 +15,7 @@ pub struct Dependencies {
 	pub storage: storage::SharedStore,
 	pub p2p_context: Arc<p2p::Context>,
 	pub remote: Remote,
+	pub memory_pool_path: PathBuf,
 }
 
 #[derive(Debug, PartialEq)]
@@ -1,17 +1,99 @@
 use std::sync::Arc;
-use std::path::PathBuf;
-use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::fs::{self, create_dir_all};
 use app_dirs::{app_dir, AppDataType};
+use network::Network;
 use {storage, APP_INFO};
 use db;
 use config::Config;
 
-pub fn open_db(data_dir: &Option<String>, db_cache: usize) -> storage::SharedStore {
-	let db_path = match *data_dir {
-		Some(ref data_dir) => custom_path(&data_dir, "db"),
-		None => app_dir(AppDataType::UserData, &APP_INFO, "db").expect("Failed to get app dir"),
-	};
-	Arc::new(db::BlockChainDatabase::open_at_path(db_path, db_cache).expect("Failed to open database"))
+/// Options, the database is opened with
+#[derive(Debug, Clone)]
+pub struct DbOptions {
+	pub cache: usize,
+	pub prune_target: Option<u64>,
+	pub address_index: bool,
+	pub spent_index: bool,
+	pub block_filter_index: bool,
+	pub block_files: bool,
+}
+
+pub fn open_db(data_dir: &Option<String>, options: &DbOptions) -> storage::SharedStore {
+	open_db_at(&db_path(data_dir), &block_files_path(data_dir), options)
+}
+
+/// Opens the empty database, which is filled by reindex and then replaces the database
+pub fn open_reindex_db(data_dir: &Option<String>, options: &DbOptions) -> storage::SharedStore {
+	open_db_at(&reindex_path(&db_path(data_dir)), &reindex_path(&block_files_path(data_dir)), options)
+}
+
+/// Replaces the database (and block files) with the reindexed ones
+pub fn replace_with_reindex_db(data_dir: &Option<String>) -> Result<(), String> {
+	for path in vec![db_path(data_dir), block_files_path(data_dir)] {
+		let reindex_path = reindex_path(&path);
+		if reindex_path.exists() {
+			fs::remove_dir_all(&path).map_err(|err| format!("Cannot remove {}: {}", path.display(), err))?;
+			fs::rename(&reindex_path, &path).map_err(|err| format!("Cannot move {}: {}", reindex_path.display(), err))?;
+		}
+	}
+	Ok(())
+}
+
+/// Opens the database, where blocks below the loaded utxo snapshot are validated in the background.
+/// Bodies of validated blocks are not needed, so they are always pruned.
+pub fn open_utxo_snapshot_db(data_dir: &Option<String>, options: &DbOptions) -> storage::SharedStore {
+	let db = db::BlockChainDatabase::open_at_path(utxo_snapshot_path(&db_path(data_dir)), options.cache)
+		.expect("Failed to open utxo snapshot validation database")
+		.with_prune_target(0);
+	Arc::new(db)
+}
+
+/// Removes the database, where blocks below the loaded utxo snapshot have been validated
+pub fn remove_utxo_snapshot_db(data_dir: &Option<String>) -> Result<(), String> {
+	let path = utxo_snapshot_path(&db_path(data_dir));
+	match path.exists() {
+		true => fs::remove_dir_all(&path).map_err(|err| format!("Cannot remove {}: {}", path.display(), err)),
+		false => Ok(()),
+	}
+}
+
+fn open_db_at(path: &Path, block_files_path: &Path, options: &DbOptions) -> storage::SharedStore {
+	let mut db = db::BlockChainDatabase::open_at_path(path, options.cache).expect("Failed to open database");
+	if options.block_files {
+		let block_files = db::BlockFiles::open(block_files_path).expect("Failed to open block files");
+		db = db.with_block_files(block_files);
+	}
+	if let Some(prune_target) = options.prune_target {
+		db = db.with_prune_target(prune_target);
+	}
+	if options.address_index {
+		db = db.with_address_index();
+	}
+	if options.spent_index {
+		db = db.with_spent_index();
+	}
+	if options.block_filter_index {
+		db = db.with_block_filter_index();
+	}
+	Arc::new(db)
+}
+
+fn reindex_path(path: &Path) -> PathBuf {
+	let mut file_name = path.file_name().expect("database paths always have file name; qed").to_os_string();
+	file_name.push("-reindex");
+	path.with_file_name(file_name)
+}
+
+fn utxo_snapshot_path(path: &Path) -> PathBuf {
+	let mut file_name = path.file_name().expect("database paths always have file name; qed").to_os_string();
+	file_name.push("-utxo-snapshot");
+	path.with_file_name(file_name)
+}
+
+/// Directory of flat files with block bodies. Once created, block files are always used,
+/// because some of block bodies are only stored there.
+pub fn block_files_path(data_dir: &Option<String>) -> PathBuf {
+	db_path(data_dir).with_file_name("blocks")
 }
 
 pub fn node_table_path(cfg: &Config) -> PathBuf {
@@ -23,21 +105,44 @@ pub fn node_table_path(cfg: &Config) -> PathBuf {
 	node_table
 }
 
+pub fn memory_pool_path(data_dir: &Option<String>) -> PathBuf {
+	db_path(data_dir).with_file_name("mempool.dat")
+}
+
 pub fn init_db(cfg: &Config) -> Result<(), String> {
-	// insert genesis block if db is empty
-	let genesis_block = cfg.network.genesis_block();
-	match cfg.db.block_hash(0) {
+	init_genesis(&cfg.db, cfg.network)?;
+
+	// light client only stores headers, so its database can't be used by full node and vice versa
+	match (cfg.light_client_params.is_some(), cfg.db.is_headers_only()) {
+		(true, false) if cfg.db.best_block().number == 0 => cfg.db.set_headers_only().map_err(Into::into),
+		(true, false) => Err("Database has been created by full node. Remove it to start light client".into()),
+		(false, true) => Err("Database has been created by light client. Remove it to start full node".into()),
+		_ => Ok(()),
+	}
+}
+
+/// Inserts genesis block if db is empty
+pub fn init_genesis(db: &storage::SharedStore, network: Network) -> Result<(), String> {
+	let genesis_block = network.genesis_block();
+	match db.block_hash(0) {
 		Some(ref db_genesis_block_hash) if db_genesis_block_hash != genesis_block.hash() => Err("Trying to open database with incompatible genesis block".into()),
 		Some(_) => Ok(()),
 		None => {
 			let hash = genesis_block.hash().clone();
-			cfg.db.insert(genesis_block).expect("Failed to insert genesis block to the database");
-			cfg.db.canonize(&hash).expect("Failed to canonize genesis block");
+			db.insert(genesis_block).expect("Failed to insert genesis block to the database");
+			db.canonize(&hash).expect("Failed to canonize genesis block");
 			Ok(())
 		}
 	}
 }
 
+fn db_path(data_dir: &Option<String>) -> PathBuf {
+	match *data_dir {
+		Some(ref data_dir) => custom_path(&data_dir, "db"),
+		None => app_dir(AppDataType::UserData, &APP_INFO, "db").expect("Failed to get app dir"),
+	}
+}
+
 fn custom_path(data_dir: &str, sub_dir: &str) -> PathBuf {
 	let mut path = PathBuf::from(data_dir);
 	path.push(sub_dir);
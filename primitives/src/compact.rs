@@ -84,6 +84,19 @@ impl Compact {
 		Compact(compact | (size << 24) as u32)
 	}
 
+	/// Computes the expected number of hashes, required to find a block with this target.
+	/// Invalid targets represent no work.
+	pub fn to_work(&self) -> U256 {
+		let target = match self.to_u256() {
+			Ok(target) if !target.is_zero() => target,
+			_ => return U256::zero(),
+		};
+
+		// 2**256 / (target + 1) can't be computed directly, but as 2**256 is at least as large
+		// as target + 1, it is equal to ((2**256 - target - 1) / (target + 1)) + 1
+		(!target / (target + U256::one())) + U256::one()
+	}
+
 	pub fn to_f64(&self) -> f64 {
 		let mut shift = (self.0 >> 24) & 0xff;
 		let mut diff = f64::from(0x0000ffffu32) / f64::from(self.0 & 0x00ffffffu32);
@@ -136,6 +149,14 @@ mod tests {
 		assert_eq!(compact, compact2);
 	}
 
+	#[test]
+	fn test_compact_to_work() {
+		assert_eq!(Compact::new(0x1d00ffff).to_work(), U256::from(0x100010001u64));
+		assert_eq!(Compact::new(0x207fffff).to_work(), U256::from(2));
+		assert_eq!(Compact::new(0x04923456).to_work(), U256::zero());
+		assert_eq!(Compact::new(0x01003456).to_work(), U256::zero());
+	}
+
 	#[test]
 	fn difficulty() {
 		fn compare_f64(v1: f64, v2: f64) -> bool {
@@ -4,6 +4,9 @@ mod codes {
 	// NOTE [ToDr] Codes from [-32099, -32000]
 	pub const UNKNOWN: i64 = -32000;
 	pub const EXECUTION_ERROR: i64 = -32015;
+	pub const INDEX_INCOMPLETE: i64 = -32093;
+	pub const INDEX_DISABLED: i64 = -32094;
+	pub const BLOCK_PRUNED: i64 = -32095;
 	pub const TRANSACTION_NOT_FOUND: i64 = -32096;
 	pub const TRANSACTION_OUTPUT_NOT_FOUND: i64 = -32097;
 	pub const TRANSACTION_OF_SIDE_BRANCH: i64 = -32098;
@@ -15,18 +18,6 @@ mod codes {
 use std::fmt;
 use jsonrpc_core::{Error, ErrorCode, Value};
 
-macro_rules! rpc_unimplemented {
-	() => (Err(::v1::helpers::errors::unimplemented(None)))
-}
-
-pub fn unimplemented(details: Option<String>) -> Error {
-	Error {
-		code: ErrorCode::InternalError,
-		message: "This request is not implemented yet. Please create an issue on Github repo.".into(),
-		data: details.map(Value::String),
-	}
-}
-
 pub fn invalid_params<T: fmt::Debug>(param: &str, details: T) -> Error {
 	Error {
 		code: ErrorCode::InvalidParams,
@@ -51,6 +42,30 @@ pub fn block_not_found<T: fmt::Debug>(data: T) -> Error {
 	}
 }
 
+pub fn block_pruned<T: fmt::Debug>(data: T) -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::BLOCK_PRUNED),
+		message: "Block is not available (pruned data)".into(),
+		data: Some(Value::String(format!("{:?}", data))),
+	}
+}
+
+pub fn index_disabled(index: &str) -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::INDEX_DISABLED),
+		message: format!("{} index is disabled", index),
+		data: None,
+	}
+}
+
+pub fn index_incomplete<T: fmt::Debug>(index: &str, data: T) -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::INDEX_INCOMPLETE),
+		message: format!("{} index does not cover given data", index),
+		data: Some(Value::String(format!("{:?}", data))),
+	}
+}
+
 pub fn block_at_height_not_found<T: fmt::Debug>(data: T) -> Error {
 	Error {
 		code: ErrorCode::ServerError(codes::BLOCK_NOT_FOUND),
@@ -1,2 +1 @@
-#[macro_use]
 pub mod errors;
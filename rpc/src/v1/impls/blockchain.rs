@@ -2,14 +2,17 @@ use v1::traits::BlockChain;
 use v1::types::{GetBlockResponse, VerboseBlock, RawBlock};
 use v1::types::{GetTxOutResponse, TransactionOutputScript};
 use v1::types::GetTxOutSetInfoResponse;
+use v1::types::{GetTxSpendingPrevOutResponse, TransactionOutPoint};
+use std::path::PathBuf;
 use v1::types::H256;
-use v1::types::U256;
+use v1::types::ChainTip;
 use keys::{self, Address};
-use v1::helpers::errors::{block_not_found, block_at_height_not_found, transaction_not_found,
-	transaction_output_not_found, transaction_of_side_branch};
+use v1::helpers::errors::{block_not_found, block_pruned, block_at_height_not_found, transaction_not_found,
+	transaction_output_not_found, invalid_params, execution, index_disabled, index_incomplete};
 use jsonrpc_macros::Trailing;
 use jsonrpc_core::Error;
 use storage;
+use sync;
 use global_script::Script;
 use chain::OutPoint;
 use verification;
@@ -28,20 +31,30 @@ pub trait BlockChainClientCoreApi: Send + Sync + 'static {
 	fn difficulty(&self) -> f64;
 	fn raw_block(&self, hash: GlobalH256) -> Option<RawBlock>;
 	fn verbose_block(&self, hash: GlobalH256) -> Option<VerboseBlock>;
+	fn is_block_pruned(&self, hash: GlobalH256) -> bool;
 	fn verbose_transaction_out(&self, prev_out: OutPoint) -> Result<GetTxOutResponse, Error>;
+	fn transaction_out_set_info(&self) -> GetTxOutSetInfoResponse;
+	fn transaction_spending_prev_out(&self, prev_out: OutPoint) -> Result<GetTxSpendingPrevOutResponse, Error>;
+	fn chain_tips(&self) -> Vec<ChainTip>;
+	fn save_memory_pool(&self) -> Result<usize, String>;
+	fn load_memory_pool(&self) -> Result<usize, String>;
 }
 
 pub struct BlockChainClientCore {
 	network: Network,
 	storage: storage::SharedStore,
+	local_sync_node: sync::LocalNodeRef,
+	memory_pool_path: PathBuf,
 }
 
 impl BlockChainClientCore {
-	pub fn new(network: Network, storage: storage::SharedStore) -> Self {
+	pub fn new(network: Network, storage: storage::SharedStore, local_sync_node: sync::LocalNodeRef, memory_pool_path: PathBuf) -> Self {
 
 		BlockChainClientCore {
 			network: network,
 			storage: storage,
+			local_sync_node: local_sync_node,
+			memory_pool_path: memory_pool_path,
 		}
 	}
 }
@@ -92,7 +105,7 @@ impl BlockChainClientCoreApi for BlockChainClientCore {
 					height: height,
 					mediantime: Some(median_time),
 					difficulty: block.header.raw.bits.to_f64(),
-					chainwork: U256::default(), // TODO: read from storage
+					chainwork: self.storage.block_chainwork(block.hash().clone().into()).unwrap_or_default().into(),
 					previousblockhash: Some(block.header.raw.previous_header_hash.clone().into()),
 					nextblockhash: height.and_then(|h| self.storage.block_hash(h + 1).map(|h| h.into())),
 					bits: block.header.raw.bits.into(),
@@ -107,44 +120,45 @@ impl BlockChainClientCoreApi for BlockChainClientCore {
 			})
 	}
 
-	fn verbose_transaction_out(&self, prev_out: OutPoint) -> Result<GetTxOutResponse, Error> {
-		let transaction = match self.storage.transaction(&prev_out.hash) {
-			Some(transaction) => transaction,
-			// no transaction => no response
-			None => return Err(transaction_not_found(prev_out.hash)),
-		};
-
-		if prev_out.index >= transaction.raw.outputs.len() as u32 {
-			return Err(transaction_output_not_found(prev_out));
-		}
+	fn is_block_pruned(&self, hash: GlobalH256) -> bool {
+		self.storage.is_block_pruned(hash.into())
+	}
 
-		let meta = match self.storage.transaction_meta(&prev_out.hash) {
-			Some(meta) => meta,
-			// not in the main branch => no response
-			None => return Err(transaction_of_side_branch(prev_out.hash)),
+	fn verbose_transaction_out(&self, prev_out: OutPoint) -> Result<GetTxOutResponse, Error> {
+		// answer from the unspent outputs set, which is never pruned
+		let entry = match self.storage.unspent_output(&prev_out) {
+			Some(entry) => entry,
+			// spent, never existed or not in the main branch => no response
+			None => return match self.storage.transaction_meta(&prev_out.hash) {
+				Some(_) => Err(transaction_output_not_found(prev_out)),
+				None => Err(transaction_not_found(prev_out.hash)),
+			},
 		};
 
-		let block_header = match self.storage.block_header(meta.height().into()) {
+		let block_header = match self.storage.block_header(entry.height().into()) {
 			Some(block_header) => block_header,
 			// this is possible during reorgs
 			None => return Err(transaction_not_found(prev_out.hash)),
 		};
 
 		let best_block = self.storage.best_block();
-		if best_block.number < meta.height() {
+		if best_block.number < entry.height() {
 			// this is possible during reorgs
 			return Err(transaction_not_found(prev_out.hash));
 		}
 
-		let ref script_bytes = transaction.raw.outputs[prev_out.index as usize].script_pubkey;
+		// transaction itself is only available until its block is pruned
+		let version = self.storage.transaction(&prev_out.hash).map(|transaction| transaction.raw.version);
+
+		let ref script_bytes = entry.output().script_pubkey;
 		let script: Script = script_bytes.clone().into();
 		let script_asm = format!("{}", script);
 		let script_addresses = script.extract_destinations().unwrap_or(vec![]);
 
 		Ok(GetTxOutResponse {
 			bestblock: block_header.hash.into(),
-			confirmations: best_block.number - meta.height() + 1,
-			value: 0.00000001f64 * (transaction.raw.outputs[prev_out.index as usize].value as f64),
+			confirmations: best_block.number - entry.height() + 1,
+			value: 0.00000001f64 * (entry.output().value as f64),
 			script: TransactionOutputScript {
 				asm: script_asm,
 				hex: script_bytes.clone().into(),
@@ -161,10 +175,64 @@ impl BlockChainClientCoreApi for BlockChainClientCore {
 					kind: a.kind,
 				}).collect(),
 			},
-			version: transaction.raw.version,
-			coinbase: transaction.raw.is_coinbase(),
+			version: version,
+			coinbase: entry.is_coinbase(),
+		})
+	}
+
+	fn transaction_out_set_info(&self) -> GetTxOutSetInfoResponse {
+		let (best_block, info) = self.storage.utxo_set_info();
+		GetTxOutSetInfoResponse {
+			height: best_block.number,
+			bestblock: best_block.hash.into(),
+			transactions: info.transactions,
+			txouts: info.transaction_outputs,
+			bogosize: info.bogo_size,
+			muhash: info.muhash.finalize().into(),
+			total_amount: 0.00000001f64 * (info.total_amount as f64),
+		}
+	}
+
+	fn transaction_spending_prev_out(&self, prev_out: OutPoint) -> Result<GetTxSpendingPrevOutResponse, Error> {
+		let spent_index_height = match self.storage.spent_index_height() {
+			Some(height) => height,
+			None => return Err(index_disabled("Spent")),
+		};
+
+		let spending_input = self.storage.spending_input(&prev_out);
+		if spending_input.is_none() && self.storage.unspent_output(&prev_out).is_none() {
+			// output could have been spent before the index has been enabled
+			let is_created_before_index = self.storage.transaction_meta(&prev_out.hash)
+				.map_or(false, |meta| meta.height() < spent_index_height);
+			if is_created_before_index {
+				return Err(index_incomplete("Spent", prev_out));
+			}
+		}
+
+		Ok(GetTxSpendingPrevOutResponse {
+			txid: prev_out.hash.into(),
+			vout: prev_out.index,
+			blockhash: spending_input.as_ref()
+				.and_then(|input| self.storage.block_hash(input.height))
+				.map(Into::into),
+			spendingtxid: spending_input.map(|input| input.tx_hash.into()),
 		})
 	}
+
+	fn chain_tips(&self) -> Vec<ChainTip> {
+		self.local_sync_node.chain_tips()
+			.into_iter()
+			.map(Into::into)
+			.collect()
+	}
+
+	fn save_memory_pool(&self) -> Result<usize, String> {
+		self.local_sync_node.save_memory_pool(&self.memory_pool_path)
+	}
+
+	fn load_memory_pool(&self) -> Result<usize, String> {
+		self.local_sync_node.load_memory_pool(&self.memory_pool_path)
+	}
 }
 
 impl<T> BlockChainClient<T> where T: BlockChainClientCoreApi {
@@ -212,7 +280,11 @@ impl<T> BlockChain for BlockChainClient<T> where T: BlockChainClientCoreApi {
 			self.core.raw_block(global_hash.reversed())
 				.map(|block| GetBlockResponse::Raw(block))
 		}
-		.ok_or(block_not_found(hash))
+		.ok_or_else(|| if self.core.is_block_pruned(global_hash.reversed()) {
+			block_pruned(hash)
+		} else {
+			block_not_found(hash)
+		})
 	}
 
 	fn transaction_out(&self, transaction_hash: H256, out_index: u32, _include_mempool: Trailing<bool>) -> Result<GetTxOutResponse, Error> {
@@ -226,7 +298,45 @@ impl<T> BlockChain for BlockChainClient<T> where T: BlockChainClientCoreApi {
 	}
 
 	fn transaction_out_set_info(&self) -> Result<GetTxOutSetInfoResponse, Error> {
-		rpc_unimplemented!()
+		let mut response = self.core.transaction_out_set_info();
+		response.bestblock = response.bestblock.reversed();
+		response.muhash = response.muhash.reversed();
+		Ok(response)
+	}
+
+	fn transaction_spending_prev_out(&self, outputs: Vec<TransactionOutPoint>) -> Result<Vec<GetTxSpendingPrevOutResponse>, Error> {
+		if outputs.is_empty() {
+			return Err(invalid_params("outputs", "Outputs are missing"));
+		}
+
+		outputs.into_iter()
+			.map(|output| {
+				let transaction_hash: GlobalH256 = output.txid.into();
+				let mut response = self.core.transaction_spending_prev_out(OutPoint { hash: transaction_hash.reversed(), index: output.vout })?;
+				response.txid = response.txid.reversed();
+				response.spendingtxid = response.spendingtxid.map(|h| h.reversed());
+				response.blockhash = response.blockhash.map(|h| h.reversed());
+				Ok(response)
+			})
+			.collect()
+	}
+
+	fn chain_tips(&self) -> Result<Vec<ChainTip>, Error> {
+		Ok(self.core.chain_tips()
+			.into_iter()
+			.map(|mut tip| {
+				tip.hash = tip.hash.reversed();
+				tip
+			})
+			.collect())
+	}
+
+	fn save_memory_pool(&self) -> Result<usize, Error> {
+		self.core.save_memory_pool().map_err(execution)
+	}
+
+	fn load_memory_pool(&self) -> Result<usize, Error> {
+		self.core.load_memory_pool().map_err(execution)
 	}
 }
 
@@ -238,19 +348,33 @@ pub mod tests {
 	use jsonrpc_core::IoHandler;
 	use jsonrpc_core::Error;
 	use db::{BlockChainDatabase};
+	use db::kv::{MemoryDatabase, SharedMemoryDatabase};
+	use chain::IndexedBlock;
+	use storage::BlockProvider;
 	use primitives::bytes::Bytes as GlobalBytes;
 	use primitives::hash::H256 as GlobalH256;
 	use v1::types::{VerboseBlock, RawBlock};
 	use v1::traits::BlockChain;
-	use v1::types::{GetTxOutResponse, TransactionOutputScript};
+	use v1::types::{GetTxOutResponse, GetTxOutSetInfoResponse, TransactionOutputScript};
 	use v1::helpers::errors::block_not_found;
 	use v1::types::Bytes;
 	use v1::types::H256;
 	use v1::types::ScriptType;
 	use chain::OutPoint;
-	use network::Network;
+	use network::{Network, ConsensusParams, ConsensusFork};
+	use v1::types::ChainTipStatus;
 	use super::*;
 
+	fn local_sync_node(storage: storage::SharedStore) -> sync::LocalNodeRef {
+		let consensus = ConsensusParams::new(Network::Mainnet, ConsensusFork::BitcoinCore);
+		let verification_params = sync::VerificationParameters {
+			verification_level: verification::VerificationLevel::Full,
+			verification_edge: 0.into(),
+			assume_valid: None,
+		};
+		sync::create_local_sync_node(consensus, storage, sync::create_sync_peers(), verification_params, None)
+	}
+
 	#[derive(Default)]
 	struct SuccessBlockChainClientCore;
 	#[derive(Default)]
@@ -304,6 +428,10 @@ pub mod tests {
 			})
 		}
 
+		fn is_block_pruned(&self, _hash: GlobalH256) -> bool {
+			false
+		}
+
 		fn verbose_transaction_out(&self, _prev_out: OutPoint) -> Result<GetTxOutResponse, Error> {
 			Ok(GetTxOutResponse {
 				bestblock: H256::from(0x56),
@@ -316,10 +444,46 @@ pub mod tests {
 					script_type: ScriptType::Multisig,
 					addresses: vec!["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into(), "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into()],
 				},
-				version: 33,
+				version: Some(33),
 				coinbase: false,
 			})
 		}
+
+		fn transaction_out_set_info(&self) -> GetTxOutSetInfoResponse {
+			GetTxOutSetInfoResponse {
+				height: 1,
+				bestblock: H256::from(0x56),
+				transactions: 1,
+				txouts: 1,
+				bogosize: 117,
+				muhash: H256::from(0x78),
+				total_amount: 50.0,
+			}
+		}
+
+		fn transaction_spending_prev_out(&self, prev_out: OutPoint) -> Result<GetTxSpendingPrevOutResponse, Error> {
+			Ok(GetTxSpendingPrevOutResponse {
+				txid: prev_out.hash.into(),
+				vout: prev_out.index,
+				spendingtxid: Some(H256::from(0x78)),
+				blockhash: Some(H256::from(0x9a)),
+			})
+		}
+
+		fn chain_tips(&self) -> Vec<ChainTip> {
+			vec![
+				ChainTip { height: 2, hash: H256::from(0x56), branchlen: 0, status: ChainTipStatus::Active },
+				ChainTip { height: 1, hash: H256::from(0x78), branchlen: 1, status: ChainTipStatus::ValidFork },
+			]
+		}
+
+		fn save_memory_pool(&self) -> Result<usize, String> {
+			Ok(2)
+		}
+
+		fn load_memory_pool(&self) -> Result<usize, String> {
+			Ok(1)
+		}
 	}
 
 	impl BlockChainClientCoreApi for ErrorBlockChainClientCore {
@@ -347,9 +511,33 @@ pub mod tests {
 			None
 		}
 
+		fn is_block_pruned(&self, _hash: GlobalH256) -> bool {
+			false
+		}
+
 		fn verbose_transaction_out(&self, prev_out: OutPoint) -> Result<GetTxOutResponse, Error> {
 			Err(block_not_found(prev_out.hash))
 		}
+
+		fn transaction_out_set_info(&self) -> GetTxOutSetInfoResponse {
+			GetTxOutSetInfoResponse::default()
+		}
+
+		fn transaction_spending_prev_out(&self, _prev_out: OutPoint) -> Result<GetTxSpendingPrevOutResponse, Error> {
+			Err(index_disabled("Spent"))
+		}
+
+		fn chain_tips(&self) -> Vec<ChainTip> {
+			vec![]
+		}
+
+		fn save_memory_pool(&self) -> Result<usize, String> {
+			Err("Cannot write memory pool dump".into())
+		}
+
+		fn load_memory_pool(&self) -> Result<usize, String> {
+			Err("Cannot read memory pool dump".into())
+		}
 	}
 
 	#[test]
@@ -451,7 +639,7 @@ pub mod tests {
 			]
 		));
 
-		let core = BlockChainClientCore::new(Network::Mainnet, storage);
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
 
 		// get info on block #1:
 		// https://blockexplorer.com/block/00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048
@@ -474,7 +662,7 @@ pub mod tests {
 			nonce: 2573394689,
 			bits: 486604799,
 			difficulty: 1.0,
-			chainwork: 0.into(),
+			chainwork: 0x200020002u64.into(),
 			previousblockhash: Some("6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000".into()),
 			nextblockhash: Some("bddd99ccfda39da1b108ce1a5d70038d0a967bacb68b6b63065f626a00000000".into()),
 		}));
@@ -500,7 +688,7 @@ pub mod tests {
 			nonce: 1639830024,
 			bits: 486604799,
 			difficulty: 1.0,
-			chainwork: 0.into(),
+			chainwork: 0x300030003u64.into(),
 			previousblockhash: Some("4860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000".into()),
 			nextblockhash: None,
 		}));
@@ -585,10 +773,42 @@ pub mod tests {
 		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32099,"message":"Block with given hash is not found","data":"000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd"},"id":1}"#);
 	}
 
+	#[test]
+	fn block_pruned_error() {
+		let storage = BlockChainDatabase::open(MemoryDatabase::default()).with_prune_target(0);
+		let mut parent_hash = GlobalH256::default();
+		for i in 0..289 {
+			let block: IndexedBlock = test_data::block_builder()
+				.transaction().coinbase().output().value(i).build().build()
+				.merkled_header().parent(parent_hash).build()
+				.build()
+				.into();
+			parent_hash = block.hash().clone();
+			storage.insert(block).unwrap();
+			storage.canonize(&parent_hash).unwrap();
+		}
+		let genesis_hash: H256 = storage.block_hash(0).unwrap().reversed().into();
+
+		let storage = Arc::new(storage);
+		let client = BlockChainClient::new(BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into()));
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&format!(r#"
+			{{
+				"jsonrpc": "2.0",
+				"method": "getblock",
+				"params": ["{:?}", false],
+				"id": 1
+			}}"#, genesis_hash)).unwrap();
+
+		assert_eq!(sample, format!(r#"{{"jsonrpc":"2.0","error":{{"code":-32095,"message":"Block is not available (pruned data)","data":"{:?}"}},"id":1}}"#, genesis_hash));
+	}
+
 	#[test]
 	fn verbose_transaction_out_contents() {
 		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
-		let core = BlockChainClientCore::new(Network::Mainnet, storage);
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
 
 		// get info on tx from genesis block:
 		// https://blockchain.info/ru/tx/4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b
@@ -607,11 +827,41 @@ pub mod tests {
 					script_type: ScriptType::PubKey,
 					addresses: vec!["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into()]
 				},
-				version: 1,
+				version: Some(1),
 				coinbase: true
 			}));
 	}
 
+	#[test]
+	fn verbose_transaction_out_of_pruned_block() {
+		let storage = BlockChainDatabase::open(MemoryDatabase::default()).with_prune_target(0);
+		let mut parent_hash = GlobalH256::default();
+		let mut coinbase_hash = None;
+		for i in 0..289 {
+			let block: IndexedBlock = test_data::block_builder()
+				.transaction().coinbase().output().value(i).build().build()
+				.merkled_header().parent(parent_hash).build()
+				.build()
+				.into();
+			coinbase_hash = coinbase_hash.or_else(|| Some(block.transactions[0].hash.clone()));
+			parent_hash = block.hash().clone();
+			storage.insert(block).unwrap();
+			storage.canonize(&parent_hash).unwrap();
+		}
+		assert!(storage.is_block_pruned(0.into()));
+
+		let storage = Arc::new(storage);
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+		let verbose_transaction_out = core.verbose_transaction_out(OutPoint {
+			hash: coinbase_hash.unwrap(),
+			index: 0,
+		}).unwrap();
+		assert_eq!(verbose_transaction_out.confirmations, 289);
+		assert_eq!(verbose_transaction_out.value, 0.0);
+		assert_eq!(verbose_transaction_out.version, None);
+		assert!(verbose_transaction_out.coinbase);
+	}
+
 	#[test]
 	fn transaction_out_success() {
 		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
@@ -645,4 +895,260 @@ pub mod tests {
 
 		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32099,"message":"Block with given hash is not found","data":"3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a"},"id":1}"#);
 	}
+
+	#[test]
+	fn transaction_out_set_info_contents() {
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(
+			vec![
+				test_data::genesis().into(),
+				test_data::block_h1().into(),
+				test_data::block_h2().into(),
+			]
+		));
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+
+		// genesis output is not spendable, so it is not included
+		let info = core.transaction_out_set_info();
+		assert_eq!(info.height, 2);
+		assert_eq!(info.bestblock, test_data::block_h2().hash().into());
+		assert_eq!(info.transactions, 2);
+		assert_eq!(info.txouts, 2);
+		assert_eq!(info.bogosize, 2 * (50 + 67));
+		assert_eq!(info.total_amount, 100.0);
+	}
+
+	#[test]
+	fn transaction_out_set_info_success() {
+		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "gettxoutsetinfo",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"bestblock":"0000000000000000000000000000000000000000000000000000000000000056","bogosize":117,"height":1,"muhash":"0000000000000000000000000000000000000000000000000000000000000078","total_amount":50.0,"transactions":1,"txouts":1},"id":1}"#);
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_contents() {
+		let storage = BlockChainDatabase::open(MemoryDatabase::default()).with_spent_index();
+		let b0: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(50).build().build()
+			.merkled_header().build()
+			.build()
+			.into();
+		let b0_coinbase = b0.transactions[0].hash.clone();
+		let b1: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(50).build().build()
+			.transaction()
+				.input().hash(b0_coinbase.clone()).index(0).build()
+				.output().value(50).build()
+				.build()
+			.merkled_header().parent(b0.hash().clone()).build()
+			.build()
+			.into();
+		let b1_hash = b1.hash().clone();
+		let b1_tx1 = b1.transactions[1].hash.clone();
+		for block in vec![b0, b1] {
+			let hash = block.hash().clone();
+			storage.insert(block).unwrap();
+			storage.canonize(&hash).unwrap();
+		}
+		let storage = Arc::new(storage);
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+
+		let spent = core.transaction_spending_prev_out(OutPoint { hash: b0_coinbase.clone(), index: 0 }).unwrap();
+		assert_eq!(spent, GetTxSpendingPrevOutResponse {
+			txid: b0_coinbase.into(),
+			vout: 0,
+			spendingtxid: Some(b1_tx1.clone().into()),
+			blockhash: Some(b1_hash.into()),
+		});
+
+		let unspent = core.transaction_spending_prev_out(OutPoint { hash: b1_tx1.clone(), index: 0 }).unwrap();
+		assert_eq!(unspent, GetTxSpendingPrevOutResponse {
+			txid: b1_tx1.into(),
+			vout: 0,
+			spendingtxid: None,
+			blockhash: None,
+		});
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_spent_before_spent_index() {
+		let database = SharedMemoryDatabase::default();
+		let storage = BlockChainDatabase::open(database.clone());
+		let b0: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(50).build().build()
+			.merkled_header().build()
+			.build()
+			.into();
+		let b0_coinbase = b0.transactions[0].hash.clone();
+		let b1: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(50).build().build()
+			.transaction()
+				.input().hash(b0_coinbase.clone()).index(0).build()
+				.output().value(50).build()
+				.build()
+			.merkled_header().parent(b0.hash().clone()).build()
+			.build()
+			.into();
+		let b1_tx1 = b1.transactions[1].hash.clone();
+		for block in vec![b0, b1] {
+			let hash = block.hash().clone();
+			storage.insert(block).unwrap();
+			storage.canonize(&hash).unwrap();
+		}
+		let storage = Arc::new(BlockChainDatabase::open(database).with_spent_index());
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+
+		let spent = OutPoint { hash: b0_coinbase, index: 0 };
+		assert_eq!(core.transaction_spending_prev_out(spent.clone()), Err(index_incomplete("Spent", spent)));
+
+		let unspent = core.transaction_spending_prev_out(OutPoint { hash: b1_tx1, index: 0 }).unwrap();
+		assert_eq!(unspent.spendingtxid, None);
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_without_spent_index() {
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+
+		let genesis_coinbase = test_data::genesis().transactions[0].hash();
+		assert_eq!(core.transaction_spending_prev_out(OutPoint { hash: genesis_coinbase, index: 0 }), Err(index_disabled("Spent")));
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_success() {
+		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "gettxspendingprevout",
+				"params": [[{"txid": "0000000000000000000000000000000000000000000000000000000000000034", "vout": 1}]],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":[{"blockhash":"000000000000000000000000000000000000000000000000000000000000009a","spendingtxid":"0000000000000000000000000000000000000000000000000000000000000078","txid":"0000000000000000000000000000000000000000000000000000000000000034","vout":1}],"id":1}"#);
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_index_disabled() {
+		let client = BlockChainClient::new(ErrorBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "gettxspendingprevout",
+				"params": [[{"txid": "0000000000000000000000000000000000000000000000000000000000000034", "vout": 1}]],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32094,"message":"Spent index is disabled"},"id":1}"#);
+	}
+
+	#[test]
+	fn transaction_spending_prev_out_no_outputs() {
+		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "gettxspendingprevout",
+				"params": [[]],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: outputs","data":"\"Outputs are missing\""},"id":1}"#);
+	}
+
+	#[test]
+	fn chain_tips_success() {
+		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getchaintips",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":[{"branchlen":0,"hash":"0000000000000000000000000000000000000000000000000000000000000056","height":2,"status":"active"},{"branchlen":1,"hash":"0000000000000000000000000000000000000000000000000000000000000078","height":1,"status":"valid-fork"}],"id":1}"#);
+	}
+
+	#[test]
+	fn chain_tips_contents() {
+		let b0: IndexedBlock = test_data::genesis().into();
+		let b1: IndexedBlock = test_data::block_h1().into();
+		let fork: IndexedBlock = test_data::block_builder().header().parent(b0.hash().clone()).build().build().into();
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![b0, b1.clone()]));
+		storage.insert(fork.clone()).unwrap();
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), "mempool.dat".into());
+
+		assert_eq!(core.chain_tips(), vec![
+			ChainTip { height: 1, hash: b1.hash().clone().into(), branchlen: 0, status: ChainTipStatus::Active },
+			ChainTip { height: 1, hash: fork.hash().clone().into(), branchlen: 1, status: ChainTipStatus::ValidFork },
+		]);
+	}
+
+	#[test]
+	fn save_memory_pool_success() {
+		let client = BlockChainClient::new(SuccessBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "savemempool",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":2,"id":1}"#);
+	}
+
+	#[test]
+	fn load_memory_pool_error() {
+		let client = BlockChainClient::new(ErrorBlockChainClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "loadmempool",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"Cannot read memory pool dump\""},"id":1}"#);
+	}
+
+	#[test]
+	fn memory_pool_save_load() {
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let path = ::std::env::temp_dir().join(format!("pbtc-rpc-mempool-{}.dat", ::std::process::id()));
+		let core = BlockChainClientCore::new(Network::Mainnet, storage.clone(), local_sync_node(storage), path.clone());
+
+		assert!(core.load_memory_pool().is_err());
+		assert_eq!(core.save_memory_pool(), Ok(0));
+		assert_eq!(core.load_memory_pool(), Ok(0));
+		::std::fs::remove_file(path).unwrap();
+	}
 }
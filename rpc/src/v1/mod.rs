@@ -1,4 +1,3 @@
-#[macro_use]
 pub mod helpers;
 pub mod impls;
 pub mod traits;
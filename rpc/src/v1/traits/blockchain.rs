@@ -2,9 +2,12 @@ use jsonrpc_macros::Trailing;
 use jsonrpc_core::Error;
 
 use v1::types::H256;
+use v1::types::ChainTip;
 use v1::types::GetBlockResponse;
 use v1::types::GetTxOutResponse;
 use v1::types::GetTxOutSetInfoResponse;
+use v1::types::GetTxSpendingPrevOutResponse;
+use v1::types::TransactionOutPoint;
 
 
 build_rpc_trait! {
@@ -38,5 +41,21 @@ build_rpc_trait! {
 		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "gettxoutsetinfo", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
 		#[rpc(name = "gettxoutsetinfo")]
 		fn transaction_out_set_info(&self) -> Result<GetTxOutSetInfoResponse, Error>;
+		/// Get canon chain transactions, spending given outputs. Requires spent index.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "gettxspendingprevout", "params": [[{"txid": "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9", "vout": 0}]], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "gettxspendingprevout")]
+		fn transaction_spending_prev_out(&self, Vec<TransactionOutPoint>) -> Result<Vec<GetTxSpendingPrevOutResponse>, Error>;
+		/// Get information about all known tips in the block tree, including the main chain and side branches.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getchaintips", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "getchaintips")]
+		fn chain_tips(&self) -> Result<Vec<ChainTip>, Error>;
+		/// Write all memory pool transactions to the disk. Returns number of written transactions.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "savemempool", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "savemempool")]
+		fn save_memory_pool(&self) -> Result<usize, Error>;
+		/// Verify transactions, previously written by savemempool, and insert them to the memory pool. Returns number of inserted transactions.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "loadmempool", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "loadmempool")]
+		fn load_memory_pool(&self) -> Result<usize, Error>;
 	}
 }
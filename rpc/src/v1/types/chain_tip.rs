@@ -0,0 +1,84 @@
+use serde::{Serialize, Serializer};
+use sync;
+use super::hash::H256;
+
+/// getchaintips response item
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ChainTip {
+	/// Height of the chain tip
+	pub height: u32,
+	/// Hash of the chain tip
+	pub hash: H256,
+	/// Length of the branch, connecting the tip to the main chain. Zero for the main chain
+	pub branchlen: u32,
+	/// Status of the chain
+	pub status: ChainTipStatus,
+}
+
+/// Status of the chain, ending with the tip
+#[derive(Debug, PartialEq)]
+pub enum ChainTipStatus {
+	/// This is the tip of the main chain
+	Active,
+	/// This branch is fully validated, but is not the main chain
+	ValidFork,
+	/// All blocks of this branch are available, but not fully validated yet
+	ValidHeaders,
+	/// Only headers of this branch are available
+	HeadersOnly,
+	/// This branch contains at least one invalid block
+	Invalid,
+}
+
+impl From<sync::ChainTip> for ChainTip {
+	fn from(tip: sync::ChainTip) -> Self {
+		ChainTip {
+			height: tip.height,
+			hash: tip.hash.into(),
+			branchlen: tip.branch_len,
+			status: tip.status.into(),
+		}
+	}
+}
+
+impl From<sync::ChainTipStatus> for ChainTipStatus {
+	fn from(status: sync::ChainTipStatus) -> Self {
+		match status {
+			sync::ChainTipStatus::Active => ChainTipStatus::Active,
+			sync::ChainTipStatus::ValidFork => ChainTipStatus::ValidFork,
+			sync::ChainTipStatus::ValidHeaders => ChainTipStatus::ValidHeaders,
+			sync::ChainTipStatus::HeadersOnly => ChainTipStatus::HeadersOnly,
+			sync::ChainTipStatus::Invalid => ChainTipStatus::Invalid,
+		}
+	}
+}
+
+impl Serialize for ChainTipStatus {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+		match *self {
+			ChainTipStatus::Active => "active".serialize(serializer),
+			ChainTipStatus::ValidFork => "valid-fork".serialize(serializer),
+			ChainTipStatus::ValidHeaders => "valid-headers".serialize(serializer),
+			ChainTipStatus::HeadersOnly => "headers-only".serialize(serializer),
+			ChainTipStatus::Invalid => "invalid".serialize(serializer),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn chain_tip_serialize() {
+		let tip = ChainTip {
+			height: 100,
+			hash: H256::from(0x56),
+			branchlen: 2,
+			status: ChainTipStatus::ValidFork,
+		};
+		assert_eq!(serde_json::to_string(&tip).unwrap(), r#"{"height":100,"hash":"5600000000000000000000000000000000000000000000000000000000000000","branchlen":2,"status":"valid-fork"}"#);
+	}
+}
@@ -14,8 +14,9 @@ pub struct GetTxOutResponse {
 	/// Script info
 	#[serde(rename = "scriptPubKey")]
 	pub script: TransactionOutputScript,
-	/// This transaction version
-	pub version: i32,
+	/// This transaction version. None if the block, containing transaction, has been pruned
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version: Option<i32>,
 	/// Is this transactio a coinbase transaction?
 	pub coinbase: bool,
 }
@@ -42,7 +43,7 @@ mod tests {
 				script_type: ScriptType::Multisig,
 				addresses: vec!["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into(), "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into()],
 			},
-			version: 33,
+			version: Some(33),
 			coinbase: false,
 		};
 		assert_eq!(serde_json::to_string(&txout).unwrap(), r#"{"bestblock":"5600000000000000000000000000000000000000000000000000000000000000","confirmations":777,"value":100000.56,"scriptPubKey":{"asm":"Hello, world!!!","hex":"01020304","reqSigs":777,"type":"multisig","addresses":["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa","1H5m1XzvHsjWX3wwU781ubctznEpNACrNC"]},"version":33,"coinbase":false}"#);
@@ -61,7 +62,7 @@ mod tests {
 				script_type: ScriptType::Multisig,
 				addresses: vec!["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into(), "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into()],
 			},
-			version: 33,
+			version: Some(33),
 			coinbase: false,
 		};
 		assert_eq!(
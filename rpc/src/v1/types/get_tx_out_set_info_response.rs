@@ -1,3 +1,57 @@
-#[derive(Debug, Serialize, Deserialize)]
+use super::hash::H256;
+
+/// gettxoutsetinfo response
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct GetTxOutSetInfoResponse {
+	/// Height of the best block
+	pub height: u32,
+	/// Hash of the best block
+	pub bestblock: H256,
+	/// Number of transactions with unspent outputs
+	pub transactions: u64,
+	/// Number of unspent transaction outputs
+	pub txouts: u64,
+	/// Meaningless metric of the unspent outputs set size
+	pub bogosize: u64,
+	/// MuHash3072 commitment of the unspent outputs set
+	pub muhash: H256,
+	/// Total amount of unspent outputs in BTC
+	pub total_amount: f64,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn tx_out_set_info_response_serialize() {
+		let info = GetTxOutSetInfoResponse {
+			height: 1,
+			bestblock: H256::from(0x56),
+			transactions: 1,
+			txouts: 2,
+			bogosize: 150,
+			muhash: H256::from(0x78),
+			total_amount: 50.5,
+		};
+		assert_eq!(serde_json::to_string(&info).unwrap(), r#"{"height":1,"bestblock":"5600000000000000000000000000000000000000000000000000000000000000","transactions":1,"txouts":2,"bogosize":150,"muhash":"7800000000000000000000000000000000000000000000000000000000000000","total_amount":50.5}"#);
+	}
+
+	#[test]
+	fn tx_out_set_info_response_deserialize() {
+		let info = GetTxOutSetInfoResponse {
+			height: 1,
+			bestblock: H256::from(0x56),
+			transactions: 1,
+			txouts: 2,
+			bogosize: 150,
+			muhash: H256::from(0x78),
+			total_amount: 50.5,
+		};
+		assert_eq!(
+			serde_json::from_str::<GetTxOutSetInfoResponse>(r#"{"height":1,"bestblock":"5600000000000000000000000000000000000000000000000000000000000000","transactions":1,"txouts":2,"bogosize":150,"muhash":"7800000000000000000000000000000000000000000000000000000000000000","total_amount":50.5}"#).unwrap(),
+			info);
+	}
 }
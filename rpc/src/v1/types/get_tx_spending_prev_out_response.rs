@@ -0,0 +1,42 @@
+use super::hash::H256;
+
+/// gettxspendingprevout response item
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct GetTxSpendingPrevOutResponse {
+	/// Id of the transaction, which output is checked
+	pub txid: H256,
+	/// Index of the checked output
+	pub vout: u32,
+	/// Id of the canon chain transaction, spending the output. None if output is unspent
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub spendingtxid: Option<H256>,
+	/// Hash of the block, which contains the spending transaction
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub blockhash: Option<H256>,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::*;
+
+	#[test]
+	fn tx_spending_prev_out_response_serialize() {
+		let spent = GetTxSpendingPrevOutResponse {
+			txid: H256::from(0x56),
+			vout: 1,
+			spendingtxid: Some(H256::from(0x78)),
+			blockhash: Some(H256::from(0x9a)),
+		};
+		assert_eq!(serde_json::to_string(&spent).unwrap(), r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","vout":1,"spendingtxid":"7800000000000000000000000000000000000000000000000000000000000000","blockhash":"9a00000000000000000000000000000000000000000000000000000000000000"}"#);
+
+		let unspent = GetTxSpendingPrevOutResponse {
+			txid: H256::from(0x56),
+			vout: 1,
+			spendingtxid: None,
+			blockhash: None,
+		};
+		assert_eq!(serde_json::to_string(&unspent).unwrap(), r#"{"txid":"5600000000000000000000000000000000000000000000000000000000000000","vout":1}"#);
+	}
+}
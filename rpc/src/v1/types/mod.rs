@@ -3,9 +3,11 @@ mod block;
 mod block_template;
 mod block_template_request;
 mod bytes;
+mod chain_tip;
 mod get_block_response;
 mod get_tx_out_response;
 mod get_tx_out_set_info_response;
+mod get_tx_spending_prev_out_response;
 mod hash;
 mod script;
 mod transaction;
@@ -16,12 +18,14 @@ pub use self::block::RawBlock;
 pub use self::block_template::{BlockTemplate, BlockTemplateTransaction};
 pub use self::block_template_request::{BlockTemplateRequest, BlockTemplateRequestMode};
 pub use self::bytes::Bytes;
+pub use self::chain_tip::{ChainTip, ChainTipStatus};
 pub use self::get_block_response::{GetBlockResponse, VerboseBlock};
 pub use self::get_tx_out_response::GetTxOutResponse;
 pub use self::get_tx_out_set_info_response::GetTxOutSetInfoResponse;
+pub use self::get_tx_spending_prev_out_response::GetTxSpendingPrevOutResponse;
 pub use self::hash::{H160, H256};
 pub use self::script::ScriptType;
-pub use self::transaction::{RawTransaction, Transaction, TransactionInput, TransactionOutput,
+pub use self::transaction::{RawTransaction, Transaction, TransactionInput, TransactionOutPoint, TransactionOutput,
 	TransactionOutputWithAddress, TransactionOutputWithScriptData, TransactionInputScript,
 	TransactionOutputScript, SignedTransactionInput, GetRawTransactionResponse,
 	SignedTransactionOutput, TransactionOutputs};
@@ -21,6 +21,15 @@ pub struct TransactionInput {
 	pub sequence: Option<u32>,
 }
 
+/// Reference to the transaction output
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransactionOutPoint {
+	/// Transaction id
+	pub txid: H256,
+	/// Transaction output index
+	pub vout: u32,
+}
+
 /// Transaction output of form "address": amount
 #[derive(Debug, PartialEq)]
 pub struct TransactionOutputWithAddress {
@@ -258,6 +267,18 @@ mod tests {
 			txinput);
 	}
 
+	#[test]
+	fn transaction_out_point_deserialize() {
+		let outpoint = TransactionOutPoint {
+			txid: H256::from(7),
+			vout: 33,
+		};
+
+		assert_eq!(
+			serde_json::from_str::<TransactionOutPoint>(r#"{"txid":"0700000000000000000000000000000000000000000000000000000000000000","vout":33}"#).unwrap(),
+			outpoint);
+	}
+
 	#[test]
 	fn transaction_outputs_serialize() {
 		let txout = TransactionOutputs {
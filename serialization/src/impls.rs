@@ -2,6 +2,7 @@ use std::io;
 use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
 use bytes::Bytes;
 use compact::Compact;
+use bigint::U256;
 use hash::{H32, H48, H96, H160, H256, H264, H512, H520};
 use compact_integer::CompactInteger;
 use {Serializable, Stream, Deserializable, Reader, Error};
@@ -213,6 +214,27 @@ impl_ser_for_hash!(H264, 33);
 impl_ser_for_hash!(H512, 64);
 impl_ser_for_hash!(H520, 65);
 
+impl Serializable for U256 {
+	fn serialize(&self, stream: &mut Stream) {
+		let mut bytes = [0u8; 32];
+		self.to_little_endian(&mut bytes);
+		stream.append_slice(&bytes);
+	}
+
+	#[inline]
+	fn serialized_size(&self) -> usize {
+		32
+	}
+}
+
+impl Deserializable for U256 {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, Error> where T: io::Read {
+		let mut bytes = [0u8; 32];
+		reader.read_slice(&mut bytes)?;
+		Ok(U256::from_little_endian(&bytes))
+	}
+}
+
 impl Serializable for Bytes {
 	fn serialize(&self, stream: &mut Stream) {
 		stream
@@ -249,6 +271,7 @@ impl Deserializable for Compact {
 
 #[cfg(test)]
 mod tests {
+	use bigint::U256;
 	use bytes::Bytes;
 	use {serialize, deserialize, deserialize_iterator, Stream, Reader, Error};
 
@@ -346,4 +369,13 @@ mod tests {
 		stream.append_slice(&slice);
 		assert_eq!(stream.out(), "64000000".into());
 	}
+
+	#[test]
+	fn test_u256_serialize_roundtrip() {
+		let value = U256::from(0x0102030405060708u64) << 128;
+		let serialized = serialize(&value);
+		assert_eq!(serialized.len(), 32);
+		assert_eq!(serialized[16], 0x08);
+		assert_eq!(value, deserialize::<_, U256>(serialized.as_ref()).unwrap());
+	}
 }
@@ -7,7 +7,7 @@ mod list;
 mod reader;
 mod stream;
 
-pub use primitives::{hash, bytes, compact};
+pub use primitives::{hash, bytes, compact, bigint};
 
 pub use compact_integer::CompactInteger;
 pub use list::List;
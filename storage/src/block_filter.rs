@@ -0,0 +1,274 @@
+//! BIP158 compact block filters
+
+use std::collections::HashSet;
+use std::io;
+use hash::H256;
+use bytes::Bytes;
+use chain::IndexedBlock;
+use crypto::{dhash256, siphash24};
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader, CompactInteger, serialize};
+
+/// Type of the basic filter
+pub const BASIC_FILTER_TYPE: u8 = 0;
+/// Golomb-Rice coding parameter of the basic filter
+const BASIC_FILTER_P: u8 = 19;
+/// Inverse of the basic filter false positive rate
+const BASIC_FILTER_M: u64 = 784931;
+/// Outputs with scripts, starting with OP_RETURN, are not included in the filter
+const OP_RETURN: u8 = 0x6a;
+
+/// Basic compact filter of the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockFilter {
+	/// Serialized Golomb-coded set
+	pub filter: Bytes,
+	/// Header, committing to this filter and to headers of all previous filters
+	pub header: H256,
+}
+
+impl BlockFilter {
+	/// Builds basic filter of the block from its outputs and from outputs, spent by the block.
+	pub fn build_basic<'a, I>(block: &'a IndexedBlock, spent_scripts: I, previous_header: &H256) -> Self where I: IntoIterator<Item=&'a [u8]> {
+		let elements: HashSet<&[u8]> = block.transactions.iter()
+			.flat_map(|tx| tx.raw.outputs.iter())
+			.map(|output| -> &[u8] { &output.script_pubkey })
+			.filter(|script| script.first() != Some(&OP_RETURN))
+			.chain(spent_scripts)
+			.filter(|script| !script.is_empty())
+			.collect();
+
+		let filter = encode_set(block.hash(), &elements);
+		let header = filter_header(&dhash256(&filter), previous_header);
+		BlockFilter {
+			filter: filter,
+			header: header,
+		}
+	}
+
+	/// Returns hash of the filter
+	pub fn filter_hash(&self) -> H256 {
+		dhash256(&self.filter)
+	}
+
+	/// Returns true if any of given scripts possibly matches the filter of the block with given hash.
+	pub fn matches_any(&self, block_hash: &H256, scripts: &[&[u8]]) -> bool {
+		let n: u64 = match Reader::new(&self.filter).read::<CompactInteger>() {
+			Ok(n) => n.into(),
+			Err(_) => return false,
+		};
+		if n == 0 || scripts.is_empty() {
+			return false;
+		}
+
+		let (key0, key1) = siphash_keys(block_hash);
+		let mut queries: Vec<u64> = scripts.iter()
+			.map(|script| hash_to_range(key0, key1, n * BASIC_FILTER_M, script))
+			.collect();
+		queries.sort();
+
+		let offset = serialize(&CompactInteger::from(n)).len();
+		let mut bits = BitReader::new(&self.filter[offset..]);
+		let mut value = 0u64;
+		let mut queries = queries.into_iter().peekable();
+		for _ in 0..n {
+			value = match bits.read_golomb_rice(BASIC_FILTER_P) {
+				Some(delta) => value + delta,
+				None => return false,
+			};
+
+			while let Some(&query) = queries.peek() {
+				if query == value {
+					return true;
+				}
+				if query > value {
+					break;
+				}
+				queries.next();
+			}
+
+			if queries.peek().is_none() {
+				return false;
+			}
+		}
+
+		false
+	}
+}
+
+/// Computes filter header from the filter hash and the previous filter header
+pub fn filter_header(filter_hash: &H256, previous_header: &H256) -> H256 {
+	let mut stream = Stream::new();
+	stream.append(filter_hash).append(previous_header);
+	dhash256(&stream.out())
+}
+
+/// SipHash keys are the first two little-endian 64-bit integers of the block hash
+fn siphash_keys(block_hash: &H256) -> (u64, u64) {
+	let read_u64 = |bytes: &[u8]| bytes.iter().rev().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+	(read_u64(&block_hash[0..8]), read_u64(&block_hash[8..16]))
+}
+
+/// Maps element hash uniformly to the [0, range) range
+fn hash_to_range(key0: u64, key1: u64, range: u64, element: &[u8]) -> u64 {
+	((siphash24(key0, key1, element) as u128 * range as u128) >> 64) as u64
+}
+
+/// Serializes Golomb-coded set of given elements
+fn encode_set(block_hash: &H256, elements: &HashSet<&[u8]>) -> Bytes {
+	let n = elements.len() as u64;
+	let (key0, key1) = siphash_keys(block_hash);
+	let mut values: Vec<u64> = elements.iter()
+		.map(|element| hash_to_range(key0, key1, n * BASIC_FILTER_M, element))
+		.collect();
+	values.sort();
+
+	let mut bits = BitWriter::default();
+	values.into_iter().fold(0u64, |previous, value| {
+		bits.write_golomb_rice(value - previous, BASIC_FILTER_P);
+		value
+	});
+
+	let mut stream = Stream::new();
+	stream.append(&CompactInteger::from(n));
+	let mut result: Vec<u8> = stream.out().into();
+	result.extend(bits.into_bytes());
+	result.into()
+}
+
+/// Writes bits starting from the most significant bit of each byte
+#[derive(Default)]
+struct BitWriter {
+	bytes: Vec<u8>,
+	bits_len: usize,
+}
+
+impl BitWriter {
+	fn write_bit(&mut self, bit: bool) {
+		if self.bits_len % 8 == 0 {
+			self.bytes.push(0);
+		}
+		if bit {
+			let last = self.bytes.len() - 1;
+			self.bytes[last] |= 0x80 >> (self.bits_len % 8);
+		}
+		self.bits_len += 1;
+	}
+
+	fn write_golomb_rice(&mut self, value: u64, p: u8) {
+		for _ in 0..(value >> p) {
+			self.write_bit(true);
+		}
+		self.write_bit(false);
+		for bit in (0..p).rev() {
+			self.write_bit(value & (1 << bit) != 0);
+		}
+	}
+
+	fn into_bytes(self) -> Vec<u8> {
+		self.bytes
+	}
+}
+
+/// Reads bits starting from the most significant bit of each byte
+struct BitReader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl<'a> BitReader<'a> {
+	fn new(bytes: &'a [u8]) -> Self {
+		BitReader {
+			bytes: bytes,
+			position: 0,
+		}
+	}
+
+	fn read_bit(&mut self) -> Option<bool> {
+		let byte = *self.bytes.get(self.position / 8)?;
+		let bit = byte & (0x80 >> (self.position % 8)) != 0;
+		self.position += 1;
+		Some(bit)
+	}
+
+	fn read_golomb_rice(&mut self, p: u8) -> Option<u64> {
+		let mut quotient = 0u64;
+		while self.read_bit()? {
+			quotient += 1;
+		}
+
+		let mut remainder = 0u64;
+		for _ in 0..p {
+			remainder = (remainder << 1) | self.read_bit()? as u64;
+		}
+
+		Some((quotient << p) | remainder)
+	}
+}
+
+impl Serializable for BlockFilter {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.filter)
+			.append(&self.header);
+	}
+}
+
+impl Deserializable for BlockFilter {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(BlockFilter {
+			filter: reader.read()?,
+			header: reader.read()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test_data;
+
+	use hash::H256;
+	use chain::IndexedBlock;
+	use network::Network;
+	use ser::{serialize, deserialize};
+	use super::BlockFilter;
+
+	#[test]
+	fn test_basic_filter_of_testnet_genesis_block() {
+		// test vector from BIP158
+		let genesis = Network::Testnet.genesis_block();
+		let filter = BlockFilter::build_basic(&genesis, None, &H256::default());
+		assert_eq!(&*filter.filter, &[0x01, 0x9d, 0xfc, 0xa8][..]);
+		assert_eq!(filter.header, H256::from_reversed_str("21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750"));
+	}
+
+	#[test]
+	fn test_basic_filter_matches_block_scripts() {
+		let block: IndexedBlock = test_data::block_builder()
+			.transaction()
+				.coinbase()
+				.output().value(10).script_pubkey("51").build()
+				.output().value(10).script_pubkey("6a01ff").build()
+				.build()
+			.merkled_header().build()
+			.build()
+			.into();
+		let spent_script: &[u8] = &[0x52, 0x53];
+		let filter = BlockFilter::build_basic(&block, vec![spent_script], &H256::default());
+
+		assert!(filter.matches_any(block.hash(), &[&[0x51][..]]));
+		assert!(filter.matches_any(block.hash(), &[&[0x00][..], spent_script]));
+		assert!(!filter.matches_any(block.hash(), &[&[0x6a, 0x01, 0xff][..]]));
+		assert!(!filter.matches_any(block.hash(), &[]));
+	}
+
+	#[test]
+	fn test_block_filter_serialization_roundtrip() {
+		let filter = BlockFilter {
+			filter: vec![1, 2, 3].into(),
+			header: 5.into(),
+		};
+
+		let deserialized: BlockFilter = deserialize(serialize(&filter).as_ref()).unwrap();
+		assert_eq!(filter, deserialized);
+	}
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 use hash::H256;
+use primitives::bigint::U256;
 
 #[derive(Clone)]
 pub struct SideChainOrigin {
@@ -11,6 +12,8 @@ pub struct SideChainOrigin {
 	pub decanonized_route: Vec<H256>,
 	/// new block number
 	pub block_number: u32,
+	/// total work of the side chain, ending with the new block
+	pub chainwork: U256,
 }
 
 impl fmt::Debug for SideChainOrigin {
@@ -20,6 +23,7 @@ impl fmt::Debug for SideChainOrigin {
 			.field("canonized_route", &self.canonized_route.iter().map(|h| h.reversed()).collect::<Vec<_>>())
 			.field("decanonized_route", &self.decanonized_route.iter().map(|h| h.reversed()).collect::<Vec<_>>())
 			.field("block_number", &self.block_number)
+			.field("chainwork", &self.chainwork)
 			.finish()
 	}
 }
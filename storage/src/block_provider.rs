@@ -1,7 +1,8 @@
 use hash::H256;
+use primitives::bigint::U256;
 use bytes::Bytes;
 use chain::{IndexedBlock, IndexedBlockHeader, IndexedTransaction};
-use {BlockRef};
+use {BlockRef, BlockFilter};
 
 pub trait BlockHeaderProvider {
 	/// resolves header bytes by block reference (number/hash)
@@ -9,6 +10,9 @@ pub trait BlockHeaderProvider {
 
 	/// resolves header bytes by block reference (number/hash)
 	fn block_header(&self, block_ref: BlockRef) -> Option<IndexedBlockHeader>;
+
+	/// resolves total work of the chain, ending with the block, by block reference (number/hash)
+	fn block_chainwork(&self, block_ref: BlockRef) -> Option<U256>;
 }
 
 pub trait BlockProvider: BlockHeaderProvider {
@@ -26,9 +30,22 @@ pub trait BlockProvider: BlockHeaderProvider {
 		self.block_header_bytes(block_ref).is_some()
 	}
 
+	/// returns true if store contains header of given block, but its body has been pruned
+	fn is_block_pruned(&self, block_ref: BlockRef) -> bool;
+
 	/// resolves list of block transactions by block reference (number/hash)
 	fn block_transaction_hashes(&self, block_ref: BlockRef) -> Vec<H256>;
 
 	/// returns all transactions in the block by block reference (number/hash)
 	fn block_transactions(&self, block_ref: BlockRef) -> Vec<IndexedTransaction>;
 }
+
+/// Provides compact filters of canon chain blocks.
+/// Filters are only built when block filter index is enabled.
+pub trait BlockFilterProvider: Send + Sync {
+	/// resolves basic filter of the block by block hash
+	fn block_filter(&self, hash: &H256) -> Option<BlockFilter>;
+
+	/// number of the first block, since which filters are built. Filters chain is only complete if it starts at genesis
+	fn block_filter_index_height(&self) -> Option<u32>;
+}
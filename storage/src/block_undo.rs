@@ -0,0 +1,60 @@
+//! Block undo data
+
+use std::io;
+use chain::OutPoint;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader, CompactInteger};
+use UtxoEntry;
+
+/// Outputs, spent by canonized block.
+/// Used to restore unspent outputs set when block is decanonized.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockUndo {
+	/// Spent outputs in order of spending
+	pub spent: Vec<(OutPoint, UtxoEntry)>,
+	/// Unspent outputs, overwritten by outputs of duplicate transactions (BIP30 exceptions)
+	pub overwritten: Vec<(OutPoint, UtxoEntry)>,
+}
+
+impl Serializable for BlockUndo {
+	fn serialize(&self, stream: &mut Stream) {
+		stream.append(&CompactInteger::from(self.spent.len()));
+		for &(ref outpoint, ref entry) in &self.spent {
+			stream
+				.append(outpoint)
+				.append(entry);
+		}
+
+		// undo data of most blocks has no overwritten outputs => list is only written when it is not empty
+		if !self.overwritten.is_empty() {
+			stream.append(&CompactInteger::from(self.overwritten.len()));
+			for &(ref outpoint, ref entry) in &self.overwritten {
+				stream
+					.append(outpoint)
+					.append(entry);
+			}
+		}
+	}
+}
+
+impl Deserializable for BlockUndo {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let len: usize = reader.read::<CompactInteger>()?.into();
+		let mut spent = Vec::with_capacity(len);
+		for _ in 0..len {
+			spent.push((reader.read()?, reader.read()?));
+		}
+
+		let mut overwritten = Vec::new();
+		if !reader.is_finished() {
+			let len: usize = reader.read::<CompactInteger>()?.into();
+			for _ in 0..len {
+				overwritten.push((reader.read()?, reader.read()?));
+			}
+		}
+
+		Ok(BlockUndo {
+			spent: spent,
+			overwritten: overwritten,
+		})
+	}
+}
@@ -0,0 +1,216 @@
+//! Database consistency checks
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use hash::H256;
+use chain::{OutPoint, merkle_root};
+use {Store, BlockRef, TransactionMeta};
+
+/// Thoroughness of the database consistency checks. Every level includes all previous levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckLevel {
+	/// Check best block and mapping between canon block numbers and hashes
+	Numbers = 0,
+	/// Also check that canon headers are linked by previous header hash
+	Headers = 1,
+	/// Also recompute merkle roots from lists of block transactions
+	MerkleRoots = 2,
+	/// Also check that spent outputs of transactions meta are backed by canon spenders
+	TransactionMeta = 3,
+}
+
+impl CheckLevel {
+	/// Converts level number to the check level
+	pub fn from_u8(level: u8) -> Option<Self> {
+		match level {
+			0 => Some(CheckLevel::Numbers),
+			1 => Some(CheckLevel::Headers),
+			2 => Some(CheckLevel::MerkleRoots),
+			3 => Some(CheckLevel::TransactionMeta),
+			_ => None,
+		}
+	}
+}
+
+/// Inconsistency, found in the database
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inconsistency {
+	/// Best block is not the last block of the canon chain
+	BestBlock { number: u32, hash: H256 },
+	/// Canon block with given number is missing
+	MissingBlockHash { number: u32 },
+	/// Canon block hash is mapped to another number
+	BlockNumber { number: u32, hash: H256, stored_number: Option<u32> },
+	/// Header of canon block is missing
+	MissingHeader { number: u32, hash: H256 },
+	/// Header of canon block does not reference previous canon block
+	HeaderLink { number: u32, hash: H256 },
+	/// Body of the block, which has not been pruned, is missing
+	MissingBody { number: u32, hash: H256 },
+	/// Some of transactions of the canon block are missing
+	MissingTransactions { number: u32, hash: H256 },
+	/// Merkle root of block transactions does not match the header
+	MerkleRoot { number: u32, hash: H256 },
+	/// Meta of canon transaction is missing
+	MissingTransactionMeta { hash: H256 },
+	/// Meta of canon transaction points to another block
+	TransactionMetaHeight { hash: H256, height: u32, stored_height: u32 },
+	/// Output is marked as spent, but it is not spent by canon transaction
+	UnbackedSpentOutput { outpoint: OutPoint },
+	/// Output is spent by canon transaction, but it is not marked as spent
+	UnmarkedSpentOutput { outpoint: OutPoint },
+}
+
+impl fmt::Display for Inconsistency {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Inconsistency::BestBlock { number, ref hash } =>
+				write!(f, "best block {} ({}) is not the last canon block", number, hash.reversed()),
+			Inconsistency::MissingBlockHash { number } =>
+				write!(f, "canon block {} is missing", number),
+			Inconsistency::BlockNumber { number, ref hash, stored_number } =>
+				write!(f, "canon block {} ({}) is stored with number {:?}", number, hash.reversed(), stored_number),
+			Inconsistency::MissingHeader { number, ref hash } =>
+				write!(f, "header of canon block {} ({}) is missing", number, hash.reversed()),
+			Inconsistency::HeaderLink { number, ref hash } =>
+				write!(f, "header of canon block {} ({}) does not reference previous canon block", number, hash.reversed()),
+			Inconsistency::MissingBody { number, ref hash } =>
+				write!(f, "body of canon block {} ({}) is missing", number, hash.reversed()),
+			Inconsistency::MissingTransactions { number, ref hash } =>
+				write!(f, "transactions of canon block {} ({}) are missing", number, hash.reversed()),
+			Inconsistency::MerkleRoot { number, ref hash } =>
+				write!(f, "merkle root of canon block {} ({}) does not match its transactions", number, hash.reversed()),
+			Inconsistency::MissingTransactionMeta { ref hash } =>
+				write!(f, "meta of canon transaction {} is missing", hash.reversed()),
+			Inconsistency::TransactionMetaHeight { ref hash, height, stored_height } =>
+				write!(f, "meta of canon transaction {} from block {} points to block {}", hash.reversed(), height, stored_height),
+			Inconsistency::UnbackedSpentOutput { ref outpoint } =>
+				write!(f, "output {}:{} is marked as spent, but it has no canon spender", outpoint.hash.reversed(), outpoint.index),
+			Inconsistency::UnmarkedSpentOutput { ref outpoint } =>
+				write!(f, "output {}:{} is spent by canon transaction, but it is not marked as spent", outpoint.hash.reversed(), outpoint.index),
+		}
+	}
+}
+
+/// Checks the latest `depth` canon blocks (or all canon blocks if `depth` is zero) and returns all found inconsistencies.
+pub fn check_consistency(store: &dyn Store, depth: u32, level: CheckLevel) -> Vec<Inconsistency> {
+	let mut inconsistencies = Vec::new();
+
+	let best_block = store.best_block();
+	if store.block_hash(best_block.number).as_ref() != Some(&best_block.hash) || store.block_hash(best_block.number + 1).is_some() {
+		inconsistencies.push(Inconsistency::BestBlock { number: best_block.number, hash: best_block.hash.clone() });
+	}
+
+	let first_block = match depth {
+		0 => 0,
+		depth => (best_block.number + 1).saturating_sub(depth),
+	};
+
+	// transactions of checked blocks in the chain order
+	let mut created: Vec<(H256, u32, usize)> = Vec::new();
+	// outputs, spent by checked blocks
+	let mut spent: HashSet<OutPoint> = HashSet::new();
+	let mut previous_hash = match first_block {
+		0 => None,
+		number => store.block_hash(number - 1),
+	};
+	for number in first_block..best_block.number + 1 {
+		let hash = match store.block_hash(number) {
+			Some(hash) => hash,
+			None => {
+				inconsistencies.push(Inconsistency::MissingBlockHash { number: number });
+				previous_hash = None;
+				continue;
+			},
+		};
+
+		let stored_number = store.block_number(&hash);
+		if stored_number != Some(number) {
+			inconsistencies.push(Inconsistency::BlockNumber { number: number, hash: hash.clone(), stored_number: stored_number });
+		}
+
+		if level >= CheckLevel::Headers {
+			match store.block_header(hash.clone().into()) {
+				None => inconsistencies.push(Inconsistency::MissingHeader { number: number, hash: hash.clone() }),
+				Some(ref header) if number != 0 && previous_hash.as_ref() != Some(&header.raw.previous_header_hash) =>
+					inconsistencies.push(Inconsistency::HeaderLink { number: number, hash: hash.clone() }),
+				Some(_) => (),
+			}
+		}
+
+		if level >= CheckLevel::MerkleRoots && !store.is_block_pruned(hash.clone().into()) {
+			let transaction_hashes = store.block_transaction_hashes(hash.clone().into());
+			match store.block_header(hash.clone().into()) {
+				Some(_) if transaction_hashes.is_empty() =>
+					inconsistencies.push(Inconsistency::MissingBody { number: number, hash: hash.clone() }),
+				Some(ref header) if merkle_root(&transaction_hashes) != header.raw.merkle_root_hash =>
+					inconsistencies.push(Inconsistency::MerkleRoot { number: number, hash: hash.clone() }),
+				_ => (),
+			}
+		}
+
+		if level >= CheckLevel::TransactionMeta {
+			match store.block(BlockRef::Hash(hash.clone())) {
+				Some(block) => for transaction in &block.transactions {
+					created.push((transaction.hash.clone(), number, transaction.raw.outputs.len()));
+					if !transaction.raw.is_coinbase() {
+						spent.extend(transaction.raw.inputs.iter().map(|input| input.previous_output.clone()));
+					}
+				},
+				// missing bodies are reported by the previous level
+				None if store.block_transaction_hashes(hash.clone().into()).is_empty() => (),
+				None => inconsistencies.push(Inconsistency::MissingTransactions { number: number, hash: hash.clone() }),
+			}
+		}
+
+		previous_hash = Some(hash);
+	}
+
+	if level >= CheckLevel::TransactionMeta {
+		check_transactions_meta(store, &created, &spent, &mut inconsistencies);
+	}
+
+	inconsistencies
+}
+
+/// Spenders of outputs, created by checked blocks, could only be in checked blocks
+fn check_transactions_meta(store: &dyn Store, created: &[(H256, u32, usize)], spent: &HashSet<OutPoint>, inconsistencies: &mut Vec<Inconsistency>) {
+	// meta of duplicate (pre-BIP30) transaction belongs to the latest one
+	let heights: HashMap<&H256, u32> = created.iter().map(|&(ref hash, height, _)| (hash, height)).collect();
+	for &(ref hash, height, outputs) in created {
+		if heights[hash] != height {
+			continue;
+		}
+
+		let meta = match store.transaction_meta(hash) {
+			Some(meta) => meta,
+			None => {
+				inconsistencies.push(Inconsistency::MissingTransactionMeta { hash: hash.clone() });
+				continue;
+			},
+		};
+
+		if meta.height() != height {
+			inconsistencies.push(Inconsistency::TransactionMetaHeight { hash: hash.clone(), height: height, stored_height: meta.height() });
+		}
+
+		for index in 0..outputs {
+			let outpoint = OutPoint { hash: hash.clone(), index: index as u32 };
+			match (meta.is_spent(index).unwrap_or(false), spent.contains(&outpoint)) {
+				(true, false) => inconsistencies.push(Inconsistency::UnbackedSpentOutput { outpoint: outpoint }),
+				(false, true) => inconsistencies.push(Inconsistency::UnmarkedSpentOutput { outpoint: outpoint }),
+				_ => (),
+			}
+		}
+	}
+
+	// outputs, created before the first checked block, must be marked as spent
+	let mut metas: HashMap<H256, Option<TransactionMeta>> = HashMap::new();
+	for outpoint in spent.iter().filter(|outpoint| !heights.contains_key(&outpoint.hash)) {
+		let meta = metas.entry(outpoint.hash.clone()).or_insert_with(|| store.transaction_meta(&outpoint.hash));
+		match *meta {
+			Some(ref meta) if meta.is_spent(outpoint.index as usize) == Some(true) => (),
+			_ => inconsistencies.push(Inconsistency::UnmarkedSpentOutput { outpoint: outpoint.clone() }),
+		}
+	}
+}
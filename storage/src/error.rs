@@ -15,6 +15,15 @@ pub enum Error {
 	/// Ancient fork
 	#[display(fmt = "Fork is too long to proceed")]
 	AncientFork,
+	/// Unspent outputs set snapshot could not be loaded
+	#[display(fmt = "Cannot load utxo snapshot: {}", _0)]
+	CannotLoadUtxoSnapshot(String),
+	/// Database could not be reindexed
+	#[display(fmt = "Cannot reindex database: {}", _0)]
+	CannotReindex(String),
+	/// Database has been written by newer version of the node
+	#[display(fmt = "Database version {} is newer than the latest supported version {}. Please upgrade the node", _0, _1)]
+	NewerDatabaseVersion(u32, u32),
 }
 
 impl From<Error> for String {
@@ -8,35 +8,53 @@ extern crate display_derive;
 extern crate primitives;
 extern crate serialization as ser;
 extern crate chain;
+extern crate bitcrypto as crypto;
 extern crate network;
 
 mod best_block;
 mod block_ancestors;
 mod block_chain;
+mod block_filter;
 mod block_impls;
 mod block_iterator;
 mod block_origin;
 mod block_provider;
 mod block_ref;
+mod block_undo;
+mod consistency;
 mod duplex_store;
 mod error;
+mod script_history;
+mod spending_input;
 mod store;
 mod transaction_meta;
 mod transaction_provider;
+mod utxo_entry;
+mod utxo_set_info;
+mod utxo_snapshot;
 
 pub use primitives::{hash, bytes};
 
 pub use best_block::BestBlock;
 pub use block_ancestors::BlockAncestors;
 pub use block_chain::{BlockChain, ForkChain, Forkable};
+pub use block_filter::{BlockFilter, BASIC_FILTER_TYPE, filter_header};
 pub use block_iterator::BlockIterator;
 pub use block_origin::{BlockOrigin, SideChainOrigin};
-pub use block_provider::{BlockHeaderProvider, BlockProvider};
+pub use block_provider::{BlockHeaderProvider, BlockProvider, BlockFilterProvider};
 pub use block_ref::BlockRef;
+pub use block_undo::BlockUndo;
+pub use consistency::{CheckLevel, Inconsistency, check_consistency};
 pub use duplex_store::{DuplexTransactionOutputProvider, NoopStore, transaction_index_for_output_check};
 pub use error::Error;
-pub use store::{AsSubstore, Store, SharedStore, CanonStore, ConfigStore};
+pub use script_history::{ScriptHistoryEntry, ScriptHistoryKind, ScriptHistoryKey, script_hash};
+pub use spending_input::SpendingInput;
+pub use store::{AsSubstore, Store, SharedStore, CanonStore, ConfigStore, UtxoSnapshotStore, HeadersStore};
 pub use transaction_meta::TransactionMeta;
 pub use transaction_provider::{
 	TransactionProvider, TransactionOutputProvider, TransactionMetaProvider, CachedTransactionOutputProvider,
+	ScriptHistoryProvider, SpendingInputProvider,
 };
+pub use utxo_entry::UtxoEntry;
+pub use utxo_set_info::UtxoSetInfo;
+pub use utxo_snapshot::{UtxoSnapshotHeader, UtxoSnapshotValidation};
@@ -0,0 +1,149 @@
+//! History of transactions funding and spending outputs with given script
+
+use std::io;
+use hash::H256;
+use chain::OutPoint;
+use crypto::sha256;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader};
+
+/// Returns hash of the script pubkey, which is used as a key of the script history.
+pub fn script_hash(script_pubkey: &[u8]) -> H256 {
+	sha256(script_pubkey)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptHistoryKind {
+	/// Transaction creates output with the script
+	Funding,
+	/// Transaction spends output with the script
+	Spending,
+}
+
+/// Single entry of the script history
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptHistoryEntry {
+	/// Funding or spending transaction
+	pub tx_hash: H256,
+	/// Height of the block, which contains the transaction
+	pub height: u32,
+	/// Does transaction fund or spend the output
+	pub kind: ScriptHistoryKind,
+	/// Funded or spent output
+	pub outpoint: OutPoint,
+	/// Value of the output
+	pub value: u64,
+}
+
+/// Key of the single script history entry. Entries of the script share the script hash prefix
+/// and are ordered by height and index of the entry within the block.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScriptHistoryKey {
+	/// Hash of the script pubkey
+	pub script_hash: H256,
+	/// Height of the block, which contains the transaction
+	pub height: u32,
+	/// Index of the entry among block entries of the same script
+	pub index: u32,
+}
+
+impl Serializable for ScriptHistoryKey {
+	fn serialize(&self, stream: &mut Stream) {
+		// big-endian numbers keep entries of the script ordered in the database
+		stream
+			.append(&self.script_hash)
+			.append_slice(&self.height.to_be_bytes())
+			.append_slice(&self.index.to_be_bytes());
+	}
+}
+
+impl Deserializable for ScriptHistoryKey {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let script_hash = reader.read()?;
+		let mut height = [0u8; 4];
+		reader.read_slice(&mut height)?;
+		let mut index = [0u8; 4];
+		reader.read_slice(&mut index)?;
+
+		Ok(ScriptHistoryKey {
+			script_hash: script_hash,
+			height: u32::from_be_bytes(height),
+			index: u32::from_be_bytes(index),
+		})
+	}
+}
+
+impl Serializable for ScriptHistoryEntry {
+	fn serialize(&self, stream: &mut Stream) {
+		let kind: u8 = match self.kind {
+			ScriptHistoryKind::Funding => 0,
+			ScriptHistoryKind::Spending => 1,
+		};
+
+		stream
+			.append(&self.tx_hash)
+			.append(&self.height)
+			.append(&kind)
+			.append(&self.outpoint)
+			.append(&self.value);
+	}
+}
+
+impl Deserializable for ScriptHistoryEntry {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let tx_hash = reader.read()?;
+		let height = reader.read()?;
+		let kind = match reader.read::<u8>()? {
+			0 => ScriptHistoryKind::Funding,
+			1 => ScriptHistoryKind::Spending,
+			_ => return Err(ReaderError::MalformedData),
+		};
+
+		Ok(ScriptHistoryEntry {
+			tx_hash: tx_hash,
+			height: height,
+			kind: kind,
+			outpoint: reader.read()?,
+			value: reader.read()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::OutPoint;
+	use ser::{serialize, deserialize};
+	use super::{ScriptHistoryEntry, ScriptHistoryKind, ScriptHistoryKey};
+
+	#[test]
+	fn test_script_history_entry_serialization_roundtrip() {
+		let entry = ScriptHistoryEntry {
+			tx_hash: 2.into(),
+			height: 100,
+			kind: ScriptHistoryKind::Spending,
+			outpoint: OutPoint {
+				hash: 1.into(),
+				index: 3,
+			},
+			value: 50,
+		};
+
+		let deserialized: ScriptHistoryEntry = deserialize(serialize(&entry).as_ref()).unwrap();
+		assert_eq!(entry, deserialized);
+	}
+
+	#[test]
+	fn test_script_history_keys_are_ordered() {
+		let key = |height, index| serialize(&ScriptHistoryKey {
+			script_hash: 1.into(),
+			height: height,
+			index: index,
+		}).take();
+
+		assert!(key(1, 0x100) < key(2, 0));
+		assert!(key(0x100, 0) > key(2, 0));
+		assert!(key(2, 1) < key(2, 0x100));
+
+		let deserialized: ScriptHistoryKey = deserialize(&key(0x100, 3) as &[u8]).unwrap();
+		assert_eq!(deserialized, ScriptHistoryKey { script_hash: 1.into(), height: 0x100, index: 3 });
+	}
+}
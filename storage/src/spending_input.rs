@@ -0,0 +1,51 @@
+use std::io;
+use hash::H256;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader};
+
+/// Canon chain input, which spends an output
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendingInput {
+	/// Hash of the spending transaction
+	pub tx_hash: H256,
+	/// Index of the spending input within the transaction
+	pub input_index: u32,
+	/// Height of the block, which contains the spending transaction
+	pub height: u32,
+}
+
+impl Serializable for SpendingInput {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.tx_hash)
+			.append(&self.input_index)
+			.append(&self.height);
+	}
+}
+
+impl Deserializable for SpendingInput {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(SpendingInput {
+			tx_hash: reader.read()?,
+			input_index: reader.read()?,
+			height: reader.read()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ser::{serialize, deserialize};
+	use super::SpendingInput;
+
+	#[test]
+	fn test_spending_input_serialization_roundtrip() {
+		let input = SpendingInput {
+			tx_hash: 5.into(),
+			input_index: 2,
+			height: 1000,
+		};
+
+		let deserialized: SpendingInput = deserialize(serialize(&input).as_ref()).unwrap();
+		assert_eq!(input, deserialized);
+	}
+}
@@ -1,12 +1,52 @@
 use std::sync::Arc;
-use chain::IndexedBlockHeader;
+use hash::H256;
+use chain::{IndexedBlockHeader, OutPoint};
 use {
 	BestBlock, BlockProvider, BlockHeaderProvider, TransactionProvider, TransactionMetaProvider,
-	TransactionOutputProvider, BlockChain, Forkable, Error,
+	TransactionOutputProvider, BlockChain, Forkable, Error, UtxoSetInfo, ScriptHistoryProvider,
+	SpendingInputProvider, BlockFilterProvider, UtxoEntry, UtxoSnapshotValidation,
 };
 
-pub trait CanonStore: Store + Forkable + ConfigStore {
+pub trait CanonStore: Store + Forkable + ConfigStore + UtxoSnapshotStore + HeadersStore {
 	fn as_store(&self) -> &dyn Store;
+
+	/// rebuild unspent outputs set, transactions meta, undo data and indexes from stored canon blocks
+	fn reindex_chainstate(&self) -> Result<(), Error>;
+}
+
+/// Unspent outputs set snapshot interface
+pub trait UtxoSnapshotStore {
+	/// iterate over unspent outputs of the canon chain in unspecified order
+	fn utxos<'a>(&'a self) -> Box<dyn Iterator<Item = (OutPoint, UtxoEntry)> + 'a>;
+
+	/// replace the chain, which only contains genesis block, with given headers chain
+	/// and unspent outputs set at its best block. Bodies of these blocks are treated as pruned.
+	fn load_utxo_snapshot(
+		&self,
+		headers: Vec<IndexedBlockHeader>,
+		utxos: &mut dyn Iterator<Item = Result<(OutPoint, UtxoEntry), Error>>,
+	) -> Result<UtxoSetInfo, Error>;
+
+	/// get the loaded snapshot, which history is not yet validated in the background
+	fn utxo_snapshot_validation(&self) -> Option<UtxoSnapshotValidation>;
+
+	/// remember result of the background validation of the loaded snapshot history.
+	/// Once the history is valid, the snapshot is no longer different from the synchronized chain.
+	fn finish_utxo_snapshot_validation(&self, is_valid: bool) -> Result<(), Error>;
+}
+
+/// Headers-only chain interface, used by light clients
+pub trait HeadersStore {
+	/// insert header of the block, which body is never stored, and make it the best block
+	/// if its chain has more work than the canon chain. Returns true if the best block has changed.
+	/// Only blocks without bodies could be decanonized this way.
+	fn insert_header(&self, header: &IndexedBlockHeader) -> Result<bool, Error>;
+
+	/// mark the database as the one, which only stores headers. Bodies of all blocks are treated as pruned after that.
+	fn set_headers_only(&self) -> Result<(), Error>;
+
+	/// true if the database only stores headers
+	fn is_headers_only(&self) -> bool;
 }
 
 /// Configuration storage interface
@@ -28,10 +68,22 @@ pub trait Store: AsSubstore {
 
 	/// get blockchain difficulty
 	fn difficulty(&self) -> f64;
+
+	/// get statistics of the unspent outputs set along with the best block they are computed at
+	fn utxo_set_info(&self) -> (BestBlock, UtxoSetInfo);
+
+	/// get hashes of stored blocks, which have no stored children
+	fn chain_tips(&self) -> Vec<H256>;
+
+	/// get number of the first canon block, which body has not been pruned
+	fn pruned_height(&self) -> u32;
+
+	/// get entry of the canon chain unspent outputs set
+	fn unspent_output(&self, outpoint: &OutPoint) -> Option<UtxoEntry>;
 }
 
 /// Allows casting Arc<Store> to reference to any substore type
-pub trait AsSubstore: BlockChain + BlockProvider + TransactionProvider + TransactionMetaProvider + TransactionOutputProvider {
+pub trait AsSubstore: BlockChain + BlockProvider + TransactionProvider + TransactionMetaProvider + TransactionOutputProvider + ScriptHistoryProvider + SpendingInputProvider + BlockFilterProvider {
 	fn as_block_provider(&self) -> &dyn BlockProvider;
 
 	fn as_block_header_provider(&self) -> &dyn BlockHeaderProvider;
@@ -41,9 +93,15 @@ pub trait AsSubstore: BlockChain + BlockProvider + TransactionProvider + Transac
 	fn as_transaction_output_provider(&self) -> &dyn TransactionOutputProvider;
 
 	fn as_transaction_meta_provider(&self) -> &dyn TransactionMetaProvider;
+
+	fn as_script_history_provider(&self) -> &dyn ScriptHistoryProvider;
+
+	fn as_spending_input_provider(&self) -> &dyn SpendingInputProvider;
+
+	fn as_block_filter_provider(&self) -> &dyn BlockFilterProvider;
 }
 
-impl<T> AsSubstore for T where T: BlockChain + BlockProvider + TransactionProvider + TransactionMetaProvider + TransactionOutputProvider {
+impl<T> AsSubstore for T where T: BlockChain + BlockProvider + TransactionProvider + TransactionMetaProvider + TransactionOutputProvider + ScriptHistoryProvider + SpendingInputProvider + BlockFilterProvider {
 	fn as_block_provider(&self) -> &dyn BlockProvider {
 		&*self
 	}
@@ -63,6 +121,18 @@ impl<T> AsSubstore for T where T: BlockChain + BlockProvider + TransactionProvid
 	fn as_transaction_meta_provider(&self) -> &dyn TransactionMetaProvider {
 		&*self
 	}
+
+	fn as_script_history_provider(&self) -> &dyn ScriptHistoryProvider {
+		&*self
+	}
+
+	fn as_spending_input_provider(&self) -> &dyn SpendingInputProvider {
+		&*self
+	}
+
+	fn as_block_filter_provider(&self) -> &dyn BlockFilterProvider {
+		&*self
+	}
 }
 
 pub type SharedStore = Arc<dyn CanonStore + Send + Sync>;
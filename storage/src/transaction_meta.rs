@@ -60,6 +60,14 @@ impl TransactionMeta {
 		self.bits.set(index + 1 , true);
 	}
 
+	/// Increase number of transaction outputs up to given number, denoting new outputs as used
+	pub fn grow_outputs(&mut self, outputs: usize) {
+		let outputs_len = self.outputs_len();
+		if outputs > outputs_len {
+			self.bits.grow(outputs - outputs_len, true);
+		}
+	}
+
 	/// Denote particular output as not used
 	pub fn denote_unused(&mut self, index: usize) {
 		self.bits.set(index + 1, false);
@@ -73,6 +81,11 @@ impl TransactionMeta {
 		self.bits.get(idx + 1)
 	}
 
+	/// Returns number of transaction outputs
+	pub fn outputs_len(&self) -> usize {
+		self.bits.len() - 1
+	}
+
 	pub fn is_fully_spent(&self) -> bool {
 		// skip coinbase bit, the rest needs to true
 		self.bits.iter().skip(1).all(|x| x)
@@ -95,4 +108,17 @@ mod tests {
 		t.denote_unused(0);
 		assert!(!t.is_fully_spent());
 	}
+
+	#[test]
+	fn test_grow_outputs() {
+		let mut t = TransactionMeta::new_coinbase(0, 1);
+		t.grow_outputs(3);
+		assert_eq!(t.outputs_len(), 3);
+		assert!(t.is_coinbase());
+		assert_eq!(t.is_spent(0), Some(false));
+		assert_eq!(t.is_spent(2), Some(true));
+
+		t.grow_outputs(2);
+		assert_eq!(t.outputs_len(), 3);
+	}
 }
@@ -3,7 +3,7 @@ use parking_lot::RwLock;
 use hash::H256;
 use bytes::Bytes;
 use chain::{IndexedTransaction, OutPoint, TransactionOutput};
-use {TransactionMeta};
+use {TransactionMeta, ScriptHistoryEntry, ScriptHistoryKind, SpendingInput};
 
 /// Should be used to obtain all transactions from canon chain and forks.
 pub trait TransactionProvider {
@@ -35,6 +35,40 @@ pub trait TransactionMetaProvider: Send + Sync {
 	fn transaction_meta(&self, hash: &H256) -> Option<TransactionMeta>;
 }
 
+/// Provides history of canon chain transactions, funding and spending outputs with given script.
+/// History is only recorded when address index is enabled.
+pub trait ScriptHistoryProvider: Send + Sync {
+	/// Returns history of the script with given hash, ordered by block height.
+	/// History is only complete if address index has been enabled since the genesis block.
+	fn script_history(&self, script_hash: &H256) -> Vec<ScriptHistoryEntry>;
+
+	/// Returns number of the first block, since which history of every script is recorded.
+	/// None if address index is disabled.
+	fn address_index_height(&self) -> Option<u32>;
+
+	/// Returns total value of unspent outputs with the script with given hash.
+	fn script_balance(&self, script_hash: &H256) -> u64 {
+		let (funded, spent) = self.script_history(script_hash)
+			.into_iter()
+			.fold((0u64, 0u64), |(funded, spent), entry| match entry.kind {
+				ScriptHistoryKind::Funding => (funded + entry.value, spent),
+				ScriptHistoryKind::Spending => (funded, spent + entry.value),
+			});
+		funded.saturating_sub(spent)
+	}
+}
+
+/// Provides canon chain inputs, spending given outputs.
+/// Inputs are only recorded when spent index is enabled.
+pub trait SpendingInputProvider: Send + Sync {
+	/// Returns input, which spends given output.
+	fn spending_input(&self, outpoint: &OutPoint) -> Option<SpendingInput>;
+
+	/// Returns number of the first block, since which inputs, spending outputs, are recorded.
+	/// None if spent index is disabled.
+	fn spent_index_height(&self) -> Option<u32>;
+}
+
 /// Transaction output provider that caches all read outputs.
 ///
 /// Not intended for long-lasting life, because it never clears its internal
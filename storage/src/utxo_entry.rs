@@ -0,0 +1,118 @@
+//! Unspent transaction output
+
+use std::io;
+use chain::TransactionOutput;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader};
+
+/// Maximal size of the script that could be spent.
+const MAX_SCRIPT_SIZE: usize = 10_000;
+/// OP_RETURN opcode.
+const OP_RETURN: u8 = 0x6a;
+
+/// Entry of the unspent transaction outputs set
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoEntry {
+	/// Unspent output
+	output: TransactionOutput,
+	/// Height of the block, which contains output transaction
+	block_height: u32,
+	/// Is output created by a coinbase transaction
+	is_coinbase: bool,
+}
+
+impl Serializable for UtxoEntry {
+	fn serialize(&self, stream: &mut Stream) {
+		// coinbase flag is packed into the lowest bit of height, just like in Bitcoin Core
+		let code = (self.block_height << 1) | (self.is_coinbase as u32);
+		stream
+			.append(&code)
+			.append(&self.output);
+	}
+}
+
+impl Deserializable for UtxoEntry {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let code: u32 = reader.read()?;
+		let result = UtxoEntry {
+			output: reader.read()?,
+			block_height: code >> 1,
+			is_coinbase: code & 1 != 0,
+		};
+
+		Ok(result)
+	}
+}
+
+impl UtxoEntry {
+	/// New unspent output entry
+	pub fn new(output: TransactionOutput, block_height: u32, is_coinbase: bool) -> Self {
+		UtxoEntry {
+			output: output,
+			block_height: block_height,
+			is_coinbase: is_coinbase,
+		}
+	}
+
+	/// Returns true if output could never be spent and should not be added to the utxo set
+	pub fn is_unspendable(output: &TransactionOutput) -> bool {
+		output.script_pubkey.len() > MAX_SCRIPT_SIZE ||
+			output.script_pubkey.first() == Some(&OP_RETURN)
+	}
+
+	pub fn output(&self) -> &TransactionOutput {
+		&self.output
+	}
+
+	pub fn into_output(self) -> TransactionOutput {
+		self.output
+	}
+
+	pub fn height(&self) -> u32 {
+		self.block_height
+	}
+
+	pub fn is_coinbase(&self) -> bool {
+		self.is_coinbase
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::TransactionOutput;
+	use ser::{serialize, deserialize};
+	use super::UtxoEntry;
+
+	#[test]
+	fn test_utxo_entry_serialization_roundtrip() {
+		let output = TransactionOutput {
+			value: 50,
+			script_pubkey: "76a914".into(),
+		};
+
+		let entry = UtxoEntry::new(output.clone(), 100, true);
+		let deserialized: UtxoEntry = deserialize(serialize(&entry).as_ref()).unwrap();
+		assert_eq!(entry, deserialized);
+		assert_eq!(deserialized.height(), 100);
+		assert!(deserialized.is_coinbase());
+
+		let entry = UtxoEntry::new(output, 101, false);
+		let deserialized: UtxoEntry = deserialize(serialize(&entry).as_ref()).unwrap();
+		assert_eq!(entry, deserialized);
+		assert!(!deserialized.is_coinbase());
+	}
+
+	#[test]
+	fn test_is_unspendable() {
+		let output = TransactionOutput {
+			value: 0,
+			script_pubkey: "6a00".into(),
+		};
+		assert!(UtxoEntry::is_unspendable(&output));
+
+		let output = TransactionOutput {
+			value: 0,
+			script_pubkey: "76a914".into(),
+		};
+		assert!(!UtxoEntry::is_unspendable(&output));
+	}
+}
@@ -0,0 +1,133 @@
+//! Unspent transaction outputs set statistics
+
+use std::io;
+use crypto::{MuHash3072, MUHASH_STATE_SIZE};
+use chain::OutPoint;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader, serialize};
+use UtxoEntry;
+
+/// Size of the outpoint + entry header in Bitcoin Core's coins database, used to compute bogo size.
+const BOGO_SIZE_PER_OUTPUT: u64 = 50;
+
+/// Statistics of the unspent transaction outputs set.
+/// Updated incrementally on every canonize/decanonize.
+#[derive(Clone, Default)]
+pub struct UtxoSetInfo {
+	/// Number of transactions with unspent outputs
+	pub transactions: u64,
+	/// Number of unspent outputs
+	pub transaction_outputs: u64,
+	/// Meaningless metric of the utxo set size, compatible with Bitcoin Core
+	pub bogo_size: u64,
+	/// Total amount of unspent outputs in satoshis
+	pub total_amount: u64,
+	/// Total size of serialized unspent outputs
+	pub serialized_size: u64,
+	/// Rolling set hash of serialized unspent outputs
+	pub muhash: MuHash3072,
+}
+
+impl Serializable for UtxoSetInfo {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.transactions)
+			.append(&self.transaction_outputs)
+			.append(&self.bogo_size)
+			.append(&self.total_amount)
+			.append(&self.serialized_size)
+			.append_slice(&self.muhash.to_bytes());
+	}
+}
+
+impl Deserializable for UtxoSetInfo {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let transactions = reader.read()?;
+		let transaction_outputs = reader.read()?;
+		let bogo_size = reader.read()?;
+		let total_amount = reader.read()?;
+		let serialized_size = reader.read()?;
+		let mut muhash = [0u8; MUHASH_STATE_SIZE];
+		reader.read_slice(&mut muhash)?;
+
+		Ok(UtxoSetInfo {
+			transactions: transactions,
+			transaction_outputs: transaction_outputs,
+			bogo_size: bogo_size,
+			total_amount: total_amount,
+			serialized_size: serialized_size,
+			muhash: MuHash3072::from_bytes(&muhash),
+		})
+	}
+}
+
+impl UtxoSetInfo {
+	/// Adds output to the set
+	pub fn insert_output(&mut self, outpoint: &OutPoint, entry: &UtxoEntry) {
+		self.transaction_outputs += 1;
+		self.bogo_size += Self::bogo_size(entry);
+		self.total_amount += entry.output().value;
+		let element = Self::muhash_element(outpoint, entry);
+		self.serialized_size += element.len() as u64;
+		self.muhash.insert(&element);
+	}
+
+	/// Removes output from the set
+	pub fn remove_output(&mut self, outpoint: &OutPoint, entry: &UtxoEntry) {
+		self.transaction_outputs -= 1;
+		self.bogo_size -= Self::bogo_size(entry);
+		self.total_amount -= entry.output().value;
+		let element = Self::muhash_element(outpoint, entry);
+		self.serialized_size -= element.len() as u64;
+		self.muhash.remove(&element);
+	}
+
+	fn bogo_size(entry: &UtxoEntry) -> u64 {
+		BOGO_SIZE_PER_OUTPUT + entry.output().script_pubkey.len() as u64
+	}
+
+	fn muhash_element(outpoint: &OutPoint, entry: &UtxoEntry) -> Vec<u8> {
+		let mut element: Vec<u8> = serialize(outpoint).into();
+		element.extend_from_slice(&serialize(entry));
+		element
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::{OutPoint, TransactionOutput};
+	use ser::{serialize, deserialize};
+	use UtxoEntry;
+	use super::UtxoSetInfo;
+
+	#[test]
+	fn test_utxo_set_info_insert_remove() {
+		let outpoint = OutPoint {
+			hash: 1.into(),
+			index: 0,
+		};
+		let entry = UtxoEntry::new(TransactionOutput {
+			value: 50,
+			script_pubkey: "76a914".into(),
+		}, 1, true);
+
+		let empty = UtxoSetInfo::default();
+		let mut info = UtxoSetInfo::default();
+		info.insert_output(&outpoint, &entry);
+		assert_eq!(info.transaction_outputs, 1);
+		assert_eq!(info.bogo_size, 53);
+		assert_eq!(info.total_amount, 50);
+		assert_eq!(info.serialized_size, 36 + 16);
+		assert!(info.muhash.finalize() != empty.muhash.finalize());
+
+		let deserialized: UtxoSetInfo = deserialize(serialize(&info).as_ref()).unwrap();
+		assert_eq!(deserialized.transaction_outputs, 1);
+		assert_eq!(deserialized.muhash.finalize(), info.muhash.finalize());
+
+		info.remove_output(&outpoint, &entry);
+		assert_eq!(info.transaction_outputs, 0);
+		assert_eq!(info.bogo_size, 0);
+		assert_eq!(info.total_amount, 0);
+		assert_eq!(info.serialized_size, 0);
+		assert_eq!(info.muhash.finalize(), empty.muhash.finalize());
+	}
+}
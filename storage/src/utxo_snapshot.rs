@@ -0,0 +1,120 @@
+//! Snapshot of the unspent transaction outputs set
+
+use std::io;
+use hash::H256;
+use ser::{Serializable, Deserializable, Error as ReaderError, Stream, Reader};
+
+/// Magic bytes, starting every snapshot
+const UTXO_SNAPSHOT_MAGIC: [u8; 5] = [b'u', b't', b'x', b'o', 0xff];
+/// Version of the snapshot format
+const UTXO_SNAPSHOT_VERSION: u16 = 1;
+
+/// Header of the unspent outputs set snapshot.
+/// It is followed by headers of all blocks from genesis to the snapshot block and then
+/// by all (outpoint, entry) pairs of the unspent outputs set at this block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoSnapshotHeader {
+	/// Hash of the block, at which the snapshot is taken
+	pub block_hash: H256,
+	/// Height of the block, at which the snapshot is taken
+	pub block_height: u32,
+	/// Number of unspent outputs in the snapshot
+	pub outputs: u64,
+}
+
+impl Serializable for UtxoSnapshotHeader {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append_slice(&UTXO_SNAPSHOT_MAGIC)
+			.append(&UTXO_SNAPSHOT_VERSION)
+			.append(&self.block_hash)
+			.append(&self.block_height)
+			.append(&self.outputs);
+	}
+}
+
+impl Deserializable for UtxoSnapshotHeader {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let mut magic = [0u8; 5];
+		reader.read_slice(&mut magic)?;
+		let version: u16 = reader.read()?;
+		if magic != UTXO_SNAPSHOT_MAGIC || version != UTXO_SNAPSHOT_VERSION {
+			return Err(ReaderError::MalformedData);
+		}
+
+		Ok(UtxoSnapshotHeader {
+			block_hash: reader.read()?,
+			block_height: reader.read()?,
+			outputs: reader.read()?,
+		})
+	}
+}
+
+/// Unspent outputs set snapshot, loaded into the database. Blocks below the snapshot are validated
+/// in the background until the unspent outputs set of their chain is compared to the snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoSnapshotValidation {
+	/// Hash of the block, at which the snapshot is taken
+	pub block_hash: H256,
+	/// Height of the block, at which the snapshot is taken
+	pub block_height: u32,
+	/// MuHash3072 of the loaded unspent outputs set
+	pub utxo_set_hash: H256,
+	/// True if the background chain has reached the snapshot block with another unspent outputs set
+	pub is_failed: bool,
+}
+
+impl Serializable for UtxoSnapshotValidation {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(&self.block_hash)
+			.append(&self.block_height)
+			.append(&self.utxo_set_hash)
+			.append(&self.is_failed);
+	}
+}
+
+impl Deserializable for UtxoSnapshotValidation {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		Ok(UtxoSnapshotValidation {
+			block_hash: reader.read()?,
+			block_height: reader.read()?,
+			utxo_set_hash: reader.read()?,
+			is_failed: reader.read()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ser::{serialize, deserialize, Error as ReaderError};
+	use super::{UtxoSnapshotHeader, UtxoSnapshotValidation};
+
+	#[test]
+	fn test_utxo_snapshot_header_serialization_roundtrip() {
+		let header = UtxoSnapshotHeader {
+			block_hash: 5.into(),
+			block_height: 100,
+			outputs: 1000,
+		};
+
+		let serialized = serialize(&header);
+		assert_eq!(header, deserialize(serialized.as_ref()).unwrap());
+
+		let mut corrupted: Vec<u8> = serialized.into();
+		corrupted[0] = 0;
+		assert_eq!(deserialize::<_, UtxoSnapshotHeader>(corrupted.as_slice()).unwrap_err(), ReaderError::MalformedData);
+	}
+
+	#[test]
+	fn test_utxo_snapshot_validation_serialization_roundtrip() {
+		let validation = UtxoSnapshotValidation {
+			block_hash: 5.into(),
+			block_height: 100,
+			utxo_set_hash: 7.into(),
+			is_failed: true,
+		};
+
+		assert_eq!(validation, deserialize(serialize(&validation).as_ref()).unwrap());
+	}
+}
@@ -13,8 +13,8 @@ use types::StorageRef;
 use utils::OrphanBlocksPool;
 use VerificationParameters;
 
-/// Maximum number of orphaned in-memory blocks
-pub const MAX_ORPHANED_BLOCKS: usize = 1024;
+/// Default maximal total size of orphaned in-memory blocks
+pub const DEFAULT_MAX_ORPHANED_BLOCKS_SIZE: usize = 256 * 1024 * 1024;
 
 /// Synchronous block writer
 pub struct BlocksWriter {
@@ -22,6 +22,10 @@ pub struct BlocksWriter {
 	storage: StorageRef,
 	/// Orphaned blocks pool
 	orphaned_blocks_pool: OrphanBlocksPool,
+	/// Total size of orphaned blocks
+	orphaned_blocks_size: usize,
+	/// Maximal total size of orphaned blocks
+	max_orphaned_blocks_size: usize,
 	/// Blocks verifier
 	verifier: SyncVerifier<BlocksWriterSink>,
 	/// Verification events receiver
@@ -51,11 +55,19 @@ impl BlocksWriter {
 		BlocksWriter {
 			storage: storage,
 			orphaned_blocks_pool: OrphanBlocksPool::new(),
+			orphaned_blocks_size: 0,
+			max_orphaned_blocks_size: DEFAULT_MAX_ORPHANED_BLOCKS_SIZE,
 			verifier: verifier,
 			sink: sink_data,
 		}
 	}
 
+	/// Limit total size of blocks, which are kept in memory until their parent is appended
+	pub fn with_max_orphaned_blocks_size(mut self, size: usize) -> Self {
+		self.max_orphaned_blocks_size = size;
+		self
+	}
+
 	/// Append new block
 	pub fn append_block(&mut self, block: chain::IndexedBlock) -> Result<(), Error> {
 		// do not append block if it is already there
@@ -65,9 +77,12 @@ impl BlocksWriter {
 
 		// verify && insert only if parent block is already in the storage
 		if !self.storage.contains_block(storage::BlockRef::Hash(block.header.raw.previous_header_hash.clone())) {
-			self.orphaned_blocks_pool.insert_orphaned_block(block);
+			let block_size = block.size();
+			if self.orphaned_blocks_pool.insert_orphaned_block(block) {
+				self.orphaned_blocks_size += block_size;
+			}
 			// we can't hold many orphaned blocks in memory during import
-			if self.orphaned_blocks_pool.len() > MAX_ORPHANED_BLOCKS {
+			if self.orphaned_blocks_size > self.max_orphaned_blocks_size {
 				return Err(Error::TooManyOrphanBlocks);
 			}
 			return Ok(());
@@ -75,6 +90,7 @@ impl BlocksWriter {
 
 		// verify && insert block && all its orphan children
 		let mut verification_queue: VecDeque<chain::IndexedBlock> = self.orphaned_blocks_pool.remove_blocks_for_parent(block.hash());
+		self.orphaned_blocks_size -= verification_queue.iter().map(chain::IndexedBlock::size).sum::<usize>();
 		verification_queue.push_front(block);
 		while let Some(block) = verification_queue.pop_front() {
 			self.verifier.verify_block(block);
@@ -144,17 +160,19 @@ mod tests {
 	extern crate test_data;
 
 	use std::sync::Arc;
+	use chain::IndexedBlock;
 	use db::{BlockChainDatabase};
 	use network::{ConsensusParams, ConsensusFork, Network};
 	use verification::VerificationLevel;
 	use super::super::Error;
-	use super::{BlocksWriter, MAX_ORPHANED_BLOCKS};
+	use super::BlocksWriter;
 	use VerificationParameters;
 
 	fn default_verification_params() -> VerificationParameters {
 		VerificationParameters {
 			verification_level: VerificationLevel::Full,
 			verification_edge: 0u8.into(),
+			assume_valid: None,
 		}
 	}
 
@@ -169,12 +187,15 @@ mod tests {
 	#[test]
 	fn blocks_writer_verification_error() {
 		let db = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
-		let blocks = test_data::build_n_empty_blocks_from_genesis((MAX_ORPHANED_BLOCKS + 2) as u32, 1);
-		let mut blocks_target = BlocksWriter::new(db.clone(), ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore), default_verification_params());
+		let max_orphaned_blocks = 16;
+		let blocks = test_data::build_n_empty_blocks_from_genesis((max_orphaned_blocks + 2) as u32, 1);
+		let max_orphaned_blocks_size = max_orphaned_blocks * IndexedBlock::from(blocks[1].clone()).size();
+		let mut blocks_target = BlocksWriter::new(db.clone(), ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore), default_verification_params())
+			.with_max_orphaned_blocks_size(max_orphaned_blocks_size);
 		for (index, block) in blocks.into_iter().skip(1).enumerate() {
 			match blocks_target.append_block(block.into()) {
-				Err(Error::TooManyOrphanBlocks) if index == MAX_ORPHANED_BLOCKS => (),
-				Ok(_) if index != MAX_ORPHANED_BLOCKS => (),
+				Err(Error::TooManyOrphanBlocks) if index == max_orphaned_blocks => (),
+				Ok(_) if index != max_orphaned_blocks => (),
 				_ => panic!("unexpected"),
 			}
 		}
@@ -196,6 +217,28 @@ mod tests {
 		assert_eq!(db.best_block().number, 0);
 	}
 
+	#[test]
+	fn blocks_writer_appends_orphaned_blocks_after_parent() {
+		let db = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let blocks = test_data::build_n_empty_blocks_from_genesis(4, 1);
+		let mut blocks_target = BlocksWriter::new(db.clone(), ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore), VerificationParameters {
+			verification_level: VerificationLevel::NoVerification,
+			verification_edge: 0u8.into(),
+			assume_valid: None,
+		});
+		for block in blocks.iter().skip(1).rev() {
+			assert_eq!(blocks_target.append_block(block.clone().into()), Ok(()));
+			assert_eq!(db.best_block().number, 0);
+		}
+		// duplicate orphans are only counted once
+		assert_eq!(blocks_target.append_block(blocks[1].clone().into()), Ok(()));
+		assert_eq!(blocks_target.orphaned_blocks_size, blocks.iter().skip(1).map(|block| IndexedBlock::from(block.clone()).size()).sum::<usize>());
+
+		assert_eq!(blocks_target.append_block(blocks[0].clone().into()), Ok(()));
+		assert_eq!(db.best_block().number, 4);
+		assert_eq!(blocks_target.orphaned_blocks_size, 0);
+	}
+
 	#[test]
 	fn blocks_writer_append_to_existing_db() {
 		let db = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
@@ -221,6 +264,7 @@ mod tests {
 		let mut blocks_target = BlocksWriter::new(db.clone(), ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore), VerificationParameters {
 			verification_level: VerificationLevel::NoVerification,
 			verification_edge: 0u8.into(),
+			assume_valid: None,
 		});
 		assert_eq!(blocks_target.append_block(b1.into()), Ok(()));
 		assert_eq!(blocks_target.append_block(b2.into()), Ok(()));
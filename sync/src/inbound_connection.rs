@@ -171,6 +171,18 @@ impl InboundSyncConnection for InboundConnection {
 	fn on_notfound(&self, message: types::NotFound) {
 		self.node.on_notfound(self.peer_index, message);
 	}
+
+	fn on_get_cfilters(&self, message: types::GetCFilters) {
+		self.node.on_get_cfilters(self.peer_index, message);
+	}
+
+	fn on_get_cfheaders(&self, message: types::GetCFHeaders) {
+		self.node.on_get_cfheaders(self.peer_index, message);
+	}
+
+	fn on_get_cfcheckpt(&self, message: types::GetCFCheckpt) {
+		self.node.on_get_cfcheckpt(self.peer_index, message);
+	}
 }
 
 #[cfg(test)]
@@ -214,9 +226,14 @@ pub mod tests {
 		fn send_feefilter(&self, _message: &types::FeeFilter) { *self.messages.lock().entry("feefilter".to_owned()).or_insert(0) += 1; }
 		fn send_send_compact(&self, _message: &types::SendCompact) { *self.messages.lock().entry("sendcompact".to_owned()).or_insert(0) += 1; }
 		fn send_compact_block(&self, _message: &types::CompactBlock) { *self.messages.lock().entry("cmpctblock".to_owned()).or_insert(0) += 1; }
+		fn send_witness_compact_block(&self, _message: &types::CompactBlock) { *self.messages.lock().entry("witness_cmpctblock".to_owned()).or_insert(0) += 1; }
 		fn send_get_block_txn(&self, _message: &types::GetBlockTxn) { *self.messages.lock().entry("getblocktxn".to_owned()).or_insert(0) += 1; }
 		fn send_block_txn(&self, _message: &types::BlockTxn) { *self.messages.lock().entry("blocktxn".to_owned()).or_insert(0) += 1; }
+		fn send_witness_block_txn(&self, _message: &types::BlockTxn) { *self.messages.lock().entry("witness_blocktxn".to_owned()).or_insert(0) += 1; }
 		fn send_notfound(&self, _message: &types::NotFound) { *self.messages.lock().entry("notfound".to_owned()).or_insert(0) += 1; }
+		fn send_cfilter(&self, _message: &types::CFilter) { *self.messages.lock().entry("cfilter".to_owned()).or_insert(0) += 1; }
+		fn send_cfheaders(&self, _message: &types::CFHeaders) { *self.messages.lock().entry("cfheaders".to_owned()).or_insert(0) += 1; }
+		fn send_cfcheckpt(&self, _message: &types::CFCheckpt) { *self.messages.lock().entry("cfcheckpt".to_owned()).or_insert(0) += 1; }
 		fn ignored(&self, _id: RequestId) {}
 		fn close(&self) {}
 	}
@@ -24,6 +24,7 @@ extern crate network;
 mod blocks_writer;
 mod inbound_connection;
 mod inbound_connection_factory;
+mod light_client;
 mod local_node;
 mod synchronization_chain;
 mod synchronization_client;
@@ -36,7 +37,9 @@ mod synchronization_server;
 mod synchronization_verifier;
 mod types;
 mod utils;
+mod utxo_snapshot_validator;
 
+pub use synchronization_chain::{ChainTip, ChainTipStatus};
 pub use types::LocalNodeRef;
 pub use types::PeersRef;
 
@@ -44,9 +47,14 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use message::Services;
 use network::{Network, ConsensusParams};
+use chain::IndexedTransaction;
+use primitives::bytes::Bytes;
 use primitives::hash::H256;
 use verification::BackwardsCompatibleChainVerifier as ChainVerifier;
 
+/// False positive rate of the light client bloom filter
+const LIGHT_CLIENT_FALSE_POSITIVE_RATE: f64 = 0.0001;
+
 /// Sync errors.
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -66,6 +74,9 @@ pub struct VerificationParameters {
 	/// Blocks verification edge: all blocks before this are validated using verification_level.
 	/// All blocks after this (inclusive) are validated using VerificationLevel::Full level.
 	pub verification_edge: H256,
+	/// Assumed valid block: scripts of this block and its ancestors are not verified, if
+	/// the best headers chain, containing this block, has at least `minimum_chain_work` work.
+	pub assume_valid: Option<H256>,
 }
 
 /// Synchronization events listener
@@ -76,6 +87,22 @@ pub trait SyncListener: Send + 'static {
 	fn best_storage_block_inserted(&self, block_hash: &H256);
 }
 
+/// Light client events listener
+pub trait LightClientListener: Send + Sync + 'static {
+	/// Called when transaction, matching the loaded bloom filter, is received.
+	/// `block_hash` is the hash of the block, containing the transaction, or None for memory pool transactions
+	fn transaction_matched(&self, transaction: &IndexedTransaction, block_hash: Option<&H256>);
+}
+
+#[derive(Debug, Clone)]
+/// Light client parameters.
+pub struct LightClientParams {
+	/// Data elements (public keys, public key hashes, script hashes, ...), which are watched by the client.
+	pub watched_elements: Vec<Bytes>,
+	/// Merkle blocks are only requested for blocks, mined at or after this time.
+	pub earliest_block_time: u32,
+}
+
 /// Create blocks writer.
 pub fn create_sync_blocks_writer(db: storage::SharedStore, consensus: ConsensusParams, verification_params: VerificationParameters) -> blocks_writer::BlocksWriter {
 	blocks_writer::BlocksWriter::new(db, consensus, verification_params)
@@ -88,8 +115,9 @@ pub fn create_sync_peers() -> PeersRef {
 	Arc::new(PeersImpl::default())
 }
 
-/// Creates local sync node for given `db`
-pub fn create_local_sync_node(consensus: ConsensusParams, db: storage::SharedStore, peers: PeersRef, verification_params: VerificationParameters) -> LocalNodeRef {
+/// Creates local sync node for given `db`. If the unspent outputs set snapshot has been loaded into the `db`,
+/// blocks below the snapshot are validated in the background, using the separate `utxo_snapshot_db`.
+pub fn create_local_sync_node(consensus: ConsensusParams, db: storage::SharedStore, peers: PeersRef, verification_params: VerificationParameters, utxo_snapshot_db: Option<storage::SharedStore>) -> LocalNodeRef {
 	use miner::MemoryPool;
 	use synchronization_chain::Chain as SyncChain;
 	use synchronization_executor::LocalSynchronizationTaskExecutor as SyncExecutor;
@@ -98,13 +126,15 @@ pub fn create_local_sync_node(consensus: ConsensusParams, db: storage::SharedSto
 	use synchronization_client::SynchronizationClient;
 	use synchronization_client_core::{SynchronizationClientCore, CoreVerificationSink, Config as SynchronizationConfig};
 	use synchronization_verifier::AsyncVerifier;
-	use utils::SynchronizationState;
+	use utils::{AssumeValid, SynchronizationState};
+	use utxo_snapshot_validator::UtxoSnapshotValidator;
 	use types::SynchronizationStateRef;
 
 	let network = consensus.network;
 	let sync_client_config = SynchronizationConfig {
 		// during regtests, peer is providing us with bad blocks => we shouldn't close connection because of this
 		close_connection_on_bad_block: network != Network::Regtest,
+		minimum_chain_work: consensus.minimum_chain_work.clone(),
 	};
 	let mut memory_pool = MemoryPool::new();
 	if network == Network::Regtest {
@@ -123,12 +153,51 @@ pub fn create_local_sync_node(consensus: ConsensusParams, db: storage::SharedSto
 	let sync_executor = SyncExecutor::new(peers.clone());
 	let sync_server = Arc::new(ServerImpl::new(peers.clone(), db.clone(), memory_pool.clone(), sync_executor.clone()));
 	let sync_client_core = SynchronizationClientCore::new(sync_client_config, sync_state.clone(), peers.clone(), sync_executor.clone(), sync_chain, chain_verifier.clone());
+	let assume_valid = verification_params.assume_valid.clone()
+		.map(|block| Arc::new(AssumeValid::new(block, consensus.minimum_chain_work.clone())));
+	if let Some(ref assume_valid) = assume_valid {
+		sync_client_core.lock().set_assume_valid(assume_valid.clone());
+	}
+	if let (Some(utxo_snapshot_db), Some(snapshot)) = (utxo_snapshot_db, db.utxo_snapshot_validation()) {
+		if !snapshot.is_failed {
+			let utxo_snapshot_verification_params = VerificationParameters {
+				verification_level: verification_params.verification_level,
+				verification_edge: verification_params.verification_edge.clone(),
+				assume_valid: verification_params.assume_valid.clone(),
+			};
+			let validator = UtxoSnapshotValidator::new(consensus.clone(), db.clone(), utxo_snapshot_db, snapshot, utxo_snapshot_verification_params);
+			sync_client_core.lock().set_utxo_snapshot_validator(validator);
+		}
+	}
 	let verifier_sink = Arc::new(CoreVerificationSink::new(sync_client_core.clone()));
-	let verifier = AsyncVerifier::new(chain_verifier, db.clone(), memory_pool.clone(), verifier_sink, verification_params);
+	let verifier = AsyncVerifier::new(chain_verifier, db.clone(), memory_pool.clone(), verifier_sink, verification_params, assume_valid);
 	let sync_client = SynchronizationClient::new(sync_state.clone(), sync_client_core, verifier);
 	Arc::new(SyncNode::new(consensus, db, memory_pool, peers, sync_state, sync_client, sync_server))
 }
 
+/// Creates local light (SPV) sync node for given `db`
+pub fn create_light_sync_node(consensus: ConsensusParams, db: storage::SharedStore, peers: PeersRef, params: LightClientParams, listener: types::LightClientListenerRef) -> LocalNodeRef {
+	use miner::MemoryPool;
+	use synchronization_executor::LocalSynchronizationTaskExecutor as SyncExecutor;
+	use local_node::LocalNode as SyncNode;
+	use synchronization_server::ServerImpl;
+	use light_client::LightClient;
+	use utils::{SynchronizationState, build_filter_load};
+	use types::SynchronizationStateRef;
+
+	// light client never verifies transactions => memory pool is always empty
+	let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
+	let sync_state = SynchronizationStateRef::new(SynchronizationState::with_storage(db.clone()));
+	peers.require_peer_services(Services::default().with_bloom(true));
+
+	let filter = build_filter_load(&params.watched_elements, LIGHT_CLIENT_FALSE_POSITIVE_RATE, rand::random(), message::types::FilterFlags::All);
+	let sync_executor = SyncExecutor::new(peers.clone());
+	let sync_server = Arc::new(ServerImpl::new(peers.clone(), db.clone(), memory_pool.clone(), sync_executor.clone()));
+	let sync_client = LightClient::new(consensus.clone(), db.clone(), peers.clone(), sync_state.clone(), sync_executor, filter, params.earliest_block_time, listener);
+	LightClient::start_management_worker(&sync_client);
+	Arc::new(SyncNode::new(consensus, db, memory_pool, peers, sync_state, sync_client, sync_server))
+}
+
 /// Create inbound synchronization connections factory for given local sync node.
 pub fn create_sync_connection_factory(peers: PeersRef, local_sync_node: LocalNodeRef) -> p2p::LocalSyncNodeRef {
 	use inbound_connection_factory::InboundConnectionFactory as SyncConnectionFactory;
@@ -0,0 +1,618 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use bit_vec::BitVec;
+use futures::Future;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+use time;
+use chain::{IndexedBlock, IndexedBlockHeader, IndexedTransaction};
+use message::common::{InventoryType, InventoryVector};
+use message::types;
+use network::ConsensusParams;
+use primitives::hash::H256;
+use verification::{CanonHeader, Deployments, HeaderAcceptor, HeaderVerifier};
+use synchronization_chain::{ChainTip, ChainTipStatus};
+use synchronization_client::Client;
+use synchronization_executor::{Task, TaskExecutor};
+use synchronization_manager::ManagementWorker;
+use synchronization_verifier::TransactionVerificationSink;
+use types::{BlockHeight, EmptyBoxFuture, ExecutorRef, LightClientListenerRef, PeerIndex, PeersRef,
+	StorageRef, SynchronizationStateRef, SyncListenerRef};
+use utils::parse_partial_merkle_tree;
+use utils::PartialMerkleTree;
+
+/// Merkle blocks with more transactions are rejected. Blocks of all supported forks
+/// are smaller than 32MB, while every transaction is at least 60 bytes long.
+const MAX_MERKLE_BLOCK_TRANSACTIONS: u32 = 32 * 1024 * 1024 / 60;
+/// Number of the latest memory pool transactions hashes, which are remembered to avoid duplicate requests
+const MAX_REQUESTED_TRANSACTIONS: usize = 10_000;
+
+/// Light (SPV) client. Synchronizes headers chain only and receives transactions,
+/// matching its bloom filter, along with `merkleblock` messages (BIP37).
+pub struct LightClient<T: TaskExecutor> {
+	/// Consensus parameters
+	consensus: ConsensusParams,
+	/// Storage with headers chain
+	storage: StorageRef,
+	/// Synchronization peers
+	peers: PeersRef,
+	/// Shared synchronization state
+	state: SynchronizationStateRef,
+	/// Synchronization tasks executor
+	executor: ExecutorRef<T>,
+	/// Bloom filter, loaded to every peer
+	filter: types::FilterLoad,
+	/// Merkle blocks are only requested for blocks, mined at or after this time
+	earliest_block_time: u32,
+	/// Listener of matched transactions
+	listener: LightClientListenerRef,
+	/// Verify block headers or not?
+	verify_headers: bool,
+	/// Mutable client data
+	data: Mutex<LightClientData>,
+	/// Management worker
+	management_worker: Mutex<Option<ManagementWorker>>,
+}
+
+/// Mutable light client data
+#[derive(Default)]
+struct LightClientData {
+	/// Merkle blocks, requested from peers, along with request time
+	requested_blocks: HashMap<H256, (PeerIndex, f64)>,
+	/// Matched transactions of the last merkle block, which are still expected from peer
+	expected_transactions: HashMap<PeerIndex, (H256, HashSet<H256>)>,
+	/// Memory pool transactions, requested from peers
+	requested_transactions: LinkedHashMap<H256, ()>,
+	/// Synchronization events listener
+	sync_listener: Option<SyncListenerRef>,
+}
+
+impl<T> LightClient<T> where T: TaskExecutor {
+	/// Create new light client
+	#[cfg_attr(feature="cargo-clippy", allow(too_many_arguments))]
+	pub fn new(consensus: ConsensusParams, storage: StorageRef, peers: PeersRef, state: SynchronizationStateRef, executor: ExecutorRef<T>,
+		filter: types::FilterLoad, earliest_block_time: u32, listener: LightClientListenerRef) -> Arc<Self> {
+		Arc::new(LightClient {
+			consensus: consensus,
+			storage: storage,
+			peers: peers,
+			state: state,
+			executor: executor,
+			filter: filter,
+			earliest_block_time: earliest_block_time,
+			listener: listener,
+			verify_headers: true,
+			data: Mutex::new(LightClientData::default()),
+			management_worker: Mutex::new(None),
+		})
+	}
+
+	/// Start worker, which periodically re-requests merkle blocks, not received in time
+	pub fn start_management_worker(client: &Arc<Self>) {
+		let worker = ManagementWorker::with_light_client(Arc::downgrade(client));
+		*client.management_worker.lock() = Some(worker);
+	}
+
+	/// Re-request merkle blocks, which are not received within given interval, from other peers
+	pub fn manage_requested_blocks(&self, failure_interval_ms: u32) {
+		let mut data = self.data.lock();
+		let now = time::precise_time_s();
+		let failed_blocks: Vec<_> = data.requested_blocks.iter()
+			.filter(|&(_, &(_, timestamp))| now - timestamp >= failure_interval_ms as f64 / 1000f64)
+			.map(|(hash, &(peer_index, _))| (hash.clone(), peer_index))
+			.collect();
+		if failed_blocks.is_empty() {
+			return;
+		}
+
+		// prefer any other peer, falling back to the same peer if it is the only one connected
+		let peers = self.peers.enumerate();
+		let mut blocks_to_request: HashMap<PeerIndex, Vec<H256>> = HashMap::new();
+		for (hash, failed_peer_index) in failed_blocks {
+			let peer_index = peers.iter().cloned().find(|peer_index| *peer_index != failed_peer_index)
+				.or_else(|| peers.iter().cloned().find(|peer_index| *peer_index == failed_peer_index));
+			if let Some(peer_index) = peer_index {
+				warn!(target: "sync", "Failed to get merkle block {} from peer#{} in time", hash.to_reversed_str(), failed_peer_index);
+				blocks_to_request.entry(peer_index).or_insert_with(Vec::new).push(hash);
+			}
+		}
+		for (peer_index, hashes) in blocks_to_request {
+			self.request_merkle_blocks(&mut data, peer_index, hashes);
+		}
+	}
+
+	/// Verify block headers or not?
+	#[cfg(test)]
+	pub fn set_verify_headers(&mut self, verify: bool) {
+		self.verify_headers = verify;
+	}
+
+	/// Request headers, following the best stored header, from peer
+	fn request_headers(&self, peer_index: PeerIndex) {
+		let block_locator_hashes = self.block_locator_hashes();
+		self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(block_locator_hashes)));
+	}
+
+	/// Request merkle blocks from peer
+	fn request_merkle_blocks(&self, data: &mut LightClientData, peer_index: PeerIndex, hashes: Vec<H256>) {
+		if hashes.is_empty() {
+			return;
+		}
+
+		let now = time::precise_time_s();
+		let inventory = hashes.into_iter()
+			.map(|hash| {
+				data.requested_blocks.insert(hash.clone(), (peer_index, now));
+				InventoryVector {
+					inv_type: InventoryType::MessageFilteredBlock,
+					hash: hash,
+				}
+			})
+			.collect();
+		self.executor.execute(Task::GetData(peer_index, types::GetData::with_inventory(inventory)));
+	}
+
+	/// Block locator hashes of the best stored chain
+	fn block_locator_hashes(&self) -> Vec<H256> {
+		let mut block_locator_hashes = Vec::new();
+		let mut number = self.storage.best_block().number;
+		let mut step = 1;
+		loop {
+			block_locator_hashes.push(self.storage.block_hash(number).expect("number is less than best block number; qed"));
+			if number == 0 {
+				break;
+			}
+			if block_locator_hashes.len() >= 10 {
+				step <<= 1;
+			}
+			number = number.saturating_sub(step);
+		}
+		block_locator_hashes
+	}
+
+	/// Height of the stored block along with the number of blocks, connecting it to the best stored chain
+	fn block_height(&self, hash: &H256) -> Option<(BlockHeight, BlockHeight)> {
+		let mut hash = hash.clone();
+		let mut branch_len = 0;
+		loop {
+			if let Some(number) = self.storage.block_number(&hash) {
+				return Some((number + branch_len, branch_len));
+			}
+			hash = self.storage.block_header(hash.into())?.raw.previous_header_hash;
+			branch_len += 1;
+		}
+	}
+
+	/// Verify header against consensus rules
+	fn verify_header(&self, header: &IndexedBlockHeader, height: BlockHeight) -> Result<(), String> {
+		if !self.verify_headers {
+			return Ok(());
+		}
+
+		// stored chain is verified against checkpoints => all checkpoints below the best block are known
+		let last_known_checkpoint = self.consensus.checkpoints.range(..=self.storage.best_block().number)
+			.next_back()
+			.map(|(height, _)| *height);
+		let current_time = time::get_time().sec as u32;
+		HeaderVerifier::new(header, self.consensus.network, current_time).check()
+			.and_then(|_| HeaderAcceptor::new(self.storage.as_block_header_provider(), &self.consensus, CanonHeader::new(header), height, Deployments::new(), last_known_checkpoint).check())
+			.map_err(|error| format!("{:?}", error))
+	}
+}
+
+impl<T> Client for LightClient<T> where T: TaskExecutor {
+	fn on_connect(&self, peer_index: PeerIndex) {
+		self.executor.execute(Task::FilterLoad(peer_index, self.filter.clone()));
+		self.request_headers(peer_index);
+	}
+
+	fn on_disconnect(&self, peer_index: PeerIndex) {
+		let mut data = self.data.lock();
+		data.expected_transactions.remove(&peer_index);
+
+		// merkle blocks, requested from this peer, are requested from another peer
+		let blocks: Vec<_> = data.requested_blocks.iter()
+			.filter(|&(_, &(requested_from, _))| requested_from == peer_index)
+			.map(|(hash, _)| hash.clone())
+			.collect();
+		for hash in &blocks {
+			data.requested_blocks.remove(hash);
+		}
+		if let Some(other_peer_index) = self.peers.enumerate().into_iter().find(|other_peer_index| *other_peer_index != peer_index) {
+			self.request_merkle_blocks(&mut data, other_peer_index, blocks);
+		}
+	}
+
+	fn on_inventory(&self, peer_index: PeerIndex, message: types::Inv) {
+		let mut data = self.data.lock();
+		let mut unknown_transactions = Vec::new();
+		let mut has_unknown_blocks = false;
+		for item in message.inventory {
+			match item.inv_type {
+				InventoryType::MessageBlock | InventoryType::MessageWitnessBlock =>
+					has_unknown_blocks = has_unknown_blocks || !self.storage.contains_block(item.hash.into()),
+				InventoryType::MessageTx | InventoryType::MessageWitnessTx => {
+					// peer only announces transactions, matching our filter
+					if !data.requested_transactions.contains_key(&item.hash) {
+						data.requested_transactions.insert(item.hash.clone(), ());
+						unknown_transactions.push(InventoryVector::tx(item.hash));
+					}
+				},
+				_ => (),
+			}
+		}
+
+		while data.requested_transactions.len() > MAX_REQUESTED_TRANSACTIONS {
+			data.requested_transactions.pop_front();
+		}
+
+		if has_unknown_blocks {
+			self.request_headers(peer_index);
+		}
+		if !unknown_transactions.is_empty() {
+			self.executor.execute(Task::GetData(peer_index, types::GetData::with_inventory(unknown_transactions)));
+		}
+	}
+
+	fn on_headers(&self, peer_index: PeerIndex, headers: Vec<IndexedBlockHeader>) {
+		let mut data = self.data.lock();
+		let headers_len = headers.len();
+		let best_block_hash = self.storage.best_block().hash;
+		let mut merkle_blocks = Vec::new();
+		// headers, preceding the failed one, are processed as usual
+		let mut is_failed = false;
+		let mut misbehaving = None;
+		for (header_index, header) in headers.into_iter().enumerate() {
+			if self.storage.contains_block(header.hash.clone().into()) {
+				continue;
+			}
+
+			let height = match self.block_height(&header.raw.previous_header_hash) {
+				Some((parent_height, _)) => parent_height + 1,
+				// peer has announced new block, while we're not synchronized yet
+				None if header_index == 0 => {
+					trace!(target: "sync", "Got unconnected header {} from peer#{}", header.hash.to_reversed_str(), peer_index);
+					self.request_headers(peer_index);
+					is_failed = true;
+					break;
+				},
+				None => {
+					misbehaving = Some(format!("Provided non-continuous header {} in `headers`", header.hash.to_reversed_str()));
+					is_failed = true;
+					break;
+				},
+			};
+
+			if let Err(error) = self.verify_header(&header, height) {
+				misbehaving = Some(format!("Error verifying header {} from `headers`: {}", header.hash.to_reversed_str(), error));
+				is_failed = true;
+				break;
+			}
+
+			if let Err(error) = self.storage.insert_header(&header) {
+				warn!(target: "sync", "Error inserting header {}: {}", header.hash.to_reversed_str(), error);
+				is_failed = true;
+				break;
+			}
+
+			if header.raw.time >= self.earliest_block_time {
+				merkle_blocks.push(header.hash);
+			}
+		}
+
+		// merkle blocks, requested from misbehaving peer, are requested from another peer once it is disconnected
+		self.request_merkle_blocks(&mut data, peer_index, merkle_blocks);
+
+		let best_block = self.storage.best_block();
+		if best_block.hash != best_block_hash {
+			self.state.update_best_storage_block_height(best_block.number);
+			if let Some(ref listener) = data.sync_listener {
+				listener.best_storage_block_inserted(&best_block.hash);
+			}
+		}
+
+		if let Some(misbehaving) = misbehaving {
+			self.peers.misbehaving(peer_index, &misbehaving);
+		}
+		if is_failed {
+			return;
+		}
+
+		// peer has more headers for us
+		let is_synchronizing = headers_len == types::HEADERS_MAX_HEADERS_LEN;
+		if is_synchronizing != self.state.synchronizing() {
+			self.state.update_synchronizing(is_synchronizing);
+			if let Some(ref listener) = data.sync_listener {
+				listener.synchronization_state_switched(is_synchronizing);
+			}
+		}
+		if is_synchronizing {
+			self.request_headers(peer_index);
+		}
+	}
+
+	fn on_block(&self, peer_index: PeerIndex, _block: IndexedBlock) {
+		self.peers.misbehaving(peer_index, "Got unrequested 'block' message");
+	}
+
+	fn on_compact_block(&self, peer_index: PeerIndex, _message: types::CompactBlock) {
+		self.peers.misbehaving(peer_index, "Got unrequested 'cmpctblock' message");
+	}
+
+	fn on_block_txn(&self, peer_index: PeerIndex, _message: types::BlockTxn) {
+		self.peers.misbehaving(peer_index, "Got unrequested 'blocktxn' message");
+	}
+
+	fn on_transaction(&self, peer_index: PeerIndex, transaction: IndexedTransaction) {
+		let mut data = self.data.lock();
+		let (block_hash, is_last_transaction) = match data.expected_transactions.get_mut(&peer_index) {
+			Some(&mut (ref block_hash, ref mut transactions)) => match transactions.remove(&transaction.hash) {
+				true => (Some(block_hash.clone()), transactions.is_empty()),
+				false => (None, false),
+			},
+			None => (None, false),
+		};
+
+		if is_last_transaction {
+			data.expected_transactions.remove(&peer_index);
+		}
+
+		self.listener.transaction_matched(&transaction, block_hash.as_ref());
+	}
+
+	fn on_notfound(&self, peer_index: PeerIndex, message: types::NotFound) {
+		let mut data = self.data.lock();
+		for item in message.inventory {
+			if item.inv_type == InventoryType::MessageFilteredBlock && data.requested_blocks.get(&item.hash).map(|&(requested_from, _)| requested_from) == Some(peer_index) {
+				trace!(target: "sync", "Peer#{} has no merkle block {}", peer_index, item.hash.to_reversed_str());
+				data.requested_blocks.remove(&item.hash);
+			}
+		}
+	}
+
+	fn on_merkleblock(&self, peer_index: PeerIndex, message: types::MerkleBlock) {
+		let mut data = self.data.lock();
+		let header = IndexedBlockHeader::from_raw(message.block_header);
+		if data.requested_blocks.get(&header.hash).map(|&(requested_from, _)| requested_from) != Some(peer_index) {
+			self.peers.misbehaving(peer_index, "Got unrequested 'merkleblock' message");
+			return;
+		}
+		data.requested_blocks.remove(&header.hash);
+
+		// only blocks with stored headers are requested => header hash is enough to check header
+		if message.total_transactions > MAX_MERKLE_BLOCK_TRANSACTIONS {
+			self.peers.misbehaving(peer_index, &format!("Merkle block {} has too many transactions", header.hash.to_reversed_str()));
+			return;
+		}
+
+		// flags are serialized starting from the least significant bit of every byte
+		let flags = message.flags.iter().map(|flags| flags.reverse_bits()).collect::<Vec<_>>();
+		let tree = PartialMerkleTree::new(message.total_transactions as usize, message.hashes, BitVec::from_bytes(&flags));
+		let parsed_tree = match parse_partial_merkle_tree(tree) {
+			Ok(parsed_tree) => parsed_tree,
+			Err(error) => {
+				self.peers.misbehaving(peer_index, &format!("Merkle block {} has invalid partial merkle tree: {}", header.hash.to_reversed_str(), error));
+				return;
+			},
+		};
+
+		if parsed_tree.root != header.raw.merkle_root_hash {
+			self.peers.misbehaving(peer_index, &format!("Merkle block {} has invalid merkle root", header.hash.to_reversed_str()));
+			return;
+		}
+
+		// peer sends matched transactions right after the merkle block
+		if parsed_tree.hashes.is_empty() {
+			data.expected_transactions.remove(&peer_index);
+		} else {
+			data.expected_transactions.insert(peer_index, (header.hash, parsed_tree.hashes.into_iter().collect()));
+		}
+	}
+
+	fn after_peer_nearly_blocks_verified(&self, _peer_index: PeerIndex, future: EmptyBoxFuture) {
+		// there are no blocks to verify
+		future.wait().expect("no-error future");
+	}
+
+	fn accept_transaction(&self, _transaction: IndexedTransaction, _sink: Box<dyn TransactionVerificationSink>) -> Result<(), String> {
+		Err("Transactions can not be verified by the light client".into())
+	}
+
+	fn install_sync_listener(&self, listener: SyncListenerRef) {
+		self.data.lock().sync_listener = Some(listener);
+	}
+
+	fn chain_tips(&self) -> Vec<ChainTip> {
+		let best_block = self.storage.best_block();
+		self.storage.chain_tips().into_iter()
+			.filter_map(|hash| self.block_height(&hash).map(|(height, branch_len)| ChainTip {
+				status: if hash == best_block.hash { ChainTipStatus::Active } else { ChainTipStatus::HeadersOnly },
+				hash: hash,
+				height: height,
+				branch_len: branch_len,
+			}))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test_data;
+
+	use std::sync::Arc;
+	use bit_vec::BitVec;
+	use parking_lot::Mutex;
+	use chain::{IndexedBlock, IndexedTransaction};
+	use db::BlockChainDatabase;
+	use message::common::{InventoryType, InventoryVector};
+	use message::{Services, types};
+	use network::{ConsensusParams, ConsensusFork, Network};
+	use primitives::hash::H256;
+	use inbound_connection::tests::DummyOutboundSyncConnection;
+	use synchronization_client::Client;
+	use synchronization_executor::Task;
+	use synchronization_executor::tests::DummyTaskExecutor;
+	use synchronization_peers::PeersImpl;
+	use utils::{SynchronizationState, build_partial_merkle_tree};
+	use types::{PeersRef, StorageRef, SynchronizationStateRef};
+	use super::super::LightClientListener;
+	use super::LightClient;
+
+	#[derive(Default)]
+	struct DummyLightClientListener {
+		matched: Arc<Mutex<Vec<(H256, Option<H256>)>>>,
+	}
+
+	impl LightClientListener for DummyLightClientListener {
+		fn transaction_matched(&self, transaction: &IndexedTransaction, block_hash: Option<&H256>) {
+			self.matched.lock().push((transaction.hash.clone(), block_hash.cloned()));
+		}
+	}
+
+	fn create_light_client() -> (Arc<DummyTaskExecutor>, StorageRef, PeersRef, Arc<Mutex<Vec<(H256, Option<H256>)>>>, Arc<LightClient<DummyTaskExecutor>>) {
+		let storage: StorageRef = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let peers: PeersRef = Arc::new(PeersImpl::default());
+		let state = SynchronizationStateRef::new(SynchronizationState::with_storage(storage.clone()));
+		let executor = DummyTaskExecutor::new();
+		let listener = DummyLightClientListener::default();
+		let matched = listener.matched.clone();
+		let filter = types::FilterLoad {
+			filter: vec![0xff].into(),
+			hash_functions: 1,
+			tweak: 0,
+			flags: types::FilterFlags::None,
+		};
+		let mut client = LightClient::new(ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), storage.clone(),
+			peers.clone(), state, executor.clone(), filter, 0, Box::new(listener));
+		Arc::get_mut(&mut client).expect("single reference").set_verify_headers(false);
+		peers.insert(0, Services::default(), DummyOutboundSyncConnection::new());
+		(executor, storage, peers, matched, client)
+	}
+
+	fn merkle_block(block: &IndexedBlock, matches: Vec<bool>) -> types::MerkleBlock {
+		let hashes = block.transactions.iter().map(|tx| tx.hash.clone()).collect();
+		let tree = build_partial_merkle_tree(hashes, matches.into_iter().collect::<BitVec>());
+		types::MerkleBlock {
+			block_header: block.header.raw.clone(),
+			total_transactions: block.transactions.len() as u32,
+			hashes: tree.hashes,
+			flags: tree.flags.to_bytes().into_iter().map(|flags| flags.reverse_bits()).collect::<Vec<_>>().into(),
+		}
+	}
+
+	fn request_merkle_blocks(peer_index: usize, hashes: Vec<H256>) -> Task {
+		Task::GetData(peer_index, types::GetData::with_inventory(hashes.into_iter()
+			.map(|hash| InventoryVector { inv_type: InventoryType::MessageFilteredBlock, hash: hash })
+			.collect()))
+	}
+
+	#[test]
+	fn light_client_loads_filter_and_stores_headers() {
+		let (executor, storage, _, _, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+
+		client.on_connect(0);
+		let tasks = executor.take_tasks();
+		assert_eq!(tasks.len(), 2);
+		match tasks[0] {
+			Task::FilterLoad(0, _) => (),
+			_ => panic!("unexpected task: {:?}", tasks[0]),
+		}
+		assert_eq!(tasks[1], Task::GetHeaders(0, types::GetHeaders::with_block_locator_hashes(vec![test_data::genesis().hash()])));
+
+		client.on_headers(0, vec![b1.header.clone()]);
+		assert_eq!(executor.take_tasks(), vec![request_merkle_blocks(0, vec![b1.hash().clone()])]);
+		assert_eq!(storage.best_block().number, 1);
+		assert_eq!(storage.best_block().hash, *b1.hash());
+		assert!(storage.block(b1.hash().clone().into()).is_none());
+	}
+
+	#[test]
+	fn light_client_stores_headers_before_non_continuous_header() {
+		let (executor, storage, peers, _, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+		let b3: IndexedBlock = test_data::block_h3().into();
+
+		client.on_headers(0, vec![b1.header.clone(), b3.header.clone()]);
+		assert_eq!(executor.take_tasks(), vec![request_merkle_blocks(0, vec![b1.hash().clone()])]);
+		assert_eq!(storage.best_block().hash, *b1.hash());
+		assert!(!peers.enumerate().contains(&0));
+	}
+
+	#[test]
+	fn light_client_requests_headers_on_unconnected_header() {
+		let (executor, storage, peers, _, client) = create_light_client();
+		let b3: IndexedBlock = test_data::block_h3().into();
+
+		client.on_headers(0, vec![b3.header.clone()]);
+		assert_eq!(executor.take_tasks(), vec![Task::GetHeaders(0, types::GetHeaders::with_block_locator_hashes(vec![test_data::genesis().hash()]))]);
+		assert_eq!(storage.best_block().number, 0);
+		assert!(peers.enumerate().contains(&0));
+	}
+
+	#[test]
+	fn light_client_notifies_listener_about_matched_transactions() {
+		let (executor, _, peers, matched, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+		let tx = b1.transactions[0].clone();
+
+		client.on_headers(0, vec![b1.header.clone()]);
+		executor.take_tasks();
+
+		client.on_merkleblock(0, merkle_block(&b1, vec![true]));
+		client.on_transaction(0, tx.clone());
+		assert!(peers.enumerate().contains(&0));
+		assert_eq!(*matched.lock(), vec![(tx.hash.clone(), Some(b1.hash().clone()))]);
+
+		// following transactions are memory pool transactions
+		client.on_transaction(0, tx.clone());
+		assert_eq!(matched.lock()[1], (tx.hash, None));
+	}
+
+	#[test]
+	fn light_client_disconnects_peer_on_invalid_merkle_root() {
+		let (executor, _, peers, matched, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+		let b2: IndexedBlock = test_data::block_h2().into();
+
+		client.on_headers(0, vec![b1.header.clone()]);
+		executor.take_tasks();
+
+		let mut message = merkle_block(&b2, vec![true]);
+		message.block_header = b1.header.raw.clone();
+		client.on_merkleblock(0, message);
+		assert!(!peers.enumerate().contains(&0));
+		assert!(matched.lock().is_empty());
+	}
+
+	#[test]
+	fn light_client_disconnects_peer_on_unrequested_merkle_block() {
+		let (_, _, peers, _, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+
+		client.on_merkleblock(0, merkle_block(&b1, vec![true]));
+		assert!(!peers.enumerate().contains(&0));
+	}
+
+	#[test]
+	fn light_client_rerequests_merkle_blocks_not_received_in_time() {
+		let (executor, _, peers, _, client) = create_light_client();
+		let b1: IndexedBlock = test_data::block_h1().into();
+		peers.insert(1, Services::default(), DummyOutboundSyncConnection::new());
+
+		client.on_headers(0, vec![b1.header.clone()]);
+		assert_eq!(executor.take_tasks(), vec![request_merkle_blocks(0, vec![b1.hash().clone()])]);
+
+		// not yet timed out
+		client.manage_requested_blocks(60 * 1000);
+		assert!(executor.take_tasks().is_empty());
+
+		// timed out => requested from another peer
+		client.manage_requested_blocks(0);
+		assert_eq!(executor.take_tasks(), vec![request_merkle_blocks(1, vec![b1.hash().clone()])]);
+
+		// and is only accepted from this peer
+		client.on_merkleblock(1, merkle_block(&b1, vec![true]));
+		assert!(peers.enumerate().contains(&1));
+	}
+}
@@ -1,11 +1,15 @@
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 use parking_lot::{Mutex, Condvar};
 use time;
 use futures::{lazy, finished};
 use chain::{IndexedTransaction, IndexedBlock, IndexedBlockHeader};
 use message::types;
-use miner::BlockAssembler;
+use miner::{BlockAssembler, MemoryPoolDump};
+use ser::{deserialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS};
 use network::ConsensusParams;
+use synchronization_chain::ChainTip;
 use synchronization_client::{Client};
 use synchronization_server::{Server, ServerTask};
 use synchronization_verifier::{TransactionVerificationSink};
@@ -17,7 +21,7 @@ use types::{PeerIndex, RequestId, StorageRef, MemoryPoolRef, PeersRef,
 	ClientRef, ServerRef, SynchronizationStateRef, SyncListenerRef};
 
 /// Local synchronization node
-pub struct LocalNode<U: Server, V: Client> {
+pub struct LocalNode<U: Server, V: Client + ?Sized> {
 	/// Network we are working on
 	consensus: ConsensusParams,
 	/// Storage reference
@@ -45,7 +49,7 @@ struct TransactionAcceptSinkData {
 	waiter: Condvar,
 }
 
-impl<U, V> LocalNode<U, V> where U: Server, V: Client {
+impl<U, V> LocalNode<U, V> where U: Server, V: Client + ?Sized {
 	/// Create new synchronization node
 	#[cfg_attr(feature="cargo-clippy", allow(too_many_arguments))]
 	pub fn new(consensus: ConsensusParams, storage: StorageRef, memory_pool: MemoryPoolRef, peers: PeersRef,
@@ -162,6 +166,24 @@ impl<U, V> LocalNode<U, V> where U: Server, V: Client {
 		self.server.execute(ServerTask::GetBlockTxn(peer_index, message));
 	}
 
+	/// When peer asks us for compact filters of blocks
+	pub fn on_get_cfilters(&self, peer_index: PeerIndex, message: types::GetCFilters) {
+		trace!(target: "sync", "Got `getcfilters` message from peer#{}", peer_index);
+		self.server.execute(ServerTask::GetCFilters(peer_index, message));
+	}
+
+	/// When peer asks us for compact filters headers
+	pub fn on_get_cfheaders(&self, peer_index: PeerIndex, message: types::GetCFHeaders) {
+		trace!(target: "sync", "Got `getcfheaders` message from peer#{}", peer_index);
+		self.server.execute(ServerTask::GetCFHeaders(peer_index, message));
+	}
+
+	/// When peer asks us for evenly spaced compact filters headers
+	pub fn on_get_cfcheckpt(&self, peer_index: PeerIndex, message: types::GetCFCheckpt) {
+		trace!(target: "sync", "Got `getcfcheckpt` message from peer#{}", peer_index);
+		self.server.execute(ServerTask::GetCFCheckpt(peer_index, message));
+	}
+
 	/// When peer sets bloom filter for connection
 	pub fn on_filterload(&self, peer_index: PeerIndex, message: types::FilterLoad) {
 		trace!(target: "sync", "Got `filterload` message from peer#{}", peer_index);
@@ -196,42 +218,45 @@ impl<U, V> LocalNode<U, V> where U: Server, V: Client {
 	pub fn on_send_compact(&self, peer_index: PeerIndex, message: types::SendCompact) {
 		trace!(target: "sync", "Got `sendcmpct` message from peer#{}", peer_index);
 
-		// The second integer SHALL be interpreted as a little-endian version number. Nodes sending a sendcmpct message MUST currently set this value to 1.
-		// TODO: version 2 supports segregated witness transactions
-		if message.second != 1 {
+		// The second integer SHALL be interpreted as a little-endian version number.
+		// Version 1 uses transactions hashes as short ids, version 2 uses witness transactions hashes.
+		if message.second != 1 && message.second != 2 {
 			return;
 		}
 
-		// Upon receipt of a "sendcmpct" message with the first and second integers set to 1, the node SHOULD announce new blocks by sending a cmpctblock message.
-		if message.first {
-			self.peers.set_block_announcement_type(peer_index, BlockAnnouncementType::SendCompactBlock);
+		// node announces all versions it supports, the most preferred first
+		// => the first version is used for both announcing and requesting compact blocks
+		self.peers.set_compact_block_version(peer_index, message.second);
+		if self.peers.compact_block_version(peer_index) != Some(message.second) {
+			return;
 		}
 
-		// else:
+		// Upon receipt of a "sendcmpct" message with the first integer set to 1, the node SHOULD announce new blocks by sending a cmpctblock message.
 		// Upon receipt of a "sendcmpct" message with the first integer set to 0, the node SHOULD NOT announce new blocks by sending a cmpctblock message,
 		// but SHOULD announce new blocks by sending invs or headers, as defined by BIP130.
-		// => work as before
+		if message.first {
+			self.peers.set_block_announcement_type(peer_index, BlockAnnouncementType::SendCompactBlock);
+		} else {
+			self.peers.reset_compact_block_announcement(peer_index);
+		}
 	}
 
 	/// When peer sents us a merkle block
-	pub fn on_merkleblock(&self, peer_index: PeerIndex, _message: types::MerkleBlock) {
+	pub fn on_merkleblock(&self, peer_index: PeerIndex, message: types::MerkleBlock) {
 		trace!(target: "sync", "Got `merkleblock` message from peer#{}", peer_index);
-		// we never setup filter on connections => misbehaving
-		self.peers.misbehaving(peer_index, "Got unrequested 'merkleblock' message");
+		self.client.on_merkleblock(peer_index, message);
 	}
 
 	/// When peer sents us a compact block
-	pub fn on_compact_block(&self, peer_index: PeerIndex, _message: types::CompactBlock) {
+	pub fn on_compact_block(&self, peer_index: PeerIndex, message: types::CompactBlock) {
 		trace!(target: "sync", "Got `cmpctblock` message from peer#{}", peer_index);
-		// we never ask compact block from peers => misbehaving
-		self.peers.misbehaving(peer_index, "Got unrequested 'cmpctblock' message");
+		self.client.on_compact_block(peer_index, message);
 	}
 
 	/// When peer sents us specific transactions for specific block
-	pub fn on_block_txn(&self, peer_index: PeerIndex, _message: types::BlockTxn) {
+	pub fn on_block_txn(&self, peer_index: PeerIndex, message: types::BlockTxn) {
 		trace!(target: "sync", "Got `blocktxn` message from peer#{}", peer_index);
-		// we never ask for this => misbehaving
-		self.peers.misbehaving(peer_index, "Got unrequested 'blocktxn' message");
+		self.client.on_block_txn(peer_index, message);
 	}
 
 	/// Verify and then schedule new transaction
@@ -246,6 +271,47 @@ impl<U, V> LocalNode<U, V> where U: Server, V: Client {
 		sink_data.wait()
 	}
 
+	/// Write all memory pool transactions to the file. Returns number of written transactions.
+	pub fn save_memory_pool(&self, path: &Path) -> Result<usize, String> {
+		let dump = self.memory_pool.read().dump();
+		// previous dump is only replaced when the new one is completely written
+		let new_path = path.with_extension("new");
+		fs::write(&new_path, serialize_with_flags(&dump, SERIALIZE_TRANSACTION_WITNESS))
+			.and_then(|_| fs::rename(&new_path, path))
+			.map_err(|err| format!("Cannot write memory pool dump: {}", err))?;
+		Ok(dump.entries.len())
+	}
+
+	/// Verify transactions from the memory pool dump and insert them to the memory pool,
+	/// keeping their entry time and virtual fee. Returns number of inserted transactions.
+	pub fn load_memory_pool(&self, path: &Path) -> Result<usize, String> {
+		let dump = fs::read(path).map_err(|err| format!("Cannot read memory pool dump: {}", err))?;
+		let dump: MemoryPoolDump = deserialize(dump.as_slice()).map_err(|err| format!("Cannot read memory pool dump: {:?}", err))?;
+
+		let mut loaded = 0;
+		for entry in dump.entries {
+			let transaction = IndexedTransaction::from_raw(entry.transaction);
+			if self.memory_pool.read().contains(&transaction.hash) {
+				continue;
+			}
+
+			let hash = transaction.hash.clone();
+			match self.accept_transaction(transaction) {
+				Ok(_) => {
+					let mut memory_pool = self.memory_pool.write();
+					memory_pool.set_entry_time(&hash, entry.time);
+					if entry.virtual_fee != 0 {
+						memory_pool.set_virtual_fee(&hash, entry.virtual_fee);
+					}
+					loaded += 1;
+				},
+				Err(err) => trace!(target: "sync", "Dumped transaction {} is not loaded: {}", hash.to_reversed_str(), err),
+			}
+		}
+
+		Ok(loaded)
+	}
+
 	/// Get block template for mining
 	pub fn get_block_template(&self) -> BlockTemplate {
 		let previous_block_height = self.storage.best_block().number;
@@ -261,6 +327,11 @@ impl<U, V> LocalNode<U, V> where U: Server, V: Client {
 		block_assembler.create_new_block(&self.storage, memory_pool, time::get_time().sec as u32, median_timestamp, &self.consensus)
 	}
 
+	/// Get all known blocks without known children
+	pub fn chain_tips(&self) -> Vec<ChainTip> {
+		self.client.chain_tips()
+	}
+
 	/// Install synchronization events listener
 	pub fn install_sync_listener(&self, listener: SyncListenerRef) {
 		self.client.install_sync_listener(listener);
@@ -307,8 +378,11 @@ impl TransactionVerificationSink for TransactionAcceptSink {
 pub mod tests {
 	extern crate test_data;
 
+	extern crate tempdir;
+
 	use std::sync::Arc;
 	use parking_lot::RwLock;
+	use self::tempdir::TempDir;
 	use synchronization_executor::Task;
 	use synchronization_executor::tests::DummyTaskExecutor;
 	use synchronization_client::SynchronizationClient;
@@ -354,7 +428,7 @@ pub mod tests {
 		let sync_peers = Arc::new(PeersImpl::default());
 		let executor = DummyTaskExecutor::new();
 		let server = Arc::new(DummyServer::new());
-		let config = Config { close_connection_on_bad_block: true };
+		let config = Config { close_connection_on_bad_block: true, minimum_chain_work: 0.into() };
 		let chain_verifier = Arc::new(ChainVerifier::new(storage.clone(), ConsensusParams::new(Network::Mainnet, ConsensusFork::BitcoinCore)));
 		let client_core = SynchronizationClientCore::new(config, sync_state.clone(), sync_peers.clone(), executor.clone(), chain, chain_verifier);
 		let mut verifier = match verifier {
@@ -425,4 +499,36 @@ pub mod tests {
 
 		assert_eq!(executor.take_tasks(), vec![]);
 	}
+
+	#[test]
+	fn local_node_saves_and_loads_memory_pool() {
+		let dir = TempDir::new("memory_pool").unwrap();
+		let path = dir.path().join("mempool.dat");
+
+		let genesis = test_data::genesis();
+		let parent: Transaction = test_data::TransactionBuilder::with_output(10).add_output(10).add_input(&genesis.transactions[0], 0).into();
+		let child: Transaction = test_data::TransactionBuilder::with_output(5).add_input(&parent, 0).into();
+		let rejected: Transaction = test_data::TransactionBuilder::with_output(5).add_input(&parent, 1).into();
+
+		let (_, _, local_node) = create_local_node(None);
+		assert_eq!(local_node.accept_transaction(parent.clone().into()), Ok(parent.hash()));
+		assert_eq!(local_node.accept_transaction(child.clone().into()), Ok(child.hash()));
+		assert_eq!(local_node.accept_transaction(rejected.clone().into()), Ok(rejected.hash()));
+		local_node.memory_pool.write().set_entry_time(&parent.hash(), 100);
+		local_node.memory_pool.write().set_virtual_fee(&child.hash(), 1000);
+		assert_eq!(local_node.save_memory_pool(&path), Ok(3));
+
+		// every dumped transaction is verified again
+		let mut verifier = DummyVerifier::default();
+		verifier.error_when_verifying(rejected.hash(), "simulated");
+		let (_, _, local_node) = create_local_node(Some(verifier));
+		assert_eq!(local_node.load_memory_pool(&path), Ok(2));
+
+		let dump = local_node.memory_pool.read().dump();
+		assert_eq!(dump.entries.iter().map(|entry| entry.transaction.clone()).collect::<Vec<_>>(), vec![parent, child]);
+		assert_eq!(dump.entries[0].time, 100);
+		assert_eq!(dump.entries[1].virtual_fee, 1000);
+
+		assert!(local_node.load_memory_pool(&dir.path().join("missing.dat")).is_err());
+	}
 }
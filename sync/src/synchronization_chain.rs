@@ -1,13 +1,14 @@
-use std::collections::{VecDeque, HashSet};
+use std::collections::{VecDeque, HashSet, HashMap};
 use std::fmt;
 use linked_hash_map::LinkedHashMap;
 use chain::{IndexedBlockHeader, IndexedBlock, IndexedTransaction, OutPoint, TransactionOutput};
 use storage;
 use miner::{MemoryPoolOrderingStrategy, MemoryPoolInformation, FeeCalculator};
 use network::ConsensusParams;
+use primitives::bigint::U256;
 use primitives::bytes::Bytes;
 use primitives::hash::H256;
-use utils::{BestHeadersChain, BestHeadersChainInformation, HashQueueChain, HashPosition};
+use utils::{BestHeadersChain, BestHeadersChainInformation, BestHeadersChainInsertion, HashQueueChain, HashPosition};
 use types::{BlockHeight, StorageRef, MemoryPoolRef};
 
 /// Index of 'verifying' queue
@@ -64,6 +65,34 @@ pub enum BlockState {
 	DeadEnd,
 }
 
+/// Status of the known chain tip
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChainTipStatus {
+	/// Tip of the best stored chain
+	Active,
+	/// Tip of the stored side chain
+	ValidFork,
+	/// Block is currently verifying
+	ValidHeaders,
+	/// Only header of the block is known
+	HeadersOnly,
+	/// Block or one of its ancestors has been marked as dead-end block
+	Invalid,
+}
+
+/// Block, which has no known children
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainTip {
+	/// Hash of the block
+	pub hash: H256,
+	/// Height of the block
+	pub height: BlockHeight,
+	/// Number of blocks, connecting the tip to the best stored chain
+	pub branch_len: BlockHeight,
+	/// Status of the tip
+	pub status: ChainTipStatus,
+}
+
 /// Transactions synchronization state
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum TransactionState {
@@ -113,8 +142,8 @@ pub struct Chain {
 	verifying_transactions: LinkedHashMap<H256, IndexedTransaction>,
 	/// Transactions memory pool
 	memory_pool: MemoryPoolRef,
-	/// Blocks that have been marked as dead-ends
-	dead_end_blocks: HashSet<H256>,
+	/// Blocks that have been marked as dead-ends, along with their parents hashes (if known)
+	dead_end_blocks: HashMap<H256, Option<H256>>,
 	/// Is SegWit is possible on this chain? SegWit inventory types are used when block/tx-es are
 	/// requested and this flag is true.
 	is_segwit_possible: bool,
@@ -148,6 +177,8 @@ impl Chain {
 			.expect("storage with genesis block is required");
 		let best_storage_block = storage.best_block();
 		let best_storage_block_hash = best_storage_block.hash.clone();
+		let best_storage_block_chainwork = storage.block_chainwork(best_storage_block_hash.clone().into())
+			.expect("chainwork is stored for every block in storage; qed");
 		let is_segwit_possible = consensus.is_segwit_possible();
 
 		Chain {
@@ -155,10 +186,10 @@ impl Chain {
 			best_storage_block: best_storage_block,
 			storage: storage,
 			hash_chain: HashQueueChain::with_number_of_queues(NUMBER_OF_QUEUES),
-			headers_chain: BestHeadersChain::new(best_storage_block_hash),
+			headers_chain: BestHeadersChain::new(best_storage_block_hash, best_storage_block_chainwork),
 			verifying_transactions: LinkedHashMap::new(),
 			memory_pool: memory_pool,
-			dead_end_blocks: HashSet::new(),
+			dead_end_blocks: HashMap::new(),
 			is_segwit_possible,
 		}
 	}
@@ -222,6 +253,12 @@ impl Chain {
 		self.best_storage_block.clone()
 	}
 
+	/// Get total work of the best storage chain
+	fn best_storage_block_chainwork(&self) -> U256 {
+		self.storage.block_chainwork(self.best_storage_block.hash.clone().into())
+			.expect("chainwork is stored for every block in storage; qed")
+	}
+
 	/// Get best block header
 	pub fn best_block_header(&self) -> storage::BestBlock {
 		let headers_chain_information = self.headers_chain.information();
@@ -268,7 +305,12 @@ impl Chain {
 		if let Some(header) = self.storage.block_header(storage::BlockRef::Hash(*hash)) {
 			return Some(header);
 		}
-		self.headers_chain.by_hash(hash)
+		self.headers_chain.known_header(hash)
+	}
+
+	/// Get total work of the best headers chain
+	pub fn best_headers_chainwork(&self) -> U256 {
+		self.headers_chain.best_chainwork()
 	}
 
 	/// Get block state
@@ -277,7 +319,7 @@ impl Chain {
 			Some(queue_index) => BlockState::from_queue_index(queue_index),
 			None => if self.storage.contains_block(storage::BlockRef::Hash(*hash)) {
 				BlockState::Stored
-			} else if self.dead_end_blocks.contains(hash) {
+			} else if self.dead_end_blocks.contains_key(hash) {
 				BlockState::DeadEnd
 			} else {
 				BlockState::Unknown
@@ -303,10 +345,17 @@ impl Chain {
 		block_locator_hashes
 	}
 
-	/// Schedule blocks hashes for requesting
-	pub fn schedule_blocks_headers(&mut self, headers: Vec<IndexedBlockHeader>) {
-		self.hash_chain.push_back_n_at(SCHEDULED_QUEUE, headers.iter().map(|h| h.hash.clone()).collect());
-		self.headers_chain.insert_n(headers);
+	/// Schedule blocks hashes for requesting. Returns hashes of requested blocks,
+	/// which are no longer requested, because they have left the best headers chain.
+	pub fn schedule_blocks_headers(&mut self, headers: Vec<IndexedBlockHeader>) -> Vec<H256> {
+		let mut unrequested = Vec::new();
+		for header in headers {
+			let hash = header.hash.clone();
+			if self.insert_header(header, &mut unrequested) {
+				self.hash_chain.push_back_at(SCHEDULED_QUEUE, hash);
+			}
+		}
+		unrequested
 	}
 
 	/// Moves n blocks from scheduled queue to requested queue
@@ -320,7 +369,7 @@ impl Chain {
 	pub fn verify_block(&mut self, header: IndexedBlockHeader) {
 		// insert header to the in-memory chain in case when it is not already there (non-headers-first sync)
 		self.hash_chain.push_back_at(VERIFYING_QUEUE, header.hash.clone());
-		self.headers_chain.insert(header);
+		self.insert_header(header, &mut Vec::new());
 	}
 
 	/// Add blocks to verifying queue
@@ -340,7 +389,75 @@ impl Chain {
 
 	/// Mark this block as dead end, so these tasks won't be synchronized
 	pub fn mark_dead_end_block(&mut self, hash: &H256) {
-		self.dead_end_blocks.insert(*hash);
+		let parent_hash = self.block_header_by_hash(hash).map(|header| header.raw.previous_header_hash);
+		self.dead_end_blocks.insert(*hash, parent_hash);
+	}
+
+	/// Mark block with this header as dead end, so these tasks won't be synchronized
+	pub fn mark_dead_end_header(&mut self, header: &IndexedBlockHeader) {
+		self.dead_end_blocks.insert(header.hash.clone(), Some(header.raw.previous_header_hash.clone()));
+	}
+
+	/// Get all known blocks without known children, ordered by height (highest first)
+	pub fn chain_tips(&self) -> Vec<ChainTip> {
+		let stored_tips = self.storage.chain_tips().into_iter()
+			.map(|hash| {
+				let status = if hash == self.best_storage_block.hash { ChainTipStatus::Active } else { ChainTipStatus::ValidFork };
+				(hash, status)
+			});
+		let headers_tips = self.headers_chain.tips().into_iter()
+			.map(|header| {
+				let status = match self.block_state(&header.hash) {
+					BlockState::Verifying => ChainTipStatus::ValidHeaders,
+					BlockState::DeadEnd => ChainTipStatus::Invalid,
+					_ => ChainTipStatus::HeadersOnly,
+				};
+				(header.hash, status)
+			});
+
+		let mut known = HashSet::new();
+		let mut tips: Vec<ChainTip> = stored_tips.chain(headers_tips)
+			.filter(|&(ref hash, _)| known.insert(hash.clone()))
+			.filter_map(|(hash, status)| self.fork_position(&hash)
+				.map(|(height, branch_len)| ChainTip {
+					hash: hash,
+					height: height,
+					branch_len: branch_len,
+					status: status,
+				}))
+			.collect();
+
+		// dead-end blocks are never stored, so their parents are used to find their position
+		tips.extend(self.dead_end_blocks.iter()
+			.filter(|&(hash, _)| !known.contains(hash))
+			.filter_map(|(hash, parent_hash)| parent_hash.as_ref()
+				.and_then(|parent_hash| self.fork_position(parent_hash))
+				.map(|(height, branch_len)| ChainTip {
+					hash: hash.clone(),
+					height: height + 1,
+					branch_len: branch_len + 1,
+					status: ChainTipStatus::Invalid,
+				})));
+
+		tips.sort_by(|a, b| b.height.cmp(&a.height));
+		tips
+	}
+
+	/// Get height of the block and number of blocks, connecting it to the best stored chain.
+	/// Returns None if block is not connected to the best stored chain.
+	fn fork_position(&self, hash: &H256) -> Option<(BlockHeight, BlockHeight)> {
+		let mut hash = hash.clone();
+		let mut branch_len = 0;
+		loop {
+			if let Some(number) = self.storage.block_number(&hash) {
+				return Some((number + branch_len, branch_len));
+			}
+
+			let header = self.storage.block_header(storage::BlockRef::Hash(hash.clone()))
+				.or_else(|| self.headers_chain.known_header(&hash))?;
+			hash = header.raw.previous_header_hash;
+			branch_len += 1;
+		}
 	}
 
 	/// Insert new best block to storage
@@ -363,7 +480,7 @@ impl Chain {
 
 				// remove inserted block + handle possible reorganization in headers chain
 				// TODO: mk, not sure if we need both of those params
-				self.headers_chain.block_inserted_to_storage(block.hash(), &self.best_storage_block.hash);
+				self.headers_chain.block_inserted_to_storage(block.hash(), &self.best_storage_block.hash, self.best_storage_block_chainwork());
 
 				// double check
 				assert_eq!(self.best_storage_block.hash, block.hash().clone());
@@ -398,7 +515,7 @@ impl Chain {
 
 				// remove inserted block + handle possible reorganization in headers chain
 				// TODO: mk, not sure if we need both of those params
-				self.headers_chain.block_inserted_to_storage(block.hash(), &self.best_storage_block.hash);
+				self.headers_chain.block_inserted_to_storage(block.hash(), &self.best_storage_block.hash, self.best_storage_block_chainwork());
 
 				// all transactions from this block were accepted
 				// + all transactions from previous blocks of this fork were accepted
@@ -460,7 +577,7 @@ impl Chain {
 
 				// remove inserted block + handle possible reorganization in headers chain
 				// TODO: mk, not sure if it's needed here at all
-				self.headers_chain.block_inserted_to_storage(&block_hash, &self.best_storage_block.hash);
+				self.headers_chain.block_inserted_to_storage(&block_hash, &self.best_storage_block.hash, self.best_storage_block_chainwork());
 
 				// no transactions were accepted
 				// no transactions to reverify
@@ -609,6 +726,36 @@ impl Chain {
 		memory_pool.insert_verified(transaction, &FeeCalculator(self.storage.as_transaction_output_provider()));
 	}
 
+	/// Insert header to the headers chain. When the best headers chain is switched to the fork,
+	/// blocks of the old best chain are no longer requested and blocks of the fork are scheduled instead.
+	/// Hashes of requested blocks, which have left the best headers chain, are appended to `unrequested`.
+	/// Returns true if block must be scheduled for requesting.
+	fn insert_header(&mut self, header: IndexedBlockHeader, unrequested: &mut Vec<H256>) -> bool {
+		match self.headers_chain.insert(header) {
+			// blocks of forks, which are not connected to the best storage block, are requested
+			// so that storage could decide if reorganization is required
+			BestHeadersChainInsertion::Appended | BestHeadersChainInsertion::Detached => true,
+			BestHeadersChainInsertion::Switched { decanonized, canonized } => {
+				trace!(target: "sync", "Switching best headers chain: {} headers are replaced with {} headers", decanonized.len(), canonized.len());
+
+				// blocks, which are already verifying, will be inserted to the storage as side chain blocks
+				for hash in &decanonized {
+					self.hash_chain.remove_at(SCHEDULED_QUEUE, hash);
+					if self.hash_chain.remove_at(REQUESTED_QUEUE, hash) != HashPosition::Missing {
+						unrequested.push(hash.clone());
+					}
+				}
+				for hash in canonized {
+					if self.hash_chain.contains_in(&hash).is_none() {
+						self.hash_chain.push_back_at(SCHEDULED_QUEUE, hash);
+					}
+				}
+				false
+			},
+			BestHeadersChainInsertion::Known | BestHeadersChainInsertion::Fork => false,
+		}
+	}
+
 	/// Calculate block locator hashes for hash queue
 	fn block_locator_hashes_for_queue(&self, hashes: &mut Vec<H256>) -> (BlockHeight, BlockHeight) {
 		let queue_len = self.hash_chain.len();
@@ -691,6 +838,15 @@ impl storage::BlockHeaderProvider for Chain {
 			storage::BlockRef::Number(n) => self.block_header_by_number(n),
 		}
 	}
+
+	fn block_chainwork(&self, block_ref: storage::BlockRef) -> Option<U256> {
+		self.storage.block_chainwork(block_ref.clone())
+			.or_else(|| match block_ref {
+				storage::BlockRef::Hash(hash) => self.headers_chain.chainwork(&hash),
+				storage::BlockRef::Number(n) => self.block_header_by_number(n)
+					.and_then(|header| self.headers_chain.chainwork(&header.hash)),
+			})
+	}
 }
 
 impl fmt::Debug for Information {
@@ -739,7 +895,7 @@ mod tests {
 	use miner::MemoryPool;
 	use network::{Network, ConsensusParams, ConsensusFork};
 	use primitives::hash::H256;
-	use super::{Chain, BlockState, TransactionState, BlockInsertionResult};
+	use super::{Chain, BlockState, TransactionState, BlockInsertionResult, ChainTip, ChainTipStatus};
 	use utils::HashPosition;
 
 	#[test]
@@ -1137,4 +1293,30 @@ mod tests {
 		chain.insert_verified_transaction(data_chain.at(2).into());
 		assert_eq!(chain.information().transactions.transactions_count, 2); // tx was replaced
 	}
+	#[test]
+	fn chain_tips() {
+		let b0 = test_data::genesis();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build();
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build();
+		let b3 = test_data::block_builder().header().parent(b2.hash()).build().build();
+		let b4 = test_data::block_builder().header().parent(b3.hash()).build().build();
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build();
+		let g2 = test_data::block_builder().header().parent(b1.hash()).nonce(2).build().build();
+
+		let db = Arc::new(BlockChainDatabase::init_test_chain(vec![b0.clone().into(), b1.clone().into()]));
+		db.insert(f1.clone().into()).unwrap();
+		let mut chain = Chain::new(db, ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), Arc::new(RwLock::new(MemoryPool::new())));
+		chain.schedule_blocks_headers(vec![b2.block_header.clone().into(), b3.block_header.clone().into()]);
+		chain.verify_block(g2.block_header.clone().into());
+		chain.mark_dead_end_header(&b4.block_header.clone().into());
+
+		let tip = |hash: H256, height, branch_len, status| ChainTip { hash: hash, height: height, branch_len: branch_len, status: status };
+		let tips = chain.chain_tips();
+		assert_eq!(tips.len(), 5);
+		assert_eq!(tips[0], tip(b4.hash(), 4, 3, ChainTipStatus::Invalid));
+		assert_eq!(tips[1], tip(b3.hash(), 3, 2, ChainTipStatus::HeadersOnly));
+		assert_eq!(tips[2], tip(g2.hash(), 2, 1, ChainTipStatus::ValidHeaders));
+		assert!(tips.contains(&tip(b1.hash(), 1, 0, ChainTipStatus::Active)));
+		assert!(tips.contains(&tip(f1.hash(), 1, 1, ChainTipStatus::ValidFork)));
+	}
 }
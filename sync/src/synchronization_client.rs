@@ -1,7 +1,9 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
 use parking_lot::Mutex;
 use chain::{IndexedTransaction, IndexedBlock, IndexedBlockHeader};
 use message::types;
+use synchronization_chain::ChainTip;
 use synchronization_executor::TaskExecutor;
 use synchronization_verifier::{Verifier, TransactionVerificationSink};
 use synchronization_client_core::{ClientCore, SynchronizationClientCore};
@@ -126,11 +128,15 @@ pub trait Client : Send + Sync + 'static {
 	fn on_inventory(&self, peer_index: PeerIndex, message: types::Inv);
 	fn on_headers(&self, peer_index: PeerIndex, headers: Vec<IndexedBlockHeader>);
 	fn on_block(&self, peer_index: PeerIndex, block: IndexedBlock);
+	fn on_compact_block(&self, peer_index: PeerIndex, message: types::CompactBlock);
+	fn on_block_txn(&self, peer_index: PeerIndex, message: types::BlockTxn);
 	fn on_transaction(&self, peer_index: PeerIndex, transaction: IndexedTransaction);
 	fn on_notfound(&self, peer_index: PeerIndex, message: types::NotFound);
+	fn on_merkleblock(&self, peer_index: PeerIndex, message: types::MerkleBlock);
 	fn after_peer_nearly_blocks_verified(&self, peer_index: PeerIndex, future: EmptyBoxFuture);
 	fn accept_transaction(&self, transaction: IndexedTransaction, sink: Box<dyn TransactionVerificationSink>) -> Result<(), String>;
 	fn install_sync_listener(&self, listener: SyncListenerRef);
+	fn chain_tips(&self) -> Vec<ChainTip>;
 }
 
 /// Synchronization client facade
@@ -163,29 +169,15 @@ impl<T, U> Client for SynchronizationClient<T, U> where T: TaskExecutor, U: Veri
 	}
 
 	fn on_block(&self, peer_index: PeerIndex, block: IndexedBlock) {
-		// block can became:
-		// ignored, unknown, orphaned => no verification should occur
-		// on-time => this block + all dependent orphaned should be verified
-		{
-			// verification tasks must be scheduled in the same order as they were built in on_block
-			// => here we use verification_lock for this
-			let _verification_lock = self.verification_lock.lock();
-			let blocks_to_verify = self.core.lock().on_block(peer_index, block);
+		self.process_block(|core| core.on_block(peer_index, block));
+	}
 
-			// verify blocks
-			if let Some(mut blocks_to_verify) = blocks_to_verify {
-				while let Some(block) = blocks_to_verify.pop_front() {
-					self.verifier.verify_block(block);
-				}
-			}
-		}
+	fn on_compact_block(&self, peer_index: PeerIndex, message: types::CompactBlock) {
+		self.process_block(|core| core.on_compact_block(peer_index, message));
+	}
 
-		// in case if verification was synchronous
-		// => try to switch to saturated state OR execute sync tasks
-		let mut client = self.core.lock();
-		if !client.try_switch_to_saturated_state() {
-			client.execute_synchronization_tasks(None, None);
-		}
+	fn on_block_txn(&self, peer_index: PeerIndex, message: types::BlockTxn) {
+		self.process_block(|core| core.on_block_txn(peer_index, message));
 	}
 
 	fn on_transaction(&self, peer_index: PeerIndex, transaction: IndexedTransaction) {
@@ -210,6 +202,10 @@ impl<T, U> Client for SynchronizationClient<T, U> where T: TaskExecutor, U: Veri
 		self.core.lock().on_notfound(peer_index, message);
 	}
 
+	fn on_merkleblock(&self, peer_index: PeerIndex, message: types::MerkleBlock) {
+		self.core.lock().on_merkleblock(peer_index, message);
+	}
+
 	fn after_peer_nearly_blocks_verified(&self, peer_index: PeerIndex, future: EmptyBoxFuture) {
 		self.core.lock().after_peer_nearly_blocks_verified(peer_index, future);
 	}
@@ -227,6 +223,10 @@ impl<T, U> Client for SynchronizationClient<T, U> where T: TaskExecutor, U: Veri
 	fn install_sync_listener(&self, listener: SyncListenerRef) {
 		self.core.lock().install_sync_listener(listener);
 	}
+
+	fn chain_tips(&self) -> Vec<ChainTip> {
+		self.core.lock().chain_tips()
+	}
 }
 
 impl<T, U> SynchronizationClient<T, U> where T: TaskExecutor, U: Verifier {
@@ -239,4 +239,31 @@ impl<T, U> SynchronizationClient<T, U> where T: TaskExecutor, U: Verifier {
 			verifier: verifier,
 		})
 	}
+
+	/// Process received block (or its part) and verify all blocks, which could be verified after that
+	fn process_block<F>(&self, process: F) where F: FnOnce(&mut SynchronizationClientCore<T>) -> Option<VecDeque<IndexedBlock>> {
+		// block can became:
+		// ignored, unknown, orphaned => no verification should occur
+		// on-time => this block + all dependent orphaned should be verified
+		{
+			// verification tasks must be scheduled in the same order as they were built in on_block
+			// => here we use verification_lock for this
+			let _verification_lock = self.verification_lock.lock();
+			let blocks_to_verify = process(&mut *self.core.lock());
+
+			// verify blocks
+			if let Some(mut blocks_to_verify) = blocks_to_verify {
+				while let Some(block) = blocks_to_verify.pop_front() {
+					self.verifier.verify_block(block);
+				}
+			}
+		}
+
+		// in case if verification was synchronous
+		// => try to switch to saturated state OR execute sync tasks
+		let mut client = self.core.lock();
+		if !client.try_switch_to_saturated_state() {
+			client.execute_synchronization_tasks(None, None);
+		}
+	}
 }
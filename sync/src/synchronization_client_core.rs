@@ -7,19 +7,22 @@ use parking_lot::Mutex;
 use time::precise_time_s;
 use chain::{IndexedBlockHeader, IndexedTransaction, IndexedBlock};
 use message::types;
-use message::common::{InventoryType, InventoryVector};
+use message::common::{InventoryType, InventoryVector, BlockTransactionsRequest};
 use miner::transaction_fee_rate;
+use primitives::bigint::U256;
 use primitives::hash::H256;
+use storage::BlockHeaderProvider;
 use verification::BackwardsCompatibleChainVerifier as ChainVerifier;
-use synchronization_chain::{Chain, BlockState, TransactionState, BlockInsertionResult};
+use synchronization_chain::{Chain, BlockState, TransactionState, BlockInsertionResult, ChainTip};
 use synchronization_executor::{Task, TaskExecutor};
 use synchronization_manager::ManagementWorker;
 use synchronization_peers_tasks::PeersTasks;
 use synchronization_verifier::{VerificationSink, BlockVerificationSink, TransactionVerificationSink, VerificationTask};
-use types::{BlockHeight, ClientCoreRef, PeersRef, PeerIndex, SynchronizationStateRef, EmptyBoxFuture, SyncListenerRef};
-use utils::{AverageSpeedMeter, MessageBlockHeadersProvider, OrphanBlocksPool, OrphanTransactionsPool, HashPosition};
-#[cfg(test)] use synchronization_peers_tasks::{Information as PeersTasksInformation};
-#[cfg(test)] use synchronization_chain::{Information as ChainInformation};
+use types::{AssumeValidRef, BlockHeight, ClientCoreRef, PeersRef, PeerIndex, SynchronizationStateRef, EmptyBoxFuture, SyncListenerRef};
+use utils::{AverageSpeedMeter, MessageBlockHeadersProvider, OrphanBlocksPool, OrphanTransactionsPool, HashPosition, PartialCompactBlock, CompactBlockError};
+use utxo_snapshot_validator::UtxoSnapshotValidator;
+use synchronization_peers_tasks::{Information as PeersTasksInformation};
+use synchronization_chain::{Information as ChainInformation};
 
 /// Approximate maximal number of blocks hashes in scheduled queue.
 const MAX_SCHEDULED_HASHES: BlockHeight = 4 * 1024;
@@ -31,6 +34,26 @@ const MAX_VERIFYING_BLOCKS: BlockHeight = 256;
 const MIN_BLOCKS_IN_REQUEST: BlockHeight = 32;
 /// Maximum number of blocks to request from peer
 const MAX_BLOCKS_IN_REQUEST: BlockHeight = 128;
+/// Requested blocks window is sized to hold blocks, which are downloaded (verified) in this number of seconds
+const REQUESTED_BLOCKS_WINDOW_S: f64 = 10_f64;
+/// Verifying blocks window is sized to hold blocks, which are verified in this number of seconds
+const VERIFYING_BLOCKS_WINDOW_S: f64 = 10_f64;
+/// Single blocks request is sized to be served by peer in this number of seconds
+const BLOCKS_REQUEST_WINDOW_S: f64 = 5_f64;
+/// Maximal size of requested (verifying) blocks window
+const MAX_BLOCKS_WINDOW: BlockHeight = 2048;
+/// Minimal size of single blocks request
+const MIN_BLOCKS_REQUEST_WINDOW: BlockHeight = 2;
+/// Maximal size of single blocks request
+const MAX_BLOCKS_REQUEST_WINDOW: BlockHeight = 256;
+/// Maximal total size of blocks in requested window
+const MAX_REQUESTED_BLOCKS_SIZE: usize = 256 * 1024 * 1024;
+/// Maximal total size of blocks in verifying window
+const MAX_VERIFYING_BLOCKS_SIZE: usize = 256 * 1024 * 1024;
+/// Maximal total size of blocks in single blocks request
+const MAX_BLOCKS_REQUEST_SIZE: usize = 32 * 1024 * 1024;
+/// Scheduled queue holds hashes to fill this number of requested blocks windows
+const SCHEDULED_BLOCKS_WINDOWS: BlockHeight = MAX_SCHEDULED_HASHES / MAX_REQUESTED_BLOCKS;
 /// Number of blocks to receive since synchronization start to begin duplicating blocks requests
 const NEAR_EMPTY_VERIFICATION_QUEUE_THRESHOLD_BLOCKS: usize = 20;
 /// Number of seconds left before verification queue will be empty to count it as 'near empty queue'
@@ -47,7 +70,6 @@ const MAX_BLOCKS_IN_DUPLICATE_REQUEST: BlockHeight = 4;
 const MIN_BLOCKS_IN_DUPLICATE_REQUEST: BlockHeight = 8;
 
 /// Information on current synchronization state.
-#[cfg(test)]
 #[derive(Debug)]
 pub struct Information {
 	/// Current synchronization state.
@@ -60,6 +82,8 @@ pub struct Information {
 	pub orphaned_blocks: usize,
 	/// Number of currently orphaned transactions.
 	pub orphaned_transactions: usize,
+	/// Current blocks request limits.
+	pub limits: BlocksRequestLimits,
 }
 
 /// Synchronization client trait
@@ -69,13 +93,17 @@ pub trait ClientCore {
 	fn on_inventory(&self, peer_index: PeerIndex, message: types::Inv);
 	fn on_headers(&mut self, peer_index: PeerIndex, message: Vec<IndexedBlockHeader>);
 	fn on_block(&mut self, peer_index: PeerIndex, block: IndexedBlock) -> Option<VecDeque<IndexedBlock>>;
+	fn on_compact_block(&mut self, peer_index: PeerIndex, message: types::CompactBlock) -> Option<VecDeque<IndexedBlock>>;
+	fn on_block_txn(&mut self, peer_index: PeerIndex, message: types::BlockTxn) -> Option<VecDeque<IndexedBlock>>;
 	fn on_transaction(&mut self, peer_index: PeerIndex, transaction: IndexedTransaction) -> Option<VecDeque<IndexedTransaction>>;
 	fn on_notfound(&mut self, peer_index: PeerIndex, message: types::NotFound);
+	fn on_merkleblock(&mut self, peer_index: PeerIndex, message: types::MerkleBlock);
 	fn after_peer_nearly_blocks_verified(&mut self, peer_index: PeerIndex, future: EmptyBoxFuture);
 	fn accept_transaction(&mut self, transaction: IndexedTransaction, sink: Box<dyn TransactionVerificationSink>) -> Result<VecDeque<IndexedTransaction>, String>;
 	fn install_sync_listener(&mut self, listener: SyncListenerRef);
 	fn execute_synchronization_tasks(&mut self, forced_blocks_requests: Option<Vec<H256>>, final_blocks_requests: Option<Vec<H256>>);
 	fn try_switch_to_saturated_state(&mut self) -> bool;
+	fn chain_tips(&self) -> Vec<ChainTip>;
 }
 
 /// Synchronization client configuration options.
@@ -83,6 +111,8 @@ pub trait ClientCore {
 pub struct Config {
 	/// If true, connection to peer who has provided us with bad block is closed
 	pub close_connection_on_bad_block: bool,
+	/// Headers chains with less work are not stored until they have enough work
+	pub minimum_chain_work: U256,
 }
 
 /// Synchronization client.
@@ -105,6 +135,8 @@ pub struct SynchronizationClientCore<T: TaskExecutor> {
 	orphaned_blocks_pool: OrphanBlocksPool,
 	/// Orphaned transactions pool.
 	orphaned_transactions_pool: OrphanTransactionsPool,
+	/// Compact blocks, waiting for missing transactions from peers
+	compact_blocks: HashMap<PeerIndex, PartialCompactBlock>,
 	/// Chain verifier
 	chain_verifier: Arc<ChainVerifier>,
 	/// Verify block headers?
@@ -127,6 +159,22 @@ pub struct SynchronizationClientCore<T: TaskExecutor> {
 	listener: Option<SyncListenerRef>,
 	/// Time of last duplicated blocks request.
 	last_dup_time: f64,
+	/// Blocks request limits, updated using current download && verification speed
+	limits: BlocksRequestLimits,
+	/// Average size of recently downloaded blocks. Zero until the first block is downloaded
+	average_block_size: f64,
+	/// Assumed valid blocks
+	assume_valid: Option<AssumeValidRef>,
+	/// Headers chains with less than minimum chain work, which are still provided by peers
+	low_work_headers: HashMap<PeerIndex, LowWorkHeadersChain>,
+	/// Low-work headers chains, which have got enough work && are requested again
+	enough_work_headers: HashMap<PeerIndex, EnoughWorkHeadersChain>,
+	/// Headers chain, leading to the assumed valid block, which is not yet in the best headers chain
+	assume_valid_headers: Option<AssumeValidHeadersChain>,
+	/// Peers, which have failed to provide headers chain, leading to the assumed valid block
+	assume_valid_failed_peers: HashSet<PeerIndex>,
+	/// Background validator of blocks below the loaded unspent outputs set snapshot
+	utxo_snapshot_validator: Option<UtxoSnapshotValidator>,
 }
 
 /// Verification sink for synchronization client core
@@ -147,6 +195,7 @@ pub enum State {
 }
 
 /// Blocks request limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BlocksRequestLimits {
 	/// Approximate maximal number of blocks hashes in scheduled queue.
 	pub max_scheduled_hashes: BlockHeight,
@@ -166,6 +215,49 @@ enum AppendTransactionError {
 	Orphan(HashSet<H256>),
 }
 
+/// Headers chain of the peer with less than minimum chain work. Headers of this chain are not stored
+struct LowWorkHeadersChain {
+	/// Hash of the last header of the chain
+	last_hash: H256,
+	/// Height of the last header of the chain, if known
+	last_height: Option<BlockHeight>,
+	/// Total work of the chain, ending with the last header
+	chainwork: U256,
+	/// Hash of the stored header, which the chain follows
+	parent_hash: H256,
+	/// Number of headers in the chain
+	headers_len: BlockHeight,
+	/// Hashes of the last headers of every `headers` message, along with number of chain headers up to them
+	commitments: VecDeque<(BlockHeight, H256)>,
+}
+
+/// Low-work headers chain of the peer, which has got enough work && is requested again. Requested headers
+/// are stored without chain work check, while they match the headers, which have been counted before
+struct EnoughWorkHeadersChain {
+	/// Hash of the last requested header
+	last_hash: H256,
+	/// Number of requested headers
+	headers_len: BlockHeight,
+	/// Hashes of the counted chain headers, which are still expected from peer, along with their number in the chain
+	commitments: VecDeque<(BlockHeight, H256)>,
+}
+
+/// Headers chain, which follows the best headers chain && leads to the assumed valid block. Headers of this
+/// chain are not stored - only their hashes are remembered, so that ancestors of the assumed valid block
+/// are known long before its header gets to the best headers chain.
+struct AssumeValidHeadersChain {
+	/// Peer, which provides headers of the chain
+	peer_index: PeerIndex,
+	/// Hash of the best headers chain header, which the chain follows
+	anchor_hash: H256,
+	/// Hashes of the chain headers, following the anchor header
+	hashes: Vec<H256>,
+	/// Total work of the chain headers, following the anchor header
+	work: U256,
+	/// True if the last header of the chain is the assumed valid block
+	is_complete: bool,
+}
+
 /// Blocks headers verification result
 enum BlocksHeadersVerificationResult {
 	/// Skip these blocks headers
@@ -176,6 +268,40 @@ enum BlocksHeadersVerificationResult {
 	Success,
 }
 
+impl EnoughWorkHeadersChain {
+	/// Returns false if requested headers do not follow the counted chain
+	fn verify(&mut self, parent_hash: &H256, headers: &[IndexedBlockHeader]) -> bool {
+		if *parent_hash != self.last_hash {
+			return false;
+		}
+
+		for header in headers {
+			self.headers_len += 1;
+			let is_commitment = self.commitments.front()
+				.map(|&(headers_len, _)| headers_len == self.headers_len)
+				.unwrap_or(false);
+			if is_commitment && self.commitments.pop_front().map(|(_, hash)| hash) != Some(header.hash.clone()) {
+				return false;
+			}
+		}
+
+		self.last_hash = headers[headers.len() - 1].hash.clone();
+		true
+	}
+
+	/// True if all counted headers are received again
+	fn is_complete(&self) -> bool {
+		self.commitments.is_empty()
+	}
+}
+
+impl AssumeValidHeadersChain {
+	/// Hash of the last header of the chain
+	fn last_hash(&self) -> &H256 {
+		self.hashes.last().unwrap_or(&self.anchor_hash)
+	}
+}
+
 impl State {
 	pub fn is_saturated(&self) -> bool {
 		match *self {
@@ -205,6 +331,12 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		// ask peer for its block headers to find our best common block
 		let block_locator_hashes = self.chain.block_locator_hashes();
 		self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(block_locator_hashes)));
+		// we do not want peer to push compact blocks to us, but we could ask for them
+		// supported versions are announced in the order of preference
+		if self.chain.is_segwit_possible() {
+			self.executor.execute(Task::SendCompact(peer_index, types::SendCompact { first: false, second: 2 }));
+		}
+		self.executor.execute(Task::SendCompact(peer_index, types::SendCompact { first: false, second: 1 }));
 		// unuseful until respond with headers message
 		self.peers_tasks.unuseful_peer(peer_index);
 		self.peers_tasks.on_headers_requested(peer_index);
@@ -214,12 +346,24 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		// sync tasks from this peers must be executed by other peers
 		let peer_tasks = self.peers_tasks.reset_blocks_tasks(peer_index);
 		self.peers_tasks.disconnect(peer_index);
+		self.compact_blocks.remove(&peer_index);
+		self.low_work_headers.remove(&peer_index);
+		self.enough_work_headers.remove(&peer_index);
+		self.assume_valid_failed_peers.remove(&peer_index);
+		if self.assume_valid_headers.as_ref().map(|chain| !chain.is_complete && chain.peer_index == peer_index).unwrap_or(false) {
+			self.assume_valid_headers = None;
+		}
+		if let Some(ref mut validator) = self.utxo_snapshot_validator {
+			validator.on_disconnect(peer_index);
+		}
 		self.execute_synchronization_tasks(Some(peer_tasks), None);
 	}
 
 	fn on_inventory(&self, peer_index: PeerIndex, message: types::Inv) {
 		// else ask for all unknown transactions and blocks
 		let is_segwit_possible = self.chain.is_segwit_possible();
+		// new blocks are requested as compact blocks, when most of their transactions are likely in our memory pool
+		let is_compact_block_possible = !self.state.is_synchronizing() && self.is_compact_block_possible(peer_index);
 		let unknown_inventory: Vec<_> = message.inventory.into_iter()
 			.filter(|item| {
 				match item.inv_type {
@@ -251,10 +395,14 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 			// we are not synchronizing =>
 			// 1) either segwit is active and we are connected to segwit-enabled nodes => we could ask for witness
 			// 2) or segwit is inactive => we shall not ask for witness
-			.map(|item| if !is_segwit_possible {
-					item
-				} else {
-					match item.inv_type {
+			// new blocks could be requested as compact blocks in both cases
+			.map(|item| match item.inv_type {
+					InventoryType::MessageBlock if is_compact_block_possible => InventoryVector {
+						inv_type: InventoryType::MessageCompactBlock,
+						hash: item.hash,
+					},
+					_ if !is_segwit_possible => item,
+					_ => match item.inv_type {
 						InventoryType::MessageTx => InventoryVector {
 							inv_type: InventoryType::MessageWitnessTx,
 							hash: item.hash,
@@ -286,11 +434,43 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		// update peers to select next tasks
 		self.peers_tasks.on_headers_received(peer_index);
 
+		// headers, continuing low-work headers chain of this peer, are only verified && counted
+		let is_last_headers = headers.len() < types::HEADERS_MAX_HEADERS_LEN;
+		if let Some(low_work_headers) = self.low_work_headers.remove(&peer_index) {
+			if low_work_headers.last_hash == headers[0].raw.previous_header_hash {
+				let last_known_hash = low_work_headers.last_hash.clone();
+				let last_known_height = low_work_headers.last_height;
+				if let BlocksHeadersVerificationResult::Success = self.verify_headers(peer_index, last_known_hash, last_known_height, &headers) {
+					self.on_low_work_headers(peer_index, low_work_headers, &headers, is_last_headers);
+				}
+				return;
+			}
+		}
+
+		// headers, continuing headers chain to the assumed valid block, are only verified && remembered by hash
+		let is_assume_valid_peer = self.assume_valid_headers.as_ref()
+			.map(|chain| !chain.is_complete && chain.peer_index == peer_index)
+			.unwrap_or(false);
+		if is_assume_valid_peer {
+			let is_assume_valid_headers = self.assume_valid_headers.as_ref()
+				.map(|chain| *chain.last_hash() == headers[0].raw.previous_header_hash)
+				.unwrap_or(false);
+			if is_assume_valid_headers {
+				self.on_assume_valid_headers(peer_index, &headers, is_last_headers);
+				return;
+			}
+
+			// peer has switched to other chain => headers chain is requested again later
+			self.assume_valid_headers = None;
+		}
+
 		// headers are ordered
 		// => if we know nothing about headers[0].parent
 		// => all headers are also unknown to us
+		// (parent could also be the header of the fork, which has less work than the best headers chain)
 		let header0 = headers[0].clone();
-		if self.chain.block_state(&header0.raw.previous_header_hash) == BlockState::Unknown {
+		if self.chain.block_state(&header0.raw.previous_header_hash) == BlockState::Unknown
+			&& self.chain.block_header_by_hash(&header0.raw.previous_header_hash).is_none() {
 			warn!(
 				target: "sync",
 				"Previous header of the first header from peer#{} `headers` message is unknown. First: {}. Previous: {}",
@@ -337,10 +517,17 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 			self.peers.misbehaving(peer_index, &format!("Provided after dead-end block {}", last_known_hash.to_reversed_str()));
 			return;
 		}
-		match self.verify_headers(peer_index, last_known_hash, &headers[first_unknown_index..num_headers]) {
-			BlocksHeadersVerificationResult::Error(error_index) => self.chain.mark_dead_end_block(&headers[first_unknown_index + error_index].hash),
+		let last_known_height = self.chain.block_number(&last_known_hash);
+		match self.verify_headers(peer_index, last_known_hash.clone(), last_known_height, &headers[first_unknown_index..num_headers]) {
+			BlocksHeadersVerificationResult::Error(error_index) => self.chain.mark_dead_end_header(&headers[first_unknown_index + error_index]),
 			BlocksHeadersVerificationResult::Skip => (),
 			BlocksHeadersVerificationResult::Success => {
+				// do not store low-work headers chains
+				if let Some(low_work_headers) = self.low_work_headers_chain(peer_index, &last_known_hash, last_known_height, &headers[first_unknown_index..num_headers], is_last_headers) {
+					self.on_low_work_headers(peer_index, low_work_headers, &headers[first_unknown_index..num_headers], is_last_headers);
+					return;
+				}
+
 				// report progress
 				let num_new_headers = num_headers - first_unknown_index;
 				trace!(target: "sync", "New {} headers from peer#{}. First {:?}, last: {:?}",
@@ -352,7 +539,9 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 
 				// prepare new headers array
 				let new_headers = headers.split_off(first_unknown_index);
-				self.chain.schedule_blocks_headers(new_headers);
+				let unrequested = self.chain.schedule_blocks_headers(new_headers);
+				// blocks of the old best headers chain are not expected from peers anymore
+				self.peers_tasks.forget_blocks_requests(&unrequested);
 
 				// switch to synchronization state
 				if !self.state.is_synchronizing() {
@@ -366,6 +555,10 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 
 				// this peers has supplied us with new headers => useful indeed
 				self.peers_tasks.useful_peer(peer_index);
+				// assumed valid block could be far ahead of the best headers chain => ask for its ancestors
+				if !is_last_headers {
+					self.request_assume_valid_headers(peer_index);
+				}
 				// and execute tasks
 				self.execute_synchronization_tasks(None, None);
 			},
@@ -373,6 +566,15 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 	}
 
 	fn on_block(&mut self, peer_index: PeerIndex, block: IndexedBlock) -> Option<VecDeque<IndexedBlock>> {
+		// blocks below the loaded utxo snapshot are validated in the background
+		if self.utxo_snapshot_validator.as_ref().map(|validator| validator.is_requested_block(block.hash())).unwrap_or(false) {
+			if let Some(ref mut validator) = self.utxo_snapshot_validator {
+				validator.on_block(block);
+			}
+			self.execute_utxo_snapshot_validation_tasks();
+			return None;
+		}
+
 		// update peers to select next tasks
 		self.peers_tasks.on_block_received(peer_index, &block.header.hash);
 
@@ -435,8 +637,16 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 					BlockState::Verifying | BlockState::Stored => {
 						// update synchronization speed
 						self.sync_speed_meter.checkpoint();
+						self.average_block_size = match self.average_block_size == 0_f64 {
+							true => block.size() as f64,
+							false => self.average_block_size + (block.size() as f64 - self.average_block_size) / SYNC_SPEED_BLOCKS_TO_INSPECT as f64,
+						};
 						// remember peer as useful
 						self.peers_tasks.useful_peer(peer_index);
+						// push new best block to high-bandwidth peers before it is verified
+						if !self.state.is_synchronizing() && block.header.raw.previous_header_hash == self.chain.best_storage_block().hash {
+							self.relay_unverified_compact_block(&block);
+						}
 						// schedule verification
 						let mut blocks_to_verify: VecDeque<IndexedBlock> = VecDeque::new();
 						blocks_to_verify.extend(self.orphaned_blocks_pool.remove_blocks_for_parent(&block.header.hash));
@@ -447,6 +657,10 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 						// remember that we are verifying these blocks
 						let blocks_headers_to_verify: Vec<_> = blocks_to_verify.iter().map(|b| b.header.clone()).collect();
 						self.chain.verify_blocks(blocks_headers_to_verify);
+						// remember blocks, which scripts are assumed to be valid
+						for verifying_block_hash in blocks_to_verify.iter().map(|b| b.hash()) {
+							self.on_assumed_valid_candidate(verifying_block_hash);
+						}
 						// remember that we are verifying block from this peer
 						for verifying_block_hash in blocks_to_verify.iter().map(|b| b.hash().clone()) {
 							self.verifying_blocks_by_peer.insert(verifying_block_hash, peer_index);
@@ -475,6 +689,80 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		result
 	}
 
+	fn on_compact_block(&mut self, peer_index: PeerIndex, message: types::CompactBlock) -> Option<VecDeque<IndexedBlock>> {
+		// do nothing if we have already processed this block before
+		let block_hash = message.header.header.hash();
+		match self.chain.block_state(&block_hash) {
+			BlockState::Verifying | BlockState::Stored => {
+				self.peers_tasks.useful_peer(peer_index);
+				return None;
+			},
+			_ => (),
+		}
+
+		let witness = self.peers.compact_block_version(peer_index) == Some(2);
+		let mut block = match PartialCompactBlock::new(message.header, witness) {
+			Ok(block) => block,
+			// short ids of different transactions could collide => request the whole block
+			Err(CompactBlockError::DuplicateShortIds) => {
+				self.request_block(peer_index, block_hash);
+				return None;
+			},
+			Err(CompactBlockError::Invalid(err)) => {
+				self.peers.misbehaving(peer_index, &format!("Provided invalid compact block: {}", err));
+				return None;
+			},
+		};
+
+		// compact blocks v1 are built from transactions without witness
+		// => we can't reconstruct witness block
+		if !self.is_compact_block_possible(peer_index) {
+			self.request_block(peer_index, block_hash);
+			return None;
+		}
+
+		// try to reconstruct block using memory pool and orphaned transactions
+		{
+			let memory_pool = self.chain.memory_pool();
+			let memory_pool = memory_pool.read();
+			block.fill_known(memory_pool.entries().map(|entry| (&entry.hash, &entry.transaction)));
+		}
+		block.fill_known(self.orphaned_transactions_pool.transactions().iter().map(|(hash, orphan)| (hash, &orphan.transaction.raw)));
+
+		let missing_indexes = block.missing_indexes();
+		if !missing_indexes.is_empty() {
+			let getblocktxn = types::GetBlockTxn {
+				request: BlockTransactionsRequest {
+					blockhash: block_hash,
+					indexes: missing_indexes,
+				},
+			};
+			self.compact_blocks.insert(peer_index, block);
+			self.executor.execute(Task::GetBlockTxn(peer_index, getblocktxn));
+			return None;
+		}
+
+		self.on_compact_block_reconstructed(peer_index, block)
+	}
+
+	fn on_block_txn(&mut self, peer_index: PeerIndex, message: types::BlockTxn) -> Option<VecDeque<IndexedBlock>> {
+		let is_requested = self.compact_blocks.get(&peer_index)
+			.map(|block| block.header().hash == message.request.blockhash)
+			.unwrap_or(false);
+		if !is_requested {
+			self.peers.misbehaving(peer_index, "Got unrequested 'blocktxn' message");
+			return None;
+		}
+
+		let mut block = self.compact_blocks.remove(&peer_index).expect("checked above; qed");
+		if let Err(err) = block.fill_missing(message.request.transactions) {
+			self.peers.misbehaving(peer_index, &format!("Provided invalid 'blocktxn' message: {}", err));
+			return None;
+		}
+
+		self.on_compact_block_reconstructed(peer_index, block)
+	}
+
 	fn on_transaction(&mut self, peer_index: PeerIndex, transaction: IndexedTransaction) -> Option<VecDeque<IndexedTransaction>> {
 		// check if this transaction is already known
 		if self.orphaned_transactions_pool.contains(&transaction.hash) ||
@@ -485,8 +773,22 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		self.process_peer_transaction(Some(peer_index), transaction, true)
 	}
 
+	/// When peer sends us a merkle block
+	fn on_merkleblock(&mut self, peer_index: PeerIndex, _message: types::MerkleBlock) {
+		// we never setup filter on connections => misbehaving
+		self.peers.misbehaving(peer_index, "Got unrequested 'merkleblock' message");
+	}
+
 	/// When peer has no blocks
 	fn on_notfound(&mut self, peer_index: PeerIndex, message: types::NotFound) {
+		if let Some(ref mut validator) = self.utxo_snapshot_validator {
+			let notfound_blocks: HashSet<_> = message.inventory.iter()
+				.filter(|item| item.inv_type == InventoryType::MessageBlock || item.inv_type == InventoryType::MessageWitnessBlock)
+				.map(|item| item.hash.clone())
+				.collect();
+			validator.on_notfound(peer_index, &notfound_blocks);
+		}
+
 		let notfound_blocks: HashSet<_> = message.inventory
 			.into_iter()
 			.filter(|item| item.inv_type == InventoryType::MessageBlock)
@@ -563,12 +865,8 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		// display information if processed many blocks || enough time has passed since sync start
 		self.print_synchronization_information();
 
-		// prepare limits. TODO: must be updated using current retrieval && verification speed && blocks size
-		let mut limits = BlocksRequestLimits::default();
-		if self.chain.length_of_blocks_state(BlockState::Stored) > 150_000 {
-			limits.min_blocks_in_request = 8;
-			limits.max_blocks_in_request = 16;
-		}
+		// prepare limits
+		let limits = self.update_blocks_request_limits();
 
 		// if some blocks requests are forced => we should ask peers even if there are no idle peers
 		let verifying_hashes_len = self.chain.length_of_blocks_state(BlockState::Verifying);
@@ -601,7 +899,7 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 			let headers_idle_peers: Vec<_> = self.peers_tasks.idle_peers_for_headers().iter().cloned().collect();
 			if !headers_idle_peers.is_empty() {
 				let scheduled_hashes_len = self.chain.length_of_blocks_state(BlockState::Scheduled);
-				if scheduled_hashes_len < MAX_SCHEDULED_HASHES {
+				if scheduled_hashes_len < limits.max_scheduled_hashes {
 					for header_peer in &headers_idle_peers {
 						self.peers_tasks.on_headers_requested(*header_peer);
 					}
@@ -685,9 +983,13 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 				{
 					// TODO: only request minimal number of blocks, if other urgent blocks are requested
 					let scheduled_hashes_len = self.chain.length_of_blocks_state(BlockState::Scheduled);
-					if requested_hashes_len + verifying_hashes_len < MAX_REQUESTED_BLOCKS + MAX_VERIFYING_BLOCKS && scheduled_hashes_len != 0 {
+					// download window starts at the first requested block and moves only when this block is received
+					// => blocks, which are received and waiting for the window base, are also in the window
+					let download_window_len = requested_hashes_len + self.orphaned_blocks_pool.len() as BlockHeight;
+					if requested_hashes_len + verifying_hashes_len < limits.max_requested_blocks + limits.max_verifying_blocks
+						&& download_window_len < limits.max_requested_blocks && scheduled_hashes_len != 0 {
 						let chunk_size = min(limits.max_blocks_in_request, max(scheduled_hashes_len / blocks_idle_peers_len, limits.min_blocks_in_request));
-						let hashes_to_request_len = chunk_size * blocks_idle_peers_len;
+						let hashes_to_request_len = min(chunk_size * blocks_idle_peers_len, limits.max_requested_blocks - download_window_len);
 						let hashes_to_request = self.chain.request_blocks_hashes(hashes_to_request_len);
 						match blocks_requests {
 							Some(ref mut blocks_requests) => blocks_requests.extend(hashes_to_request),
@@ -707,6 +1009,8 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 		for task in tasks {
 			self.executor.execute(task);
 		}
+
+		self.execute_utxo_snapshot_validation_tasks();
 	}
 
 	fn try_switch_to_saturated_state(&mut self) -> bool {
@@ -722,6 +1026,10 @@ impl<T> ClientCore for SynchronizationClientCore<T> where T: TaskExecutor {
 
 		switch_to_saturated
 	}
+
+	fn chain_tips(&self) -> Vec<ChainTip> {
+		self.chain.chain_tips()
+	}
 }
 
 impl<T> CoreVerificationSink<T> where T: TaskExecutor {
@@ -773,6 +1081,7 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 				chain: chain,
 				orphaned_blocks_pool: OrphanBlocksPool::new(),
 				orphaned_transactions_pool: OrphanTransactionsPool::new(),
+				compact_blocks: HashMap::new(),
 				chain_verifier: chain_verifier,
 				verify_headers: true,
 				verifying_blocks_by_peer: HashMap::new(),
@@ -784,6 +1093,14 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 				config: config,
 				listener: None,
 				last_dup_time: 0f64,
+				limits: BlocksRequestLimits::default(),
+				average_block_size: 0_f64,
+				assume_valid: None,
+				low_work_headers: HashMap::new(),
+				enough_work_headers: HashMap::new(),
+				assume_valid_headers: None,
+				assume_valid_failed_peers: HashSet::new(),
+				utxo_snapshot_validator: None,
 			}
 		));
 
@@ -796,8 +1113,211 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 		sync
 	}
 
+	/// Pass reconstructed compact block to the regular blocks processing
+	fn on_compact_block_reconstructed(&mut self, peer_index: PeerIndex, block: PartialCompactBlock) -> Option<VecDeque<IndexedBlock>> {
+		match block.into_block() {
+			Ok(block) => self.on_block(peer_index, block),
+			Err(block) => {
+				// some transactions have been matched by wrong short ids => ask for the whole block
+				let block_hash = block.header().hash.clone();
+				trace!(target: "sync", "Failed to reconstruct compact block {} from peer#{}", block_hash.to_reversed_str(), peer_index);
+				self.request_block(peer_index, block_hash);
+				None
+			},
+		}
+	}
+
+	/// Remember block as assumed valid, if it is an ancestor of assumed valid block
+	/// && the headers chain, containing assumed valid block, has enough work.
+	fn on_assumed_valid_candidate(&mut self, hash: &H256) {
+		let assume_valid = match self.assume_valid {
+			Some(ref assume_valid) => assume_valid.clone(),
+			None => return,
+		};
+		let height = match self.chain.block_number(hash) {
+			Some(height) => height,
+			None => return,
+		};
+
+		// assumed valid block is either stored or in the best headers chain
+		if let Some(assume_valid_height) = self.chain.block_number(assume_valid.block()) {
+			self.assume_valid_headers = None;
+			if height <= assume_valid_height && self.chain.best_headers_chainwork() >= *assume_valid.minimum_chain_work() {
+				assume_valid.insert_ancestor(hash.clone());
+			}
+			return;
+		}
+
+		// else it could be in the headers chain, which follows the best headers chain
+		let is_assumed_valid = match self.assume_valid_headers {
+			Some(ref chain) if chain.is_complete => {
+				let anchor_height = self.chain.block_number(&chain.anchor_hash);
+				let anchor_chainwork = self.chain.block_chainwork(chain.anchor_hash.clone().into());
+				match (anchor_height, anchor_chainwork) {
+					(Some(anchor_height), Some(anchor_chainwork)) => {
+						let is_ancestor = height <= anchor_height
+							|| chain.hashes.get((height - anchor_height - 1) as usize) == Some(hash);
+						is_ancestor && anchor_chainwork + chain.work >= *assume_valid.minimum_chain_work()
+					},
+					// anchor has been removed from the best headers chain
+					_ => false,
+				}
+			},
+			_ => false,
+		};
+		if is_assumed_valid {
+			assume_valid.insert_ancestor(hash.clone());
+		}
+	}
+
+	/// Ask peer for headers, following the best headers chain, if assumed valid block is not yet known.
+	fn request_assume_valid_headers(&mut self, peer_index: PeerIndex) {
+		let assume_valid_hash = match self.assume_valid {
+			Some(ref assume_valid) => assume_valid.block().clone(),
+			None => return,
+		};
+		if self.assume_valid_headers.is_some()
+			|| self.assume_valid_failed_peers.contains(&peer_index)
+			|| self.chain.block_number(&assume_valid_hash).is_some() {
+			return;
+		}
+
+		let anchor_hash = self.chain.best_block_header().hash;
+		trace!(target: "sync", "Requesting headers after {} from peer#{} to find assumed valid block {}",
+			anchor_hash.to_reversed_str(), peer_index, assume_valid_hash.to_reversed_str());
+		self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(vec![anchor_hash.clone()])));
+		self.peers_tasks.on_headers_requested(peer_index);
+		self.assume_valid_headers = Some(AssumeValidHeadersChain {
+			peer_index: peer_index,
+			anchor_hash: anchor_hash,
+			hashes: Vec::new(),
+			work: U256::zero(),
+			is_complete: false,
+		});
+	}
+
+	/// Remember hashes of headers, leading to the assumed valid block, && ask peer for more headers until it is found.
+	fn on_assume_valid_headers(&mut self, peer_index: PeerIndex, headers: &[IndexedBlockHeader], is_last_headers: bool) {
+		let mut chain = self.assume_valid_headers.take().expect("checked by caller; qed");
+		let last_known_hash = chain.last_hash().clone();
+		let last_known_height = self.chain.block_number(&chain.anchor_hash)
+			.map(|height| height + chain.hashes.len() as BlockHeight);
+		match self.verify_headers(peer_index, last_known_hash, last_known_height, headers) {
+			BlocksHeadersVerificationResult::Success => (),
+			// headers chain is requested again later
+			_ => return,
+		}
+
+		let assume_valid_hash = self.assume_valid.as_ref()
+			.map(|assume_valid| assume_valid.block().clone())
+			.expect("headers chain is only requested when assumed valid block is set; qed");
+		for header in headers {
+			chain.hashes.push(header.hash.clone());
+			chain.work = chain.work + header.raw.bits.to_work();
+			if header.hash == assume_valid_hash {
+				trace!(target: "sync", "Assumed valid block {} is found {} headers after {}",
+					assume_valid_hash.to_reversed_str(), chain.hashes.len(), chain.anchor_hash.to_reversed_str());
+				chain.is_complete = true;
+				self.assume_valid_headers = Some(chain);
+				return;
+			}
+		}
+
+		// peer has no more headers => its chain does not contain assumed valid block
+		if is_last_headers {
+			trace!(target: "sync", "Peer#{} has no assumed valid block {} in its headers chain", peer_index, assume_valid_hash.to_reversed_str());
+			self.assume_valid_failed_peers.insert(peer_index);
+			return;
+		}
+
+		let block_locator_hashes = vec![chain.last_hash().clone()];
+		self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(block_locator_hashes)));
+		self.peers_tasks.on_headers_requested(peer_index);
+		self.assume_valid_headers = Some(chain);
+	}
+
+	/// Relay compact block to peers, which want to receive new blocks as soon as possible.
+	/// Only block header (including proof of work) is verified before relaying.
+	fn relay_unverified_compact_block(&self, block: &IndexedBlock) {
+		if self.verify_headers {
+			let headers_provider = MessageBlockHeadersProvider::new(&self.chain, self.chain.best_block_header().number);
+			let height = self.chain.block_number(&block.header.raw.previous_header_hash).map(|number| number + 1);
+			if self.chain_verifier.verify_block_header(&headers_provider, &block.header.hash, &block.header.raw, height).is_err() {
+				return;
+			}
+		}
+
+		self.executor.execute(Task::RelayNewCompactBlock(block.clone()));
+	}
+
+	/// Select peer, which has delivered new block to us, as high-bandwidth compact blocks peer
+	fn select_high_bandwidth_peer(&self, peer_index: PeerIndex) {
+		if !self.is_compact_block_possible(peer_index) {
+			return;
+		}
+
+		let change = self.peers.select_high_bandwidth_peer(peer_index);
+		let announcements = change.selected.map(|peer_index| (peer_index, true)).into_iter()
+			.chain(change.deselected.map(|peer_index| (peer_index, false)));
+		for (peer_index, first) in announcements {
+			if let Some(version) = self.peers.compact_block_version(peer_index) {
+				self.executor.execute(Task::SendCompact(peer_index, types::SendCompact { first: first, second: version }));
+			}
+		}
+	}
+
+	/// Could we reconstruct blocks from compact blocks of given peer?
+	fn is_compact_block_possible(&self, peer_index: PeerIndex) -> bool {
+		match self.peers.compact_block_version(peer_index) {
+			Some(2) => true,
+			Some(1) => !self.chain.is_segwit_possible(),
+			_ => false,
+		}
+	}
+
+	/// Request the whole block from peer
+	fn request_block(&self, peer_index: PeerIndex, block_hash: H256) {
+		let inv_type = if self.chain.is_segwit_possible() { InventoryType::MessageWitnessBlock } else { InventoryType::MessageBlock };
+		let getdata = types::GetData::with_inventory(vec![InventoryVector {
+			inv_type: inv_type,
+			hash: block_hash,
+		}]);
+		self.executor.execute(Task::GetData(peer_index, getdata));
+	}
+
+	/// Request blocks below the loaded utxo snapshot from idle peers. Blocks are only requested
+	/// when the main chain is synchronized.
+	pub fn execute_utxo_snapshot_validation_tasks(&mut self) {
+		if self.state.is_synchronizing() {
+			return;
+		}
+
+		let is_finished = match self.utxo_snapshot_validator {
+			Some(ref validator) => validator.is_finished(),
+			None => return,
+		};
+		if is_finished {
+			self.utxo_snapshot_validator = None;
+			return;
+		}
+
+		let peers: Vec<_> = self.peers_tasks.idle_peers_for_blocks().iter().cloned().collect();
+		let inv_type = if self.chain.is_segwit_possible() { InventoryType::MessageWitnessBlock } else { InventoryType::MessageBlock };
+		let requests = self.utxo_snapshot_validator.as_mut()
+			.expect("validator existence is checked above; qed")
+			.prepare_blocks_requests(peers, precise_time_s());
+		for (peer_index, hashes) in requests {
+			let getdata = types::GetData {
+				inventory: hashes.into_iter().map(|hash| InventoryVector {
+					inv_type: inv_type,
+					hash: hash,
+				}).collect(),
+			};
+			self.executor.execute(Task::GetData(peer_index, getdata));
+		}
+	}
+
 	/// Get information on current synchronization state.
-	#[cfg(test)]
 	pub fn information(&self) -> Information {
 		Information {
 			state: self.state,
@@ -805,6 +1325,7 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 			chain: self.chain.information(),
 			orphaned_blocks: self.orphaned_blocks_pool.len(),
 			orphaned_transactions: self.orphaned_transactions_pool.len(),
+			limits: self.limits,
 		}
 	}
 
@@ -828,6 +1349,16 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 		&mut self.peers_tasks
 	}
 
+	/// Set assumed valid blocks
+	pub fn set_assume_valid(&mut self, assume_valid: AssumeValidRef) {
+		self.assume_valid = Some(assume_valid);
+	}
+
+	/// Set validator of blocks below the loaded utxo snapshot
+	pub fn set_utxo_snapshot_validator(&mut self, validator: UtxoSnapshotValidator) {
+		self.utxo_snapshot_validator = Some(validator);
+	}
+
 	/// Get orphaned blocks pool reference
 	pub fn orphaned_blocks_pool(&mut self) -> &mut OrphanBlocksPool {
 		&mut self.orphaned_blocks_pool
@@ -844,6 +1375,12 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 		self.verify_headers = verify;
 	}
 
+	/// Set minimum chain work of stored headers chain
+	#[cfg(test)]
+	pub fn set_minimum_chain_work(&mut self, minimum_chain_work: U256) {
+		self.config.minimum_chain_work = minimum_chain_work;
+	}
+
 	/// Print synchronization information
 	pub fn print_synchronization_information(&mut self) {
 		if let State::Synchronizing(timestamp, num_of_blocks) = self.state {
@@ -854,16 +1391,36 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 			if timestamp_diff >= 60.0 || blocks_diff >= 1000 {
 				self.state = State::Synchronizing(precise_time_s(), new_num_of_blocks);
 				let blocks_speed = blocks_diff as f64 / timestamp_diff;
-				info!(target: "sync", "Processed {} blocks in {:.2} seconds ({:.2} blk/s).\tPeers: {:?}.\tChain: {:?}"
+				let information = self.information();
+				info!(target: "sync", "Processed {} blocks in {:.2} seconds ({:.2} blk/s).\tState: {:?}.\tPeers: {:?}.\tChain: {:?}.\tOrphans: {} blocks, {} transactions.\tLimits: {:?}"
 					, blocks_diff
 					, timestamp_diff
 					, blocks_speed
-					, self.peers_tasks.information()
-					, self.chain.information());
+					, information.state
+					, information.peers_tasks
+					, information.chain
+					, information.orphaned_blocks
+					, information.orphaned_transactions
+					, information.limits);
 			}
 		}
 	}
 
+	/// Update blocks request limits using current download && verification speed
+	fn update_blocks_request_limits(&mut self) -> BlocksRequestLimits {
+		// until speed is measured, requests for the blocks of the latest chain era are smaller, because blocks are larger
+		if self.sync_speed_meter.inspected_items_len() == 0 && self.chain.length_of_blocks_state(BlockState::Stored) > 150_000 {
+			self.limits.min_blocks_in_request = 8;
+			self.limits.max_blocks_in_request = 16;
+		}
+
+		let download_speed = self.sync_speed_meter.speed();
+		let verification_speed = self.block_speed_meter.speed();
+		let peers_len = self.peers_tasks.useful_peers().len();
+		self.limits.update(download_speed, verification_speed, peers_len, self.average_block_size);
+		self.limits
+	}
+
 	/// Forget blocks, which have been requested several times, but no one has responded
 	pub fn forget_failed_blocks(&mut self, blocks_to_forget: &[H256]) {
 		if blocks_to_forget.is_empty() {
@@ -876,7 +1433,7 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 	}
 
 	/// Verify and select unknown headers for scheduling
-	fn verify_headers(&mut self, peer_index: PeerIndex, last_known_hash: H256, headers: &[IndexedBlockHeader]) -> BlocksHeadersVerificationResult {
+	fn verify_headers(&mut self, peer_index: PeerIndex, last_known_hash: H256, mut last_known_height: Option<BlockHeight>, headers: &[IndexedBlockHeader]) -> BlocksHeadersVerificationResult {
 		// validate blocks headers before scheduling
 		let mut last_known_hash = &last_known_hash;
 		let mut headers_provider = MessageBlockHeadersProvider::new(&self.chain, self.chain.best_block_header().number);
@@ -908,7 +1465,8 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 
 			// verify header
 			if self.verify_headers {
-				if let Err(error) = self.chain_verifier.verify_block_header(&headers_provider, &header.hash, &header.raw) {
+				let height = last_known_height.map(|height| height + 1);
+				if let Err(error) = self.chain_verifier.verify_block_header(&headers_provider, &header.hash, &header.raw, height) {
 					if self.config.close_connection_on_bad_block {
 						self.peers.misbehaving(peer_index, &format!("Error verifying header {} from `headers`: {:?}", header.hash.to_reversed_str(), error));
 					} else {
@@ -919,12 +1477,83 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 			}
 
 			last_known_hash = &header.hash;
+			last_known_height = last_known_height.map(|height| height + 1);
 			headers_provider.append_header(header.hash.clone(), header.clone());
 		}
 
 		BlocksHeadersVerificationResult::Success
 	}
 
+	/// Returns empty low-work chain, starting at the parent of headers, if headers chain has less than minimum chain work.
+	fn low_work_headers_chain(&mut self, peer_index: PeerIndex, parent_hash: &H256, parent_height: Option<BlockHeight>, headers: &[IndexedBlockHeader], is_last_headers: bool) -> Option<LowWorkHeadersChain> {
+		// low-work chain of the peer has got enough work => it is stored, while requested headers match the counted ones
+		if let Some(mut enough_work_headers) = self.enough_work_headers.remove(&peer_index) {
+			let is_matching = enough_work_headers.verify(parent_hash, headers);
+			match (is_matching, enough_work_headers.is_complete()) {
+				(true, true) => return None,
+				(true, false) if !is_last_headers => {
+					self.enough_work_headers.insert(peer_index, enough_work_headers);
+					return None;
+				},
+				// peer provides other chain => it has to have enough work itself
+				_ => trace!(target: "sync", "Headers from peer#{} do not match its headers chain, which has got enough work", peer_index),
+			}
+		}
+
+		// total work of the fork, connected below the best storage block, is unknown
+		let parent_chainwork = self.chain.block_chainwork(parent_hash.clone().into())?;
+		let chainwork = headers.iter().fold(parent_chainwork, |chainwork, header| chainwork + header.raw.bits.to_work());
+		if chainwork >= self.config.minimum_chain_work {
+			return None;
+		}
+
+		Some(LowWorkHeadersChain {
+			last_hash: parent_hash.clone(),
+			last_height: parent_height,
+			chainwork: parent_chainwork,
+			parent_hash: parent_hash.clone(),
+			headers_len: 0,
+			commitments: VecDeque::new(),
+		})
+	}
+
+	/// Headers of the chain with less than minimum chain work are not stored. Instead, peer is asked for more headers
+	/// && total work of its chain is counted. Once chain has enough work, its headers are requested again && stored.
+	fn on_low_work_headers(&mut self, peer_index: PeerIndex, mut low_work_headers: LowWorkHeadersChain, headers: &[IndexedBlockHeader], is_last_headers: bool) {
+		low_work_headers.last_hash = headers[headers.len() - 1].hash.clone();
+		low_work_headers.last_height = low_work_headers.last_height.map(|height| height + headers.len() as BlockHeight);
+		low_work_headers.chainwork = headers.iter().fold(low_work_headers.chainwork, |chainwork, header| chainwork + header.raw.bits.to_work());
+		low_work_headers.headers_len += headers.len() as BlockHeight;
+		low_work_headers.commitments.push_back((low_work_headers.headers_len, low_work_headers.last_hash.clone()));
+
+		if low_work_headers.chainwork >= self.config.minimum_chain_work {
+			trace!(target: "sync", "Headers chain of peer#{} has got enough work at {}. Requesting headers again",
+				peer_index, low_work_headers.last_hash.to_reversed_str());
+			let block_locator_hashes = self.chain.block_locator_hashes();
+			self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(block_locator_hashes)));
+			self.peers_tasks.on_headers_requested(peer_index);
+			self.enough_work_headers.insert(peer_index, EnoughWorkHeadersChain {
+				last_hash: low_work_headers.parent_hash,
+				headers_len: 0,
+				commitments: low_work_headers.commitments,
+			});
+			return;
+		}
+
+		// peer has no more headers => its chain will never have enough work
+		if is_last_headers {
+			trace!(target: "sync", "Ignoring {} headers from peer#{} - headers chain has not enough work", headers.len(), peer_index);
+			return;
+		}
+
+		// ask peer for headers, following the last low-work header
+		trace!(target: "sync", "Requesting headers after low-work header {} from peer#{}", low_work_headers.last_hash.to_reversed_str(), peer_index);
+		let block_locator_hashes = vec![low_work_headers.last_hash.clone()];
+		self.executor.execute(Task::GetHeaders(peer_index, types::GetHeaders::with_block_locator_hashes(block_locator_hashes)));
+		self.peers_tasks.on_headers_requested(peer_index);
+		self.low_work_headers.insert(peer_index, low_work_headers);
+	}
+
 	/// Process new peer transaction
 	fn process_peer_transaction(&mut self, _peer_index: Option<PeerIndex>, transaction: IndexedTransaction, relay: bool) -> Option<VecDeque<IndexedTransaction>> {
 		match self.try_append_transaction(transaction.clone(), relay) {
@@ -1103,6 +1732,13 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 					}
 				}
 
+				// peer, which has been the first to deliver new best block, is asked to push next blocks to us
+				if !self.state.is_synchronizing() && insert_result.canonized_blocks_hashes.contains(&block_hash) {
+					if let Some(peer_index) = self.verifying_blocks_by_peer.get(&block_hash).cloned() {
+						self.select_high_bandwidth_peer(peer_index);
+					}
+				}
+
 				// awake threads, waiting for this block insertion
 				self.awake_waiting_threads(&block_hash);
 
@@ -1152,13 +1788,14 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 			}
 		}
 
+		// mark failed block as dead end (this branch won't be synchronized)
+		// this is done before forgetting the block, while its header is still known
+		self.chain.mark_dead_end_block(hash);
+
 		// forget for this block and all its children
 		// headers are also removed as they all are invalid
 		self.chain.forget_block_with_children(hash);
 
-		// mark failed block as dead end (this branch won't be synchronized)
-		self.chain.mark_dead_end_block(hash);
-
 		// awake threads, waiting for this block insertion
 		self.awake_waiting_threads(hash);
 
@@ -1238,6 +1875,48 @@ impl<T> SynchronizationClientCore<T> where T: TaskExecutor {
 	}
 }
 
+impl BlocksRequestLimits {
+	/// Resize blocks windows using current download && verification speed (in blocks per second).
+	/// Limits, which depend on unmeasured (zero) speed, are left untouched. Requested and verifying
+	/// windows never shrink below their default sizes: the measured speed is itself limited by the
+	/// windows, so otherwise a single slow period would keep them small forever.
+	/// The only exception is the total size of blocks in windows, estimated using average block size (in bytes).
+	pub fn update(&mut self, download_speed: f64, verification_speed: f64, peers_len: usize, average_block_size: f64) {
+		let window = |speed: f64, seconds: f64, min_size: BlockHeight, max_size: BlockHeight|
+			max(min_size, min(max_size, (speed * seconds) as BlockHeight));
+
+		if download_speed > 0_f64 {
+			// keep all peers busy for the next few seconds
+			self.max_requested_blocks = window(download_speed, REQUESTED_BLOCKS_WINDOW_S, MAX_REQUESTED_BLOCKS, MAX_BLOCKS_WINDOW);
+
+			let peer_speed = download_speed / max(peers_len, 1) as f64;
+			self.max_blocks_in_request = window(peer_speed, BLOCKS_REQUEST_WINDOW_S, MIN_BLOCKS_REQUEST_WINDOW, MAX_BLOCKS_REQUEST_WINDOW);
+			self.min_blocks_in_request = max(MIN_BLOCKS_REQUEST_WINDOW, self.max_blocks_in_request / 4);
+		}
+
+		if verification_speed > 0_f64 {
+			self.max_verifying_blocks = window(verification_speed, VERIFYING_BLOCKS_WINDOW_S, MAX_VERIFYING_BLOCKS, MAX_BLOCKS_WINDOW);
+
+			// when verifier is slower than network, downloaded blocks are piling up in memory
+			// => do not request more blocks than verifier is able to process
+			let verifiable_requested_blocks = window(verification_speed, REQUESTED_BLOCKS_WINDOW_S, MAX_REQUESTED_BLOCKS, MAX_BLOCKS_WINDOW);
+			self.max_requested_blocks = min(self.max_requested_blocks, verifiable_requested_blocks);
+		}
+
+		// large blocks are kept in memory until verified => limit memory, occupied by windows
+		if average_block_size > 0_f64 {
+			let size_window = |max_size: usize| max(MIN_BLOCKS_REQUEST_WINDOW, (max_size as f64 / average_block_size) as BlockHeight);
+			self.max_requested_blocks = min(self.max_requested_blocks, size_window(MAX_REQUESTED_BLOCKS_SIZE));
+			self.max_verifying_blocks = min(self.max_verifying_blocks, size_window(MAX_VERIFYING_BLOCKS_SIZE));
+			self.max_blocks_in_request = min(self.max_blocks_in_request, size_window(MAX_BLOCKS_REQUEST_SIZE));
+			self.min_blocks_in_request = min(self.min_blocks_in_request, self.max_blocks_in_request);
+		}
+
+		// headers are requested in advance to keep larger requested window filled
+		self.max_scheduled_hashes = max(MAX_SCHEDULED_HASHES, self.max_requested_blocks * SCHEDULED_BLOCKS_WINDOWS);
+	}
+}
+
 impl Default for BlocksRequestLimits {
 	fn default() -> Self {
 		BlocksRequestLimits {
@@ -1256,9 +1935,9 @@ pub mod tests {
 
 	use std::sync::Arc;
 	use parking_lot::{Mutex, RwLock};
-	use chain::{Block, Transaction};
+	use chain::{Block, Transaction, IndexedBlock, IndexedBlockHeader};
 	use db::BlockChainDatabase;
-	use message::common::InventoryVector;
+	use message::common::{InventoryVector, InventoryType, BlockTransactions, BlockTransactionsRequest};
 	use message::{Services, types};
 	use miner::MemoryPool;
 	use network::{ConsensusParams, ConsensusFork, Network};
@@ -1271,9 +1950,11 @@ pub mod tests {
 	use synchronization_executor::Task;
 	use synchronization_executor::tests::DummyTaskExecutor;
 	use synchronization_verifier::tests::DummyVerifier;
-	use utils::SynchronizationState;
+	use primitives::bigint::U256;
+	use storage::BlockHeaderProvider;
+	use utils::{AssumeValid, SynchronizationState, build_compact_block};
 	use types::{PeerIndex, StorageRef, SynchronizationStateRef, ClientCoreRef};
-	use super::{Config, SynchronizationClientCore, ClientCore, CoreVerificationSink};
+	use super::{Config, SynchronizationClientCore, ClientCore, CoreVerificationSink, BlocksRequestLimits};
 	use super::super::SyncListener;
 
 	#[derive(Default)]
@@ -1305,6 +1986,10 @@ pub mod tests {
 	}
 
 	fn create_sync(storage: Option<StorageRef>, verifier: Option<DummyVerifier>) -> (Arc<DummyTaskExecutor>, ClientCoreRef<SynchronizationClientCore<DummyTaskExecutor>>, Arc<SynchronizationClient<DummyTaskExecutor, DummyVerifier>>) {
+		create_sync_with_consensus(storage, verifier, ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore))
+	}
+
+	fn create_sync_with_consensus(storage: Option<StorageRef>, verifier: Option<DummyVerifier>, consensus: ConsensusParams) -> (Arc<DummyTaskExecutor>, ClientCoreRef<SynchronizationClientCore<DummyTaskExecutor>>, Arc<SynchronizationClient<DummyTaskExecutor, DummyVerifier>>) {
 		let sync_peers = Arc::new(PeersImpl::default());
 		let storage = match storage {
 			Some(storage) => storage,
@@ -1312,11 +1997,11 @@ pub mod tests {
 		};
 		let sync_state = SynchronizationStateRef::new(SynchronizationState::with_storage(storage.clone()));
 		let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
-		let chain = Chain::new(storage.clone(), ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), memory_pool.clone());
+		let chain = Chain::new(storage.clone(), consensus.clone(), memory_pool.clone());
 		let executor = DummyTaskExecutor::new();
-		let config = Config { close_connection_on_bad_block: true };
+		let config = Config { close_connection_on_bad_block: true, minimum_chain_work: 0.into() };
 
-		let chain_verifier = Arc::new(ChainVerifier::new(storage.clone(), ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore)));
+		let chain_verifier = Arc::new(ChainVerifier::new(storage.clone(), consensus));
 		let client_core = SynchronizationClientCore::new(config, sync_state.clone(), sync_peers.clone(), executor.clone(), chain, chain_verifier.clone());
 		{
 			client_core.lock().set_verify_headers(false);
@@ -1353,7 +2038,11 @@ pub mod tests {
 		sync.on_connect(0);
 		// => ask for inventory
 		let tasks = executor.take_tasks();
-		assert_eq!(tasks, vec![request_block_headers_genesis(0)]);
+		assert_eq!(tasks, vec![
+			request_block_headers_genesis(0),
+			Task::SendCompact(0, types::SendCompact { first: false, second: 2 }),
+			Task::SendCompact(0, types::SendCompact { first: false, second: 1 }),
+		]);
 	}
 
 	#[test]
@@ -1405,6 +2094,86 @@ pub mod tests {
 		assert_eq!(core.lock().information().peers_tasks.active, 0);
 	}
 
+	#[test]
+	fn synchronization_ignores_last_headers_with_not_enough_chainwork() {
+		let (executor, core, sync) = create_sync(None, None);
+		core.lock().set_minimum_chain_work(U256::max_value());
+
+		sync.on_headers(5, vec![test_data::block_h1().block_header.into()]);
+		assert_eq!(executor.take_tasks(), vec![]);
+		assert_eq!(core.lock().information().chain.headers.total, 0);
+		assert_eq!(core.lock().information().chain.scheduled, 0);
+		assert_eq!(core.lock().information().chain.requested, 0);
+	}
+
+	#[test]
+	fn synchronization_accepts_headers_with_enough_chainwork() {
+		let (executor, core, sync) = create_sync(None, None);
+		let block1 = test_data::block_h1();
+		let genesis_chainwork = core.lock().chain().block_chainwork(test_data::genesis().hash().into()).unwrap();
+		core.lock().set_minimum_chain_work(genesis_chainwork + block1.block_header.bits.to_work());
+
+		sync.on_headers(5, vec![block1.block_header.clone().into()]);
+		assert_eq!(executor.take_tasks(), vec![request_block_headers_genesis_and(5, vec![block1.hash()]), request_blocks(5, vec![block1.hash()])]);
+		assert_eq!(core.lock().information().chain.requested, 1);
+	}
+
+	#[test]
+	fn synchronization_requests_low_work_headers_again_when_chain_has_enough_work() {
+		let (executor, core, sync) = create_sync(None, None);
+		let headers: Vec<IndexedBlockHeader> = test_data::build_n_empty_blocks_from_genesis(2 * types::HEADERS_MAX_HEADERS_LEN as u32, 0)
+			.into_iter()
+			.map(|block| block.block_header.into())
+			.collect();
+		let genesis_chainwork = core.lock().chain().block_chainwork(test_data::genesis().hash().into()).unwrap();
+		let chainwork = headers.iter().fold(genesis_chainwork, |chainwork, header| chainwork + header.raw.bits.to_work());
+		core.lock().set_minimum_chain_work(chainwork);
+		let (headers1, headers2) = headers.split_at(types::HEADERS_MAX_HEADERS_LEN);
+
+		// low-work headers are not stored, but following headers are requested
+		sync.on_headers(5, headers1.to_vec());
+		let last_low_work_hash = headers1[headers1.len() - 1].hash.clone();
+		assert_eq!(executor.take_tasks(), vec![Task::GetHeaders(5, types::GetHeaders::with_block_locator_hashes(vec![last_low_work_hash]))]);
+		assert_eq!(core.lock().information().chain.headers.total, 0);
+
+		// headers chain has got enough work => headers are requested again
+		sync.on_headers(5, headers2.to_vec());
+		assert_eq!(executor.take_tasks(), vec![request_block_headers_genesis(5)]);
+		assert_eq!(core.lock().information().chain.headers.total, 0);
+
+		// && stored
+		sync.on_headers(5, headers1.to_vec());
+		sync.on_headers(5, headers2.to_vec());
+		assert_eq!(core.lock().information().chain.headers.total, 2 * types::HEADERS_MAX_HEADERS_LEN as u32);
+	}
+
+	#[test]
+	fn synchronization_checks_work_of_other_chain_when_low_work_headers_are_requested_again() {
+		let (executor, core, sync) = create_sync(None, None);
+		let headers: Vec<IndexedBlockHeader> = test_data::build_n_empty_blocks_from_genesis(2 * types::HEADERS_MAX_HEADERS_LEN as u32, 0)
+			.into_iter()
+			.map(|block| block.block_header.into())
+			.collect();
+		let other_headers: Vec<IndexedBlockHeader> = test_data::build_n_empty_blocks_from_genesis(types::HEADERS_MAX_HEADERS_LEN as u32, 1_000_000)
+			.into_iter()
+			.map(|block| block.block_header.into())
+			.collect();
+		let genesis_chainwork = core.lock().chain().block_chainwork(test_data::genesis().hash().into()).unwrap();
+		let chainwork = headers.iter().fold(genesis_chainwork, |chainwork, header| chainwork + header.raw.bits.to_work());
+		core.lock().set_minimum_chain_work(chainwork);
+		let (headers1, headers2) = headers.split_at(types::HEADERS_MAX_HEADERS_LEN);
+
+		sync.on_headers(5, headers1.to_vec());
+		sync.on_headers(5, headers2.to_vec());
+		assert_eq!(executor.take_tasks().last(), Some(&request_block_headers_genesis(5)));
+
+		// peer provides other low-work chain instead of the counted one => it is not stored
+		sync.on_headers(5, other_headers.clone());
+		let last_other_hash = other_headers[other_headers.len() - 1].hash.clone();
+		assert_eq!(executor.take_tasks(), vec![Task::GetHeaders(5, types::GetHeaders::with_block_locator_hashes(vec![last_other_hash]))]);
+		assert_eq!(core.lock().information().chain.headers.total, 0);
+	}
+
 	#[test]
 	fn synchronization_out_of_order_block_path() {
 		let (_, core, sync) = create_sync(None, None);
@@ -1510,7 +2279,7 @@ pub mod tests {
 		sync.on_block(2, block.clone().into());
 
 		let tasks = executor.take_tasks();
-		assert_eq!(tasks.len(), 6);
+		assert_eq!(tasks.len(), 7);
 		// TODO: when saturating, RequestBlocksHeaders is sent twice to the peer who has supplied last block:
 		// 1) from on_block_verification_success
 		// 2) from switch_to_saturated_state
@@ -1519,6 +2288,7 @@ pub mod tests {
 		assert!(tasks.iter().any(|t| t == &Task::MemoryPool(1)));
 		assert!(tasks.iter().any(|t| t == &Task::MemoryPool(2)));
 		assert!(tasks.iter().any(|t| t == &Task::RelayNewBlock(block.clone().into())));
+		assert!(tasks.iter().any(|t| t == &Task::RelayNewCompactBlock(block.clone().into())));
 	}
 
 	#[test]
@@ -1639,8 +2409,8 @@ pub mod tests {
 		let tasks = { executor.take_tasks() };
 		assert_eq!(tasks, vec![request_block_headers_genesis_and(1, vec![fork1[1].hash(), fork1[0].hash()]),
 			request_blocks(1, vec![fork1[0].hash(), fork1[1].hash()]),
-			// this is possibly wrong, because we have mixed two forks, but this works because we ask for headers on saturating
-			request_block_headers_genesis_and(2, vec![fork2[2].hash(), fork2[1].hash(), fork2[0].hash(), fork1[1].hash(), fork1[0].hash()]),
+			// fork2 has more work => it is the only fork, which is used in block locator
+			request_block_headers_genesis_and(2, vec![fork2[2].hash(), fork2[1].hash(), fork2[0].hash()]),
 			request_blocks(2, vec![fork2[0].hash(), fork2[1].hash(), fork2[2].hash()]),
 		]);
 
@@ -1705,16 +2475,16 @@ pub mod tests {
 		let tasks = { executor.take_tasks() };
 		assert_eq!(tasks, vec![request_block_headers_genesis_and(1, vec![fork1[1].hash(), fork1[0].hash(), common_block.hash()]),
 			request_blocks(1, vec![common_block.hash(), fork1[0].hash(), fork1[1].hash()]),
-			request_block_headers_genesis_and(2, vec![fork2[2].hash(), fork2[1].hash(), fork2[0].hash(), fork1[1].hash(), fork1[0].hash(), common_block.hash()]),
+			request_block_headers_genesis_and(2, vec![fork2[2].hash(), fork2[1].hash(), fork2[0].hash(), common_block.hash()]),
 			request_blocks(2, vec![fork2[0].hash(), fork2[1].hash(), fork2[2].hash()]),
 		]);
 
-		// TODO: this will change from 3 to 4 after longest fork will be stored in the BestHeadersChain
-		// however id doesn't affect sync process, as it is shown below
+		// longest fork is the best headers chain
 		{
 			let mut core = core.lock(); let chain = core.chain();
-			assert_eq!(chain.information().headers.best, 3);
-			assert_eq!(chain.information().headers.total, 3);
+			assert_eq!(chain.information().headers.best, 4);
+			assert_eq!(chain.information().headers.total, 4);
+			assert_eq!(chain.best_block_header().hash, fork2[2].hash());
 		}
 
 		sync.on_block(1, common_block.clone().into());
@@ -1821,6 +2591,187 @@ pub mod tests {
 		assert_eq!(chain.best_block(), best_genesis);
 	}
 
+	#[test]
+	fn blocks_request_limits_are_not_changed_until_speed_is_measured() {
+		let mut limits = BlocksRequestLimits::default();
+		limits.update(0_f64, 0_f64, 8, 0_f64);
+		assert_eq!(limits, BlocksRequestLimits::default());
+
+		let (_, core, _) = create_sync(None, None);
+		assert_eq!(core.lock().information().limits, BlocksRequestLimits::default());
+	}
+
+	#[test]
+	fn blocks_request_limits_follow_download_speed() {
+		// slow network, fast verifier => small requests, but in-flight window keeps its default size
+		let mut limits = BlocksRequestLimits::default();
+		limits.update(4_f64, 1000_f64, 2, 0_f64);
+		assert_eq!(limits.max_requested_blocks, 256);
+		assert_eq!(limits.max_blocks_in_request, 10);
+		assert_eq!(limits.min_blocks_in_request, 2);
+		assert_eq!(limits.max_scheduled_hashes, 4096);
+
+		// fast network => windows grow up to their upper bounds
+		limits.update(10_000_f64, 10_000_f64, 2, 0_f64);
+		assert_eq!(limits.max_requested_blocks, 2048);
+		assert_eq!(limits.max_blocks_in_request, 256);
+		assert_eq!(limits.min_blocks_in_request, 64);
+		assert_eq!(limits.max_verifying_blocks, 2048);
+		assert_eq!(limits.max_scheduled_hashes, 2048 * 16);
+
+		// and shrink back, when network slows down
+		limits.update(4_f64, 10_000_f64, 2, 0_f64);
+		assert_eq!(limits.max_requested_blocks, 256);
+		assert_eq!(limits.max_scheduled_hashes, 4096);
+	}
+
+	#[test]
+	fn blocks_request_limits_do_not_outrun_verifier() {
+		// fast network, slow verifier => in-flight window is limited by verification speed
+		let mut limits = BlocksRequestLimits::default();
+		limits.update(1000_f64, 50_f64, 4, 0_f64);
+		assert_eq!(limits.max_requested_blocks, 500);
+		assert_eq!(limits.max_verifying_blocks, 500);
+		assert_eq!(limits.max_blocks_in_request, 256);
+
+		// very slow verifier => windows are never smaller than their default sizes
+		limits.update(1000_f64, 0.1_f64, 4, 0_f64);
+		assert_eq!(limits.max_requested_blocks, 256);
+		assert_eq!(limits.max_verifying_blocks, 256);
+	}
+
+	#[test]
+	fn blocks_request_limits_are_limited_by_blocks_size() {
+		// fast network and verifier, but 2MB blocks => windows are limited in bytes
+		let mut limits = BlocksRequestLimits::default();
+		limits.update(10_000_f64, 10_000_f64, 2, 2_f64 * 1024_f64 * 1024_f64);
+		assert_eq!(limits.max_requested_blocks, 128);
+		assert_eq!(limits.max_verifying_blocks, 128);
+		assert_eq!(limits.max_blocks_in_request, 16);
+		assert_eq!(limits.min_blocks_in_request, 16);
+		assert_eq!(limits.max_scheduled_hashes, 4096);
+
+		// small blocks => windows are limited by speed only
+		limits.update(10_000_f64, 10_000_f64, 2, 250_f64);
+		assert_eq!(limits.max_requested_blocks, 2048);
+		assert_eq!(limits.max_verifying_blocks, 2048);
+		assert_eq!(limits.max_blocks_in_request, 256);
+	}
+
+	#[test]
+	fn blocks_are_not_requested_beyond_download_window() {
+		let (executor, core, sync) = create_sync(None, None);
+
+		// download window is filled with blocks, waiting for the window base
+		{
+			let mut core = core.lock();
+			let max_requested_blocks = core.information().limits.max_requested_blocks;
+			for nonce in 0..max_requested_blocks {
+				let block = test_data::block_builder().header().parent(H256::from(1)).nonce(nonce).build().build();
+				core.orphaned_blocks_pool().insert_unknown_block(block.into());
+			}
+		}
+
+		let b1 = test_data::block_h1();
+		let b2 = test_data::block_h2();
+		sync.on_headers(1, vec![b1.block_header.clone().into(), b2.block_header.clone().into()]);
+		assert!(!executor.take_tasks().iter().any(|task| match *task {
+			Task::GetData(_, _) => true,
+			_ => false,
+		}));
+		assert_eq!(core.lock().information().chain.scheduled, 2);
+	}
+
+	#[test]
+	fn blocks_of_fork_with_more_work_are_requested() {
+		let (executor, core, sync) = create_sync(None, None);
+
+		let genesis = test_data::genesis();
+		let b1 = test_data::block_builder().header().parent(genesis.hash()).build().build();
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build();
+		let f1 = test_data::block_builder().header().parent(genesis.hash()).nonce(1).build().build();
+		let f2 = test_data::block_builder().header().parent(f1.hash()).nonce(1).build().build();
+		let f3 = test_data::block_builder().header().parent(f2.hash()).nonce(1).build().build();
+
+		sync.on_headers(1, vec![b1.block_header.clone().into(), b2.block_header.clone().into()]);
+		let tasks = executor.take_tasks();
+		assert_eq!(tasks, vec![request_block_headers_genesis_and(1, vec![b2.hash(), b1.hash()]), request_blocks(1, vec![b1.hash(), b2.hash()])]);
+
+		// fork with less work is remembered, but its blocks are not requested
+		sync.on_headers(2, vec![f1.block_header.clone().into(), f2.block_header.clone().into()]);
+		assert!(!executor.take_tasks().iter().any(|task| match *task {
+			Task::GetData(_, _) => true,
+			_ => false,
+		}));
+		assert_eq!(core.lock().information().chain.requested, 2);
+		assert_eq!(core.lock().information().chain.scheduled, 0);
+
+		// when fork gets more work, its blocks are requested instead
+		sync.on_headers(2, vec![f3.block_header.clone().into()]);
+		let tasks = executor.take_tasks();
+		assert!(tasks.contains(&request_blocks(2, vec![f1.hash(), f2.hash(), f3.hash()])));
+		assert_eq!(core.lock().information().chain.requested, 3);
+		assert_eq!(core.lock().information().chain.scheduled, 0);
+		assert_eq!(core.lock().chain().best_block_header().hash, f3.hash());
+	}
+
+	#[test]
+	fn ancestors_of_assumed_valid_block_are_not_script_verified() {
+		let (_, core, sync) = create_sync(None, None);
+		let assume_valid = Arc::new(AssumeValid::new(test_data::block_h2().hash(), 0.into()));
+		core.lock().set_assume_valid(assume_valid.clone());
+
+		let b1 = test_data::block_h1();
+		let b2 = test_data::block_h2();
+		let b3 = test_data::block_h3();
+		sync.on_headers(1, vec![b1.block_header.clone().into(), b2.block_header.clone().into(), b3.block_header.clone().into()]);
+		sync.on_block(1, b1.clone().into());
+		sync.on_block(1, b2.clone().into());
+		sync.on_block(1, b3.clone().into());
+
+		assert!(assume_valid.take_ancestor(&b1.hash()));
+		assert!(assume_valid.take_ancestor(&b2.hash()));
+		assert!(!assume_valid.take_ancestor(&b3.hash()));
+	}
+
+	#[test]
+	fn ancestors_of_assumed_valid_block_outside_of_best_headers_chain_are_not_script_verified() {
+		let (executor, core, sync) = create_sync(None, None);
+		let blocks = test_data::build_n_empty_blocks_from_genesis(types::HEADERS_MAX_HEADERS_LEN as u32 + 10, 0);
+		let headers: Vec<IndexedBlockHeader> = blocks.iter().map(|block| block.block_header.clone().into()).collect();
+		let (headers1, headers2) = headers.split_at(types::HEADERS_MAX_HEADERS_LEN);
+		let assume_valid_hash = headers2[5].hash.clone();
+		let assume_valid = Arc::new(AssumeValid::new(assume_valid_hash.clone(), 0.into()));
+		core.lock().set_assume_valid(assume_valid.clone());
+
+		// headers, following the best headers chain, are requested to find assumed valid block
+		sync.on_headers(1, headers1.to_vec());
+		let last_hash = headers1[headers1.len() - 1].hash.clone();
+		assert!(executor.take_tasks().contains(&Task::GetHeaders(1, types::GetHeaders::with_block_locator_hashes(vec![last_hash]))));
+
+		// but they are not stored
+		sync.on_headers(1, headers2.to_vec());
+		assert_eq!(core.lock().information().chain.headers.total, types::HEADERS_MAX_HEADERS_LEN as u32);
+		assert_eq!(core.lock().chain().block_number(&assume_valid_hash), None);
+
+		sync.on_block(1, blocks[0].clone().into());
+		assert!(assume_valid.take_ancestor(&blocks[0].hash()));
+	}
+
+	#[test]
+	fn blocks_are_script_verified_when_assumed_valid_chain_has_not_enough_work() {
+		let (_, core, sync) = create_sync(None, None);
+		let assume_valid = Arc::new(AssumeValid::new(test_data::block_h2().hash(), U256::max_value()));
+		core.lock().set_assume_valid(assume_valid.clone());
+
+		let b1 = test_data::block_h1();
+		let b2 = test_data::block_h2();
+		sync.on_headers(1, vec![b1.block_header.clone().into(), b2.block_header.clone().into()]);
+		sync.on_block(1, b1.clone().into());
+
+		assert!(!assume_valid.take_ancestor(&b1.hash()));
+	}
+
 	#[test]
 	fn peer_removed_from_sync_after_responding_with_requested_block_notfound() {
 		let (executor, core, sync) = create_sync(None, None);
@@ -2073,10 +3024,11 @@ pub mod tests {
 
 		sync.on_block(2, b2.clone().into());
 
-		// we were in saturated state => block is relayed
+		// we were in saturated state => block is relayed (as compact block before verification)
 		{
 			let tasks = executor.take_tasks();
 			assert_eq!(tasks, vec![
+				Task::RelayNewCompactBlock(b2.clone().into()),
 				request_block_headers_genesis_and(2, vec![b2.hash(), b1.hash(), b0.hash()]),
 				Task::RelayNewBlock(b2.clone().into())
 			]);
@@ -2231,8 +3183,10 @@ pub mod tests {
 		// and peer2 announces 'true' b1
 		sync.on_headers(2, vec![b1.block_header.clone().into(), b2.block_header.clone().into()]);
 
-		// check that all blocks are requested
-		assert_eq!(core.lock().information().chain.requested, 3);
+		// check that all blocks of the branch with more work are requested
+		// (b0 is no longer requested and peer1 is not waiting for it)
+		assert_eq!(core.lock().information().chain.requested, 2);
+		assert!(core.lock().peers_tasks().get_blocks_tasks(1).is_none());
 
 		// forget tasks
 		{ executor.take_tasks(); }
@@ -2407,4 +3361,140 @@ pub mod tests {
 		assert_eq!(data.lock().is_synchronizing, false);
 		assert_eq!(data.lock().best_blocks.len(), 3);
 	}
+
+	fn compact_block_test_data(compact_block_version: u64) -> (IndexedBlock, types::CompactBlock) {
+		let block: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(10).build().build()
+			.transaction()
+				.input().hash(test_data::genesis().transactions[0].hash()).index(0).build()
+				.output().value(20).build()
+				.build()
+			.merkled_header().parent(test_data::genesis().hash()).build()
+			.build()
+			.into();
+		let compact_block = types::CompactBlock {
+			header: build_compact_block(&block, vec![0].into_iter().collect(), compact_block_version == 2),
+		};
+		(block, compact_block)
+	}
+
+	fn create_compact_block_sync(consensus: ConsensusParams, compact_block_version: u64) -> (Arc<DummyTaskExecutor>, ClientCoreRef<SynchronizationClientCore<DummyTaskExecutor>>, Arc<SynchronizationClient<DummyTaskExecutor, DummyVerifier>>) {
+		let (executor, core, sync) = create_sync_with_consensus(None, None, consensus);
+		let peers = core.lock().peers();
+		peers.insert(1, Services::default().with_witness(true), DummyOutboundSyncConnection::new());
+		peers.set_compact_block_version(1, compact_block_version);
+		(executor, core, sync)
+	}
+
+	#[test]
+	fn compact_block_is_reconstructed_from_memory_pool() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore), 1);
+		let (block, compact_block) = compact_block_test_data(1);
+
+		core.lock().chain().insert_verified_transaction(block.transactions[1].clone());
+		sync.on_compact_block(1, compact_block);
+
+		assert_eq!(core.lock().information().chain.stored, 2);
+		assert_eq!(core.lock().information().chain.transactions.transactions_count, 0);
+		assert!(executor.take_tasks().iter().all(|task| match *task {
+			Task::GetBlockTxn(_, _) | Task::GetData(_, _) => false,
+			_ => true,
+		}));
+	}
+
+	#[test]
+	fn compact_block_missing_transactions_are_requested() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore), 1);
+		let (block, compact_block) = compact_block_test_data(1);
+
+		sync.on_compact_block(1, compact_block);
+		assert_eq!(core.lock().information().chain.stored, 1);
+		assert_eq!(executor.take_tasks(), vec![Task::GetBlockTxn(1, types::GetBlockTxn {
+			request: BlockTransactionsRequest {
+				blockhash: block.hash().clone(),
+				indexes: vec![1],
+			},
+		})]);
+
+		sync.on_block_txn(1, types::BlockTxn {
+			request: BlockTransactions {
+				blockhash: block.hash().clone(),
+				transactions: vec![block.transactions[1].raw.clone()],
+			},
+		});
+		assert_eq!(core.lock().information().chain.stored, 2);
+	}
+
+	#[test]
+	fn compact_block_with_duplicate_short_ids_is_requested_as_block() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Regtest, ConsensusFork::BitcoinCore), 1);
+		let (block, mut compact_block) = compact_block_test_data(1);
+		let short_id = compact_block.header.short_ids[0].clone();
+		compact_block.header.short_ids.push(short_id);
+
+		sync.on_compact_block(1, compact_block);
+		assert_eq!(core.lock().information().chain.stored, 1);
+		assert_eq!(executor.take_tasks(), vec![Task::GetData(1, types::GetData::with_inventory(vec![
+			InventoryVector::block(block.hash().clone()),
+		]))]);
+		// peer is not penalized for collision
+		assert!(core.lock().peers.enumerate().contains(&1));
+	}
+
+	#[test]
+	fn compact_block_v1_is_requested_as_block_when_segwit_is_possible() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), 1);
+		let (block, compact_block) = compact_block_test_data(1);
+
+		sync.on_compact_block(1, compact_block);
+		assert_eq!(core.lock().information().chain.stored, 1);
+		assert_eq!(executor.take_tasks(), vec![request_blocks(1, vec![block.hash().clone()])]);
+	}
+
+	#[test]
+	fn compact_block_v2_is_reconstructed_when_segwit_is_possible() {
+		let (_, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), 2);
+		let (block, compact_block) = compact_block_test_data(2);
+
+		core.lock().chain().insert_verified_transaction(block.transactions[1].clone());
+		sync.on_compact_block(1, compact_block);
+		assert_eq!(core.lock().information().chain.stored, 2);
+	}
+
+	#[test]
+	fn fast_peer_is_selected_as_high_bandwidth_peer() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), 2);
+		let (block, compact_block) = compact_block_test_data(2);
+
+		core.lock().chain().insert_verified_transaction(block.transactions[1].clone());
+		sync.on_compact_block(1, compact_block);
+
+		// compact block is relayed before verification, peer is selected after block is verified
+		let tasks = executor.take_tasks();
+		let relay_index = tasks.iter().position(|t| t == &Task::RelayNewCompactBlock(block.clone())).unwrap();
+		let select_index = tasks.iter().position(|t| t == &Task::SendCompact(1, types::SendCompact { first: true, second: 2 })).unwrap();
+		assert!(relay_index < select_index);
+	}
+
+	#[test]
+	fn new_blocks_are_requested_as_compact_blocks() {
+		let (executor, core, sync) = create_compact_block_sync(ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore), 2);
+		let peers = core.lock().peers();
+		peers.insert(2, Services::default().with_witness(true), DummyOutboundSyncConnection::new());
+		peers.insert(3, Services::default().with_witness(true), DummyOutboundSyncConnection::new());
+		peers.set_compact_block_version(3, 1);
+
+		let inventory = vec![InventoryVector::block(test_data::block_h1().hash())];
+		sync.on_inventory(1, types::Inv::with_inventory(inventory.clone()));
+		sync.on_inventory(2, types::Inv::with_inventory(inventory.clone()));
+		sync.on_inventory(3, types::Inv::with_inventory(inventory));
+		assert_eq!(executor.take_tasks(), vec![
+			Task::GetData(1, types::GetData::with_inventory(vec![InventoryVector {
+				inv_type: InventoryType::MessageCompactBlock,
+				hash: test_data::block_h1().hash(),
+			}])),
+			request_blocks(2, vec![test_data::block_h1().hash()]),
+			request_blocks(3, vec![test_data::block_h1().hash()]),
+		]);
+	}
 }
@@ -23,6 +23,12 @@ pub enum Task {
 	GetHeaders(PeerIndex, types::GetHeaders),
 	/// Get memory pool
 	MemoryPool(PeerIndex),
+	/// Ask peer to only relay transactions, matching the bloom filter
+	FilterLoad(PeerIndex, types::FilterLoad),
+	/// Ask peer to announce blocks using cmpctblock messages
+	SendCompact(PeerIndex, types::SendCompact),
+	/// Get missing transactions of compact block
+	GetBlockTxn(PeerIndex, types::GetBlockTxn),
 	/// Send block
 	Block(PeerIndex, IndexedBlock),
 	/// Send merkleblock
@@ -39,12 +45,20 @@ pub enum Task {
 	BlockTxn(PeerIndex, types::BlockTxn),
 	/// Send notfound
 	NotFound(PeerIndex, types::NotFound),
+	/// Send compact filter
+	CFilter(PeerIndex, types::CFilter),
+	/// Send compact filters headers
+	CFHeaders(PeerIndex, types::CFHeaders),
+	/// Send compact filters checkpoints
+	CFCheckpt(PeerIndex, types::CFCheckpt),
 	/// Send inventory
 	Inventory(PeerIndex, types::Inv),
 	/// Send headers
 	Headers(PeerIndex, types::Headers, Option<RequestId>),
 	/// Relay new block to peers
 	RelayNewBlock(IndexedBlock),
+	/// Relay new block to peers, which want to receive compact blocks before block is verified
+	RelayNewCompactBlock(IndexedBlock),
 	/// Relay new transaction to peers
 	RelayNewTransaction(IndexedTransaction, u64),
 }
@@ -93,6 +107,28 @@ impl LocalSynchronizationTaskExecutor {
 		}
 	}
 
+	fn execute_filterload(&self, peer_index: PeerIndex, filterload: types::FilterLoad) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Sending filterload to peer#{}", peer_index);
+			connection.send_filterload(&filterload);
+		}
+	}
+
+	fn execute_send_compact(&self, peer_index: PeerIndex, sendcompact: types::SendCompact) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Sending sendcmpct to peer#{}", peer_index);
+			connection.send_send_compact(&sendcompact);
+		}
+	}
+
+	fn execute_get_block_txn(&self, peer_index: PeerIndex, getblocktxn: types::GetBlockTxn) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Querying {} missing transactions of compact block {} from peer#{}",
+				getblocktxn.request.indexes.len(), getblocktxn.request.blockhash.to_reversed_str(), peer_index);
+			connection.send_get_block_txn(&getblocktxn);
+		}
+	}
+
 	fn execute_block(&self, peer_index: PeerIndex, block: IndexedBlock) {
 		if let Some(connection) = self.peers.connection(peer_index) {
 			trace!(target: "sync", "Sending block {} to peer#{}", block.hash().to_reversed_str(), peer_index);
@@ -116,7 +152,11 @@ impl LocalSynchronizationTaskExecutor {
 		if let Some(connection) = self.peers.connection(peer_index) {
 			trace!(target: "sync", "Sending compact block {} to peer#{}", hash.to_reversed_str(), peer_index);
 			self.peers.hash_known_as(peer_index, hash, KnownHashType::CompactBlock);
-			connection.send_compact_block(&block);
+			// compact blocks version 2 carry witness data
+			match self.peers.compact_block_version(peer_index) {
+				Some(2) => connection.send_witness_compact_block(&block),
+				_ => connection.send_compact_block(&block),
+			}
 		}
 	}
 
@@ -156,7 +196,10 @@ impl LocalSynchronizationTaskExecutor {
 	fn execute_block_txn(&self, peer_index: PeerIndex, blocktxn: types::BlockTxn) {
 		if let Some(connection) = self.peers.connection(peer_index) {
 			trace!(target: "sync", "Sending blocktxn with {} transactions to peer#{}", blocktxn.request.transactions.len(), peer_index);
-			connection.send_block_txn(&blocktxn);
+			match self.peers.compact_block_version(peer_index) {
+				Some(2) => connection.send_witness_block_txn(&blocktxn),
+				_ => connection.send_block_txn(&blocktxn),
+			}
 		}
 	}
 
@@ -167,6 +210,27 @@ impl LocalSynchronizationTaskExecutor {
 		}
 	}
 
+	fn execute_cfilter(&self, peer_index: PeerIndex, cfilter: types::CFilter) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Sending cfilter of block {} to peer#{}", cfilter.block_hash.to_reversed_str(), peer_index);
+			connection.send_cfilter(&cfilter);
+		}
+	}
+
+	fn execute_cfheaders(&self, peer_index: PeerIndex, cfheaders: types::CFHeaders) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Sending cfheaders to peer#{} with {} items", peer_index, cfheaders.filter_hashes.len());
+			connection.send_cfheaders(&cfheaders);
+		}
+	}
+
+	fn execute_cfcheckpt(&self, peer_index: PeerIndex, cfcheckpt: types::CFCheckpt) {
+		if let Some(connection) = self.peers.connection(peer_index) {
+			trace!(target: "sync", "Sending cfcheckpt to peer#{} with {} items", peer_index, cfcheckpt.filter_headers.len());
+			connection.send_cfcheckpt(&cfcheckpt);
+		}
+	}
+
 	fn execute_inventory(&self, peer_index: PeerIndex, inventory: types::Inv) {
 		if let Some(connection) = self.peers.connection(peer_index) {
 			trace!(target: "sync", "Sending inventory to peer#{} with {} items", peer_index, inventory.inventory.len());
@@ -205,6 +269,18 @@ impl LocalSynchronizationTaskExecutor {
 		}
 	}
 
+	fn execute_relay_compact_block(&self, block: IndexedBlock) {
+		// peers could ask for missing transactions before the block is stored
+		self.peers.remember_relayed_block(block.clone());
+		for peer_index in self.peers.enumerate() {
+			if let BlockAnnouncementType::SendCompactBlock = self.peers.filter_block(peer_index, &block) {
+				if let Some(compact_block) = self.peers.build_compact_block(peer_index, &block) {
+					self.execute_compact_block(peer_index, *block.hash(), compact_block);
+				}
+			}
+		}
+	}
+
 	fn execute_relay_transaction(&self, transaction: IndexedTransaction, fee_rate: u64) {
 		for peer_index in self.peers.enumerate() {
 			match self.peers.filter_transaction(peer_index, &transaction, Some(fee_rate)) {
@@ -224,6 +300,9 @@ impl TaskExecutor for LocalSynchronizationTaskExecutor {
 			Task::GetData(peer_index, getdata) => self.execute_getdata(peer_index, getdata),
 			Task::GetHeaders(peer_index, getheaders) => self.execute_getheaders(peer_index, getheaders),
 			Task::MemoryPool(peer_index) => self.execute_memorypool(peer_index),
+			Task::FilterLoad(peer_index, filterload) => self.execute_filterload(peer_index, filterload),
+			Task::SendCompact(peer_index, sendcompact) => self.execute_send_compact(peer_index, sendcompact),
+			Task::GetBlockTxn(peer_index, getblocktxn) => self.execute_get_block_txn(peer_index, getblocktxn),
 			Task::Block(peer_index, block) => self.execute_block(peer_index, block),
 			Task::MerkleBlock(peer_index, hash, block) => self.execute_merkleblock(peer_index, hash, block),
 			Task::CompactBlock(peer_index, hash, block) => self.execute_compact_block(peer_index, hash, block),
@@ -232,9 +311,13 @@ impl TaskExecutor for LocalSynchronizationTaskExecutor {
 			Task::WitnessTransaction(peer_index, transaction) => self.execute_witness_transaction(peer_index, transaction),
 			Task::BlockTxn(peer_index, blocktxn) => self.execute_block_txn(peer_index, blocktxn),
 			Task::NotFound(peer_index, notfound) => self.execute_notfound(peer_index, notfound),
+			Task::CFilter(peer_index, cfilter) => self.execute_cfilter(peer_index, cfilter),
+			Task::CFHeaders(peer_index, cfheaders) => self.execute_cfheaders(peer_index, cfheaders),
+			Task::CFCheckpt(peer_index, cfcheckpt) => self.execute_cfcheckpt(peer_index, cfcheckpt),
 			Task::Inventory(peer_index, inventory) => self.execute_inventory(peer_index, inventory),
 			Task::Headers(peer_index, headers, request_id) => self.execute_headers(peer_index, headers, request_id),
 			Task::RelayNewBlock(block) => self.execute_relay_block(block),
+			Task::RelayNewCompactBlock(block) => self.execute_relay_compact_block(block),
 			Task::RelayNewTransaction(transaction, fee_rate) => self.execute_relay_transaction(transaction, fee_rate),
 		}
 	}
@@ -312,6 +395,44 @@ pub mod tests {
 		assert_eq!(*c2.messages.lock().entry("cmpctblock".to_owned()).or_insert(0), 1);
 	}
 
+	#[test]
+	fn relay_new_witness_block_after_sendcmpct_v2() {
+		let peers = Arc::new(PeersImpl::default());
+		let executor = LocalSynchronizationTaskExecutor::new(peers.clone());
+
+		let c1 = DummyOutboundSyncConnection::new();
+		peers.insert(1, Services::default(), c1.clone());
+		peers.set_compact_block_version(1, 2);
+		peers.set_compact_block_version(1, 1);
+		peers.set_block_announcement_type(1, BlockAnnouncementType::SendCompactBlock);
+
+		executor.execute(Task::RelayNewBlock(test_data::genesis().into()));
+		assert_eq!(*c1.messages.lock().entry("witness_cmpctblock".to_owned()).or_insert(0), 1);
+		assert_eq!(*c1.messages.lock().entry("cmpctblock".to_owned()).or_insert(0), 0);
+	}
+
+	#[test]
+	fn relay_new_compact_block_to_high_bandwidth_peers_only() {
+		let peers = Arc::new(PeersImpl::default());
+		let executor = LocalSynchronizationTaskExecutor::new(peers.clone());
+
+		let c1 = DummyOutboundSyncConnection::new();
+		peers.insert(1, Services::default(), c1.clone());
+		peers.set_compact_block_version(1, 1);
+		peers.set_block_announcement_type(1, BlockAnnouncementType::SendCompactBlock);
+		let c2 = DummyOutboundSyncConnection::new();
+		peers.insert(2, Services::default(), c2.clone());
+
+		executor.execute(Task::RelayNewCompactBlock(test_data::genesis().into()));
+		assert_eq!(*c1.messages.lock().entry("cmpctblock".to_owned()).or_insert(0), 1);
+		assert_eq!(*c2.messages.lock().entry("inventory".to_owned()).or_insert(0), 0);
+
+		// block is not announced again to the high-bandwidth peer after verification
+		executor.execute(Task::RelayNewBlock(test_data::genesis().into()));
+		assert_eq!(*c1.messages.lock().entry("cmpctblock".to_owned()).or_insert(0), 1);
+		assert_eq!(*c2.messages.lock().entry("inventory".to_owned()).or_insert(0), 1);
+	}
+
 	#[test]
 	fn relay_new_block_after_sendheaders() {
 		let peers = Arc::new(PeersImpl::default());
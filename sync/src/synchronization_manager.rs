@@ -5,8 +5,10 @@ use std::time::Duration;
 use parking_lot::{Mutex, Condvar};
 use time::precise_time_s;
 use primitives::hash::H256;
+use light_client::LightClient;
 use synchronization_client_core::{ClientCore, SynchronizationClientCore};
 use synchronization_executor::TaskExecutor;
+use synchronization_chain::BlockState;
 use synchronization_peers_tasks::{PeersTasks, TrustLevel};
 use utils::{OrphanBlocksPool, OrphanTransactionsPool};
 use types::PeersRef;
@@ -21,6 +23,10 @@ const DEFAULT_NEW_PEER_HEADERS_FAILURE_INTERVAL_MS: u32 = 5 * 1000;
 const DEFAULT_TRUSTED_PEER_BLOCK_FAILURE_INTERVAL_MS: u32 = 20 * 1000;
 /// Response time before getting headers to decrease peer score
 const DEFAULT_TRUSTED_PEER_HEADERS_FAILURE_INTERVAL_MS: u32 = 20 * 1000;
+/// Time the block at the base of blocks download window may stay requested, while later blocks are waiting for it
+const DEFAULT_BLOCK_STALLING_TIMEOUT_MS: u32 = 2 * 1000;
+/// Max time the block at the base of blocks download window may stay requested (timeout grows on every stall)
+const DEFAULT_MAX_BLOCK_STALLING_TIMEOUT_MS: u32 = 64 * 1000;
 /// Unknown orphan block removal time
 const DEFAULT_UNKNOWN_BLOCK_REMOVAL_TIME_MS: u32 = 20 * 60 * 1000;
 /// Maximal number of orphaned blocks
@@ -29,6 +35,8 @@ const DEFAULT_UNKNOWN_BLOCKS_MAX_LEN: usize = 16;
 const DEFAULT_ORPHAN_TRANSACTION_REMOVAL_TIME_MS: u32 = 10 * 60 * 1000;
 /// Maximal number of orphaned transactions
 const DEFAULT_ORPHAN_TRANSACTIONS_MAX_LEN: usize = 10000;
+/// Response time before requesting merkle block from another peer
+const DEFAULT_MERKLE_BLOCK_FAILURE_INTERVAL_MS: u32 = 20 * 1000;
 
 /// Synchronization management worker
 pub struct ManagementWorker {
@@ -42,42 +50,14 @@ pub struct ManagementWorker {
 
 impl ManagementWorker {
 	pub fn new<T: TaskExecutor>(core: Weak<Mutex<SynchronizationClientCore<T>>>) -> Self {
-		let is_stopping = Arc::new(Mutex::new(false));
-		let stopping_event = Arc::new(Condvar::new());
-		ManagementWorker {
-			is_stopping: is_stopping.clone(),
-			stopping_event: stopping_event.clone(),
-			thread: Some(thread::Builder::new()
-				.name("Sync management thread".to_string())
-				.spawn(move || ManagementWorker::worker_proc(is_stopping, stopping_event, core))
-				.expect("Error creating management thread"))
-		}
-	}
-
-	fn worker_proc<T: TaskExecutor>(is_stopping: Arc<Mutex<bool>>, stopping_event: Arc<Condvar>, core: Weak<Mutex<SynchronizationClientCore<T>>>) {
 		let peers_config = ManagePeersConfig::default();
 		let unknown_config = ManageUnknownBlocksConfig::default();
 		let orphan_config = ManageOrphanTransactionsConfig::default();
 
-		loop {
-			let mut lock = is_stopping.lock();
-			if *lock {
-				break;
-			}
-
-			if !stopping_event.wait_for(&mut lock, Duration::from_millis(MANAGEMENT_INTERVAL_MS)).timed_out() {
-				if *lock {
-					break;
-				}
-
-				// spurious wakeup?
-				continue;
-			}
-			drop(lock);
-
+		ManagementWorker::spawn(move || {
 			// if core is dropped => stop thread
 			let core = match core.upgrade() {
-				None => break,
+				None => return false,
 				Some(core) => core,
 			};
 
@@ -86,7 +66,10 @@ impl ManagementWorker {
 			core.print_synchronization_information();
 			// execute management tasks if not saturated
 			if core.state().is_synchronizing() || core.state().is_nearly_saturated() {
-				let (blocks_to_request, blocks_to_forget) = manage_synchronization_peers_blocks(&peers_config, core.peers(), core.peers_tasks());
+				let (mut blocks_to_request, blocks_to_forget) = manage_synchronization_peers_blocks(&peers_config, core.peers(), core.peers_tasks());
+				let window_base = core.chain().best_n_of_blocks_state(BlockState::Requested, 1).pop();
+				let waiting_blocks = core.orphaned_blocks_pool().len();
+				blocks_to_request.extend(manage_stalling_peer(&peers_config, core.peers_tasks(), window_base, waiting_blocks));
 				core.forget_failed_blocks(&blocks_to_forget);
 				core.execute_synchronization_tasks(
 					if blocks_to_request.is_empty() { None } else { Some(blocks_to_request) },
@@ -102,6 +85,60 @@ impl ManagementWorker {
 						core.chain().forget_block(&orphan_to_remove);
 					}
 				}
+				// re-request blocks below the loaded utxo snapshot, which have not been received in time
+				core.execute_utxo_snapshot_validation_tasks();
+			}
+
+			true
+		})
+	}
+
+	pub fn with_light_client<T: TaskExecutor>(client: Weak<LightClient<T>>) -> Self {
+		ManagementWorker::spawn(move || {
+			// if client is dropped => stop thread
+			let client = match client.upgrade() {
+				None => return false,
+				Some(client) => client,
+			};
+
+			client.manage_requested_blocks(DEFAULT_MERKLE_BLOCK_FAILURE_INTERVAL_MS);
+
+			true
+		})
+	}
+
+	fn spawn<F>(manage: F) -> Self where F: FnMut() -> bool + Send + 'static {
+		let is_stopping = Arc::new(Mutex::new(false));
+		let stopping_event = Arc::new(Condvar::new());
+		ManagementWorker {
+			is_stopping: is_stopping.clone(),
+			stopping_event: stopping_event.clone(),
+			thread: Some(thread::Builder::new()
+				.name("Sync management thread".to_string())
+				.spawn(move || ManagementWorker::worker_proc(is_stopping, stopping_event, manage))
+				.expect("Error creating management thread"))
+		}
+	}
+
+	fn worker_proc<F>(is_stopping: Arc<Mutex<bool>>, stopping_event: Arc<Condvar>, mut manage: F) where F: FnMut() -> bool {
+		loop {
+			let mut lock = is_stopping.lock();
+			if *lock {
+				break;
+			}
+
+			if !stopping_event.wait_for(&mut lock, Duration::from_millis(MANAGEMENT_INTERVAL_MS)).timed_out() {
+				if *lock {
+					break;
+				}
+
+				// spurious wakeup?
+				continue;
+			}
+			drop(lock);
+
+			if !manage() {
+				break;
 			}
 		}
 
@@ -129,6 +166,10 @@ pub struct ManagePeersConfig {
 	pub trusted_block_failure_interval_ms: u32,
 	/// Time interval (in milliseconds) to wait headers from the peer before penalizing && reexecuting tasks
 	pub trusted_headers_failure_interval_ms: u32,
+	/// Time interval (in milliseconds) to wait for the block at the base of download window before reassigning peer tasks
+	pub block_stalling_timeout_ms: u32,
+	/// Max time interval (in milliseconds) to wait for the block at the base of download window before reassigning peer tasks
+	pub max_block_stalling_timeout_ms: u32,
 }
 
 impl Default for ManagePeersConfig {
@@ -138,6 +179,8 @@ impl Default for ManagePeersConfig {
 			new_headers_failure_interval_ms: DEFAULT_NEW_PEER_HEADERS_FAILURE_INTERVAL_MS,
 			trusted_block_failure_interval_ms: DEFAULT_TRUSTED_PEER_BLOCK_FAILURE_INTERVAL_MS,
 			trusted_headers_failure_interval_ms: DEFAULT_TRUSTED_PEER_HEADERS_FAILURE_INTERVAL_MS,
+			block_stalling_timeout_ms: DEFAULT_BLOCK_STALLING_TIMEOUT_MS,
+			max_block_stalling_timeout_ms: DEFAULT_MAX_BLOCK_STALLING_TIMEOUT_MS,
 		}
 	}
 }
@@ -213,6 +256,48 @@ pub fn manage_synchronization_peers_blocks(config: &ManagePeersConfig, peers: Pe
 	(blocks_to_request, blocks_to_forget)
 }
 
+/// Manage peer, which is stalling blocks download window: the block at the base of the window
+/// (`window_base`) is not received in time, while `waiting_blocks` later blocks are waiting for it.
+/// Returns blocks that must be requested from other peers, starting with the window base.
+pub fn manage_stalling_peer(config: &ManagePeersConfig, peers_tasks: &mut PeersTasks, window_base: Option<H256>, waiting_blocks: usize) -> Vec<H256> {
+	// if no blocks are waiting for the window base => window is not stalled
+	let window_base = match window_base {
+		Some(window_base) if waiting_blocks != 0 => window_base,
+		_ => return Vec::new(),
+	};
+
+	// check if block has not been received within given time
+	let (stalling_peer_index, timestamp) = match peers_tasks.get_block_request(&window_base) {
+		Some(block_request) => block_request,
+		None => return Vec::new(),
+	};
+	// stalling timeout grows every time the window is stalled, so that slow network does not cause endless reassignments
+	let time_diff = precise_time_s() - timestamp;
+	let stalling_timeout_ms = (config.block_stalling_timeout_ms as f64 * peers_tasks.stalling_timeout_multiplier())
+		.min(config.max_block_stalling_timeout_ms as f64);
+	if time_diff <= stalling_timeout_ms / 1000f64 {
+		return Vec::new();
+	}
+
+	// if there are no other peers to ask => wait
+	if peers_tasks.useful_peers().len() < 2 {
+		return Vec::new();
+	}
+
+	warn!(target: "sync", "Peer#{} is stalling blocks download window for {:.2} seconds. Reassigning its blocks.", stalling_peer_index, time_diff);
+	// peer is not disconnected, but other peers are asked for blocks first
+	let mut blocks_to_request = peers_tasks.on_peer_stalling(stalling_peer_index);
+	if let Some(stats) = peers_tasks.get_peer_stats(stalling_peer_index) {
+		trace!(target: "sync", "Peer#{} has stalled {} times, {} blocks reassigned.", stalling_peer_index, stats.stalls(), stats.reassigned_blocks());
+	}
+
+	// fastest peer is asked for the first block => window base goes first
+	if let Some(position) = blocks_to_request.iter().position(|h| *h == window_base) {
+		blocks_to_request.swap(0, position);
+	}
+	blocks_to_request
+}
+
 /// Manage stalled synchronization peers headers tasks
 pub fn manage_synchronization_peers_headers(config: &ManagePeersConfig, peers: PeersRef, peers_tasks: &mut PeersTasks) {
 	let now = precise_time_s();
@@ -316,7 +401,7 @@ mod tests {
 	use synchronization_peers::PeersImpl;
 	use synchronization_peers_tasks::{PeersTasks, TrustLevel};
 	use super::{ManagePeersConfig, ManageUnknownBlocksConfig, ManageOrphanTransactionsConfig, manage_synchronization_peers_blocks,
-		manage_unknown_orphaned_blocks, manage_orphaned_transactions};
+		manage_stalling_peer, manage_unknown_orphaned_blocks, manage_orphaned_transactions};
 	use utils::{OrphanBlocksPool, OrphanTransactionsPool};
 
 	#[test]
@@ -350,6 +435,85 @@ mod tests {
 		assert!(idle_peers.contains(&2));
 	}
 
+	#[test]
+	fn manage_stalling_peer_not_stalled() {
+		let config = ManagePeersConfig { block_stalling_timeout_ms: 0, ..Default::default() };
+		let mut peers = PeersTasks::default();
+		peers.useful_peer(2);
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+
+		// no blocks are waiting for the window base
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 0), vec![]);
+		// window base is not requested
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(2)), 1), vec![]);
+		// timeout is not yet reached
+		let config = ManagePeersConfig { block_stalling_timeout_ms: 60 * 1000, ..Default::default() };
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 1), vec![]);
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 0);
+	}
+
+	#[test]
+	fn manage_stalling_peer_blocks_reassigned() {
+		use std::thread::sleep;
+		use std::time::Duration;
+		let config = ManagePeersConfig { block_stalling_timeout_ms: 0, ..Default::default() };
+		let mut peers = PeersTasks::default();
+		peers.useful_peer(2);
+		peers.on_blocks_requested(1, &vec![H256::from(1), H256::from(2), H256::from(3)]);
+		sleep(Duration::from_millis(1));
+
+		let blocks_to_request = manage_stalling_peer(&config, &mut peers, Some(H256::from(2)), 1);
+		assert_eq!(blocks_to_request.len(), 3);
+		assert_eq!(blocks_to_request[0], H256::from(2));
+		assert!(peers.get_blocks_tasks(1).is_none());
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 1);
+		assert_eq!(peers.get_peer_stats(1).unwrap().reassigned_blocks(), 3);
+
+		// other peer is asked first
+		let mut peers_for_blocks = vec![1, 2];
+		peers.sort_peers_for_blocks(&mut peers_for_blocks);
+		assert_eq!(peers_for_blocks, vec![2, 1]);
+	}
+
+	#[test]
+	fn manage_stalling_peer_single_peer() {
+		use std::thread::sleep;
+		use std::time::Duration;
+		let config = ManagePeersConfig { block_stalling_timeout_ms: 0, ..Default::default() };
+		let mut peers = PeersTasks::default();
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		sleep(Duration::from_millis(1));
+
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 1), vec![]);
+		assert_eq!(peers.get_blocks_tasks(1).map(|t| t.len()), Some(1));
+	}
+
+	#[test]
+	fn manage_stalling_peer_timeout_grows_with_stalls() {
+		use std::thread::sleep;
+		use std::time::Duration;
+		let config = ManagePeersConfig { block_stalling_timeout_ms: 10, max_block_stalling_timeout_ms: 60 * 1000, ..Default::default() };
+		let mut peers = PeersTasks::default();
+		peers.useful_peer(2);
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		sleep(Duration::from_millis(20));
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 1), vec![H256::from(1)]);
+
+		// after many stalls, the window base is waited for longer
+		for _ in 0..10 {
+			peers.on_blocks_requested(1, &vec![H256::from(1)]);
+			peers.on_peer_stalling(1);
+		}
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		sleep(Duration::from_millis(20));
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 1), vec![]);
+
+		// but never longer than max timeout
+		let config = ManagePeersConfig { max_block_stalling_timeout_ms: 1, ..config };
+		assert_eq!(manage_stalling_peer(&config, &mut peers, Some(H256::from(1)), 1), vec![H256::from(1)]);
+		assert!(peers.all_peers().contains(&1));
+	}
+
 	#[test]
 	fn manage_unknown_blocks_good() {
 		let config = ManageUnknownBlocksConfig { removal_time_ms: 1000, max_number: 100 };
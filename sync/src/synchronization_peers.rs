@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use parking_lot::RwLock;
 use chain::{IndexedBlock, IndexedTransaction};
 use message::{types, Services};
@@ -7,6 +7,11 @@ use primitives::hash::H256;
 use types::PeerIndex;
 use utils::{KnownHashType, ConnectionFilter};
 
+/// Maximal number of peers, which are asked to push new blocks to us using cmpctblock messages
+const MAX_HIGH_BANDWIDTH_PEERS: usize = 3;
+/// Number of the latest blocks, relayed before verification, which transactions are served from memory
+const MAX_RELAYED_BLOCKS: usize = 3;
+
 /// Block announcement type
 #[derive(Debug, Clone, Copy)]
 pub enum BlockAnnouncementType {
@@ -29,6 +34,15 @@ pub enum TransactionAnnouncementType {
 	DoNotAnnounce,
 }
 
+/// Change of the high-bandwidth compact blocks peers set
+#[derive(Debug, Default, PartialEq)]
+pub struct HighBandwidthPeersChange {
+	/// Peer, which has been selected as high-bandwidth peer
+	pub selected: Option<PeerIndex>,
+	/// Peer, which is not high-bandwidth peer anymore
+	pub deselected: Option<PeerIndex>,
+}
+
 /// `merkleblock` build artefacts
 #[derive(Debug, PartialEq)]
 pub struct MerkleBlockArtefacts {
@@ -82,14 +96,27 @@ pub trait PeersFilters {
 	fn build_compact_block(&self, peer_index: PeerIndex, block: &IndexedBlock) -> Option<types::CompactBlock>;
 	/// Build merkle block using filter for given peer
 	fn build_merkle_block(&self, peer_index: PeerIndex, block: &IndexedBlock) -> Option<MerkleBlockArtefacts>;
+	/// Remember block, which is relayed as compact block before it is verified and stored
+	fn remember_relayed_block(&self, block: IndexedBlock);
+	/// Get recently relayed block, so that 'getblocktxn' could be served before the block is stored
+	fn relayed_block(&self, hash: &H256) -> Option<IndexedBlock>;
 }
 
 /// Options for peers connections
 pub trait PeersOptions {
 	/// Is node supporting SegWit?
 	fn is_segwit_enabled(&self, peer_index: PeerIndex) -> bool;
+	/// Version of compact blocks, negotiated with the node
+	fn compact_block_version(&self, peer_index: PeerIndex) -> Option<u64>;
+	/// Negotiate version of compact blocks. The first supported version, announced by node, is used.
+	fn set_compact_block_version(&self, peer_index: PeerIndex, version: u64);
+	/// Select node, which has delivered new block to us, as high-bandwidth compact blocks peer.
+	/// The least recently selected node is deselected, when there are too many high-bandwidth nodes.
+	fn select_high_bandwidth_peer(&self, peer_index: PeerIndex) -> HighBandwidthPeersChange;
 	/// Set up new block announcement type for the connection
 	fn set_block_announcement_type(&self, peer_index: PeerIndex, announcement_type: BlockAnnouncementType);
+	/// Stop announcing new blocks using cmpctblock messages, if they were used for the connection
+	fn reset_compact_block_announcement(&self, peer_index: PeerIndex);
 	/// Set up new transaction announcement type for the connection
 	fn set_transaction_announcement_type(&self, peer_index: PeerIndex, announcement_type: TransactionAnnouncementType);
 }
@@ -106,6 +133,8 @@ struct Peer {
 	pub block_announcement_type: BlockAnnouncementType,
 	/// Transaction announcement type
 	pub transaction_announcement_type: TransactionAnnouncementType,
+	/// Version of compact blocks, negotiated with peer
+	pub compact_block_version: Option<u64>,
 }
 
 /// Default implementation of connectd peers container
@@ -114,6 +143,10 @@ pub struct PeersImpl {
 	/// All connected peers. Most of times this field is accessed, it is accessed in read mode.
 	/// So this lock shouldn't be a performance problem.
 	peers: RwLock<HashMap<PeerIndex, Peer>>,
+	/// High-bandwidth compact blocks peers, the most recently selected last.
+	high_bandwidth_peers: RwLock<VecDeque<PeerIndex>>,
+	/// Blocks, recently relayed before verification, the most recently relayed last.
+	relayed_blocks: RwLock<VecDeque<IndexedBlock>>,
 }
 
 impl Peer {
@@ -124,6 +157,7 @@ impl Peer {
 			filter: ConnectionFilter::default(),
 			block_announcement_type: BlockAnnouncementType::SendInventory,
 			transaction_announcement_type: TransactionAnnouncementType::SendInventory,
+			compact_block_version: None,
 		}
 	}
 }
@@ -237,13 +271,31 @@ impl PeersFilters for PeersImpl {
 
 	fn build_compact_block(&self, peer_index: PeerIndex, block: &IndexedBlock) -> Option<types::CompactBlock> {
 		self.peers.read().get(&peer_index)
-			.map(|peer| peer.filter.build_compact_block(block))
+			.map(|peer| peer.filter.build_compact_block(block, peer.compact_block_version == Some(2)))
 	}
 
 	fn build_merkle_block(&self, peer_index: PeerIndex, block: &IndexedBlock) -> Option<MerkleBlockArtefacts> {
 		self.peers.read().get(&peer_index)
 			.and_then(|peer| peer.filter.build_merkle_block(block))
 	}
+
+	fn remember_relayed_block(&self, block: IndexedBlock) {
+		let mut relayed_blocks = self.relayed_blocks.write();
+		if relayed_blocks.iter().any(|relayed_block| relayed_block.hash() == block.hash()) {
+			return;
+		}
+
+		relayed_blocks.push_back(block);
+		if relayed_blocks.len() > MAX_RELAYED_BLOCKS {
+			relayed_blocks.pop_front();
+		}
+	}
+
+	fn relayed_block(&self, hash: &H256) -> Option<IndexedBlock> {
+		self.relayed_blocks.read().iter()
+			.find(|block| block.hash() == hash)
+			.cloned()
+	}
 }
 
 impl PeersOptions for PeersImpl {
@@ -254,15 +306,91 @@ impl PeersOptions for PeersImpl {
 			.unwrap_or_default()
 	}
 
+	fn compact_block_version(&self, peer_index: PeerIndex) -> Option<u64> {
+		self.peers.read()
+			.get(&peer_index)
+			.and_then(|peer| peer.compact_block_version)
+	}
+
+	fn set_compact_block_version(&self, peer_index: PeerIndex, version: u64) {
+		if let Some(peer) = self.peers.write().get_mut(&peer_index) {
+			if peer.compact_block_version.is_none() {
+				peer.compact_block_version = Some(version);
+			}
+		}
+	}
+
+	fn select_high_bandwidth_peer(&self, peer_index: PeerIndex) -> HighBandwidthPeersChange {
+		let peers = self.peers.read();
+		let mut high_bandwidth_peers = self.high_bandwidth_peers.write();
+		// forget disconnected peers
+		high_bandwidth_peers.retain(|peer_index| peers.contains_key(peer_index));
+
+		let mut change = HighBandwidthPeersChange::default();
+		match high_bandwidth_peers.iter().position(|selected_peer_index| *selected_peer_index == peer_index) {
+			Some(position) => {
+				high_bandwidth_peers.remove(position);
+			},
+			None if peers.contains_key(&peer_index) => {
+				change.selected = Some(peer_index);
+			},
+			None => return change,
+		}
+
+		high_bandwidth_peers.push_back(peer_index);
+		if high_bandwidth_peers.len() > MAX_HIGH_BANDWIDTH_PEERS {
+			change.deselected = high_bandwidth_peers.pop_front();
+		}
+		change
+	}
+
 	fn set_block_announcement_type(&self, peer_index: PeerIndex, announcement_type: BlockAnnouncementType) {
 		if let Some(peer) = self.peers.write().get_mut(&peer_index) {
 			peer.block_announcement_type = announcement_type;
 		}
 	}
 
+	fn reset_compact_block_announcement(&self, peer_index: PeerIndex) {
+		if let Some(peer) = self.peers.write().get_mut(&peer_index) {
+			if let BlockAnnouncementType::SendCompactBlock = peer.block_announcement_type {
+				peer.block_announcement_type = BlockAnnouncementType::SendInventory;
+			}
+		}
+	}
+
 	fn set_transaction_announcement_type(&self, peer_index: PeerIndex, announcement_type: TransactionAnnouncementType) {
 		if let Some(peer) = self.peers.write().get_mut(&peer_index) {
 			peer.transaction_announcement_type = announcement_type;
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use message::Services;
+	use inbound_connection::tests::DummyOutboundSyncConnection;
+	use super::{PeersImpl, PeersContainer, PeersOptions, HighBandwidthPeersChange};
+
+	#[test]
+	fn select_high_bandwidth_peers() {
+		let peers = Arc::new(PeersImpl::default());
+		for peer_index in 1..6 {
+			peers.insert(peer_index, Services::default(), DummyOutboundSyncConnection::new());
+		}
+
+		for peer_index in 1..4 {
+			assert_eq!(peers.select_high_bandwidth_peer(peer_index), HighBandwidthPeersChange { selected: Some(peer_index), deselected: None });
+		}
+
+		// peer#1 becomes the most recently selected peer => peer#2 is deselected
+		assert_eq!(peers.select_high_bandwidth_peer(1), HighBandwidthPeersChange::default());
+		assert_eq!(peers.select_high_bandwidth_peer(4), HighBandwidthPeersChange { selected: Some(4), deselected: Some(2) });
+
+		// disconnected peers are forgotten
+		peers.remove(3);
+		assert_eq!(peers.select_high_bandwidth_peer(5), HighBandwidthPeersChange { selected: Some(5), deselected: None });
+		assert_eq!(peers.select_high_bandwidth_peer(3), HighBandwidthPeersChange::default());
+		assert_eq!(peers.select_high_bandwidth_peer(2), HighBandwidthPeersChange { selected: Some(2), deselected: Some(1) });
+	}
+}
@@ -11,6 +11,12 @@ use utils::AverageSpeedMeter;
 const MAX_PEER_FAILURES: usize = 4;
 /// Max blocks failures # before forgetiing this block and restarting sync
 const MAX_BLOCKS_FAILURES: usize = 6;
+/// Blocks stalling timeout is multiplied by this factor every time the download window is stalled
+const STALLING_TIMEOUT_GROWTH: f64 = 2_f64;
+/// Blocks stalling timeout is multiplied by this factor every time the requested block is received
+const STALLING_TIMEOUT_DECAY: f64 = 0.85_f64;
+/// Max blocks stalling timeout multiplier
+const MAX_STALLING_TIMEOUT_MULTIPLIER: f64 = 32_f64;
 /// Number of blocks to inspect while calculating average response time
 const BLOCKS_TO_INSPECT: usize = 32;
 
@@ -41,10 +47,14 @@ pub struct PeersTasks {
 	headers_requests: LinkedHashMap<PeerIndex, HeadersRequest>,
 	/// Pending blocks requests sent to peers
 	blocks_requests: LinkedHashMap<PeerIndex, BlocksRequest>,
+	/// Last peer asked for the block && time of this request
+	blocks_owners: HashMap<H256, (PeerIndex, f64)>,
 	/// Peers statistics
 	stats: HashMap<PeerIndex, PeerStats>,
 	/// Blocks statistics
 	blocks_stats: HashMap<H256, BlockStats>,
+	/// Blocks stalling timeout multiplier (values below 1 are treated as 1)
+	stalling_timeout_multiplier: f64,
 }
 
 /// Pending headers request
@@ -81,6 +91,10 @@ pub struct PeerStats {
 	speed: AverageSpeedMeter,
 	/// Peer trust level.
 	trust: TrustLevel,
+	/// Number of times peer has stalled blocks download window
+	stalls: usize,
+	/// Number of blocks that have been reassigned to other peers because of stalls
+	reassigned_blocks: usize,
 }
 
 /// Block statistics
@@ -125,6 +139,13 @@ impl PeersTasks {
 	/// Sort peers for blocks request
 	pub fn sort_peers_for_blocks(&self, peers: &mut Vec<PeerIndex>) {
 		peers.sort_by(|left, right| {
+			// less stalls => better
+			let left_stalls = self.stats.get(left).map(|s| s.stalls).unwrap_or(0);
+			let right_stalls = self.stats.get(right).map(|s| s.stalls).unwrap_or(0);
+			if left_stalls != right_stalls {
+				return left_stalls.cmp(&right_stalls);
+			}
+
 			let left_speed = self.stats.get(left).map(|s| s.speed.speed()).unwrap_or(0f64);
 			let right_speed = self.stats.get(right).map(|s| s.speed.speed()).unwrap_or(0f64);
 			// larger speed => better
@@ -149,6 +170,17 @@ impl PeersTasks {
 			.map(|br| &br.blocks)
 	}
 
+	/// Get peer, which has been asked for the block last && the time of this request
+	pub fn get_block_request(&self, block_hash: &H256) -> Option<(PeerIndex, f64)> {
+		self.blocks_owners.get(block_hash).cloned()
+	}
+
+	/// Get multiplier of the blocks stalling timeout. It grows every time the download window
+	/// is stalled and decays while requested blocks are received.
+	pub fn stalling_timeout_multiplier(&self) -> f64 {
+		if self.stalling_timeout_multiplier < 1_f64 { 1_f64 } else { self.stalling_timeout_multiplier }
+	}
+
 	/// Get peer statistics
 	pub fn get_peer_stats(&self, peer_index: PeerIndex) -> Option<&PeerStats> {
 		self.stats.get(&peer_index)
@@ -196,6 +228,7 @@ impl PeersTasks {
 		self.idle_for_blocks.remove(&peer_index);
 		self.headers_requests.remove(&peer_index);
 		self.blocks_requests.remove(&peer_index);
+		self.blocks_owners.retain(|_, &mut (owner, _)| owner != peer_index);
 		self.stats.remove(&peer_index);
 	}
 
@@ -203,6 +236,7 @@ impl PeersTasks {
 	pub fn on_block_received(&mut self, peer_index: PeerIndex, block_hash: &H256) {
 		// block received => reset failures
 		self.blocks_stats.remove(block_hash);
+		self.blocks_owners.remove(block_hash);
 
 		let is_last_requested_block_received = if let Some(blocks_request) = self.blocks_requests.get_mut(&peer_index) {
 			// if block hasn't been requested => do nothing
@@ -222,9 +256,14 @@ impl PeersTasks {
 				if br.failures > 0 {
 					br.failures -= 1;
 				}
+				// all requested blocks are received in time => forgive one stall
+				if is_last_requested_block_received && br.stalls > 0 {
+					br.stalls -= 1;
+				}
 				br.trust = TrustLevel::Trusted;
 				br.speed.checkpoint()
 			});
+		self.stalling_timeout_multiplier = self.stalling_timeout_multiplier() * STALLING_TIMEOUT_DECAY;
 
 		// if it hasn't been last requested block => just return
 		if !is_last_requested_block_received {
@@ -271,11 +310,35 @@ impl PeersTasks {
 		self.blocks_requests.get_mut(&peer_index)
 			.expect("inserted one line above")
 			.blocks.extend(blocks_hashes.iter().cloned());
+		let now = precise_time_s();
+		self.blocks_owners.extend(blocks_hashes.iter().map(|h| (h.clone(), (peer_index, now))));
 
 		// no more requested blocks => pause requests speed meter
 		self.stats.get_mut(&peer_index).map(|br| br.speed.start());
 	}
 
+	/// Blocks are no longer expected from peers (they have left the best headers chain).
+	pub fn forget_blocks_requests(&mut self, blocks_hashes: &[H256]) {
+		let mut idle_peers = Vec::new();
+		for (peer_index, blocks_request) in self.blocks_requests.iter_mut() {
+			for hash in blocks_hashes {
+				blocks_request.blocks.remove(hash);
+			}
+			if blocks_request.blocks.is_empty() {
+				idle_peers.push(*peer_index);
+			}
+		}
+		for peer_index in idle_peers {
+			self.blocks_requests.remove(&peer_index);
+			self.idle_for_blocks.insert(peer_index);
+			self.stats.get_mut(&peer_index).map(|br| br.speed.stop());
+		}
+		for hash in blocks_hashes {
+			self.blocks_owners.remove(hash);
+			self.blocks_stats.remove(hash);
+		}
+	}
+
 	/// Headers hashave been requested from peer.
 	pub fn on_headers_requested(&mut self, peer_index: PeerIndex) {
 		if !self.all.contains(&peer_index) {
@@ -322,6 +385,23 @@ impl PeersTasks {
 		self.penalize(peer_index)
 	}
 
+	/// Peer is stalling blocks download window. Returns blocks to request from other peers.
+	/// Peer is not excluded from sync process, but it is asked for blocks after peers with less stalls.
+	pub fn on_peer_stalling(&mut self, peer_index: PeerIndex) -> Vec<H256> {
+		let blocks = self.reset_blocks_tasks(peer_index);
+		if let Some(s) = self.stats.get_mut(&peer_index) {
+			s.stalls += 1;
+			s.reassigned_blocks += blocks.len();
+		}
+		let stalling_timeout_multiplier = self.stalling_timeout_multiplier() * STALLING_TIMEOUT_GROWTH;
+		self.stalling_timeout_multiplier = if stalling_timeout_multiplier > MAX_STALLING_TIMEOUT_MULTIPLIER {
+			MAX_STALLING_TIMEOUT_MULTIPLIER
+		} else {
+			stalling_timeout_multiplier
+		};
+		blocks
+	}
+
 	/// Penalize peer. Returns true if the peer score is too low to keep connection.
 	pub fn penalize(&mut self, peer_index: PeerIndex) -> bool {
 		self.stats.get_mut(&peer_index)
@@ -345,14 +425,21 @@ impl PeersTasks {
 		self.idle_for_blocks.clear();
 		self.headers_requests.clear();
 		self.blocks_requests.clear();
+		self.blocks_owners.clear();
 	}
 
 	/// Reset peer tasks && move peer to idle state
 	pub fn reset_blocks_tasks(&mut self, peer_index: PeerIndex) -> Vec<H256> {
 		self.idle_for_blocks.insert(peer_index);
-		self.blocks_requests.remove(&peer_index)
+		let blocks: Vec<H256> = self.blocks_requests.remove(&peer_index)
 			.map(|mut br| br.blocks.drain().collect())
-			.unwrap_or_default()
+			.unwrap_or_default();
+		for block in &blocks {
+			if self.blocks_owners.get(block).map(|&(owner, _)| owner == peer_index).unwrap_or(false) {
+				self.blocks_owners.remove(block);
+			}
+		}
+		blocks
 	}
 }
 
@@ -379,6 +466,8 @@ impl PeerStats {
 			failures: 0,
 			speed: AverageSpeedMeter::with_inspect_items(BLOCKS_TO_INSPECT),
 			trust: TrustLevel::Suspicious,
+			stalls: 0,
+			reassigned_blocks: 0,
 		}
 	}
 
@@ -386,6 +475,14 @@ impl PeerStats {
 		self.trust
 	}
 
+	pub fn stalls(&self) -> usize {
+		self.stalls
+	}
+
+	pub fn reassigned_blocks(&self) -> usize {
+		self.reassigned_blocks
+	}
+
 	#[cfg(test)]
 	pub fn set_trust(&mut self, trust: TrustLevel) {
 		self.trust = trust;
@@ -401,7 +498,7 @@ impl fmt::Debug for Information {
 #[cfg(test)]
 mod tests {
 	use primitives::hash::H256;
-	use super::{PeersTasks, MAX_PEER_FAILURES, MAX_BLOCKS_FAILURES};
+	use super::{PeersTasks, MAX_PEER_FAILURES, MAX_BLOCKS_FAILURES, MAX_STALLING_TIMEOUT_MULTIPLIER};
 	use types::PeerIndex;
 
 	#[test]
@@ -442,6 +539,19 @@ mod tests {
 		assert_eq!(peers.information().active, 0);
 	}
 
+	#[test]
+	fn peer_idle_after_all_requested_blocks_are_forgotten() {
+		let mut peers = PeersTasks::default();
+		peers.on_blocks_requested(7, &vec![H256::from(1), H256::from(2)]);
+		peers.on_blocks_requested(8, &vec![H256::from(3)]);
+		peers.forget_blocks_requests(&[H256::from(1), H256::from(3)]);
+		assert_eq!(peers.get_blocks_tasks(7), Some(&vec![H256::from(2)].into_iter().collect()));
+		assert_eq!(peers.get_block_request(&H256::from(1)), None);
+		assert_eq!(peers.get_blocks_tasks(8), None);
+		assert_eq!(peers.information().idle, 1);
+		assert_eq!(peers.information().active, 1);
+	}
+
 	#[test]
 	fn peers_active_after_headers_request() {
 		let mut peers = PeersTasks::default();
@@ -625,4 +735,84 @@ mod tests {
 		assert_eq!(peers_for_blocks[0], 2);
 		assert_eq!(peers_for_blocks[1], 1);
 	}
+
+	#[test]
+	fn peer_stalling_blocks_are_reassigned() {
+		let mut peers = PeersTasks::default();
+		peers.on_blocks_requested(1, &vec![H256::from(1), H256::from(2)]);
+		peers.on_blocks_requested(2, &vec![H256::from(3)]);
+		assert_eq!(peers.get_block_request(&H256::from(1)).map(|r| r.0), Some(1));
+		assert_eq!(peers.get_block_request(&H256::from(3)).map(|r| r.0), Some(2));
+
+		let blocks = peers.on_peer_stalling(1);
+		assert_eq!(blocks.len(), 2);
+		assert!(blocks.contains(&H256::from(1)));
+		assert!(blocks.contains(&H256::from(2)));
+		assert_eq!(peers.get_block_request(&H256::from(1)), None);
+		assert!(peers.idle_peers_for_blocks().contains(&1));
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 1);
+		assert_eq!(peers.get_peer_stats(1).unwrap().reassigned_blocks(), 2);
+		assert_eq!(peers.get_peer_stats(2).unwrap().stalls(), 0);
+
+		peers.on_block_received(2, &H256::from(3));
+		assert_eq!(peers.get_block_request(&H256::from(3)), None);
+	}
+
+	#[test]
+	fn peer_stalls_are_forgiven_after_blocks_are_received() {
+		let mut peers = PeersTasks::default();
+		for _ in 0..2 {
+			peers.on_blocks_requested(1, &vec![H256::from(1)]);
+			peers.on_peer_stalling(1);
+		}
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 2);
+		assert!(peers.all_peers().contains(&1));
+
+		// not all requested blocks are received => stalls are remembered
+		peers.on_blocks_requested(1, &vec![H256::from(1), H256::from(2)]);
+		peers.on_block_received(1, &H256::from(1));
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 2);
+
+		// all requested blocks are received => one stall is forgiven
+		peers.on_block_received(1, &H256::from(2));
+		assert_eq!(peers.get_peer_stats(1).unwrap().stalls(), 1);
+	}
+
+	#[test]
+	fn stalling_timeout_grows_on_stalls_and_decays_on_received_blocks() {
+		let mut peers = PeersTasks::default();
+		assert_eq!(peers.stalling_timeout_multiplier(), 1_f64);
+
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		peers.on_peer_stalling(1);
+		assert_eq!(peers.stalling_timeout_multiplier(), 2_f64);
+
+		for _ in 0..10 {
+			peers.on_blocks_requested(1, &vec![H256::from(1)]);
+			peers.on_peer_stalling(1);
+		}
+		assert_eq!(peers.stalling_timeout_multiplier(), MAX_STALLING_TIMEOUT_MULTIPLIER);
+
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		peers.on_block_received(1, &H256::from(1));
+		assert!(peers.stalling_timeout_multiplier() < MAX_STALLING_TIMEOUT_MULTIPLIER);
+		for _ in 0..100 {
+			peers.on_blocks_requested(1, &vec![H256::from(1)]);
+			peers.on_block_received(1, &H256::from(1));
+		}
+		assert_eq!(peers.stalling_timeout_multiplier(), 1_f64);
+	}
+
+	#[test]
+	fn peer_sort_peers_for_blocks_stalling_last() {
+		let mut peers = PeersTasks::default();
+		peers.on_blocks_requested(1, &vec![H256::from(1)]);
+		peers.on_blocks_requested(2, &vec![H256::from(2)]);
+		peers.on_block_received(1, &H256::from(1));
+		peers.on_peer_stalling(1);
+
+		let mut peers_for_blocks: Vec<PeerIndex> = vec![1, 2];
+		peers.sort_peers_for_blocks(&mut peers_for_blocks);
+		assert_eq!(peers_for_blocks, vec![2, 1]);
+	}
 }
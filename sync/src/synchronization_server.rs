@@ -9,6 +9,7 @@ use message::{types, common};
 use primitives::hash::H256;
 use synchronization_executor::{Task, TaskExecutor};
 use types::{PeerIndex, RequestId, BlockHeight, StorageRef, ExecutorRef, MemoryPoolRef, PeersRef};
+use storage::BASIC_FILTER_TYPE;
 use utils::KnownHashType;
 
 /// Synchronization server task
@@ -26,6 +27,12 @@ pub enum ServerTask {
 	Mempool(PeerIndex),
 	/// Serve 'getblocktxn' request
 	GetBlockTxn(PeerIndex, types::GetBlockTxn),
+	/// Serve 'getcfilters' request
+	GetCFilters(PeerIndex, types::GetCFilters),
+	/// Serve 'getcfheaders' request
+	GetCFHeaders(PeerIndex, types::GetCFHeaders),
+	/// Serve 'getcfcheckpt' request
+	GetCFCheckpt(PeerIndex, types::GetCFCheckpt),
 }
 
 /// Synchronization server
@@ -81,7 +88,10 @@ impl ServerTask {
 				| ServerTask::GetBlocks(peer_index, _)
 				| ServerTask::GetHeaders(peer_index, _, _)
 				| ServerTask::Mempool(peer_index)
-				| ServerTask::GetBlockTxn(peer_index, _) => peer_index,
+				| ServerTask::GetBlockTxn(peer_index, _)
+				| ServerTask::GetCFilters(peer_index, _)
+				| ServerTask::GetCFHeaders(peer_index, _)
+				| ServerTask::GetCFCheckpt(peer_index, _) => peer_index,
 		}
 	}
 }
@@ -235,6 +245,9 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 			ServerTask::GetHeaders(peer_index, message, request_id) => self.serve_get_headers(peer_index, message, request_id),
 			ServerTask::Mempool(peer_index) => self.serve_mempool(peer_index),
 			ServerTask::GetBlockTxn(peer_index, message) => self.serve_get_block_txn(peer_index, message),
+			ServerTask::GetCFilters(peer_index, message) => self.serve_get_cfilters(peer_index, message),
+			ServerTask::GetCFHeaders(peer_index, message) => self.serve_get_cfheaders(peer_index, message),
+			ServerTask::GetCFCheckpt(peer_index, message) => self.serve_get_cfcheckpt(peer_index, message),
 		}
 
 		None
@@ -288,7 +301,7 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 					trace!(target: "sync", "'getblocks' response to peer#{} is ready with block {}", peer_index, next_item.hash.to_reversed_str());
 					self.executor.execute(Task::Block(peer_index, block));
 				} else {
-					notfound.inventory.push(next_item);
+					self.block_not_found(peer_index, next_item, &mut notfound);
 				}
 			},
 			common::InventoryType::MessageFilteredBlock => {
@@ -308,7 +321,7 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 						notfound.inventory.push(next_item);
 					}
 				} else {
-					notfound.inventory.push(next_item);
+					self.block_not_found(peer_index, next_item, &mut notfound);
 				}
 			},
 			common::InventoryType::MessageCompactBlock => {
@@ -319,7 +332,7 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 						self.executor.execute(Task::CompactBlock(peer_index, *block.hash(), message));
 					}
 				} else {
-					notfound.inventory.push(next_item);
+					self.block_not_found(peer_index, next_item, &mut notfound);
 				}
 			},
 			common::InventoryType::MessageWitnessBlock => {
@@ -327,7 +340,7 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 					trace!(target: "sync", "'getblocks' response to peer#{} is ready with witness-block {}", peer_index, next_item.hash.to_reversed_str());
 					self.executor.execute(Task::WitnessBlock(peer_index, block.into()));
 				} else {
-					notfound.inventory.push(next_item);
+					self.block_not_found(peer_index, next_item, &mut notfound);
 				}
 			},
 			common::InventoryType::Error | common::InventoryType::MessageWitnessFilteredBlock => (),
@@ -336,6 +349,14 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 		Some(ServerTask::ReversedGetData(peer_index, message, notfound))
 	}
 
+	fn block_not_found(&self, peer_index: PeerIndex, item: common::InventoryVector, notfound: &mut types::NotFound) {
+		// body of pruned block is not available anymore => respond with notfound
+		if self.storage.is_block_pruned(item.hash.clone().into()) {
+			trace!(target: "sync", "'getdata' request from peer#{} for pruned block {}", peer_index, item.hash.to_reversed_str());
+		}
+		notfound.inventory.push(item);
+	}
+
 	fn serve_get_blocks(&self, peer_index: PeerIndex, message: types::GetBlocks) {
 		if let Some(block_height) = self.locate_best_common_block(&message.hash_stop, &message.block_locator_hashes) {
 			let inventory: Vec<_> = (block_height + 1..block_height + 1 + (types::GETBLOCKS_MAX_RESPONSE_HASHES as BlockHeight))
@@ -402,7 +423,12 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 			return;
 		}
 
-		let block_transactions = self.storage.block_transaction_hashes(message.request.blockhash.clone().into());
+		// block could have been relayed to high-bandwidth peers before it is verified and stored
+		let relayed_block = self.peers.relayed_block(&message.request.blockhash);
+		let block_transactions = match relayed_block {
+			Some(ref block) => block.transactions.iter().map(|tx| tx.hash.clone()).collect(),
+			None => self.storage.block_transaction_hashes(message.request.blockhash.clone().into()),
+		};
 		let block_transactions_len = block_transactions.len();
 		let requested_len = message.request.indexes.len();
 		if requested_len > block_transactions_len {
@@ -425,7 +451,11 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 				return;
 			}
 
-			if let Some(transaction) = self.storage.transaction(&block_transactions[transaction_index]) {
+			let transaction = match relayed_block {
+				Some(ref block) => Some(block.transactions[transaction_index].clone()),
+				None => self.storage.transaction(&block_transactions[transaction_index]),
+			};
+			if let Some(transaction) = transaction {
 				transactions.push(transaction);
 			} else {
 				// we have just got this hash using block_transactions_hashes
@@ -446,6 +476,126 @@ impl<TExecutor> ServerTaskExecutor<TExecutor> where TExecutor: TaskExecutor {
 		}));
 	}
 
+	fn serve_get_cfilters(&self, peer_index: PeerIndex, message: types::GetCFilters) {
+		let block_hashes = match self.locate_filtered_blocks(peer_index, "getcfilters", message.filter_type, message.start_height, &message.stop_hash, types::GETCFILTERS_MAX_FILTERS) {
+			Some(block_hashes) => block_hashes,
+			None => return,
+		};
+
+		let mut filters = Vec::with_capacity(block_hashes.len());
+		for block_hash in block_hashes {
+			match self.storage.block_filter(&block_hash) {
+				Some(filter) => filters.push((block_hash, filter)),
+				None => {
+					trace!(target: "sync", "'getcfilters' request from peer#{} is ignored as there's no filter of block {}", peer_index, block_hash.to_reversed_str());
+					return;
+				},
+			}
+		}
+
+		trace!(target: "sync", "'getcfilters' response to peer#{} is ready with {} filters", peer_index, filters.len());
+		for (block_hash, filter) in filters {
+			self.executor.execute(Task::CFilter(peer_index, types::CFilter {
+				filter_type: message.filter_type,
+				block_hash: block_hash,
+				filter: filter.filter,
+			}));
+		}
+	}
+
+	fn serve_get_cfheaders(&self, peer_index: PeerIndex, message: types::GetCFHeaders) {
+		let block_hashes = match self.locate_filtered_blocks(peer_index, "getcfheaders", message.filter_type, message.start_height, &message.stop_hash, types::GETCFHEADERS_MAX_HEADERS) {
+			Some(block_hashes) => block_hashes,
+			None => return,
+		};
+
+		let previous_filter_header = match message.start_height {
+			0 => Some(H256::default()),
+			start_height => self.storage.block_hash(start_height - 1)
+				.and_then(|block_hash| self.storage.block_filter(&block_hash))
+				.map(|filter| filter.header),
+		};
+		let filter_hashes = block_hashes.iter()
+			.map(|block_hash| self.storage.block_filter(block_hash).map(|filter| filter.filter_hash()))
+			.collect::<Option<Vec<_>>>();
+		let (previous_filter_header, filter_hashes) = match (previous_filter_header, filter_hashes) {
+			(Some(previous_filter_header), Some(filter_hashes)) => (previous_filter_header, filter_hashes),
+			_ => {
+				trace!(target: "sync", "'getcfheaders' request from peer#{} is ignored as there are no filters of requested blocks", peer_index);
+				return;
+			},
+		};
+
+		trace!(target: "sync", "'getcfheaders' response to peer#{} is ready with {} hashes", peer_index, filter_hashes.len());
+		self.executor.execute(Task::CFHeaders(peer_index, types::CFHeaders {
+			filter_type: message.filter_type,
+			stop_hash: message.stop_hash,
+			previous_filter_header: previous_filter_header,
+			filter_hashes: filter_hashes,
+		}));
+	}
+
+	fn serve_get_cfcheckpt(&self, peer_index: PeerIndex, message: types::GetCFCheckpt) {
+		if message.filter_type != BASIC_FILTER_TYPE {
+			self.peers.misbehaving(peer_index, &format!("Got 'getcfcheckpt' message with unknown filter type {}", message.filter_type));
+			return;
+		}
+
+		let stop_height = match self.storage.block_number(&message.stop_hash) {
+			Some(stop_height) => stop_height,
+			None => {
+				trace!(target: "sync", "'getcfcheckpt' request from peer#{} is ignored as stop block {} is not in the main chain", peer_index, message.stop_hash.to_reversed_str());
+				return;
+			},
+		};
+
+		let filter_headers = (1..stop_height / types::CFCHECKPT_INTERVAL + 1)
+			.map(|index| self.storage.block_hash(index * types::CFCHECKPT_INTERVAL)
+				.and_then(|block_hash| self.storage.block_filter(&block_hash))
+				.map(|filter| filter.header))
+			.collect::<Option<Vec<_>>>();
+		let filter_headers = match filter_headers {
+			Some(filter_headers) => filter_headers,
+			None => {
+				trace!(target: "sync", "'getcfcheckpt' request from peer#{} is ignored as there are no filters of requested blocks", peer_index);
+				return;
+			},
+		};
+
+		trace!(target: "sync", "'getcfcheckpt' response to peer#{} is ready with {} headers", peer_index, filter_headers.len());
+		self.executor.execute(Task::CFCheckpt(peer_index, types::CFCheckpt {
+			filter_type: message.filter_type,
+			stop_hash: message.stop_hash,
+			filter_headers: filter_headers,
+		}));
+	}
+
+	/// Returns hashes of main chain blocks in [start_height; stop_hash] range, if compact filters request is valid
+	fn locate_filtered_blocks(&self, peer_index: PeerIndex, command: &str, filter_type: u8, start_height: BlockHeight, stop_hash: &H256, max_blocks: u32) -> Option<Vec<H256>> {
+		if filter_type != BASIC_FILTER_TYPE {
+			self.peers.misbehaving(peer_index, &format!("Got '{}' message with unknown filter type {}", command, filter_type));
+			return None;
+		}
+
+		// stop block could have been decanonized since the request was sent
+		let stop_height = match self.storage.block_number(stop_hash) {
+			Some(stop_height) => stop_height,
+			None => {
+				trace!(target: "sync", "'{}' request from peer#{} is ignored as stop block {} is not in the main chain", command, peer_index, stop_hash.to_reversed_str());
+				return None;
+			},
+		};
+
+		if start_height > stop_height || stop_height - start_height >= max_blocks {
+			self.peers.misbehaving(peer_index, &format!("Got '{}' message for blocks range {}..{}", command, start_height, stop_height));
+			return None;
+		}
+
+		(start_height..stop_height + 1)
+			.map(|block_height| self.storage.block_hash(block_height))
+			.collect()
+	}
+
 	fn locate_best_common_block(&self, hash_stop: &H256, locator: &[H256]) -> Option<BlockHeight> {
 		for block_hash in locator.iter().chain(&[hash_stop.clone()]) {
 			if let Some(block_number) = self.storage.block_number(block_hash) {
@@ -482,10 +632,11 @@ pub mod tests {
 	use std::sync::Arc;
 	use parking_lot::{Mutex, RwLock};
 	use db::{BlockChainDatabase};
+	use db::kv::MemoryDatabase;
 	use message::types;
 	use message::common::{self, Services, InventoryVector, InventoryType};
 	use primitives::hash::H256;
-	use chain::Transaction;
+	use chain::{IndexedBlock, Transaction};
 	use inbound_connection::tests::DummyOutboundSyncConnection;
 	use miner::{NonZeroFeeCalculator, MemoryPool};
 	use local_node::tests::{default_filterload, make_filteradd};
@@ -493,6 +644,7 @@ pub mod tests {
 	use synchronization_executor::tests::DummyTaskExecutor;
 	use synchronization_peers::{PeersContainer, PeersFilters, PeersImpl};
 	use types::{PeerIndex, StorageRef, MemoryPoolRef, PeersRef, ExecutorRef};
+	use storage::BASIC_FILTER_TYPE;
 	use utils::KnownHashType;
 	use super::{Server, ServerTask, ServerImpl, ServerTaskExecutor};
 
@@ -686,6 +838,40 @@ pub mod tests {
 		})]);
 	}
 
+	#[test]
+	fn server_get_block_txn_responds_for_block_relayed_before_verification() {
+		let peers = Arc::new(PeersImpl::default());
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
+		let sync_executor = DummyTaskExecutor::new();
+		let executor = ServerTaskExecutor::new(peers.clone(), storage, memory_pool, sync_executor.clone());
+
+		// block is relayed to the peer, but it is not yet stored
+		let b1: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(50).build().build()
+			.transaction().output().value(10).build().build()
+			.merkled_header().parent(test_data::genesis().hash()).build()
+			.build()
+			.into();
+		peers.insert(0, Services::default(), DummyOutboundSyncConnection::new());
+		peers.hash_known_as(0, b1.hash().clone(), KnownHashType::CompactBlock);
+		peers.remember_relayed_block(b1.clone());
+
+		executor.execute(ServerTask::GetBlockTxn(0, types::GetBlockTxn {
+			request: common::BlockTransactionsRequest {
+				blockhash: b1.hash().clone(),
+				indexes: vec![1],
+			}
+		}));
+
+		assert_eq!(sync_executor.take_tasks(), vec![Task::BlockTxn(0, types::BlockTxn {
+			request: common::BlockTransactions {
+				blockhash: b1.hash().clone(),
+				transactions: vec![b1.transactions[1].raw.clone()],
+			}
+		})]);
+	}
+
 	#[test]
 	fn server_get_block_txn_do_not_responds_when_bad_request() {
 		let (_, _, _, peers, server) = create_synchronization_server();
@@ -940,4 +1126,83 @@ pub mod tests {
 			_ => panic!("unexpected"),
 		}
 	}
+
+	fn create_server_task_executor_with_filters() -> (StorageRef, ExecutorRef<DummyTaskExecutor>, ServerTaskExecutor<DummyTaskExecutor>) {
+		let peers = Arc::new(PeersImpl::default());
+		let storage = BlockChainDatabase::open(MemoryDatabase::default()).with_block_filter_index();
+		for block in vec![test_data::genesis(), test_data::block_h1()] {
+			storage.insert(block.clone().into()).expect("Db write error");
+			storage.canonize(&block.hash()).unwrap();
+		}
+		let storage: StorageRef = Arc::new(storage);
+		let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
+		let sync_executor = DummyTaskExecutor::new();
+		let executor = ServerTaskExecutor::new(peers, storage.clone(), memory_pool, sync_executor.clone());
+		(storage, sync_executor, executor)
+	}
+
+	#[test]
+	fn server_getcfilters_responds_filters() {
+		let (storage, sync_executor, executor) = create_server_task_executor_with_filters();
+		executor.execute(ServerTask::GetCFilters(0, types::GetCFilters {
+			filter_type: BASIC_FILTER_TYPE,
+			start_height: 0,
+			stop_hash: test_data::block_h1().hash(),
+		}));
+
+		let expected_tasks: Vec<_> = vec![test_data::genesis().hash(), test_data::block_h1().hash()].into_iter()
+			.map(|block_hash| Task::CFilter(0, types::CFilter {
+				filter_type: BASIC_FILTER_TYPE,
+				filter: storage.block_filter(&block_hash).unwrap().filter,
+				block_hash: block_hash,
+			}))
+			.collect();
+		assert_eq!(sync_executor.take_tasks(), expected_tasks);
+	}
+
+	#[test]
+	fn server_getcfilters_ignores_unknown_filter_type() {
+		let (_, sync_executor, executor) = create_server_task_executor_with_filters();
+		executor.execute(ServerTask::GetCFilters(0, types::GetCFilters {
+			filter_type: 1,
+			start_height: 0,
+			stop_hash: test_data::block_h1().hash(),
+		}));
+
+		assert_eq!(sync_executor.take_tasks(), vec![]);
+	}
+
+	#[test]
+	fn server_getcfheaders_responds_filter_hashes() {
+		let (storage, sync_executor, executor) = create_server_task_executor_with_filters();
+		executor.execute(ServerTask::GetCFHeaders(0, types::GetCFHeaders {
+			filter_type: BASIC_FILTER_TYPE,
+			start_height: 1,
+			stop_hash: test_data::block_h1().hash(),
+		}));
+
+		let genesis_filter = storage.block_filter(&test_data::genesis().hash()).unwrap();
+		let b1_filter = storage.block_filter(&test_data::block_h1().hash()).unwrap();
+		assert_eq!(sync_executor.take_tasks(), vec![Task::CFHeaders(0, types::CFHeaders {
+			filter_type: BASIC_FILTER_TYPE,
+			stop_hash: test_data::block_h1().hash(),
+			previous_filter_header: genesis_filter.header,
+			filter_hashes: vec![b1_filter.filter_hash()],
+		})]);
+	}
+
+	#[test]
+	fn server_getcfcheckpt_responds_filter_headers() {
+		let (_, sync_executor, executor) = create_server_task_executor_with_filters();
+		executor.execute(ServerTask::GetCFCheckpt(0, types::GetCFCheckpt {
+			filter_type: BASIC_FILTER_TYPE,
+			stop_hash: test_data::block_h1().hash(),
+		}));
+
+		assert_eq!(sync_executor.take_tasks(), vec![Task::CFCheckpt(0, types::CFCheckpt {
+			filter_type: BASIC_FILTER_TYPE,
+			stop_hash: test_data::block_h1().hash(),
+			filter_headers: vec![],
+		})]);
+	}
 }
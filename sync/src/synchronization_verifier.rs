@@ -10,7 +10,7 @@ use network::ConsensusParams;
 use primitives::hash::H256;
 use verification::{BackwardsCompatibleChainVerifier as ChainVerifier, Verify as VerificationVerify,
 	Error as VerificationError, VerificationLevel};
-use types::{BlockHeight, StorageRef, MemoryPoolRef};
+use types::{AssumeValidRef, BlockHeight, StorageRef, MemoryPoolRef};
 use utils::MemoryPoolTransactionOutputProvider;
 use VerificationParameters;
 
@@ -69,6 +69,8 @@ pub struct ChainVerifierWrapper {
 	verification_params: VerificationParameters,
 	/// Is verification edge passed.
 	pub enforce_full_verification: AtomicBool,
+	/// Assumed valid blocks.
+	pub assume_valid: Option<AssumeValidRef>,
 }
 
 impl ChainVerifierWrapper {
@@ -79,6 +81,7 @@ impl ChainVerifierWrapper {
 			verifier: verifier,
 			verification_params: verification_params,
 			enforce_full_verification: enforce_full_verification,
+			assume_valid: None,
 		}
 	}
 
@@ -95,6 +98,13 @@ impl ChainVerifierWrapper {
 		} else {
 			self.verification_params.verification_level
 		};
+		// scripts of assumed valid block ancestors are not verified
+		let is_assumed_valid = self.assume_valid.as_ref().map(|av| av.take_ancestor(block.hash())).unwrap_or(false);
+		let verification_level = if is_assumed_valid && verification_level == VerificationLevel::Full {
+			VerificationLevel::Header
+		} else {
+			verification_level
+		};
 
 		self.verifier.verify(verification_level, block)
 	}
@@ -112,14 +122,15 @@ impl VerificationTask {
 
 impl AsyncVerifier {
 	/// Create new async verifier
-	pub fn new<T: VerificationSink>(verifier: Arc<ChainVerifier>, storage: StorageRef, memory_pool: MemoryPoolRef, sink: Arc<T>, verification_params: VerificationParameters) -> Self {
+	pub fn new<T: VerificationSink>(verifier: Arc<ChainVerifier>, storage: StorageRef, memory_pool: MemoryPoolRef, sink: Arc<T>, verification_params: VerificationParameters, assume_valid: Option<AssumeValidRef>) -> Self {
 		let (verification_work_sender, verification_work_receiver) = channel();
 		AsyncVerifier {
 			verification_work_sender: Mutex::new(verification_work_sender),
 			verification_worker_thread: Some(thread::Builder::new()
 				.name("Sync verification thread".to_string())
 				.spawn(move || {
-					let verifier = ChainVerifierWrapper::new(verifier, &storage, verification_params);
+					let mut verifier = ChainVerifierWrapper::new(verifier, &storage, verification_params);
+					verifier.assume_valid = assume_valid;
 					AsyncVerifier::verification_worker_proc(sink, storage, memory_pool, verifier, verification_work_receiver)
 				})
 				.expect("Error creating sync verification thread"))
@@ -271,6 +282,7 @@ pub mod tests {
 	use chain::{IndexedBlock, IndexedTransaction};
 	use super::{Verifier, BlockVerificationSink, TransactionVerificationSink, AsyncVerifier, VerificationTask, ChainVerifierWrapper};
 	use types::{BlockHeight, StorageRef, MemoryPoolRef};
+	use utils::AssumeValid;
 	use script::Error as ScriptError;
 	use VerificationParameters;
 
@@ -301,6 +313,7 @@ pub mod tests {
 			self.verifier = Some(ChainVerifierWrapper::new(verifier, self.storage.as_ref().unwrap(), VerificationParameters {
 				verification_level: VerificationLevel::Full,
 				verification_edge: 0u8.into(),
+				assume_valid: None,
 			}));
 		}
 
@@ -357,12 +370,14 @@ pub mod tests {
 		assert_eq!(ChainVerifierWrapper::new(verifier.clone(), &storage, VerificationParameters {
 			verification_level: VerificationLevel::NoVerification,
 			verification_edge: test_data::genesis().hash(),
+			assume_valid: None,
 		}).enforce_full_verification.load(Ordering::Relaxed), true);
 
 		// switching to full verification when block with given hash is coming
 		let wrapper = ChainVerifierWrapper::new(verifier, &storage, VerificationParameters {
 			verification_level: VerificationLevel::NoVerification,
 			verification_edge: test_data::block_h1().hash(),
+			assume_valid: None,
 		});
 		assert_eq!(wrapper.enforce_full_verification.load(Ordering::Relaxed), false);
 		let block: IndexedBlock = test_data::block_h1().into();
@@ -410,15 +425,31 @@ pub mod tests {
 		let wrapper = ChainVerifierWrapper::new(verifier.clone(), &storage, VerificationParameters {
 			verification_level: VerificationLevel::Header,
 			verification_edge: 1.into(),
+			assume_valid: None,
 		});
 		assert_eq!(wrapper.verify_block(&bad_transaction_block), Ok(()));
 
 		// Error when tx script is checked
-		let wrapper = ChainVerifierWrapper::new(verifier, &storage, VerificationParameters {
+		let wrapper = ChainVerifierWrapper::new(verifier.clone(), &storage, VerificationParameters {
 			verification_level: VerificationLevel::Full,
 			verification_edge: 1.into(),
+			assume_valid: None,
 		});
 		assert_eq!(wrapper.verify_block(&bad_transaction_block), Err(VerificationError::Transaction(1, TransactionError::Signature(0, ScriptError::InvalidStackOperation))));
+
+		// Ok(()) when block is an ancestor of assumed valid block
+		let mut wrapper = ChainVerifierWrapper::new(verifier, &storage, VerificationParameters {
+			verification_level: VerificationLevel::Full,
+			verification_edge: 1.into(),
+			assume_valid: Some(1.into()),
+		});
+		let assume_valid = Arc::new(AssumeValid::new(1.into(), 0.into()));
+		assume_valid.insert_ancestor(bad_transaction_block.hash().clone());
+		wrapper.assume_valid = Some(assume_valid);
+		assert_eq!(wrapper.verify_block(&bad_transaction_block), Ok(()));
+
+		// Error when the same block is verified again
+		assert_eq!(wrapper.verify_block(&bad_transaction_block), Err(VerificationError::Transaction(1, TransactionError::Signature(0, ScriptError::InvalidStackOperation))));
 	}
 
 	#[test]
@@ -431,6 +462,7 @@ pub mod tests {
 		let wrapper = ChainVerifierWrapper::new(verifier.clone(), &storage, VerificationParameters {
 			verification_level: VerificationLevel::NoVerification,
 			verification_edge: 1.into(),
+			assume_valid: None,
 		});
 		assert_eq!(wrapper.verify_block(&bad_block), Ok(()));
 
@@ -438,6 +470,7 @@ pub mod tests {
 		let wrapper = ChainVerifierWrapper::new(verifier, &storage, VerificationParameters {
 			verification_level: VerificationLevel::Full,
 			verification_edge: 1.into(),
+			assume_valid: None,
 		});
 		assert_eq!(wrapper.verify_block(&bad_block), Err(VerificationError::Empty));
 	}
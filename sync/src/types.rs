@@ -4,13 +4,11 @@ use parking_lot::{Mutex, RwLock};
 use storage;
 use local_node::LocalNode;
 use miner::MemoryPool;
-use super::SyncListener;
-use synchronization_client::SynchronizationClient;
-use synchronization_executor::LocalSynchronizationTaskExecutor;
+use super::{LightClientListener, SyncListener};
+use synchronization_client::Client;
 use synchronization_peers::Peers;
 use synchronization_server::ServerImpl;
-use synchronization_verifier::AsyncVerifier;
-use utils::SynchronizationState;
+use utils::{AssumeValid, SynchronizationState};
 
 pub use utils::BlockHeight;
 
@@ -48,7 +46,13 @@ pub type ClientCoreRef<T> = Arc<Mutex<T>>;
 pub type ServerRef<T> = Arc<T>;
 
 /// Reference to local node
-pub type LocalNodeRef = Arc<LocalNode<ServerImpl, SynchronizationClient<LocalSynchronizationTaskExecutor, AsyncVerifier>>>;
+pub type LocalNodeRef = Arc<LocalNode<ServerImpl, dyn Client>>;
 
 /// Synchronization events listener reference
 pub type SyncListenerRef = Box<dyn SyncListener>;
+
+/// Light client listener reference
+pub type LightClientListenerRef = Box<dyn LightClientListener>;
+
+/// Reference to assumed valid blocks
+pub type AssumeValidRef = Arc<AssumeValid>;
@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+use parking_lot::Mutex;
+use primitives::bigint::U256;
+use primitives::hash::H256;
+
+/// Blocks, which scripts are assumed to be valid, because they are ancestors of the assumed valid block.
+/// Blocks are selected by the synchronization client (which knows the headers chain) and
+/// are taken by the verifier (which verifies blocks in separate thread).
+#[derive(Debug)]
+pub struct AssumeValid {
+	/// Assumed valid block hash.
+	block: H256,
+	/// Minimal work of the headers chain, containing assumed valid block.
+	minimum_chain_work: U256,
+	/// Ancestors of assumed valid block, which are waiting for verification.
+	ancestors: Mutex<HashSet<H256>>,
+}
+
+impl AssumeValid {
+	pub fn new(block: H256, minimum_chain_work: U256) -> Self {
+		AssumeValid {
+			block: block,
+			minimum_chain_work: minimum_chain_work,
+			ancestors: Mutex::new(HashSet::new()),
+		}
+	}
+
+	/// Assumed valid block hash.
+	pub fn block(&self) -> &H256 {
+		&self.block
+	}
+
+	/// Minimal work of the headers chain, containing assumed valid block.
+	pub fn minimum_chain_work(&self) -> &U256 {
+		&self.minimum_chain_work
+	}
+
+	/// Remember that block is an ancestor of assumed valid block.
+	pub fn insert_ancestor(&self, hash: H256) {
+		self.ancestors.lock().insert(hash);
+	}
+
+	/// Forget block. Returns true if block has been an ancestor of assumed valid block.
+	pub fn take_ancestor(&self, hash: &H256) -> bool {
+		self.ancestors.lock().remove(hash)
+	}
+}
@@ -1,8 +1,18 @@
-use std::collections::HashMap;
+use std::cmp::max;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::hash_map::Entry;
 use chain::IndexedBlockHeader;
+use primitives::bigint::U256;
 use primitives::hash::H256;
 use super::{HashQueue, HashPosition};
 
+/// Maximal number of remembered headers, which do not belong to the best chain
+const MAX_FORK_HEADERS: usize = 2048;
+/// Fork branches are pruned when their total work falls behind the best chain by work of this number of blocks
+const MAX_FORK_BLOCKS_BEHIND: u32 = 144;
+/// Fork branches are pruned after this number of headers is appended to the best chain
+const PRUNE_FORKS_INTERVAL: usize = 64;
+
 /// Best headers chain information
 #[derive(Debug)]
 pub struct Information {
@@ -10,27 +20,70 @@ pub struct Information {
 	pub best: u32,
 	/// Total number of headers
 	pub total: u32,
+	/// Number of headers in fork chains
+	pub forks: u32,
+}
+
+/// Result of header insertion
+#[derive(Debug, PartialEq)]
+pub enum Insertion {
+	/// Header is already known
+	Known,
+	/// Header is appended to the best chain
+	Appended,
+	/// Header is inserted to the fork branch, which has less work than the best chain
+	Fork,
+	/// Header is inserted to the fork branch, which is not connected to the best storage block,
+	/// so its total work is unknown
+	Detached,
+	/// Fork branch, ending with the header, has more work than the best chain and it has become the best chain
+	Switched {
+		/// Headers, which have been removed from the best chain, ordered from the first to the last
+		decanonized: Vec<H256>,
+		/// Headers, which have been appended to the best chain, ordered from the first to the last
+		canonized: Vec<H256>,
+	},
 }
 
-// TODO: currently it supports first chain only (so whatever headers sequence came first, it is best)
-/// Builds the block-header-chain of in-memory blocks, for which only headers are currently known
+/// Builds the block-header-chain of in-memory blocks, for which only headers are currently known.
+/// Headers are organized in the tree of branches, growing from the best storage block. The branch
+/// with the most total work is the best chain.
 #[derive(Debug)]
 pub struct BestHeadersChain {
 	/// Best hash in storage
 	storage_best_hash: H256,
+	/// Total work of the storage best chain
+	storage_best_chainwork: U256,
 	/// Headers by hash
 	headers: HashMap<H256, IndexedBlockHeader>,
+	/// Total work of the chain, ending with the header, by header hash
+	chainwork: HashMap<H256, U256>,
 	/// Best chain
 	best: HashQueue,
+	/// Headers, which do not belong to the best chain, by hash
+	forks: HashMap<H256, IndexedBlockHeader>,
+	/// Hashes of direct children of both best and fork chains headers, by parent hash
+	children: HashMap<H256, HashSet<H256>>,
+	/// Fork headers, which have no known children, ordered by total work
+	/// (work of headers, which are not connected to the best storage block, is zero)
+	fork_tips: BTreeSet<(U256, H256)>,
+	/// Number of headers, appended to the best chain since forks were pruned last time
+	appended_since_prune: usize,
 }
 
 impl BestHeadersChain {
 	/// Create new best headers chain
-	pub fn new(storage_best_hash: H256) -> Self {
+	pub fn new(storage_best_hash: H256, storage_best_chainwork: U256) -> Self {
 		BestHeadersChain {
 			storage_best_hash: storage_best_hash,
+			storage_best_chainwork: storage_best_chainwork,
 			headers: HashMap::new(),
+			chainwork: HashMap::new(),
 			best: HashQueue::new(),
+			forks: HashMap::new(),
+			children: HashMap::new(),
+			fork_tips: BTreeSet::new(),
+			appended_since_prune: 0,
 		}
 	}
 
@@ -39,6 +92,7 @@ impl BestHeadersChain {
 		Information {
 			best: self.best.len(),
 			total: self.headers.len() as u32,
+			forks: self.forks.len() as u32,
 		}
 	}
 
@@ -53,6 +107,35 @@ impl BestHeadersChain {
 		self.headers.get(hash).cloned()
 	}
 
+	/// Get header of either best or fork chain by given hash
+	pub fn known_header(&self, hash: &H256) -> Option<IndexedBlockHeader> {
+		self.headers.get(hash)
+			.or_else(|| self.forks.get(hash))
+			.cloned()
+	}
+
+	/// Get headers of both best and fork chains, which have no known children
+	pub fn tips(&self) -> Vec<IndexedBlockHeader> {
+		self.best.back()
+			.and_then(|hash| self.headers.get(&hash))
+			.into_iter()
+			.chain(self.fork_tips.iter().filter_map(|&(_, ref hash)| self.forks.get(hash)))
+			.cloned()
+			.collect()
+	}
+
+	/// Get total work of the chain, ending with given header
+	pub fn chainwork(&self, hash: &H256) -> Option<U256> {
+		self.chainwork.get(hash).cloned()
+	}
+
+	/// Get total work of the best chain
+	pub fn best_chainwork(&self) -> U256 {
+		self.best.back()
+			.and_then(|hash| self.chainwork(&hash))
+			.unwrap_or(self.storage_best_chainwork)
+	}
+
 	/// Get height of main chain
 	pub fn height(&self, hash: &H256) -> Option<u32> {
 		self.best.position(hash)
@@ -74,13 +157,67 @@ impl BestHeadersChain {
 	}
 
 	/// Insert new block header
-	pub fn insert(&mut self, header: IndexedBlockHeader) {
+	pub fn insert(&mut self, header: IndexedBlockHeader) -> Insertion {
+		if self.headers.contains_key(&header.hash) || self.forks.contains_key(&header.hash) {
+			return Insertion::Known;
+		}
+
 		// append to the best chain
 		if self.best_block_hash() == header.raw.previous_header_hash {
 			let header_hash = header.hash.clone();
+			let chainwork = self.best_chainwork() + header.raw.bits.to_work();
+			self.chainwork.insert(header_hash.clone(), chainwork);
+			self.link(&header);
 			self.headers.insert(header_hash.clone(), header);
-			self.best.push_back(header_hash);
-			return;
+			self.best.push_back(header_hash.clone());
+
+			self.appended_since_prune += 1;
+			if self.appended_since_prune >= PRUNE_FORKS_INTERVAL {
+				self.prune_forks();
+			}
+
+			// detached fork headers, following the header, extend the best chain now
+			if let Some((_, tip)) = self.connect_fork_descendants(&header_hash) {
+				if let Some(Insertion::Switched { decanonized, mut canonized }) = self.switch_to_fork(&tip) {
+					canonized.insert(0, header_hash);
+					return Insertion::Switched {
+						decanonized: decanonized,
+						canonized: canonized,
+					};
+				}
+			}
+			return Insertion::Appended;
+		}
+
+		// total work of the fork is only known when fork is connected to the best storage block
+		let header_hash = header.hash.clone();
+		let parent_chainwork = match header.raw.previous_header_hash == self.storage_best_hash {
+			true => Some(self.storage_best_chainwork),
+			false => self.chainwork(&header.raw.previous_header_hash),
+		};
+		let chainwork = parent_chainwork.map(|parent_chainwork| parent_chainwork + header.raw.bits.to_work());
+
+		// remember fork header, so that tip of the fork is known
+		// (too many fork headers => the fork with the least work is forgotten to free the space)
+		if self.forks.len() >= MAX_FORK_HEADERS && !self.evict_fork_tip(&header.raw.previous_header_hash, chainwork) {
+			return Insertion::Known;
+		}
+
+		if let Some(chainwork) = chainwork {
+			self.chainwork.insert(header_hash.clone(), chainwork);
+		}
+		self.insert_fork_header(header);
+		match chainwork {
+			Some(chainwork) => {
+				// detached fork headers, following the header, have more work than the header
+				let (tip_chainwork, tip) = self.connect_fork_descendants(&header_hash)
+					.unwrap_or((chainwork, header_hash));
+				match tip_chainwork > self.best_chainwork() {
+					true => self.switch_to_fork(&tip).unwrap_or(Insertion::Fork),
+					false => Insertion::Fork,
+				}
+			},
+			None => Insertion::Detached,
 		}
 	}
 
@@ -93,12 +230,17 @@ impl BestHeadersChain {
 
 	/// Remove block header with given hash and all its children
 	pub fn remove(&mut self, hash: &H256) {
-		if self.headers.remove(hash).is_some() {
+		if self.remove_fork_header(hash).is_some() {
+			self.remove_fork_children(hash);
+		} else if let Some(header) = self.headers.remove(hash) {
+			self.chainwork.remove(hash);
+			self.unlink(&header);
 			match self.best.remove(hash) {
 				HashPosition::Front => self.clear(),
 				HashPosition::Inside(position) => self.clear_after(position),
 				_ => (),
 			}
+			self.remove_fork_children(hash);
 		}
 	}
 
@@ -110,18 +252,159 @@ impl BestHeadersChain {
 	}
 
 	/// Called when new blocks is inserted to storage
-	pub fn block_inserted_to_storage(&mut self, hash: &H256, storage_best_hash: &H256) {
+	pub fn block_inserted_to_storage(&mut self, hash: &H256, storage_best_hash: &H256, storage_best_chainwork: U256) {
 		if self.best.front().map(|h| &h == hash).unwrap_or(false) {
 			self.best.pop_front();
-			self.headers.remove(hash);
+			let header = self.headers.remove(hash).expect("all best chain headers are stored in headers; qed");
+			self.chainwork.remove(hash);
+			self.unlink(&header);
 		}
+		self.remove_fork_header(hash);
 		self.storage_best_hash = storage_best_hash.clone();
+		self.storage_best_chainwork = storage_best_chainwork;
 	}
 
 	/// Clears headers chain
 	pub fn clear(&mut self) {
 		self.headers.clear();
+		self.chainwork.clear();
 		self.best.clear();
+		self.forks.clear();
+		self.children.clear();
+		self.fork_tips.clear();
+	}
+
+	/// Make the fork branch, ending with given header, the best chain.
+	/// Returns None if the fork is not connected to the best chain.
+	fn switch_to_fork(&mut self, tip: &H256) -> Option<Insertion> {
+		// find the point, where fork is connected to the best chain
+		let mut canonized = Vec::new();
+		let mut hash = tip.clone();
+		let fork_position = loop {
+			if hash == self.storage_best_hash {
+				break 0;
+			}
+			if let Some(position) = self.best.position(&hash) {
+				break position + 1;
+			}
+
+			let parent_hash = self.forks.get(&hash)?.raw.previous_header_hash.clone();
+			canonized.push(hash);
+			hash = parent_hash;
+		};
+		canonized.reverse();
+
+		// move headers of the old best chain to forks
+		let mut decanonized = Vec::new();
+		while self.best.len() > fork_position {
+			let hash = self.best.pop_back().expect("len() > fork_position; qed");
+			let header = self.headers.remove(&hash).expect("all best chain headers are stored in headers; qed");
+			self.forks.insert(hash.clone(), header);
+			self.update_fork_tip(&hash);
+			decanonized.push(hash);
+		}
+		decanonized.reverse();
+
+		// move headers of the fork to the best chain
+		for hash in &canonized {
+			self.fork_tips.remove(&self.fork_tip_key(hash));
+			let header = self.forks.remove(hash).expect("canonized headers are read from forks above; qed");
+			self.headers.insert(hash.clone(), header);
+			self.best.push_back(hash.clone());
+		}
+
+		self.prune_forks();
+
+		Some(Insertion::Switched {
+			decanonized: decanonized,
+			canonized: canonized,
+		})
+	}
+
+	/// Forget the fork branch tip with the least work to free the space for the fork header, which
+	/// is a child of `parent` and has given total work. Returns false if there is no such tip.
+	fn evict_fork_tip(&mut self, parent: &H256, chainwork: Option<U256>) -> bool {
+		// headers with unknown work never replace other headers
+		let chainwork = match chainwork {
+			Some(chainwork) => chainwork,
+			None => return false,
+		};
+
+		let tip = self.fork_tips.iter().find(|&&(_, ref hash)| hash != parent).cloned();
+		match tip {
+			Some((tip_chainwork, tip_hash)) => match tip_chainwork < chainwork {
+				true => {
+					trace!(target: "sync", "Forgetting fork header {} to remember header with more work", tip_hash.to_reversed_str());
+					self.remove_fork_header(&tip_hash);
+					true
+				},
+				false => false,
+			},
+			// the only fork branch is extended => its length is limited by pruning
+			None => true,
+		}
+	}
+
+	/// Compute total work of detached fork headers, descending from the header with known work.
+	/// Returns the descendant with the most work, if any.
+	fn connect_fork_descendants(&mut self, hash: &H256) -> Option<(U256, H256)> {
+		let mut best_descendant = None;
+		let mut parents = vec![hash.clone()];
+		while let Some(parent) = parents.pop() {
+			let parent_chainwork = match self.chainwork(&parent) {
+				Some(parent_chainwork) => parent_chainwork,
+				None => continue,
+			};
+
+			let children: Vec<H256> = self.children.get(&parent)
+				.map(|children| children.iter()
+					.filter(|child| self.forks.contains_key(*child) && !self.chainwork.contains_key(*child))
+					.cloned()
+					.collect())
+				.unwrap_or_default();
+			for child in children {
+				let chainwork = parent_chainwork + self.forks[&child].raw.bits.to_work();
+				// fork tips are ordered by work => tip is moved to its new place
+				let is_tip = self.fork_tips.remove(&self.fork_tip_key(&child));
+				self.chainwork.insert(child.clone(), chainwork);
+				if is_tip {
+					let key = self.fork_tip_key(&child);
+					self.fork_tips.insert(key);
+				}
+
+				best_descendant = max(best_descendant, Some((chainwork, child.clone())));
+				parents.push(child);
+			}
+		}
+		best_descendant
+	}
+
+	/// Remove fork branches, which have fallen far behind the best chain
+	fn prune_forks(&mut self) {
+		self.appended_since_prune = 0;
+
+		let block_work = match self.best.back().and_then(|hash| self.headers.get(&hash)) {
+			Some(header) => header.raw.bits.to_work(),
+			None => return,
+		};
+		let max_lag = block_work * U256::from(MAX_FORK_BLOCKS_BEHIND);
+		let best_chainwork = self.best_chainwork();
+		loop {
+			// tips are ordered by work => only the first tips are checked
+			// (tips with unknown work are never pruned)
+			let pruned: Vec<H256> = self.fork_tips.iter()
+				.take_while(|&&(chainwork, _)| chainwork + max_lag < best_chainwork)
+				.filter(|&&(_, ref hash)| self.chainwork.contains_key(hash))
+				.map(|&(_, ref hash)| hash.clone())
+				.collect();
+			if pruned.is_empty() {
+				break;
+			}
+
+			for hash in pruned {
+				self.remove_fork_header(&hash);
+			}
+		}
 	}
 
 	/// Remove headers after position
@@ -130,7 +413,75 @@ impl BestHeadersChain {
 			self.clear()
 		} else {
 			while self.best.len() > position {
-				self.headers.remove(&self.best.pop_back().expect("len() > position; qed"));
+				let hash = self.best.pop_back().expect("len() > position; qed");
+				if let Some(header) = self.headers.remove(&hash) {
+					self.unlink(&header);
+				}
+				self.chainwork.remove(&hash);
+				self.remove_fork_children(&hash);
+			}
+		}
+	}
+
+	/// Remove fork headers, descending from given header
+	fn remove_fork_children(&mut self, hash: &H256) {
+		let children: Vec<H256> = self.children.get(hash)
+			.map(|children| children.iter().filter(|child| self.forks.contains_key(*child)).cloned().collect())
+			.unwrap_or_default();
+		for child in children {
+			self.remove_fork_header(&child);
+			self.remove_fork_children(&child);
+		}
+	}
+
+	/// Insert header to the fork branch
+	fn insert_fork_header(&mut self, header: IndexedBlockHeader) {
+		let hash = header.hash.clone();
+		let parent_hash = header.raw.previous_header_hash.clone();
+		self.link(&header);
+		self.forks.insert(hash.clone(), header);
+		self.update_fork_tip(&hash);
+		self.update_fork_tip(&parent_hash);
+	}
+
+	/// Remove header from the fork branch
+	fn remove_fork_header(&mut self, hash: &H256) -> Option<IndexedBlockHeader> {
+		let key = self.fork_tip_key(hash);
+		let header = self.forks.remove(hash)?;
+		self.fork_tips.remove(&key);
+		self.chainwork.remove(hash);
+		self.unlink(&header);
+		self.update_fork_tip(&header.raw.previous_header_hash);
+		Some(header)
+	}
+
+	/// Update fork tips after fork header or its children have been changed
+	fn update_fork_tip(&mut self, hash: &H256) {
+		let key = self.fork_tip_key(hash);
+		match self.forks.contains_key(hash) && !self.children.contains_key(hash) {
+			true => self.fork_tips.insert(key),
+			false => self.fork_tips.remove(&key),
+		};
+	}
+
+	/// Key of the fork header in fork tips set
+	fn fork_tip_key(&self, hash: &H256) -> (U256, H256) {
+		(self.chainwork(hash).unwrap_or_default(), hash.clone())
+	}
+
+	/// Remember the header as the child of its parent
+	fn link(&mut self, header: &IndexedBlockHeader) {
+		self.children.entry(header.raw.previous_header_hash.clone())
+			.or_insert_with(HashSet::new)
+			.insert(header.hash.clone());
+	}
+
+	/// Forget the header as the child of its parent
+	fn unlink(&mut self, header: &IndexedBlockHeader) {
+		if let Entry::Occupied(mut entry) = self.children.entry(header.raw.previous_header_hash.clone()) {
+			entry.get_mut().remove(&header.hash);
+			if entry.get().is_empty() {
+				entry.remove();
 			}
 		}
 	}
@@ -140,12 +491,14 @@ impl BestHeadersChain {
 mod tests {
 	extern crate test_data;
 
+	use std::collections::HashSet;
+	use primitives::compact::Compact;
 	use primitives::hash::H256;
-	use super::BestHeadersChain;
+	use super::{BestHeadersChain, Insertion};
 
 	#[test]
 	fn best_chain_empty() {
-		let chain = BestHeadersChain::new(H256::default());
+		let chain = BestHeadersChain::new(H256::default(), 0.into());
 		assert_eq!(chain.at(0), None);
 		assert_eq!(chain.by_hash(&H256::from(0)), None);
 		assert_eq!(chain.height(&H256::default()), None);
@@ -155,7 +508,7 @@ mod tests {
 
 	#[test]
 	fn best_chain_insert() {
-		let mut chain = BestHeadersChain::new(test_data::genesis().hash());
+		let mut chain = BestHeadersChain::new(test_data::genesis().hash(), 0.into());
 		let b1 = test_data::block_h1().block_header;
 		let b2 = test_data::block_h2().block_header;
 		let b181 = test_data::block_h181().block_header;
@@ -173,7 +526,7 @@ mod tests {
 		chain.insert(b181.clone().into());
 		assert_eq!(chain.information().best, 0);
 		assert_eq!(chain.information().total, 0);
-		chain.block_inserted_to_storage(&b181.hash(), &b181.hash());
+		chain.block_inserted_to_storage(&b181.hash(), &b181.hash(), 0.into());
 		assert_eq!(chain.information().best, 0);
 		assert_eq!(chain.information().total, 0);
 		chain.insert(b182.into());
@@ -188,7 +541,7 @@ mod tests {
 		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
 		let b3 = test_data::block_builder().header().parent(b2.hash()).build().build().block_header;
 		let b4 = test_data::block_builder().header().parent(b3.hash()).build().build().block_header;
-		let mut chain = BestHeadersChain::new(b0.hash());
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
 
 		chain.insert_n(vec![b1.clone().into(), b2.clone().into(), b3.clone().into(), b4.clone().into()]);
 		assert_eq!(chain.information().best, 4);
@@ -211,7 +564,7 @@ mod tests {
 
 	#[test]
 	fn best_chain_insert_to_db_no_reorg() {
-		let mut chain = BestHeadersChain::new(test_data::genesis().hash());
+		let mut chain = BestHeadersChain::new(test_data::genesis().hash(), 0.into());
 		let b1 = test_data::block_h1().block_header;
 		chain.insert(b1.clone().into());
 		assert_eq!(chain.at(0), Some(b1.clone().into()));
@@ -220,7 +573,7 @@ mod tests {
 		assert_eq!(chain.at(0), Some(b1.clone().into()));
 		assert_eq!(chain.at(1), Some(b2.clone().into()));
 
-		chain.block_inserted_to_storage(&b1.hash(), &b1.hash());
+		chain.block_inserted_to_storage(&b1.hash(), &b1.hash(), 0.into());
 
 		assert_eq!(chain.at(0), Some(b2.into()));
 		assert_eq!(chain.at(1), None);
@@ -228,4 +581,238 @@ mod tests {
 		assert_eq!(chain.information().best, 1);
 		assert_eq!(chain.information().total, 1);
 	}
+
+	#[test]
+	fn best_chain_chainwork() {
+		let genesis = test_data::genesis();
+		let b1 = test_data::block_h1().block_header;
+		let b2 = test_data::block_h2().block_header;
+		let genesis_chainwork = genesis.block_header.bits.to_work();
+		let block_work = b1.bits.to_work();
+		let mut chain = BestHeadersChain::new(genesis.hash(), genesis_chainwork);
+		assert_eq!(chain.best_chainwork(), genesis_chainwork);
+
+		chain.insert_n(vec![b1.clone().into(), b2.clone().into()]);
+		assert_eq!(chain.chainwork(&b1.hash()), Some(genesis_chainwork + block_work));
+		assert_eq!(chain.best_chainwork(), genesis_chainwork + block_work + block_work);
+
+		chain.block_inserted_to_storage(&b1.hash(), &b1.hash(), genesis_chainwork + block_work);
+		assert_eq!(chain.chainwork(&b1.hash()), None);
+		assert_eq!(chain.best_chainwork(), genesis_chainwork + block_work + block_work);
+
+		chain.remove(&b2.hash());
+		assert_eq!(chain.best_chainwork(), genesis_chainwork + block_work);
+	}
+
+	#[test]
+	fn best_chain_fork_tips() {
+		let b0 = test_data::block_builder().header().build().build();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build().block_header;
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build().block_header;
+		let f2 = test_data::block_builder().header().parent(f1.hash()).nonce(1).build().build().block_header;
+		let g2 = test_data::block_builder().header().parent(b1.hash()).nonce(2).build().build().block_header;
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		chain.insert_n(vec![b1.clone().into(), b2.clone().into(), f1.clone().into(), f2.clone().into(), g2.clone().into()]);
+		assert_eq!(chain.information().best, 2);
+		assert_eq!(chain.information().total, 2);
+		assert_eq!(chain.known_header(&f1.hash()), Some(f1.clone().into()));
+		let tips: HashSet<H256> = chain.tips().into_iter().map(|header| header.hash).collect();
+		assert_eq!(tips, vec![b2.hash(), f2.hash(), g2.hash()].into_iter().collect());
+
+		// fork headers are removed along with their parents
+		chain.remove(&f1.hash());
+		assert_eq!(chain.known_header(&f2.hash()), None);
+		chain.remove(&b1.hash());
+		assert_eq!(chain.known_header(&g2.hash()), None);
+		assert!(chain.tips().is_empty());
+	}
+
+	#[test]
+	fn best_chain_switches_to_fork_with_more_work() {
+		let b0 = test_data::block_builder().header().build().build();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build().block_header;
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build().block_header;
+		let f2 = test_data::block_builder().header().parent(f1.hash()).nonce(1).build().build().block_header;
+		let f3 = test_data::block_builder().header().parent(f2.hash()).nonce(1).build().build().block_header;
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		assert_eq!(chain.insert(b1.clone().into()), Insertion::Appended);
+		assert_eq!(chain.insert(b2.clone().into()), Insertion::Appended);
+		assert_eq!(chain.insert(f1.clone().into()), Insertion::Fork);
+		assert_eq!(chain.insert(f2.clone().into()), Insertion::Fork);
+		assert_eq!(chain.best_block_hash(), b2.hash());
+		assert_eq!(chain.insert(f3.clone().into()), Insertion::Switched {
+			decanonized: vec![b1.hash(), b2.hash()],
+			canonized: vec![f1.hash(), f2.hash(), f3.hash()],
+		});
+		assert_eq!(chain.best_block_hash(), f3.hash());
+		assert_eq!(chain.at(0), Some(f1.into()));
+		assert_eq!(chain.information().best, 3);
+		assert_eq!(chain.known_header(&b2.hash()), Some(b2.clone().into()));
+		assert_eq!(chain.insert(b2.into()), Insertion::Known);
+	}
+
+	#[test]
+	fn best_chain_switches_to_shorter_fork_with_more_work() {
+		let b0 = test_data::block_builder().header().build().build();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build().block_header;
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
+		let b3 = test_data::block_builder().header().parent(b2.hash()).build().build().block_header;
+		let f2 = test_data::block_builder().header().parent(b1.hash()).bits(Compact::new(0x1d00ffff)).build().build().block_header;
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		chain.insert_n(vec![b1.clone().into(), b2.clone().into(), b3.clone().into()]);
+		assert_eq!(chain.insert(f2.clone().into()), Insertion::Switched {
+			decanonized: vec![b2.hash(), b3.hash()],
+			canonized: vec![f2.hash()],
+		});
+		assert_eq!(chain.best_block_hash(), f2.hash());
+		assert_eq!(chain.height(&b1.hash()), Some(0));
+		assert_eq!(chain.height(&f2.hash()), Some(1));
+	}
+
+	#[test]
+	fn best_chain_connects_detached_fork_headers() {
+		let b0 = test_data::block_builder().header().build().build();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build().block_header;
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
+		let b3 = test_data::block_builder().header().parent(b2.hash()).build().build().block_header;
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build().block_header;
+		let f2 = test_data::block_builder().header().parent(f1.hash()).nonce(1).build().build().block_header;
+		let f3 = test_data::block_builder().header().parent(f2.hash()).nonce(1).build().build().block_header;
+		let f4 = test_data::block_builder().header().parent(f3.hash()).nonce(1).build().build().block_header;
+		let block_work = b1.bits.to_work();
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		// header, following the best chain, is received before its parent
+		assert_eq!(chain.insert(b1.clone().into()), Insertion::Appended);
+		assert_eq!(chain.insert(b3.clone().into()), Insertion::Detached);
+		assert_eq!(chain.insert(b2.clone().into()), Insertion::Switched {
+			decanonized: vec![],
+			canonized: vec![b2.hash(), b3.hash()],
+		});
+		assert_eq!(chain.chainwork(&b3.hash()), Some(block_work + block_work + block_work));
+
+		// fork headers are received before the fork parent
+		assert_eq!(chain.insert(f4.clone().into()), Insertion::Detached);
+		assert_eq!(chain.insert(f2.clone().into()), Insertion::Detached);
+		assert_eq!(chain.insert(f3.clone().into()), Insertion::Detached);
+		assert_eq!(chain.insert(f1.clone().into()), Insertion::Switched {
+			decanonized: vec![b1.hash(), b2.hash(), b3.hash()],
+			canonized: vec![f1.hash(), f2.hash(), f3.hash(), f4.hash()],
+		});
+		assert_eq!(chain.chainwork(&f4.hash()), Some(block_work + block_work + block_work + block_work));
+		assert_eq!(chain.best_chainwork(), block_work + block_work + block_work + block_work);
+		let tips: HashSet<H256> = chain.tips().into_iter().map(|header| header.hash).collect();
+		assert_eq!(tips, vec![f4.hash(), b3.hash()].into_iter().collect());
+	}
+
+	#[test]
+	fn best_chain_evicts_fork_after_reorg() {
+		let b0 = test_data::block_builder().header().build().build();
+		let b1 = test_data::block_builder().header().parent(b0.hash()).build().build().block_header;
+		let b2 = test_data::block_builder().header().parent(b1.hash()).build().build().block_header;
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build().block_header;
+		let f2 = test_data::block_builder().header().parent(f1.hash()).nonce(1).build().build().block_header;
+		let f3 = test_data::block_builder().header().parent(f2.hash()).nonce(1).build().build().block_header;
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		chain.insert_n(vec![b1.clone().into(), b2.clone().into(), f1.clone().into(), f2.clone().into()]);
+		match chain.insert(f3.clone().into()) {
+			Insertion::Switched { .. } => (),
+			insertion => panic!("unexpected insertion: {:?}", insertion),
+		}
+		assert_eq!(chain.information().forks, 2);
+
+		// fork headers limit is reached by forks, following the best chain
+		for nonce in 0..super::MAX_FORK_HEADERS as u32 - 2 {
+			let header = test_data::block_builder().header().parent(f2.hash()).nonce(nonce + 2).build().build().block_header;
+			assert_eq!(chain.insert(header.into()), Insertion::Fork);
+		}
+		assert_eq!(chain.information().forks, super::MAX_FORK_HEADERS as u32);
+
+		// old best chain has the least work => it is evicted header by header
+		let header = test_data::block_builder().header().parent(f2.hash()).nonce(super::MAX_FORK_HEADERS as u32).build().build().block_header;
+		assert_eq!(chain.insert(header.into()), Insertion::Fork);
+		assert_eq!(chain.known_header(&b2.hash()), None);
+		assert_eq!(chain.chainwork(&b2.hash()), None);
+		assert_eq!(chain.known_header(&b1.hash()), Some(b1.clone().into()));
+
+		let header = test_data::block_builder().header().parent(f2.hash()).nonce(super::MAX_FORK_HEADERS as u32 + 1).build().build().block_header;
+		assert_eq!(chain.insert(header.into()), Insertion::Fork);
+		assert_eq!(chain.known_header(&b1.hash()), None);
+		assert_eq!(chain.chainwork(&b1.hash()), None);
+
+		// remaining forks have the same work as the new header
+		let header = test_data::block_builder().header().parent(f2.hash()).nonce(super::MAX_FORK_HEADERS as u32 + 2).build().build().block_header;
+		assert_eq!(chain.insert(header.into()), Insertion::Known);
+		assert_eq!(chain.information().forks, super::MAX_FORK_HEADERS as u32);
+	}
+
+	#[test]
+	fn best_chain_prunes_forks_with_too_little_work() {
+		let b0 = test_data::block_builder().header().build().build();
+		let f1 = test_data::block_builder().header().parent(b0.hash()).nonce(1).build().build().block_header;
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		let mut parent_hash = b0.hash();
+		for _ in 0..super::MAX_FORK_BLOCKS_BEHIND {
+			let header = test_data::block_builder().header().parent(parent_hash).build().build().block_header;
+			parent_hash = header.hash();
+			chain.insert(header.into());
+		}
+		assert_eq!(chain.insert(f1.clone().into()), Insertion::Fork);
+
+		let header = test_data::block_builder().header().parent(parent_hash).build().build().block_header;
+		chain.insert(header.into());
+		assert_eq!(chain.known_header(&f1.hash()), Some(f1.clone().into()));
+
+		// forks are pruned periodically
+		for _ in 0..super::PRUNE_FORKS_INTERVAL {
+			let header = test_data::block_builder().header().parent(chain.best_block_hash()).build().build().block_header;
+			chain.insert(header.into());
+		}
+		assert_eq!(chain.known_header(&f1.hash()), None);
+		assert_eq!(chain.chainwork(&f1.hash()), None);
+	}
+
+	#[test]
+	fn best_chain_evicts_forks_with_less_work_to_switch_to_long_fork() {
+		let b0 = test_data::block_builder().header().build().build();
+		let mut chain = BestHeadersChain::new(b0.hash(), 0.into());
+
+		// best chain is longer than the fork headers limit
+		for _ in 0..super::MAX_FORK_HEADERS + 1 {
+			let header = test_data::block_builder().header().parent(chain.best_block_hash()).build().build().block_header;
+			assert_eq!(chain.insert(header.into()), Insertion::Appended);
+		}
+
+		// fork headers limit is almost reached by single-header forks
+		let short_forks: Vec<H256> = (0..super::MAX_FORK_HEADERS as u32 - 1).map(|nonce| {
+			let header = test_data::block_builder().header().parent(b0.hash()).nonce(nonce + 2).build().build().block_header;
+			assert_eq!(chain.insert(header.clone().into()), Insertion::Fork);
+			header.hash()
+		}).collect();
+
+		// headers of the long fork replace headers of forks with less work
+		let mut parent_hash = b0.hash();
+		for _ in 0..super::MAX_FORK_HEADERS + 1 {
+			let header = test_data::block_builder().header().parent(parent_hash).nonce(1).build().build().block_header;
+			parent_hash = header.hash();
+			assert_eq!(chain.insert(header.into()), Insertion::Fork);
+		}
+		assert!(short_forks.iter().all(|hash| chain.known_header(hash).is_none()));
+		assert_eq!(chain.information().forks, super::MAX_FORK_HEADERS as u32 + 1);
+
+		// and the long fork becomes the best chain, once it has more work
+		let header = test_data::block_builder().header().parent(parent_hash).nonce(1).build().build().block_header;
+		match chain.insert(header.clone().into()) {
+			Insertion::Switched { canonized, .. } => assert_eq!(canonized.len(), super::MAX_FORK_HEADERS + 2),
+			insertion => panic!("unexpected insertion: {:?}", insertion),
+		}
+		assert_eq!(chain.best_block_hash(), header.hash());
+	}
 }
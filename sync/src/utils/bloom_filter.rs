@@ -1,8 +1,11 @@
+use std::cmp::{min, max};
+use std::f64::consts::LN_2;
 use parking_lot::Mutex;
 use bit_vec::BitVec;
 use murmur3::murmur3_32;
 use chain::{IndexedTransaction, OutPoint};
 use message::types;
+use primitives::bytes::Bytes;
 use ser::serialize;
 use script::Script;
 
@@ -169,6 +172,32 @@ impl BloomFilterData {
 	}
 }
 
+/// Builds `filterload` message, matching all given data elements, with the size and the number of hash functions,
+/// computed from the false positive rate as described in BIP37
+pub fn build_filter_load(elements: &[Bytes], false_positive_rate: f64, tweak: u32, flags: types::FilterFlags) -> types::FilterLoad {
+	let elements_len = max(elements.len(), 1) as f64;
+	let filter_len = (-1.0 / (LN_2 * LN_2) * elements_len * false_positive_rate.ln() / 8.0) as usize;
+	let filter_len = min(max(filter_len, 1), types::FILTERLOAD_MAX_FILTER_LEN);
+	let hash_functions = (filter_len as f64 * 8.0 / elements_len * LN_2) as usize;
+	let hash_functions = min(max(hash_functions, 1), types::FILTERLOAD_MAX_HASH_FUNCS);
+
+	let mut bloom = BloomFilterData {
+		filter: BitVec::from_elem(filter_len * 8, false),
+		hash_functions_num: hash_functions as u32,
+		tweak: tweak,
+	};
+	for element in elements {
+		bloom.insert(element);
+	}
+
+	types::FilterLoad {
+		filter: bloom.filter.to_bytes().into(),
+		hash_functions: hash_functions as u32,
+		tweak: tweak,
+		flags: flags,
+	}
+}
+
 fn contains_any_instruction_data(bloom: &BloomFilterData, script: Script) -> bool {
 	for instruction in script.iter() {
 		match instruction {
@@ -196,7 +225,7 @@ mod tests {
 	use primitives::bytes::Bytes;
 	use primitives::hash::H256;
 	use ser::serialize;
-	use super::{BloomFilter, BloomFilterData};
+	use super::{BloomFilter, BloomFilterData, build_filter_load};
 
 	fn default_filterload() -> types::FilterLoad {
 		types::FilterLoad {
@@ -308,4 +337,16 @@ mod tests {
 		let bloom = BloomFilterData::with_filter_load(message);
 		assert!(bloom.contains(address.as_slice()));
 	}
+
+	#[test]
+	fn filter_load_is_built_from_data_elements() {
+		let elements: Vec<Bytes> = (0u8..10).map(|element| vec![element; 20].into()).collect();
+		let message = build_filter_load(&elements, 0.0001, 5, types::FilterFlags::All);
+		assert_eq!(message.filter.len(), 23);
+		assert_eq!(message.hash_functions, 12);
+		assert_eq!(message.flags, types::FilterFlags::All);
+
+		let bloom = BloomFilterData::with_filter_load(message);
+		assert!(elements.iter().all(|element| bloom.contains(element)));
+	}
 }
@@ -5,12 +5,13 @@ use byteorder::{LittleEndian, ByteOrder};
 use chain::{BlockHeader, ShortTransactionID, IndexedBlock};
 use message::common::{BlockHeaderAndIDs, PrefilledTransaction};
 use primitives::hash::H256;
-use ser::{Stream, Serializable};
+use ser::{Stream, Serializable, SERIALIZE_TRANSACTION_WITNESS};
 
 /// Maximum size of prefilled transactions in compact block
 const MAX_COMPACT_BLOCK_PREFILLED_SIZE: usize = 10 * 1024;
 
-pub fn build_compact_block(block: &IndexedBlock, prefilled_transactions_indexes: HashSet<usize>) -> BlockHeaderAndIDs {
+/// Build compact block. Version 2 compact blocks (`witness` is true) use witness transactions hashes as short ids.
+pub fn build_compact_block(block: &IndexedBlock, prefilled_transactions_indexes: HashSet<usize>, witness: bool) -> BlockHeaderAndIDs {
 	let nonce: u64 = thread_rng().gen();
 
 	let prefilled_transactions_len = prefilled_transactions_indexes.len();
@@ -20,7 +21,11 @@ pub fn build_compact_block(block: &IndexedBlock, prefilled_transactions_indexes:
 
 	let (key0, key1) = short_transaction_id_keys(nonce, &block.header.raw);
 	for (transaction_index, transaction) in block.transactions.iter().enumerate() {
-		let transaction_size = transaction.raw.serialized_size();
+		let transaction_size = if witness {
+			transaction.raw.serialized_size_with_flags(SERIALIZE_TRANSACTION_WITNESS)
+		} else {
+			transaction.raw.serialized_size()
+		};
 		if prefilled_transactions_size + transaction_size < MAX_COMPACT_BLOCK_PREFILLED_SIZE
 			&& prefilled_transactions_indexes.contains(&transaction_index) {
 			prefilled_transactions_size += transaction_size;
@@ -29,7 +34,8 @@ pub fn build_compact_block(block: &IndexedBlock, prefilled_transactions_indexes:
 				transaction: transaction.raw.clone(),
 			})
 		} else {
-			short_ids.push(short_transaction_id(key0, key1, &transaction.hash));
+			let transaction_id = if witness { transaction.raw.witness_hash() } else { transaction.hash.clone() };
+			short_ids.push(short_transaction_id(key0, key1, &transaction_id));
 		}
 	}
 
@@ -100,7 +106,7 @@ mod tests {
 			.transaction().output().value(30).build().build()
 			.build(); // genesis -> block
 		let prefilled: HashSet<_> = vec![1].into_iter().collect();
-		let compact_block = build_compact_block(&block.clone().into(), prefilled);
+		let compact_block = build_compact_block(&block.clone().into(), prefilled, false);
 		let (key0, key1) = short_transaction_id_keys(compact_block.nonce, &block.block_header);
 		let short_ids = vec![
 			short_transaction_id(key0, key1, &block.transactions[0].hash()),
@@ -118,4 +124,25 @@ mod tests {
 			],
 		});
 	}
+
+	#[test]
+	fn witness_compact_block_uses_witness_hashes() {
+		let block = test_data::block_builder().header().parent(test_data::genesis().hash()).build()
+			.transaction().coinbase().output().value(10).build().build()
+			.transaction()
+				.input().hash(test_data::genesis().transactions[0].hash()).index(0).build()
+				.output().value(20).build()
+				.build()
+			.build();
+		let mut block: IndexedBlock = block.into();
+		block.transactions[1].raw.inputs[0].script_witness = vec![vec![1u8].into()];
+		assert!(block.transactions[1].raw.witness_hash() != block.transactions[1].hash);
+
+		let compact_block = build_compact_block(&block, HashSet::new(), true);
+		let (key0, key1) = short_transaction_id_keys(compact_block.nonce, &block.header.raw);
+		assert_eq!(compact_block.short_ids, vec![
+			short_transaction_id(key0, key1, &block.transactions[0].raw.witness_hash()),
+			short_transaction_id(key0, key1, &block.transactions[1].raw.witness_hash()),
+		]);
+	}
 }
@@ -61,13 +61,13 @@ impl ConnectionFilter {
 	}
 
 	/// Convert block to compact block using this filter
-	pub fn build_compact_block(&self, block: &IndexedBlock) -> types::CompactBlock {
+	pub fn build_compact_block(&self, block: &IndexedBlock, witness: bool) -> types::CompactBlock {
 		let unknown_transaction_indexes = block.transactions.iter().enumerate()
 			.filter(|&(_, tx)| self.known_hash_filter.contains(&tx.hash, KnownHashType::Transaction))
 			.map(|(idx, _)| idx)
 			.collect();
 		types::CompactBlock {
-			header: build_compact_block(block, unknown_transaction_indexes),
+			header: build_compact_block(block, unknown_transaction_indexes, witness),
 		}
 	}
 
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use chain::IndexedBlockHeader;
 use storage::{BlockRef, BlockHeaderProvider};
+use primitives::bigint::U256;
 use primitives::bytes::Bytes;
 use primitives::hash::H256;
 
@@ -14,6 +15,8 @@ pub struct MessageBlockHeadersProvider<'a> {
 	headers: HashMap<H256, IndexedBlockHeader>,
 	/// headers by order
 	headers_order: Vec<H256>,
+	/// total work of the chain, ending with the header, by header hash
+	chainwork: HashMap<H256, U256>,
 }
 
 impl<'a> MessageBlockHeadersProvider<'a> {
@@ -23,10 +26,14 @@ impl<'a> MessageBlockHeadersProvider<'a> {
 			first_header_number: best_block_header_height + 1,
 			headers: HashMap::new(),
 			headers_order: Vec::new(),
+			chainwork: HashMap::new(),
 		}
 	}
 
 	pub fn append_header(&mut self, hash: H256, header: IndexedBlockHeader) {
+		if let Some(parent_chainwork) = self.block_chainwork(header.raw.previous_header_hash.clone().into()) {
+			self.chainwork.insert(hash.clone(), parent_chainwork + header.raw.bits.to_work());
+		}
 		self.headers.insert(hash.clone(), header);
 		self.headers_order.push(hash);
 	}
@@ -50,6 +57,15 @@ impl<'a> BlockHeaderProvider for MessageBlockHeadersProvider<'a> {
 				},
 			})
 	}
+
+	fn block_chainwork(&self, block_ref: BlockRef) -> Option<U256> {
+		self.chain_provider.block_chainwork(block_ref.clone())
+			.or_else(move || match block_ref {
+				BlockRef::Hash(h) => self.chainwork.get(&h).cloned(),
+				BlockRef::Number(_) => self.block_header(block_ref)
+					.and_then(|header| self.chainwork.get(&header.hash).cloned()),
+			})
+	}
 }
 
 #[cfg(test)]
@@ -81,4 +97,20 @@ mod tests {
 		assert_eq!(headers_provider.block_header(BlockRef::Hash(H256::from(1))), None);
 		assert_eq!(headers_provider.block_header(BlockRef::Number(2)), None);
 	}
+
+	#[test]
+	fn test_message_block_headers_provider_chainwork() {
+		let storage = BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]);
+		let storage_provider = storage.as_block_header_provider();
+		let mut headers_provider = MessageBlockHeadersProvider::new(storage_provider, 0);
+		let genesis_chainwork = storage_provider.block_chainwork(BlockRef::Number(0)).unwrap();
+
+		headers_provider.append_header(test_data::block_h1().hash(), test_data::block_h1().block_header.into());
+		headers_provider.append_header(test_data::block_h2().hash(), test_data::block_h2().block_header.into());
+
+		let block_work = test_data::block_h1().block_header.bits.to_work();
+		assert_eq!(headers_provider.block_chainwork(BlockRef::Hash(test_data::block_h1().hash())), Some(genesis_chainwork + block_work));
+		assert_eq!(headers_provider.block_chainwork(BlockRef::Number(2)), Some(genesis_chainwork + block_work + block_work));
+		assert_eq!(headers_provider.block_chainwork(BlockRef::Number(3)), None);
+	}
 }
@@ -1,3 +1,4 @@
+mod assume_valid;
 mod average_speed_meter;
 mod best_headers_chain;
 mod bloom_filter;
@@ -10,12 +11,14 @@ mod memory_pool_transaction_provider;
 mod message_block_headers_provider;
 mod orphan_blocks_pool;
 mod orphan_transactions_pool;
+mod partial_compact_block;
 mod partial_merkle_tree;
 mod synchronization_state;
 
+pub use self::assume_valid::AssumeValid;
 pub use self::average_speed_meter::AverageSpeedMeter;
-pub use self::best_headers_chain::{BestHeadersChain, Information as BestHeadersChainInformation};
-pub use self::bloom_filter::BloomFilter;
+pub use self::best_headers_chain::{BestHeadersChain, Information as BestHeadersChainInformation, Insertion as BestHeadersChainInsertion};
+pub use self::bloom_filter::{BloomFilter, build_filter_load};
 pub use self::compact_block_builder::build_compact_block;
 pub use self::connection_filter::ConnectionFilter;
 pub use self::fee_rate_filter::FeeRateFilter;
@@ -25,7 +28,8 @@ pub use self::memory_pool_transaction_provider::MemoryPoolTransactionOutputProvi
 pub use self::message_block_headers_provider::MessageBlockHeadersProvider;
 pub use self::orphan_blocks_pool::OrphanBlocksPool;
 pub use self::orphan_transactions_pool::{OrphanTransactionsPool, OrphanTransaction};
-pub use self::partial_merkle_tree::{PartialMerkleTree, build_partial_merkle_tree};
+pub use self::partial_compact_block::{PartialCompactBlock, CompactBlockError};
+pub use self::partial_merkle_tree::{PartialMerkleTree, build_partial_merkle_tree, parse_partial_merkle_tree};
 pub use self::synchronization_state::SynchronizationState;
 
 /// Block height type
@@ -39,12 +39,14 @@ impl OrphanBlocksPool {
 		&self.unknown_blocks
 	}
 
-	/// Insert orphaned block, for which we have already requested its parent block
-	pub fn insert_orphaned_block(&mut self, block: IndexedBlock) {
+	/// Insert orphaned block, for which we have already requested its parent block.
+	/// Returns false if the block is already in the pool.
+	pub fn insert_orphaned_block(&mut self, block: IndexedBlock) -> bool {
 		self.orphaned_blocks
 			.entry(block.header.raw.previous_header_hash.clone())
 			.or_insert_with(HashMap::new)
-			.insert(block.header.hash.clone(), block);
+			.insert(block.header.hash.clone(), block)
+			.is_none()
 	}
 
 	/// Insert unknown block, for which we know nothing about its parent block
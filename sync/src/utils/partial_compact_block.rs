@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+use chain::{IndexedBlock, IndexedBlockHeader, IndexedTransaction, ShortTransactionID, Transaction};
+use message::common::BlockHeaderAndIDs;
+use primitives::hash::H256;
+use super::compact_block_builder::{short_transaction_id_keys, short_transaction_id};
+
+/// Error of the `cmpctblock` message processing
+#[derive(Debug, PartialEq)]
+pub enum CompactBlockError {
+	/// Message is invalid
+	Invalid(String),
+	/// Several transactions have the same short id. This could happen to honest peer, so the whole block must be requested instead
+	DuplicateShortIds,
+}
+
+/// Block, which is being reconstructed from the `cmpctblock` message
+#[derive(Debug)]
+pub struct PartialCompactBlock {
+	/// Block header
+	header: IndexedBlockHeader,
+	/// SipHash keys, used to compute short transaction ids
+	keys: (u64, u64),
+	/// Are short ids computed from witness transactions hashes (compact blocks version 2)?
+	witness: bool,
+	/// Block transactions. None if transaction is not yet known
+	transactions: Vec<Option<IndexedTransaction>>,
+	/// Short ids of transactions, which are not prefilled, mapped to their index in the block
+	short_ids: HashMap<ShortTransactionID, usize>,
+	/// Indexes of transactions, which have been matched by several known transactions
+	collisions: HashSet<usize>,
+}
+
+impl PartialCompactBlock {
+	/// Create partial block from the `cmpctblock` message, filling only prefilled transactions
+	pub fn new(message: BlockHeaderAndIDs, witness: bool) -> Result<Self, CompactBlockError> {
+		let transactions_len = message.short_ids.len() + message.prefilled_transactions.len();
+		if transactions_len == 0 {
+			return Err(CompactBlockError::Invalid("Compact block has no transactions".into()));
+		}
+
+		let mut transactions: Vec<Option<IndexedTransaction>> = vec![None; transactions_len];
+		for prefilled_transaction in message.prefilled_transactions {
+			// indexes are already checked to be increasing while deserializing
+			if prefilled_transaction.index >= transactions_len {
+				return Err(CompactBlockError::Invalid(format!("Prefilled transaction index {} is out of range", prefilled_transaction.index)));
+			}
+			transactions[prefilled_transaction.index] = Some(prefilled_transaction.transaction.into());
+		}
+
+		let mut short_ids = HashMap::with_capacity(message.short_ids.len());
+		let empty_indexes = transactions.iter().enumerate().filter(|&(_, t)| t.is_none()).map(|(index, _)| index);
+		for (short_id, index) in message.short_ids.into_iter().zip(empty_indexes) {
+			if short_ids.insert(short_id, index).is_some() {
+				return Err(CompactBlockError::DuplicateShortIds);
+			}
+		}
+
+		let header = IndexedBlockHeader::from_raw(message.header);
+		let keys = short_transaction_id_keys(message.nonce, &header.raw);
+		Ok(PartialCompactBlock {
+			header: header,
+			keys: keys,
+			witness: witness,
+			transactions: transactions,
+			short_ids: short_ids,
+			collisions: HashSet::new(),
+		})
+	}
+
+	/// Block header
+	pub fn header(&self) -> &IndexedBlockHeader {
+		&self.header
+	}
+
+	/// Fill block transactions using known transactions
+	pub fn fill_known<'a, I>(&mut self, known_transactions: I) where I: IntoIterator<Item=(&'a H256, &'a Transaction)> {
+		for (hash, transaction) in known_transactions {
+			let transaction_id = self.transaction_id(hash, transaction);
+			let index = match self.short_ids.get(&short_transaction_id(self.keys.0, self.keys.1, &transaction_id)) {
+				Some(index) if !self.collisions.contains(index) => *index,
+				_ => continue,
+			};
+
+			let is_collision = match self.transactions[index] {
+				Some(ref block_transaction) => self.transaction_id(&block_transaction.hash, &block_transaction.raw) != transaction_id,
+				None => false,
+			};
+			if is_collision {
+				// we can't decide which transaction is the block one => request it from peer
+				self.transactions[index] = None;
+				self.collisions.insert(index);
+			} else {
+				self.transactions[index] = Some(IndexedTransaction::new(hash.clone(), transaction.clone()));
+			}
+		}
+	}
+
+	/// Hash of transaction, used to compute its short id
+	fn transaction_id(&self, hash: &H256, transaction: &Transaction) -> H256 {
+		if self.witness {
+			transaction.witness_hash()
+		} else {
+			hash.clone()
+		}
+	}
+
+	/// Indexes of transactions, which are still unknown
+	pub fn missing_indexes(&self) -> Vec<usize> {
+		self.transactions.iter().enumerate()
+			.filter(|&(_, t)| t.is_none())
+			.map(|(index, _)| index)
+			.collect()
+	}
+
+	/// Fill missing transactions with transactions from the `blocktxn` message
+	pub fn fill_missing(&mut self, transactions: Vec<Transaction>) -> Result<(), String> {
+		let missing_indexes = self.missing_indexes();
+		if missing_indexes.len() != transactions.len() {
+			return Err(format!("Expected {} missing transactions, got {}", missing_indexes.len(), transactions.len()));
+		}
+
+		for (index, transaction) in missing_indexes.into_iter().zip(transactions) {
+			self.transactions[index] = Some(transaction.into());
+		}
+		Ok(())
+	}
+
+	/// Build block if all transactions are known. Fails if short ids have been matched by wrong transactions.
+	pub fn into_block(self) -> Result<IndexedBlock, PartialCompactBlock> {
+		if self.transactions.iter().any(Option::is_none) {
+			return Err(self);
+		}
+
+		let block = IndexedBlock::new(self.header.clone(), self.transactions.iter().cloned().map(Option::unwrap).collect());
+		if block.merkle_root() != block.header.raw.merkle_root_hash {
+			return Err(self);
+		}
+
+		Ok(block)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test_data;
+
+	use std::collections::HashSet;
+	use chain::{IndexedBlock, IndexedTransaction};
+	use message::common::PrefilledTransaction;
+	use utils::build_compact_block;
+	use super::{PartialCompactBlock, CompactBlockError};
+
+	fn block() -> IndexedBlock {
+		test_data::block_builder()
+			.transaction().coinbase().output().value(10).build().build()
+			.transaction().output().value(20).build().build()
+			.transaction().output().value(30).build().build()
+			.merkled_header().parent(test_data::genesis().hash()).build()
+			.build()
+			.into()
+	}
+
+	#[test]
+	fn partial_compact_block_is_filled_from_known_transactions() {
+		let block = block();
+		let message = build_compact_block(&block, vec![0].into_iter().collect(), false);
+
+		let mut partial = PartialCompactBlock::new(message, false).unwrap();
+		assert_eq!(partial.missing_indexes(), vec![1, 2]);
+
+		let known: Vec<IndexedTransaction> = vec![block.transactions[2].clone(), test_data::genesis().transactions[0].clone().into()];
+		partial.fill_known(known.iter().map(|t| (&t.hash, &t.raw)));
+		assert_eq!(partial.missing_indexes(), vec![1]);
+
+		let mut partial = partial.into_block().unwrap_err();
+		assert!(partial.fill_missing(vec![]).is_err());
+		partial.fill_missing(vec![block.transactions[1].raw.clone()]).unwrap();
+		assert_eq!(partial.into_block().unwrap(), block);
+	}
+
+	#[test]
+	fn partial_compact_block_rejects_invalid_messages() {
+		let block = block();
+
+		let mut message = build_compact_block(&block, HashSet::new(), false);
+		message.short_ids[1] = message.short_ids[0].clone();
+		assert_eq!(PartialCompactBlock::new(message, false).unwrap_err(), CompactBlockError::DuplicateShortIds);
+
+		let mut message = build_compact_block(&block, HashSet::new(), false);
+		message.short_ids.pop();
+		message.prefilled_transactions.push(PrefilledTransaction {
+			index: 3,
+			transaction: block.transactions[2].raw.clone(),
+		});
+		assert!(PartialCompactBlock::new(message, false).is_err());
+	}
+
+	#[test]
+	fn partial_compact_block_fails_with_wrong_transaction() {
+		let block = block();
+		let message = build_compact_block(&block, vec![0].into_iter().collect(), false);
+
+		let mut partial = PartialCompactBlock::new(message, false).unwrap();
+		partial.fill_known(block.transactions.iter().map(|t| (&t.hash, &t.raw)));
+		partial.transactions[1] = Some(test_data::genesis().transactions[0].clone().into());
+		assert!(partial.into_block().is_err());
+	}
+
+	#[test]
+	fn partial_compact_block_is_filled_using_witness_hashes() {
+		// witness doesn't change transaction hash => block is still valid
+		let mut block = block();
+		block.transactions[0].raw.inputs[0].script_witness = vec![vec![1u8].into()];
+		let message = build_compact_block(&block, HashSet::new(), true);
+
+		// transaction without witness has the same hash, but different witness hash
+		let mut stripped = block.transactions[0].raw.clone();
+		stripped.inputs[0].script_witness.clear();
+		let mut partial = PartialCompactBlock::new(message, true).unwrap();
+		partial.fill_known(vec![(&block.transactions[0].hash, &stripped)]);
+		assert_eq!(partial.missing_indexes(), vec![0, 1, 2]);
+
+		partial.fill_known(block.transactions.iter().map(|t| (&t.hash, &t.raw)));
+		assert_eq!(partial.missing_indexes(), Vec::<usize>::new());
+		assert_eq!(partial.into_block().unwrap(), block);
+	}
+}
@@ -14,7 +14,6 @@ pub struct PartialMerkleTree {
 }
 
 /// Partial merkle tree parse result
-#[cfg(test)]
 pub struct ParsedPartialMerkleTree {
 	/// Merkle root
 	pub root: H256,
@@ -30,7 +29,6 @@ pub fn build_partial_merkle_tree(tx_hashes: Vec<H256>, tx_matches: BitVec) -> Pa
 }
 
 /// Parse partial merkle tree
-#[cfg(test)]
 pub fn parse_partial_merkle_tree(tree: PartialMerkleTree) -> Result<ParsedPartialMerkleTree, String> {
 	PartialMerkleTreeBuilder::parse(tree)
 }
@@ -60,7 +58,6 @@ impl PartialMerkleTree {
 	}
 }
 
-#[cfg(test)]
 impl ParsedPartialMerkleTree {
 	pub fn new(root: H256, hashes: Vec<H256>, flags: BitVec) -> Self {
 		ParsedPartialMerkleTree {
@@ -86,7 +83,6 @@ impl PartialMerkleTreeBuilder {
 		PartialMerkleTree::new(partial_merkle_tree.all_len, partial_merkle_tree.hashes, partial_merkle_tree.matches)
 	}
 
-	#[cfg(test)]
 	/// Parse partial merkle tree as described here:
 	/// https://bitcoin.org/en/developer-reference#parsing-a-merkleblock-message
 	pub fn parse(tree: PartialMerkleTree) -> Result<ParsedPartialMerkleTree, String> {
@@ -107,7 +103,6 @@ impl PartialMerkleTreeBuilder {
 		self.build_branch(tree_height, 0)
 	}
 
-	#[cfg(test)]
 	fn parse_tree(&mut self) -> Result<H256, String> {
 		if self.all_len == 0 {
 			return Err("no transactions".into());
@@ -125,7 +120,8 @@ impl PartialMerkleTreeBuilder {
 		let tree_height = self.tree_height();
 		let merkle_root = self.parse_branch(tree_height, 0, &mut matches_used, &mut hashes_used)?;
 
-		if matches_used != self.matches.len() {
+		// flags are padded to the whole number of bytes
+		if (matches_used + 7) / 8 != (self.matches.len() + 7) / 8 {
 			return Err("not all matches used".into());
 		}
 		if hashes_used != self.hashes.len() {
@@ -157,7 +153,6 @@ impl PartialMerkleTreeBuilder {
 		}
 	}
 
-	#[cfg(test)]
 	fn parse_branch(&mut self, height: usize, pos: usize, matches_used: &mut usize, hashes_used: &mut usize) -> Result<H256, String> {
 		if *matches_used >= self.matches.len() {
 			return Err("all matches used".into());
@@ -168,7 +163,7 @@ impl PartialMerkleTreeBuilder {
 
 		if height == 0 || !flag {
 			// we're at the leaf level || there is no match
-			if *hashes_used > self.hashes.len() {
+			if *hashes_used >= self.hashes.len() {
 				return Err("all hashes used".into());
 			}
 
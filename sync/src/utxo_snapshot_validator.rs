@@ -0,0 +1,315 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread;
+use chain::IndexedBlock;
+use network::ConsensusParams;
+use primitives::hash::H256;
+use storage::UtxoSnapshotValidation;
+use blocks_writer::BlocksWriter;
+use types::{BlockHeight, PeerIndex, StorageRef};
+use super::Error;
+use VerificationParameters;
+
+/// Maximal number of blocks, which are requested or are waiting for their parent, ahead of the background chain
+const MAX_BACKGROUND_BLOCKS_WINDOW: BlockHeight = 32;
+/// Maximal number of blocks in single background blocks request
+const MAX_BACKGROUND_BLOCKS_IN_REQUEST: usize = 8;
+/// Number of seconds to wait for the requested background block before requesting it again
+const BACKGROUND_BLOCK_FAILURE_INTERVAL_S: f64 = 60_f64;
+
+/// Validates blocks below the loaded unspent outputs set snapshot. Blocks are downloaded from peers
+/// and appended to the separate (background) database. Once the background chain reaches the snapshot
+/// block, its unspent outputs set is compared to the snapshot and the result is stored in the main database.
+pub struct UtxoSnapshotValidator {
+	/// Snapshot, which history is validated
+	snapshot: UtxoSnapshotValidation,
+	/// Main database, which has the headers of all blocks below the snapshot
+	storage: StorageRef,
+	/// Background database, which has the validated blocks below the snapshot
+	background_storage: StorageRef,
+	/// Height of the next block to request
+	next_height: BlockHeight,
+	/// Requested blocks, along with their height, peer and request time
+	requested_blocks: HashMap<H256, (BlockHeight, PeerIndex, f64)>,
+	/// Heights of the blocks, which must be requested again
+	failed_heights: BTreeSet<BlockHeight>,
+	/// Peers, which have responded with notfound to background blocks request
+	peers_without_history: HashSet<PeerIndex>,
+	/// Validation worker
+	worker: ValidationWorker,
+}
+
+/// Validation worker, appending received blocks to the background database
+struct ValidationWorker {
+	/// Received blocks sender
+	blocks: Option<Sender<IndexedBlock>>,
+	/// True when the worker has stopped
+	is_finished: Arc<AtomicBool>,
+	/// Worker thread
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+impl UtxoSnapshotValidator {
+	/// Create new validator, which continues background validation from the best block of the background database
+	pub fn new(consensus: ConsensusParams, storage: StorageRef, background_storage: StorageRef, snapshot: UtxoSnapshotValidation, verification_params: VerificationParameters) -> Self {
+		let next_height = background_storage.best_block().number + 1;
+		let worker = ValidationWorker::new(consensus, storage.clone(), background_storage.clone(), snapshot.clone(), verification_params);
+		info!(target: "sync", "Validating blocks below utxo snapshot block {} ({}) in the background, starting at block {}",
+			snapshot.block_height, snapshot.block_hash.to_reversed_str(), next_height);
+
+		UtxoSnapshotValidator {
+			snapshot: snapshot,
+			storage: storage,
+			background_storage: background_storage,
+			next_height: next_height,
+			requested_blocks: HashMap::new(),
+			failed_heights: BTreeSet::new(),
+			peers_without_history: HashSet::new(),
+			worker: worker,
+		}
+	}
+
+	/// True if the snapshot history has been validated (or validation has failed)
+	pub fn is_finished(&self) -> bool {
+		self.worker.is_finished.load(Ordering::SeqCst)
+	}
+
+	/// True if the block has been requested for background validation
+	pub fn is_requested_block(&self, hash: &H256) -> bool {
+		self.requested_blocks.contains_key(hash)
+	}
+
+	/// Pass requested block to the validation worker
+	pub fn on_block(&mut self, block: IndexedBlock) {
+		if self.requested_blocks.remove(block.hash()).is_none() {
+			return;
+		}
+
+		if let Some(ref blocks) = self.worker.blocks {
+			// when worker has stopped, validation is finished => block isn't needed
+			let _ = blocks.send(block);
+		}
+	}
+
+	/// Blocks, requested from disconnected peer, must be requested from other peers
+	pub fn on_disconnect(&mut self, peer_index: PeerIndex) {
+		self.peers_without_history.remove(&peer_index);
+		self.reset_requested_blocks(|&(_, block_peer_index, _)| block_peer_index == peer_index);
+	}
+
+	/// Peer has no requested blocks (it is pruned?) => never ask it again
+	pub fn on_notfound(&mut self, peer_index: PeerIndex, hashes: &HashSet<H256>) {
+		let is_requested_block = hashes.iter()
+			.filter_map(|hash| self.requested_blocks.get(hash))
+			.any(|&(_, block_peer_index, _)| block_peer_index == peer_index);
+		if is_requested_block {
+			self.peers_without_history.insert(peer_index);
+			self.reset_requested_blocks(|&(_, block_peer_index, _)| block_peer_index == peer_index);
+		}
+	}
+
+	/// Prepare requests of the next background blocks (and blocks, which have not been received in time) to given peers
+	pub fn prepare_blocks_requests(&mut self, peers: Vec<PeerIndex>, now: f64) -> Vec<(PeerIndex, Vec<H256>)> {
+		let peers: Vec<_> = peers.into_iter().filter(|peer_index| !self.peers_without_history.contains(peer_index)).collect();
+		if peers.is_empty() || self.is_finished() {
+			return Vec::new();
+		}
+
+		self.reset_requested_blocks(|&(_, _, timestamp)| now - timestamp >= BACKGROUND_BLOCK_FAILURE_INTERVAL_S);
+
+		// blocks, which are received, but not yet validated, are also in the window
+		let validated_height = self.background_storage.best_block().number;
+		let mut heights: Vec<BlockHeight> = self.failed_heights.iter().cloned()
+			.filter(|height| *height > validated_height)
+			.collect();
+		self.failed_heights.clear();
+		let last_height = ::std::cmp::min(self.snapshot.block_height, validated_height + MAX_BACKGROUND_BLOCKS_WINDOW);
+		while self.next_height <= last_height {
+			heights.push(self.next_height);
+			self.next_height += 1;
+		}
+
+		let mut requests: Vec<(PeerIndex, Vec<H256>)> = Vec::new();
+		for (chunk_index, chunk) in heights.chunks(MAX_BACKGROUND_BLOCKS_IN_REQUEST).enumerate() {
+			let peer_index = peers[chunk_index % peers.len()];
+			let hashes: Vec<_> = chunk.iter()
+				.filter_map(|height| self.storage.block_hash(*height).map(|hash| (*height, hash)))
+				.map(|(height, hash)| {
+					self.requested_blocks.insert(hash.clone(), (height, peer_index, now));
+					hash
+				})
+				.collect();
+			requests.push((peer_index, hashes));
+		}
+		requests
+	}
+
+	/// Forget requests, matching the predicate, so that blocks are requested again
+	fn reset_requested_blocks<F>(&mut self, predicate: F) where F: Fn(&(BlockHeight, PeerIndex, f64)) -> bool {
+		let failed_blocks: Vec<_> = self.requested_blocks.iter()
+			.filter(|&(_, request)| predicate(request))
+			.map(|(hash, &(height, _, _))| (hash.clone(), height))
+			.collect();
+		for (hash, height) in failed_blocks {
+			self.requested_blocks.remove(&hash);
+			self.failed_heights.insert(height);
+		}
+	}
+}
+
+impl ValidationWorker {
+	fn new(consensus: ConsensusParams, storage: StorageRef, background_storage: StorageRef, snapshot: UtxoSnapshotValidation, verification_params: VerificationParameters) -> Self {
+		let (blocks, blocks_receiver) = channel();
+		let is_finished = Arc::new(AtomicBool::new(false));
+		let worker_is_finished = is_finished.clone();
+		ValidationWorker {
+			blocks: Some(blocks),
+			is_finished: is_finished,
+			thread: Some(thread::Builder::new()
+				.name("Utxo snapshot validation thread".to_string())
+				.spawn(move || {
+					ValidationWorker::worker_proc(blocks_receiver, consensus, storage, background_storage, snapshot, verification_params);
+					worker_is_finished.store(true, Ordering::SeqCst);
+				})
+				.expect("Error creating utxo snapshot validation thread"))
+		}
+	}
+
+	fn worker_proc(blocks: Receiver<IndexedBlock>, consensus: ConsensusParams, storage: StorageRef, background_storage: StorageRef, snapshot: UtxoSnapshotValidation, verification_params: VerificationParameters) {
+		let mut writer = BlocksWriter::new(background_storage.clone(), consensus, verification_params);
+		for block in blocks {
+			let hash = block.hash().clone();
+			match writer.append_block(block) {
+				Ok(_) => (),
+				Err(Error::Verification(err)) => {
+					error!(target: "sync", "Block {} below utxo snapshot is invalid: {}", hash.to_reversed_str(), err);
+					ValidationWorker::finish(&storage, false);
+					return;
+				},
+				Err(err) => {
+					error!(target: "sync", "Background validation of blocks below utxo snapshot has stopped: {:?}", err);
+					return;
+				},
+			}
+
+			let best_block = background_storage.best_block();
+			if best_block.number == snapshot.block_height {
+				let (_, utxo_set_info) = background_storage.utxo_set_info();
+				let utxo_set_hash = utxo_set_info.muhash.finalize();
+				let is_valid = best_block.hash == snapshot.block_hash && utxo_set_hash == snapshot.utxo_set_hash;
+				match is_valid {
+					true => info!(target: "sync", "Blocks below utxo snapshot block {} are valid", snapshot.block_height),
+					false => error!(target: "sync", "Blocks below utxo snapshot block {} ({}) produce unspent outputs set {}, while snapshot is {}",
+						snapshot.block_height, best_block.hash.to_reversed_str(), utxo_set_hash.to_reversed_str(), snapshot.utxo_set_hash.to_reversed_str()),
+				}
+				ValidationWorker::finish(&storage, is_valid);
+				return;
+			}
+		}
+	}
+
+	fn finish(storage: &StorageRef, is_valid: bool) {
+		if let Err(err) = storage.finish_utxo_snapshot_validation(is_valid) {
+			error!(target: "sync", "Failed to store result of utxo snapshot validation: {:?}", err);
+		}
+	}
+}
+
+impl Drop for ValidationWorker {
+	fn drop(&mut self) {
+		// worker stops once all received blocks are processed
+		self.blocks.take();
+		if let Some(join_handle) = self.thread.take() {
+			join_handle.join().expect("Clean shutdown.");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate test_data;
+
+	use std::collections::HashSet;
+	use std::sync::Arc;
+	use db::BlockChainDatabase;
+	use db::kv::MemoryDatabase;
+	use network::{ConsensusParams, ConsensusFork, Network};
+	use primitives::hash::H256;
+	use storage::{BlockHeaderProvider, UtxoSnapshotStore};
+	use verification::VerificationLevel;
+	use super::UtxoSnapshotValidator;
+	use VerificationParameters;
+
+	fn create_validator(is_valid_snapshot: bool) -> (Arc<BlockChainDatabase<MemoryDatabase>>, Arc<BlockChainDatabase<MemoryDatabase>>, UtxoSnapshotValidator) {
+		let source = BlockChainDatabase::init_test_chain(vec![test_data::genesis().into(), test_data::block_h1().into(), test_data::block_h2().into()]);
+		let headers = (0..3).map(|number| source.block_header(number.into()).unwrap()).collect();
+		let utxos: Vec<_> = match is_valid_snapshot {
+			true => source.utxos().filter(|&(_, ref entry)| entry.height() != 0).map(Ok).collect(),
+			false => Vec::new(),
+		};
+
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		storage.load_utxo_snapshot(headers, &mut utxos.into_iter()).unwrap();
+		let background_storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let verification_params = VerificationParameters {
+			verification_level: VerificationLevel::Full,
+			verification_edge: 0u8.into(),
+			assume_valid: None,
+		};
+		let validator = UtxoSnapshotValidator::new(ConsensusParams::new(Network::Testnet, ConsensusFork::BitcoinCore),
+			storage.clone(), background_storage.clone(), storage.utxo_snapshot_validation().unwrap(), verification_params);
+		(storage, background_storage, validator)
+	}
+
+	fn blocks_hashes() -> Vec<H256> {
+		vec![test_data::block_h1().hash(), test_data::block_h2().hash()]
+	}
+
+	#[test]
+	fn utxo_snapshot_validator_requests_blocks_below_snapshot() {
+		let (_, _, mut validator) = create_validator(true);
+		assert_eq!(validator.prepare_blocks_requests(vec![1], 0f64), vec![(1, blocks_hashes())]);
+		// blocks are only requested once
+		assert!(validator.prepare_blocks_requests(vec![1], 0f64).is_empty());
+
+		// blocks of disconnected peer are requested from other peers
+		validator.on_disconnect(1);
+		assert_eq!(validator.prepare_blocks_requests(vec![2], 0f64), vec![(2, blocks_hashes())]);
+
+		// peer, which has no requested blocks, is never asked again
+		validator.on_notfound(2, &blocks_hashes().into_iter().collect::<HashSet<_>>());
+		assert!(validator.prepare_blocks_requests(vec![2], 0f64).is_empty());
+		assert_eq!(validator.prepare_blocks_requests(vec![2, 3], 0f64), vec![(3, blocks_hashes())]);
+
+		// blocks, which are not received in time, are requested again
+		assert_eq!(validator.prepare_blocks_requests(vec![3], 100f64), vec![(3, blocks_hashes())]);
+	}
+
+	#[test]
+	fn utxo_snapshot_validator_forgets_valid_snapshot() {
+		let (storage, background_storage, mut validator) = create_validator(true);
+		validator.prepare_blocks_requests(vec![1], 0f64);
+		// blocks are validated in any order
+		validator.on_block(test_data::block_h2().into());
+		validator.on_block(test_data::block_h1().into());
+		// worker processes all received blocks before it is stopped
+		drop(validator);
+
+		assert_eq!(background_storage.best_block().number, 2);
+		assert_eq!(storage.utxo_snapshot_validation(), None);
+	}
+
+	#[test]
+	fn utxo_snapshot_validator_remembers_invalid_snapshot() {
+		let (storage, background_storage, mut validator) = create_validator(false);
+		validator.prepare_blocks_requests(vec![1], 0f64);
+		validator.on_block(test_data::block_h1().into());
+		validator.on_block(test_data::block_h2().into());
+		drop(validator);
+
+		assert_eq!(background_storage.best_block().number, 2);
+		assert!(storage.utxo_snapshot_validation().unwrap().is_failed);
+	}
+}
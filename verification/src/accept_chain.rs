@@ -19,6 +19,7 @@ pub struct ChainAcceptor<'a> {
 }
 
 impl<'a> ChainAcceptor<'a> {
+	#[cfg_attr(feature="cargo-clippy", allow(too_many_arguments))]
 	pub fn new(
 		tx_out_provider: &'a dyn TransactionOutputProvider,
 		tx_meta_provider: &'a dyn TransactionMetaProvider,
@@ -29,6 +30,7 @@ impl<'a> ChainAcceptor<'a> {
 		height: u32,
 		median_time_past: u32,
 		deployments: &'a BlockDeployments,
+		last_known_checkpoint: Option<u32>,
 	) -> Self {
 		trace!(target: "verification", "Block verification {}", block.hash().to_reversed_str());
 		let output_store = DuplexTransactionOutputProvider::new(tx_out_provider, block.raw());
@@ -43,7 +45,7 @@ impl<'a> ChainAcceptor<'a> {
 				deployments,
 				header_provider,
 			),
-			header: HeaderAcceptor::new(header_provider, consensus, block.header(), height, deployments),
+			header: HeaderAcceptor::new(header_provider, consensus, block.header(), height, deployments, last_known_checkpoint),
 			transactions: block.transactions()
 				.into_iter()
 				.enumerate()
@@ -10,6 +10,7 @@ pub struct HeaderAcceptor<'a> {
 	pub version: HeaderVersion<'a>,
 	pub work: HeaderWork<'a>,
 	pub median_timestamp: HeaderMedianTimestamp<'a>,
+	pub checkpoint: HeaderCheckpoint<'a>,
 }
 
 impl<'a> HeaderAcceptor<'a> {
@@ -19,12 +20,14 @@ impl<'a> HeaderAcceptor<'a> {
 		header: CanonHeader<'a>,
 		height: u32,
 		deployments: D,
+		last_known_checkpoint: Option<u32>,
 	) -> Self {
 		let csv_active = deployments.as_ref().csv(height, store, consensus);
 		HeaderAcceptor {
 			work: HeaderWork::new(header, store, height, consensus),
 			median_timestamp: HeaderMedianTimestamp::new(header, store, csv_active),
 			version: HeaderVersion::new(header, height, consensus),
+			checkpoint: HeaderCheckpoint::new(header, height, consensus, last_known_checkpoint),
 		}
 	}
 
@@ -32,6 +35,7 @@ impl<'a> HeaderAcceptor<'a> {
 		self.version.check()?;
 		self.work.check()?;
 		self.median_timestamp.check()?;
+		self.checkpoint.check()?;
 		Ok(())
 	}
 }
@@ -116,3 +120,35 @@ impl<'a> HeaderMedianTimestamp<'a> {
 		}
 	}
 }
+
+/// Header must match the checkpoint at the same height && must not fork the chain below the last known checkpoint.
+pub struct HeaderCheckpoint<'a> {
+	header: CanonHeader<'a>,
+	height: u32,
+	consensus: &'a ConsensusParams,
+	last_known_checkpoint: Option<u32>,
+}
+
+impl<'a> HeaderCheckpoint<'a> {
+	pub fn new(header: CanonHeader<'a>, height: u32, consensus: &'a ConsensusParams, last_known_checkpoint: Option<u32>) -> Self {
+		HeaderCheckpoint {
+			header: header,
+			height: height,
+			consensus: consensus,
+			last_known_checkpoint: last_known_checkpoint,
+		}
+	}
+
+	pub fn check(&self) -> Result<(), Error> {
+		if let Some(checkpoint) = self.consensus.checkpoints.get(&self.height) {
+			if *checkpoint != self.header.hash {
+				return Err(Error::CheckpointMismatch);
+			}
+		}
+
+		match self.last_known_checkpoint {
+			Some(last_known_checkpoint) if self.height < last_known_checkpoint => Err(Error::ForkBelowCheckpoint),
+			_ => Ok(()),
+		}
+	}
+}
@@ -1,16 +1,18 @@
 //! Bitcoin chain verifier
 
+use parking_lot::RwLock;
 use hash::H256;
 use chain::{IndexedBlock, IndexedBlockHeader, BlockHeader, IndexedTransaction};
 use storage::{SharedStore, TransactionOutputProvider, BlockHeaderProvider, BlockOrigin,
 	DuplexTransactionOutputProvider, NoopStore, CachedTransactionOutputProvider};
 use network::ConsensusParams;
 use error::{Error, TransactionError};
-use canon::{CanonBlock, CanonTransaction};
+use canon::{CanonBlock, CanonHeader, CanonTransaction};
 use verify_chain::ChainVerifier;
 use verify_header::HeaderVerifier;
 use verify_transaction::MemoryPoolTransactionVerifier;
 use accept_chain::ChainAcceptor;
+use accept_header::HeaderCheckpoint;
 use accept_transaction::MemoryPoolTransactionAcceptor;
 use deployments::{Deployments, BlockDeployments};
 use timestamp::median_timestamp_inclusive;
@@ -20,6 +22,8 @@ pub struct BackwardsCompatibleChainVerifier {
 	store: SharedStore,
 	consensus: ConsensusParams,
 	deployments: Deployments,
+	/// Height of the highest checkpoint, known to the store
+	last_known_checkpoint: RwLock<Option<u32>>,
 }
 
 impl BackwardsCompatibleChainVerifier {
@@ -28,6 +32,30 @@ impl BackwardsCompatibleChainVerifier {
 			store: store,
 			consensus: consensus,
 			deployments: Deployments::new(),
+			last_known_checkpoint: RwLock::new(None),
+		}
+	}
+
+	/// Returns height of the highest checkpoint, known to the store.
+	/// The chain is never reorganized below known checkpoint => cached height is only moving forward.
+	fn last_known_checkpoint(&self) -> Option<u32> {
+		let last_known_checkpoint = *self.last_known_checkpoint.read();
+		let first_unknown_checkpoint = last_known_checkpoint.map_or(0, |height| height + 1);
+		let best_block_number = self.store.best_block().number;
+		if first_unknown_checkpoint > best_block_number {
+			return last_known_checkpoint;
+		}
+
+		let new_known_checkpoint = self.consensus.checkpoints.range(first_unknown_checkpoint..=best_block_number)
+			.rev()
+			.find(|&(height, hash)| self.store.block_hash(*height).as_ref() == Some(hash))
+			.map(|(height, _)| *height);
+		match new_known_checkpoint {
+			Some(new_known_checkpoint) => {
+				*self.last_known_checkpoint.write() = Some(new_known_checkpoint);
+				Some(new_known_checkpoint)
+			},
+			None => last_known_checkpoint,
 		}
 	}
 
@@ -53,6 +81,7 @@ impl BackwardsCompatibleChainVerifier {
 
 		let median_time_past = median_timestamp_inclusive(block.header.raw.previous_header_hash.clone(), self.store.as_block_header_provider());
 		let canon_block = CanonBlock::new(block);
+		let last_known_checkpoint = self.last_known_checkpoint();
 		match block_origin {
 			BlockOrigin::KnownBlock => {
 				// there should be no known blocks at this point
@@ -73,6 +102,7 @@ impl BackwardsCompatibleChainVerifier {
 					block_number,
 					median_time_past,
 					&deployments,
+					last_known_checkpoint,
 				);
 				chain_acceptor.check()?;
 			},
@@ -93,6 +123,7 @@ impl BackwardsCompatibleChainVerifier {
 					block_number,
 					median_time_past,
 					&deployments,
+					last_known_checkpoint,
 				);
 				chain_acceptor.check()?;
 			},
@@ -113,6 +144,7 @@ impl BackwardsCompatibleChainVerifier {
 					block_number,
 					median_time_past,
 					&deployments,
+					last_known_checkpoint,
 				);
 				chain_acceptor.check()?;
 			},
@@ -126,14 +158,21 @@ impl BackwardsCompatibleChainVerifier {
 		&self,
 		_block_header_provider: &dyn BlockHeaderProvider,
 		hash: &H256,
-		header: &BlockHeader
+		header: &BlockHeader,
+		height: Option<u32>,
 	) -> Result<(), Error> {
 		// let's do only preverifcation
 		// TODO: full verification
 		let current_time = ::time::get_time().sec as u32;
 		let header = IndexedBlockHeader::new(hash.clone(), header.clone());
 		let header_verifier = HeaderVerifier::new(&header, self.consensus.network, current_time);
-		header_verifier.check()
+		header_verifier.check()?;
+
+		// height of the side chain header could be unknown
+		match height {
+			Some(height) => HeaderCheckpoint::new(CanonHeader::new(&header), height, &self.consensus, self.last_known_checkpoint()).check(),
+			None => Ok(()),
+		}
 	}
 
 	pub fn verify_mempool_transaction<T>(
@@ -549,4 +588,93 @@ mod tests {
 
 		assert_eq!(expected, verifier.verify(VerificationLevel::Full, &block.into()));
 	}
+
+	#[test]
+	fn checkpoint_mismatch() {
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![test_data::genesis().into()]));
+		let b1 = test_data::block_h1();
+		let mut consensus = ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore);
+		consensus.checkpoints.insert(1, 1.into());
+		let verifier = ChainVerifier::new(storage, consensus);
+		assert_eq!(Err(Error::CheckpointMismatch), verifier.verify(VerificationLevel::Full, &b1.into()));
+	}
+
+	#[test]
+	fn fork_below_checkpoint() {
+		let genesis = test_data::block_builder()
+			.transaction().coinbase().build()
+			.merkled_header().build()
+			.build();
+		let b1 = test_data::block_builder()
+			.transaction().coinbase().output().value(1).build().build()
+			.merkled_header().parent(genesis.hash()).build()
+			.build();
+		let b2 = test_data::block_builder()
+			.transaction().coinbase().output().value(2).build().build()
+			.merkled_header().parent(b1.hash()).build()
+			.build();
+		let storage = BlockChainDatabase::init_test_chain(vec![genesis.clone().into(), b1.into(), b2.clone().into()]);
+
+		let fork: IndexedBlock = test_data::block_builder()
+			.transaction().coinbase().output().value(3).build().build()
+			.merkled_header().parent(genesis.hash()).build()
+			.build()
+			.into();
+
+		let mut consensus = ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore);
+		consensus.checkpoints.insert(2, b2.hash());
+		let verifier = ChainVerifier::new(Arc::new(storage), consensus);
+		assert_eq!(Err(Error::ForkBelowCheckpoint), verifier.verify(VerificationLevel::Full, &fork));
+
+		// fork is accepted when the checkpoint is unknown
+		let storage = BlockChainDatabase::init_test_chain(vec![genesis.into()]);
+		let mut consensus = ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore);
+		consensus.checkpoints.insert(2, b2.hash());
+		let verifier = ChainVerifier::new(Arc::new(storage), consensus);
+		assert!(verifier.verify(VerificationLevel::Full, &fork).is_ok());
+	}
+
+	#[test]
+	fn header_checkpoints() {
+		let genesis = test_data::block_builder()
+			.transaction().coinbase().build()
+			.merkled_header().build()
+			.build();
+		let b1 = test_data::block_builder()
+			.transaction().coinbase().output().value(1).build().build()
+			.merkled_header().parent(genesis.hash()).build()
+			.build();
+		let b2 = test_data::block_builder()
+			.transaction().coinbase().output().value(2).build().build()
+			.merkled_header().parent(b1.hash()).build()
+			.build();
+		let b2_fork = test_data::block_builder()
+			.transaction().coinbase().output().value(3).build().build()
+			.merkled_header().parent(b1.hash()).build()
+			.build();
+		let b1_fork = test_data::block_builder()
+			.transaction().coinbase().output().value(4).build().build()
+			.merkled_header().parent(genesis.hash()).build()
+			.build();
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![genesis.into()]));
+
+		let mut consensus = ConsensusParams::new(Network::Unitest, ConsensusFork::BitcoinCore);
+		consensus.checkpoints.insert(2, b2.hash());
+		let verifier = ChainVerifier::new(storage.clone(), consensus);
+
+		// header must match the checkpoint at the same height
+		assert!(verifier.verify_block_header(&*storage, &b2.hash(), &b2.block_header, Some(2)).is_ok());
+		assert_eq!(Err(Error::CheckpointMismatch), verifier.verify_block_header(&*storage, &b2_fork.hash(), &b2_fork.block_header, Some(2)));
+		// height of the side chain header could be unknown
+		assert!(verifier.verify_block_header(&*storage, &b2_fork.hash(), &b2_fork.block_header, None).is_ok());
+
+		// header can fork the chain below the checkpoint until checkpoint is known
+		assert!(verifier.verify_block_header(&*storage, &b1_fork.hash(), &b1_fork.block_header, Some(1)).is_ok());
+		for block in vec![b1, b2] {
+			let hash = block.hash();
+			storage.insert(block.into()).unwrap();
+			storage.canonize(&hash).unwrap();
+		}
+		assert_eq!(Err(Error::ForkBelowCheckpoint), verifier.verify_block_header(&*storage, &b1_fork.hash(), &b1_fork.block_header, Some(1)));
+	}
 }
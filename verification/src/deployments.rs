@@ -270,6 +270,7 @@ mod tests {
 	use storage::{BlockHeaderProvider, BlockRef};
 	use network::Deployment;
 	use hash::H256;
+	use primitives::bigint::U256;
 	use primitives::bytes::Bytes;
 	use super::{DeploymentStateCache, ThresholdState, first_of_the_period, threshold_state};
 
@@ -308,6 +309,10 @@ mod tests {
 			unimplemented!()
 		}
 
+		fn block_chainwork(&self, _block_ref: BlockRef) -> Option<U256> {
+			unimplemented!()
+		}
+
 		fn block_header(&self, block_ref: BlockRef) -> Option<IndexedBlockHeader> {
 			self.request_count.fetch_add(1, Ordering::Relaxed);
 			match block_ref {
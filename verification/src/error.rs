@@ -45,6 +45,10 @@ pub enum Error {
 	NonFinalBlock,
 	/// Old version block.
 	OldVersionBlock,
+	/// Block hash differs from the checkpoint at the same height.
+	CheckpointMismatch,
+	/// Block forks the chain below the last known checkpoint.
+	ForkBelowCheckpoint,
 	/// Sum of the transaction fees in block + coinbase reward exceeds u64::max
 	TransactionFeeAndRewardOverflow,
 	/// Sum of the transaction fees in block exceeds u64::max
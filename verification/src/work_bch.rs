@@ -203,6 +203,10 @@ mod tests {
 			unimplemented!()
 		}
 
+		fn block_chainwork(&self, _block_ref: BlockRef) -> Option<U256> {
+			unimplemented!()
+		}
+
 		fn block_header(&self, block_ref: BlockRef) -> Option<IndexedBlockHeader> {
 			match block_ref {
 				BlockRef::Hash(ref hash) => self.by_hash.get(hash).map(|h| &self.by_height[*h]).cloned(),